@@ -1,5 +1,4 @@
 extern crate linux_embedded_hal as linux_hal;
-extern crate bit_reverse;
 extern crate pscontroller_rs;
 
 use std::io;
@@ -10,6 +9,7 @@ use linux_hal::Pin;
 use pscontroller_rs::{
     PlayStationPort,
 };
+use pscontroller_rs::baton::Baton;
 
 // Specific to the host device used on Linux, you'll have to change the following
 // parameters depending on your board and also export and allow writing to the GPIO
@@ -33,8 +33,6 @@ fn main() {
     let spi = build_spi().unwrap();
     let mut psp = PlayStationPort::new(spi, None::<Pin>);
 
-    let mut controller;
-
     psp.enable_pressure().unwrap();
 
     loop {
@@ -48,9 +46,7 @@ fn main() {
 
         // It's not possible to tell the baton apart from a NeGcon or other
         // devices that respond as type "0x2", so we have to force it for now.
-        unsafe {
-            controller = controller_data.b;
-        }
+        let controller = Baton::from_frame(&controller_data.data).unwrap();
 
         println!("\rA:{}, B:{} - Z: {:03} X: {:03} Y: {:03} A: {:03}",
             controller.buttons.a(),
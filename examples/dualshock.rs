@@ -1,5 +1,4 @@
 extern crate linux_embedded_hal as linux_hal;
-extern crate bit_reverse;
 extern crate pscontroller_rs;
 
 use std::io;
@@ -33,18 +32,14 @@ fn build_spi() -> io::Result<Spidev> {
 }
 
 fn set_motors(buttons: &GamepadButtons, small: &mut bool, big: &mut u8) {
-    if buttons.cross() {
-        *small = true;
-    } else {
-        *small = false;
-    }
+    *small = buttons.cross();
 
     if buttons.down() {
         *big = 255 / 3;
     } else if buttons.left() {
         *big = 255 / 2;
     } else if buttons.up() {
-        *big = 255 / 1;
+        *big = 255;
     } else {
         *big = 0;
     }
@@ -88,7 +83,7 @@ fn main() {
                     x.buttons.start(),
                     x.rx,
                     x.ry,
-                    x.pressures[6]);
+                    x.pressure_cross());
 
                 set_motors(&x.buttons, &mut small, &mut big);
             },
@@ -1,5 +1,4 @@
 extern crate linux_embedded_hal as linux_hal;
-extern crate bit_reverse;
 extern crate pscontroller_rs;
 
 use std::io;
@@ -10,6 +9,7 @@ use linux_hal::Pin;
 use pscontroller_rs::{
     PlayStationPort,
 };
+use pscontroller_rs::guitarhero::GuitarHero;
 
 // Specific to the host device used on Linux, you'll have to change the following
 // parameters depending on your board and also export and allow writing to the GPIO
@@ -33,8 +33,6 @@ fn main() {
     let spi = build_spi().unwrap();
     let mut psp = PlayStationPort::new(spi, None::<Pin>);
 
-    let mut controller;
-
     psp.enable_pressure().unwrap();
 
     loop {
@@ -49,9 +47,7 @@ fn main() {
         // We can't guess that we have a GuitarHero controller as it
         // appears nearly the same as a first-generation DualShock, except
         // for some buttons that are held down which isn't reliable
-        unsafe {
-            controller = controller_data.gh;
-        }
+        let controller = GuitarHero::from_frame(&controller_data.data).unwrap();
 
 //        println!("G:{0:1b}, R:{0:1b}, Y:{0:1b}, B:{0:1b}, O:{0:1b}",
         println!("G:{}\tR:{}\tY:{}\tB:{}\tO:{}\tSelect:{}",
@@ -1,5 +1,4 @@
 extern crate linux_embedded_hal as linux_hal;
-extern crate bit_reverse;
 extern crate pscontroller_rs;
 
 use std::io;
@@ -107,7 +106,7 @@ fn main() {
                     x.buttons.start(),
                     x.rx,
                     x.ry,
-                    x.pressures[6]);
+                    x.pressure_cross());
             },
             Device::JogCon(x) => {
                 println!("JogCon - Buttons: {0:08b}, Wheel: {1}", x.buttons.bits(), x.jog_position())
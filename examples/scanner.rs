@@ -55,7 +55,7 @@ fn main() {
 
     for i in MULTITAP_LIST.iter() {
 		println!("                                                  ");
-        println!("Scanning port address {0:02x}                     ", i.clone() as u8);
+        println!("Scanning port address {0:02x}                     ", *i as u8);
         println!("==================================================");
 
         // I had trouble with both something called `type ascription` and conflicting
@@ -75,7 +75,7 @@ fn main() {
             }
 
             for j in 0 ..= 0xff {
-                psp.set_multitap_port(i.clone());
+                psp.set_multitap_port(*i);
                 command[1] = j;
 
                 if escape {
@@ -91,12 +91,7 @@ fn main() {
 
                 print!("Command {:02x}: ", j);
 
-                let mut found = false;
-                for k in 2 .. buffer.len() {
-                    if buffer[k] != 0xff {
-                        found = true;
-                    }
-                }
+                let found = buffer.iter().skip(2).any(|&x| x != 0xff);
                 if !found {
                     print!("\r");
                     continue;
@@ -106,7 +101,7 @@ fn main() {
                     print!("{:02x} ", item);
                 }
 
-                println!("");
+                println!();
             }
         }
 
@@ -0,0 +1,302 @@
+//! Action Mapping
+//! ============================
+//! Binds buttons and stick pushes to actions of your own, so the rest of a
+//! program can ask "is the player jumping?" instead of "is Cross held?".
+
+use classic::{Button, GamepadButtons};
+use dualshock::signed;
+use sticks::Axis;
+use super::Device;
+
+/// Which way along an axis a stick has to be pushed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sign {
+    /// Left or up
+    Negative,
+    /// Right or down
+    Positive,
+}
+
+/// A stick pushed along an axis further than `threshold` from center, out of
+/// 127
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StickDirection {
+    /// Which axis to watch
+    pub axis: Axis,
+    /// How far from center the stick has to be before it counts
+    pub threshold: u8,
+    /// Which way the stick has to be pushed
+    pub sign: Sign,
+}
+
+impl StickDirection {
+    /// Create a stick direction
+    pub const fn new(axis: Axis, threshold: u8, sign: Sign) -> Self {
+        Self {
+            axis,
+            threshold,
+            sign,
+        }
+    }
+
+    /// Whether a signed stick position (see `dualshock::signed`) is past the
+    /// threshold
+    pub fn is_active(&self, value: i8) -> bool {
+        let threshold = i16::from(self.threshold);
+
+        match self.sign {
+            Sign::Negative => i16::from(value) < -threshold,
+            Sign::Positive => i16::from(value) > threshold,
+        }
+    }
+}
+
+/// Something that can trigger an action
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Binding {
+    /// A button being held
+    Button(Button),
+    /// A stick being pushed. Controllers without sticks never trigger these.
+    Stick(StickDirection),
+}
+
+/// The inputs bindings are checked against
+#[derive(Copy, Clone, Debug)]
+struct Inputs {
+    buttons: GamepadButtons,
+    /// Signed stick positions in the order of `Axis`
+    sticks: Option<[i8; 4]>,
+}
+
+impl Inputs {
+    fn new(device: &Device) -> Self {
+        let sticks = match device {
+            Device::AnalogJoystick(x) | Device::DualShock(x) => Some([x.lx, x.ly, x.rx, x.ry]),
+            Device::DualShock2(x) => Some([x.lx, x.ly, x.rx, x.ry]),
+            _ => None,
+        };
+
+        Self {
+            buttons: device.buttons().unwrap_or_default(),
+            sticks: sticks.map(|x| [signed(x[0]), signed(x[1]), signed(x[2]), signed(x[3])]),
+        }
+    }
+
+    fn triggers(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Button(button) => self.buttons.is_pressed(*button),
+            Binding::Stick(direction) => match self.sticks {
+                Some(sticks) => sticks.get(direction.axis as usize).is_some_and(|&x| direction.is_active(x)),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Up to `N` bindings from inputs to actions. Several bindings can lead to the
+/// same action. Created with `ActionMap::builder()`.
+#[derive(Copy, Clone, Debug)]
+pub struct ActionMap<A, const N: usize> {
+    bindings: [Option<(Binding, A)>; N],
+}
+
+impl<A: Copy + PartialEq, const N: usize> ActionMap<A, N> {
+    /// Start a map with nothing bound
+    pub fn builder() -> ActionMapBuilder<A, N> {
+        ActionMapBuilder {
+            map: ActionMap { bindings: [None; N] },
+            len: 0,
+        }
+    }
+
+    /// Each action with at least one of its bindings active. An action only
+    /// shows up once, however many of its bindings are active.
+    pub fn active(&self, device: &Device) -> ActiveActions<'_, A, N> {
+        ActiveActions {
+            map: self,
+            inputs: Inputs::new(device),
+            index: 0,
+        }
+    }
+
+    /// Whether an action has at least one of its bindings active
+    pub fn is_active(&self, device: &Device, action: A) -> bool {
+        self.active(device).any(|x| x == action)
+    }
+
+    /// How many bindings there are
+    pub fn len(&self) -> usize {
+        self.bindings.iter().filter(|x| x.is_some()).count()
+    }
+
+    /// Whether nothing is bound
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Adds bindings to an `ActionMap`. Created by `ActionMap::builder()`.
+#[derive(Copy, Clone, Debug)]
+pub struct ActionMapBuilder<A, const N: usize> {
+    map: ActionMap<A, N>,
+    len: usize,
+}
+
+impl<A: Copy + PartialEq, const N: usize> ActionMapBuilder<A, N> {
+    /// Bind an input to an action. Once `N` bindings have been added, any
+    /// more are ignored, so check `ActionMap::len` if the count isn't fixed.
+    pub fn bind(mut self, binding: Binding, action: A) -> Self {
+        if let Some(slot) = self.map.bindings.get_mut(self.len) {
+            *slot = Some((binding, action));
+            self.len += 1;
+        }
+        self
+    }
+
+    /// Bind a button to an action
+    pub fn button(self, button: Button, action: A) -> Self {
+        self.bind(Binding::Button(button), action)
+    }
+
+    /// Bind a stick direction to an action
+    pub fn stick(self, direction: StickDirection, action: A) -> Self {
+        self.bind(Binding::Stick(direction), action)
+    }
+
+    /// Finish building
+    pub fn build(self) -> ActionMap<A, N> {
+        self.map
+    }
+}
+
+/// Walks through the active actions, in the order their first active binding
+/// was added. Created by `ActionMap::active()`.
+#[derive(Debug)]
+pub struct ActiveActions<'a, A, const N: usize> {
+    map: &'a ActionMap<A, N>,
+    inputs: Inputs,
+    index: usize,
+}
+
+impl<'a, A: Copy + PartialEq, const N: usize> Iterator for ActiveActions<'a, A, N> {
+    type Item = A;
+
+    fn next(&mut self) -> Option<A> {
+        let bindings = &self.map.bindings;
+
+        while let Some(&entry) = bindings.get(self.index) {
+            let earlier = bindings.get(..self.index).unwrap_or_default();
+            self.index += 1;
+
+            let (binding, action) = match entry {
+                Some(x) => x,
+                None => continue,
+            };
+            if !self.inputs.triggers(&binding) {
+                continue;
+            }
+
+            // Skip actions that an earlier active binding already produced
+            let repeated = earlier.iter()
+                .flatten()
+                .any(|&(earlier, x)| x == action && self.inputs.triggers(&earlier));
+            if !repeated {
+                return Some(action);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use classic::Classic;
+    use dualshock::{DualShock, DualShock2};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    enum Action {
+        Jump,
+        Fire,
+        Left,
+        Right,
+        Look,
+    }
+
+    fn map() -> ActionMap<Action, 8> {
+        ActionMap::builder()
+            .button(Button::Cross, Action::Jump)
+            .button(Button::R1, Action::Fire)
+            .button(Button::Left, Action::Left)
+            .button(Button::Right, Action::Right)
+            .stick(StickDirection::new(Axis::LeftX, 40, Sign::Negative), Action::Left)
+            .stick(StickDirection::new(Axis::LeftX, 40, Sign::Positive), Action::Right)
+            .stick(StickDirection::new(Axis::RightY, 20, Sign::Negative), Action::Look)
+            .build()
+    }
+
+    fn active(device: Device) -> Vec<Action> {
+        map().active(&device).collect()
+    }
+
+    #[test]
+    fn buttons() {
+        let classic = Classic::builder().press(Button::R1).press(Button::Cross).press(Button::Square).build();
+
+        assert_eq!(active(Device::Classic(classic)), [Action::Jump, Action::Fire]);
+        assert_eq!(active(Device::Classic(Classic::default())), []);
+        assert_eq!(active(Device::None), []);
+    }
+
+    #[test]
+    fn stick_thresholds() {
+        let pushed = |lx, ry| Device::DualShock(DualShock::builder().stick_left(lx, 0x80).stick_right(0x80, ry).build());
+
+        // 40 from center is still inside, 41 is past it
+        assert_eq!(active(pushed(0x80 - 40, 0x80)), []);
+        assert_eq!(active(pushed(0x80 - 41, 0x80)), [Action::Left]);
+        assert_eq!(active(pushed(0x80 + 41, 0x80)), [Action::Right]);
+        assert_eq!(active(pushed(0x00, 0x00)), [Action::Left, Action::Look]);
+        assert_eq!(active(pushed(0xff, 0xff)), [Action::Right]);
+    }
+
+    #[test]
+    fn mixed_bindings_are_deduplicated() {
+        let dualshock2 = DualShock2::builder()
+            .press(Button::Left)
+            .press(Button::Cross)
+            .stick_left(0x00, 0x80)
+            .build();
+        let device = Device::DualShock2(dualshock2);
+
+        assert_eq!(active(device), [Action::Jump, Action::Left]);
+        assert!(map().is_active(&device, Action::Left));
+        assert!(!map().is_active(&device, Action::Right));
+
+        // Only the stick is pushed, so Left comes from the later binding
+        let stick_only = DualShock2::builder().stick_left(0x00, 0x80).build();
+        assert_eq!(active(Device::DualShock2(stick_only)), [Action::Left]);
+    }
+
+    #[test]
+    fn capacity() {
+        let full: ActionMap<Action, 2> = ActionMap::builder()
+            .button(Button::Cross, Action::Jump)
+            .button(Button::R1, Action::Fire)
+            .button(Button::Left, Action::Left)
+            .build();
+
+        assert_eq!(full.len(), 2);
+        assert!(!full.is_active(&Device::Classic(Classic::builder().press(Button::Left).build()), Action::Left));
+        assert!(ActionMap::<Action, 4>::builder().build().is_empty());
+    }
+}
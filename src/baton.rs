@@ -3,9 +3,15 @@
 //! A musical baton controller with accelerometers for playing musical conductor
 //! games such as Mad Maestro
 
+use protocol::{fill, leading};
+
 /// The two buttons found on the baton
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct BatonButtons {
     data: u16
 }
@@ -26,7 +32,10 @@ impl BatonButtons {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the classic Controller
 pub struct Baton {
     /// Standard buttons
@@ -40,3 +49,25 @@ pub struct Baton {
     /// Unknown, assuming acceleration
     pub a: u8
 }
+
+impl Baton {
+    /// Read a baton from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        let &[low, high, z, x, y, a] = leading(frame)?;
+
+        Some(Self {
+            buttons: BatonButtons { data: u16::from_le_bytes([low, high]) },
+            z,
+            x,
+            y,
+            a,
+        })
+    }
+
+    /// Write a baton back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.data.to_le_bytes();
+        fill(frame, &[low, high, self.z, self.x, self.y, self.a]);
+    }
+}
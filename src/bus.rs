@@ -0,0 +1,224 @@
+//! Shared Bus
+//! ============================
+//! The controller and the memory card next to it share the clock and data
+//! lines, and each has its own select line. `PsxBus` owns the SPI bus and
+//! lends it out one device at a time: `controller` and `memory_card` take the
+//! select pin for the device and a closure to run against it.
+//!
+//! Inside the closure it's an ordinary `PlayStationPort` (or `MemoryCard`)
+//! working the pin it was given, which goes low for each transfer and high
+//! again afterwards. Since the bus is borrowed for the whole closure, the
+//! other device can't start until it returns, and only one select line is
+//! ever low. Everything the port keeps track of, like rumble or what
+//! `probe` found, carries over from one closure to the next.
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use memcard::MemoryCard;
+use protocol::MESSAGE_MAX_LENGTH;
+use recovery::{FailFast, RecoveryPolicy};
+use super::PlayStationPort;
+
+/// A select pin lent to the bus for one closure
+pub struct BorrowedPin<'a, P: 'a>(&'a mut P);
+
+#[allow(deprecated)]
+impl<'a, P: OutputPin> OutputPin for BorrowedPin<'a, P> {
+    fn set_low(&mut self) {
+        self.0.set_low();
+    }
+
+    fn set_high(&mut self) {
+        self.0.set_high();
+    }
+}
+
+/// Stands in for a select pin while the bus isn't lent out
+pub struct Unselected;
+
+#[allow(deprecated)]
+impl OutputPin for Unselected {
+    fn set_low(&mut self) {}
+
+    fn set_high(&mut self) {}
+}
+
+/// An SPI bus shared by a controller and a memory card
+pub struct PsxBus<SPI, R = FailFast> {
+    /// Only taken while a closure runs
+    port: Option<PlayStationPort<SPI, Unselected, MESSAGE_MAX_LENGTH, R>>,
+}
+
+#[allow(deprecated)]
+impl<E, SPI> PsxBus<SPI>
+where
+    SPI: spi::Transfer<u8, Error = E> {
+
+    /// Share `spi` with a port set up the same as `PlayStationPort::new`
+    pub fn new(spi: SPI) -> Self {
+        Self {
+            port: Some(PlayStationPort::new(spi, None)),
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl<E, SPI, R> PsxBus<SPI, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    R: RecoveryPolicy {
+
+    /// Share a port that's already set up, say with `PlayStationPort::builder`,
+    /// handing back its select pin
+    pub fn from_port<CS: OutputPin>(port: PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R>) -> (Self, Option<CS>) {
+        let (port, select) = port.swap_select(None);
+
+        (Self { port: Some(port) }, select)
+    }
+
+    /// Give back the SPI bus
+    pub fn release(self) -> SPI {
+        self.port.expect("bus lost to a panic").release().0
+    }
+
+    /// Run `f` against the controller selected by `cs`
+    pub fn controller<CS, T, F>(&mut self, cs: &mut CS, f: F) -> T
+    where
+        CS: OutputPin,
+        F: FnOnce(&mut PlayStationPort<SPI, BorrowedPin<'_, CS>, MESSAGE_MAX_LENGTH, R>) -> T {
+
+        self.lend(cs, f)
+    }
+
+    /// Run `f` against the memory card selected by `cs`
+    pub fn memory_card<CS, T, F>(&mut self, cs: &mut CS, f: F) -> T
+    where
+        CS: OutputPin,
+        F: FnOnce(&mut MemoryCard<'_, SPI, BorrowedPin<'_, CS>, R>) -> T {
+
+        self.lend(cs, |port| f(&mut port.memory_card()))
+    }
+
+    fn lend<CS, T, F>(&mut self, cs: &mut CS, f: F) -> T
+    where
+        CS: OutputPin,
+        F: FnOnce(&mut PlayStationPort<SPI, BorrowedPin<'_, CS>, MESSAGE_MAX_LENGTH, R>) -> T {
+
+        // Only `None` if an earlier closure panicked
+        let port = self.port.take().expect("bus lost to a panic");
+        let (mut port, _) = port.swap_select(Some(BorrowedPin(cs)));
+        let result = f(&mut port);
+
+        let (port, _) = port.swap_select(None);
+        self.port = Some(port);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::cell::RefCell;
+    use self::std::vec::Vec;
+    use super::*;
+    use core::convert::Infallible;
+    use memcard::FRAME_SIZE;
+    use protocol::flip;
+    use testutil::SimulatedDualShock2;
+    use {Device, Error};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    enum Event {
+        Controller(bool),
+        Card(bool),
+        /// The first byte of a transfer, in the usual bit order
+        Transfer(u8),
+    }
+
+    struct Pin<'a>(&'a RefCell<Vec<Event>>, fn(bool) -> Event);
+
+    #[allow(deprecated)]
+    impl<'a> OutputPin for Pin<'a> {
+        fn set_low(&mut self) {
+            self.0.borrow_mut().push((self.1)(false));
+        }
+
+        fn set_high(&mut self) {
+            self.0.borrow_mut().push((self.1)(true));
+        }
+    }
+
+    /// A DualShock 2 answering the controller's address, and nothing for
+    /// the memory card
+    struct Bus<'a> {
+        events: &'a RefCell<Vec<Event>>,
+        controller: SimulatedDualShock2,
+    }
+
+    impl<'a> spi::Transfer<u8> for Bus<'a> {
+        type Error = Infallible;
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+            let mut first = [words[0]];
+            flip(&mut first);
+            self.events.borrow_mut().push(Event::Transfer(first[0]));
+
+            if first[0] == 0x01 {
+                self.controller.transfer(words)
+            } else {
+                words.iter_mut().for_each(|x| *x = 0xff);
+                Ok(words)
+            }
+        }
+    }
+
+    #[test]
+    fn one_device_at_a_time() {
+        let events = RefCell::new(Vec::new());
+        let mut pad = Pin(&events, Event::Controller);
+        let mut card = Pin(&events, Event::Card);
+        let mut bus = PsxBus::new(Bus { events: &events, controller: SimulatedDualShock2::new() });
+        let mut buffer = [0u8; FRAME_SIZE];
+
+        // A poll, a frame read, then pressures turned on and another poll
+        let device = bus.controller(&mut pad, |port| port.read_input(None));
+        assert!(matches!(device, Ok(Device::Classic(_))));
+        let read = bus.memory_card(&mut card, |card| card.read_frame(0, &mut buffer));
+        assert_eq!(read, Err(Error::UnsupportedDevice));
+        bus.controller(&mut pad, |port| port.enable_pressure()).unwrap();
+        let device = bus.controller(&mut pad, |port| port.read_input(None));
+        assert!(matches!(device, Ok(Device::DualShock2(_))));
+
+        // Never both low, and each transfer with just the right one
+        let (mut pad, mut card) = (true, true);
+        let mut transfers = (0, 0);
+        for event in events.borrow().iter() {
+            match *event {
+                Event::Controller(x) => pad = x,
+                Event::Card(x) => card = x,
+                Event::Transfer(address) => {
+                    if address == 0x81 {
+                        assert_eq!((pad, card), (true, false));
+                        transfers.1 += 1;
+                    } else {
+                        assert_eq!((pad, card), (false, true));
+                        transfers.0 += 1;
+                    }
+                },
+            }
+            assert!(pad || card);
+        }
+
+        assert_eq!(transfers, (10, 1));
+        assert_eq!((pad, card), (true, true));
+
+        // A port that's already set up can be shared too
+        let (mut bus, _) = PsxBus::from_port(PlayStationPort::new(bus.release(), None::<Pin>));
+        let mut pad = Pin(&events, Event::Controller);
+        assert!(matches!(bus.controller(&mut pad, |port| port.read_input(None)), Ok(Device::DualShock2(_))));
+    }
+}
@@ -3,137 +3,896 @@
 //! This is the predecessor of the DualShock and is the controller
 //! which originally shipped with the original PlayStation
 
-use super::HasStandardButtons;
+use core::fmt;
+
+use protocol::{fill, leading};
+use super::{Device, DeviceId, FromDevice, HasStandardButtons};
+
+bitflags! {
+    /// The standard buttons as a set. Unlike the raw word coming off the wire
+    /// these are active high, so a set bit means the button is held.
+    pub struct Buttons: u16 {
+        /// A button on the controller
+        const SELECT = 0x0001;
+        /// A button on the controller
+        const L3 = 0x0002;
+        /// A button on the controller
+        const R3 = 0x0004;
+        /// A button on the controller
+        const START = 0x0008;
+
+        /// A button on the controller
+        const UP = 0x0010;
+        /// A button on the controller
+        const RIGHT = 0x0020;
+        /// A button on the controller
+        const DOWN = 0x0040;
+        /// A button on the controller
+        const LEFT = 0x0080;
+
+        /// A button on the controller
+        const L2 = 0x0100;
+        /// A button on the controller
+        const R2 = 0x0200;
+        /// A button on the controller
+        const L1 = 0x0400;
+        /// A button on the controller
+        const R1 = 0x0800;
+
+        /// A button on the controller
+        const TRIANGLE = 0x1000;
+        /// A button on the controller
+        const CIRCLE = 0x2000;
+        /// A button on the controller
+        const CROSS = 0x4000;
+        /// A button on the controller
+        const SQUARE = 0x8000;
+    }
+}
+
+/// One of the standard buttons. Handy for storing which button does what in a
+/// table, since every button otherwise has its own accessor. The variants are
+/// in the same order as the bits on the wire.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Button {
+    /// A button on the controller
+    Select,
+    /// A button on the controller
+    L3,
+    /// A button on the controller
+    R3,
+    /// A button on the controller
+    Start,
+    /// A button on the controller
+    Up,
+    /// A button on the controller
+    Right,
+    /// A button on the controller
+    Down,
+    /// A button on the controller
+    Left,
+    /// A button on the controller
+    L2,
+    /// A button on the controller
+    R2,
+    /// A button on the controller
+    L1,
+    /// A button on the controller
+    R1,
+    /// A button on the controller
+    Triangle,
+    /// A button on the controller
+    Circle,
+    /// A button on the controller
+    Cross,
+    /// A button on the controller
+    Square,
+}
+
+impl Button {
+    /// Every button, in wire bit order
+    pub const ALL: [Button; 16] = [
+        Button::Select, Button::L3, Button::R3, Button::Start,
+        Button::Up, Button::Right, Button::Down, Button::Left,
+        Button::L2, Button::R2, Button::L1, Button::R1,
+        Button::Triangle, Button::Circle, Button::Cross, Button::Square,
+    ];
+
+    /// The button's name, like "Cross" or "L2"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Button::Select => "Select",
+            Button::L3 => "L3",
+            Button::R3 => "R3",
+            Button::Start => "Start",
+            Button::Up => "Up",
+            Button::Right => "Right",
+            Button::Down => "Down",
+            Button::Left => "Left",
+            Button::L2 => "L2",
+            Button::R2 => "R2",
+            Button::L1 => "L1",
+            Button::R1 => "R1",
+            Button::Triangle => "Triangle",
+            Button::Circle => "Circle",
+            Button::Cross => "Cross",
+            Button::Square => "Square",
+        }
+    }
+
+    /// The bit for this button in the button word. The same bit is used for
+    /// the raw (active low) word and the `Buttons` set.
+    pub const fn mask(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// Prints the button's name, the same as `as_str`
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which way the d-pad is pointing, diagonals included
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// Nothing held, or only opposite directions held
+    Neutral,
+    /// Pointing up
+    Up,
+    /// Pointing up and to the right
+    UpRight,
+    /// Pointing right
+    Right,
+    /// Pointing down and to the right
+    DownRight,
+    /// Pointing down
+    Down,
+    /// Pointing down and to the left
+    DownLeft,
+    /// Pointing left
+    Left,
+    /// Pointing up and to the left
+    UpLeft,
+}
+
+/// Every d-pad direction held, opposite ones included. `Direction` has to
+/// pick one way to point, but a dance mat can have all four arrows stood on
+/// at once, and a rhythm game needs to see each of them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DPadState {
+    /// Up is held
+    pub up: bool,
+    /// Right is held
+    pub right: bool,
+    /// Down is held
+    pub down: bool,
+    /// Left is held
+    pub left: bool,
+}
+
+impl DPadState {
+    /// The d-pad from four bits, 1 when held: up, right, down then left from
+    /// the lowest bit, the same order they come off the wire. Higher bits are
+    /// ignored.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self {
+            up: bits & 0x1 != 0,
+            right: bits & 0x2 != 0,
+            down: bits & 0x4 != 0,
+            left: bits & 0x8 != 0,
+        }
+    }
+
+    /// The d-pad as four bits, the reverse of `from_bits`
+    pub const fn bits(&self) -> u8 {
+        (self.up as u8) | (self.right as u8) << 1 | (self.down as u8) << 2 | (self.left as u8) << 3
+    }
+
+    /// Whether up and down, or left and right, are held together
+    pub const fn has_opposites(&self) -> bool {
+        (self.up && self.down) || (self.left && self.right)
+    }
+
+    /// Which way this points, with opposite directions cancelling out (see
+    /// `GamepadButtons::dpad`)
+    pub fn direction(&self) -> Direction {
+        let vertical = (self.down as i8) - (self.up as i8);
+        let horizontal = (self.right as i8) - (self.left as i8);
+
+        match (vertical, horizontal) {
+            (-1, -1) => Direction::UpLeft,
+            (-1, 0) => Direction::Up,
+            (-1, 1) => Direction::UpRight,
+            (0, -1) => Direction::Left,
+            (0, 1) => Direction::Right,
+            (1, -1) => Direction::DownLeft,
+            (1, 0) => Direction::Down,
+            (1, 1) => Direction::DownRight,
+            _ => Direction::Neutral,
+        }
+    }
+}
+
+/// Walks through a set of buttons in wire bit order. Created by
+/// `GamepadButtons::pressed()` and the `ButtonChanges` iterators.
+#[derive(Debug)]
+pub struct ButtonIter {
+    buttons: Buttons,
+    index: usize,
+}
+
+impl ButtonIter {
+    fn new(buttons: Buttons) -> Self {
+        Self {
+            buttons,
+            index: 0,
+        }
+    }
+}
+
+impl Iterator for ButtonIter {
+    type Item = Button;
+
+    fn next(&mut self) -> Option<Button> {
+        while let Some(&button) = Button::ALL.get(self.index) {
+            self.index += 1;
+
+            if self.buttons.bits() & button.mask() != 0 {
+                return Some(button);
+            }
+        }
+
+        None
+    }
+}
+
+/// What changed between two frames of buttons. Buttons that were held in
+/// both frames (or released in both) don't show up here.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ButtonChanges {
+    pressed: Buttons,
+    released: Buttons,
+}
+
+impl ButtonChanges {
+    /// Whether the button went down since the earlier frame
+    pub fn just_pressed(&self, button: Button) -> bool {
+        self.pressed.bits() & button.mask() != 0
+    }
+
+    /// Whether the button came up since the earlier frame
+    pub fn just_released(&self, button: Button) -> bool {
+        self.released.bits() & button.mask() != 0
+    }
+
+    /// Each button that went down since the earlier frame
+    pub fn pressed(&self) -> ButtonIter {
+        ButtonIter::new(self.pressed)
+    }
+
+    /// Each button that came up since the earlier frame
+    pub fn released(&self) -> ButtonIter {
+        ButtonIter::new(self.released)
+    }
+
+    /// Whether anything changed at all
+    pub fn is_empty(&self) -> bool {
+        self.pressed.is_empty() && self.released.is_empty()
+    }
+}
 
 /// The digital buttons of the gamepad
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct GamepadButtons {
-    data: u16,
+    pub(crate) data: u16,
+}
+
+/// Lists the held buttons by name rather than showing the active low word
+impl fmt::Debug for GamepadButtons {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.pressed()).finish()
+    }
+}
+
+/// Lists the held buttons by name, separated by commas, like `Select, Cross`.
+/// Nothing is printed when nothing is held.
+impl fmt::Display for GamepadButtons {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, button) in self.pressed().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(button.as_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the held buttons by name, the same as the `Debug` output
+#[cfg(feature = "defmt")]
+impl defmt::Format for GamepadButtons {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{{");
+        for (i, button) in self.pressed().enumerate() {
+            if i > 0 {
+                defmt::write!(f, ", ");
+            }
+            defmt::write!(f, "{}", button);
+        }
+        defmt::write!(f, "}}");
+    }
+}
+
+/// Lists the held buttons by name, the same as the `Debug` output
+#[cfg(feature = "ufmt")]
+impl ufmt::uDebug for GamepadButtons {
+    fn fmt<W: ufmt::uWrite + ?Sized>(&self, f: &mut ufmt::Formatter<W>) -> Result<(), W::Error> {
+        f.write_str("{")?;
+        ufmt::uDisplay::fmt(self, f)?;
+        f.write_str("}")
+    }
+}
+
+/// Lists the held buttons by name, the same as the `Display` output
+#[cfg(feature = "ufmt")]
+impl ufmt::uDisplay for GamepadButtons {
+    fn fmt<W: ufmt::uWrite + ?Sized>(&self, f: &mut ufmt::Formatter<W>) -> Result<(), W::Error> {
+        for (i, button) in self.pressed().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(button.as_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Nothing held
+impl Default for GamepadButtons {
+    fn default() -> Self {
+        Self::all_released()
+    }
 }
 
 /// A collection of helper functions to take the button bitfield and make them more
 /// ergonomic to use.
 impl GamepadButtons {
-    // Gamepad buttons are active low, so that's why we're comparing them to zero
+    /// Buttons with nothing held. Because the raw word is active low this is
+    /// every bit set (0xffff).
+    pub const fn all_released() -> Self {
+        GamepadButtons { data: 0xffff }
+    }
+
+    /// Buttons from a raw, **active low** word, as it comes off the wire: a
+    /// held button is a 0 bit and `0xffff` is nothing held. For pressed = 1,
+    /// use `from_pressed_bits`.
+    pub const fn from_bits(raw: u16) -> Self {
+        GamepadButtons { data: raw }
+    }
+
+    /// Buttons from an **active high** word: a held button is a 1 bit and
+    /// `0x0000` is nothing held. This is the inverse of `pressed_bits`.
+    pub const fn from_pressed_bits(pressed: u16) -> Self {
+        GamepadButtons { data: !pressed }
+    }
+
+    /// Buttons with just the listed ones held, which is handy for tests
+    pub const fn from_pressed(buttons: &[Button]) -> Self {
+        let mut data = 0xffff;
+        let mut rest = buttons;
+        while let [button, tail @ ..] = rest {
+            data &= !button.mask();
+            rest = tail;
+        }
 
-    const PS_SELECT: u16 = 0x0001;
-    const PS_L3: u16 = 0x0002;
-    const PS_R3: u16 = 0x0004;
-    const PS_START: u16 = 0x0008;
+        GamepadButtons { data }
+    }
 
-    const PS_UP: u16 = 0x0010;
-    const PS_RIGHT: u16 = 0x0020;
-    const PS_DOWN: u16 = 0x0040;
-    const PS_LEFT: u16 = 0x0080;
+    /// The buttons currently held, as a set. Use this to check several
+    /// buttons at once, like `pressed_set().contains(Buttons::CROSS | Buttons::L1)`
+    /// for "both held" or `intersects()` for "any of these held".
+    pub fn pressed_set(&self) -> Buttons {
+        // Gamepad buttons are active low, so flip them to get the held ones
+        Buttons::from_bits_truncate(!self.data)
+    }
 
-    const PS_L2: u16 = 0x0100;
-    const PS_R2: u16 = 0x0200;
-    const PS_L1: u16 = 0x0400;
-    const PS_R1: u16 = 0x0800;
+    /// Each held button, once, in wire bit order (Select, L3, R3, Start, Up,
+    /// Right, Down, Left, L2, R2, L1, R1, Triangle, Circle, Cross, Square)
+    pub fn pressed(&self) -> ButtonIter {
+        ButtonIter::new(self.pressed_set())
+    }
 
-    const PS_TRIANGLE: u16 = 0x1000;
-    const PS_CIRCLE: u16 = 0x2000;
-    const PS_CROSS: u16 = 0x4000;
-    const PS_SQUARE: u16 = 0x8000;
+    /// Compare against an earlier frame to find what was pressed and released
+    /// in between
+    pub fn changes_since(&self, previous: &GamepadButtons) -> ButtonChanges {
+        let now = self.pressed_set();
+        let before = previous.pressed_set();
+
+        ButtonChanges {
+            pressed: now - before,
+            released: before - now,
+        }
+    }
+
+    /// Whether anything at all is held
+    pub fn any_pressed(&self) -> bool {
+        !self.pressed_set().is_empty()
+    }
+
+    /// Whether nothing at all is held
+    pub fn none_pressed(&self) -> bool {
+        self.pressed_set().is_empty()
+    }
+
+    /// How many buttons are held at once
+    pub fn pressed_count(&self) -> u8 {
+        self.pressed_set().bits().count_ones() as u8
+    }
+
+    /// Which way the d-pad is pointing. A normal d-pad can't press opposite
+    /// directions at once, but dance mats can. When that happens the two
+    /// cancel out, so up and down together reads as neutral on that axis and
+    /// up, down and right together reads as `Right`. Use `dpad_state` to
+    /// see every direction held.
+    pub fn dpad(&self) -> Direction {
+        self.dpad_state().direction()
+    }
+
+    /// Every d-pad direction held, without opposite ones cancelling out
+    pub fn dpad_state(&self) -> DPadState {
+        DPadState::from_bits((self.pressed_set().bits() >> 4) as u8)
+    }
+
+    /// Whether the given button is held
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.data & button.mask() == 0
+    }
 
     /// A button on the controller
     pub fn select(&self) -> bool {
-        self.data & Self::PS_SELECT == 0
+        self.pressed_set().contains(Buttons::SELECT)
     }
 
     /// A button on the controller
     pub fn l3(&self) -> bool {
-        self.data & Self::PS_L3 == 0
+        self.pressed_set().contains(Buttons::L3)
     }
 
     /// A button on the controller
     pub fn r3(&self) -> bool {
-        self.data & Self::PS_R3 == 0
+        self.pressed_set().contains(Buttons::R3)
     }
 
     /// A button on the controller
     pub fn start(&self) -> bool {
-        self.data & Self::PS_START == 0
+        self.pressed_set().contains(Buttons::START)
     }
 
     /// A button on the controller
     pub fn up(&self) -> bool {
-        self.data & Self::PS_UP == 0
+        self.pressed_set().contains(Buttons::UP)
     }
 
     /// A button on the controller
     pub fn right(&self) -> bool {
-        self.data & Self::PS_RIGHT == 0
+        self.pressed_set().contains(Buttons::RIGHT)
     }
 
     /// A button on the controller
     pub fn down(&self) -> bool {
-        self.data & Self::PS_DOWN == 0
+        self.pressed_set().contains(Buttons::DOWN)
     }
 
     /// A button on the controller
     pub fn left(&self) -> bool {
-        self.data & Self::PS_LEFT == 0
+        self.pressed_set().contains(Buttons::LEFT)
     }
 
     /// A button on the controller
     pub fn l2(&self) -> bool {
-        self.data & Self::PS_L2 == 0
+        self.pressed_set().contains(Buttons::L2)
     }
 
     /// A button on the controller
     pub fn r2(&self) -> bool {
-        self.data & Self::PS_R2 == 0
+        self.pressed_set().contains(Buttons::R2)
     }
 
     /// A button on the controller
     pub fn l1(&self) -> bool {
-        self.data & Self::PS_L1 == 0
+        self.pressed_set().contains(Buttons::L1)
     }
 
     /// A button on the controller
     pub fn r1(&self) -> bool {
-        self.data & Self::PS_R1 == 0
+        self.pressed_set().contains(Buttons::R1)
     }
 
     /// A button on the controller
     pub fn triangle(&self) -> bool {
-        self.data & Self::PS_TRIANGLE == 0
+        self.pressed_set().contains(Buttons::TRIANGLE)
     }
 
     /// A button on the controller
     pub fn circle(&self) -> bool {
-        self.data & Self::PS_CIRCLE == 0
+        self.pressed_set().contains(Buttons::CIRCLE)
     }
 
     /// A button on the controller
     pub fn cross(&self) -> bool {
-        self.data & Self::PS_CROSS == 0
+        self.pressed_set().contains(Buttons::CROSS)
     }
 
     /// A button on the controller
     pub fn square(&self) -> bool {
-        self.data & Self::PS_SQUARE == 0
+        self.pressed_set().contains(Buttons::SQUARE)
     }
 
-    /// The raw value of the buttons on the controller. Useful for
-    /// aggregate functions
+    /// The raw, **active low** word as it came off the wire: a held button
+    /// is a 0 bit. For pressed = 1, use `pressed_bits`.
     pub fn bits(&self) -> u16 {
         self.data
     }
+
+    /// The buttons as an **active high** word: a held button is a 1 bit,
+    /// using the same bit positions as `Button::mask`. This is the inverse of
+    /// `bits`.
+    pub const fn pressed_bits(&self) -> u16 {
+        !self.data
+    }
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
-/// Represents the classic Controller
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Represents the classic Controller. The default has nothing held.
 pub struct Classic {
     /// Standard buttons (Cross, Circle, L3, Start)
     pub buttons: GamepadButtons,
 }
 
+impl Classic {
+    /// Read a Classic from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        ClassicRef::new(frame).map(|x| x.to_owned())
+    }
+
+    /// Write a Classic back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        fill(frame, &self.buttons.data.to_le_bytes());
+    }
+
+    /// Start building a controller state by hand, with nothing held
+    pub fn builder() -> ClassicBuilder {
+        ClassicBuilder { value: Classic::default() }
+    }
+
+    /// The controller's bytes as they came off the wire, without the header
+    pub fn as_bytes(&self) -> [u8; 2] {
+        let mut bytes = [0u8; 2];
+        self.write_frame(&mut bytes);
+
+        bytes
+    }
+}
+
+/// A `Classic` read straight out of a poll buffer as it's asked for, rather
+/// than copied out up front
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClassicRef<'a> {
+    frame: &'a [u8; 2],
+}
+
+impl<'a> ClassicRef<'a> {
+    /// View the bytes that follow a frame's header as a Classic. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn new(frame: &'a [u8]) -> Option<Self> {
+        Some(Self { frame: leading(frame)? })
+    }
+
+    /// The buttons
+    pub fn buttons(&self) -> GamepadButtons {
+        GamepadButtons::from_bits(u16::from_le_bytes(*self.frame))
+    }
+
+    /// Copy the controller out of the buffer
+    pub fn to_owned(&self) -> Classic {
+        Classic { buttons: self.buttons() }
+    }
+}
+
+/// Builds a `Classic` by hand, for tests and simulators. Created by
+/// `Classic::builder()`.
+#[derive(Copy, Clone, Debug)]
+pub struct ClassicBuilder {
+    value: Classic,
+}
+
+impl ClassicBuilder {
+    /// Hold a button
+    pub fn press(mut self, button: Button) -> Self {
+        self.value.buttons.data &= !button.mask();
+        self
+    }
+
+    /// Finish building
+    pub fn build(self) -> Classic {
+        self.value
+    }
+}
+
 impl HasStandardButtons for Classic {
-    fn buttons(&self) -> GamepadButtons {
-        self.buttons.clone()
+    fn buttons(&self) -> &GamepadButtons {
+        &self.buttons
+    }
+}
+
+/// Anything with the standard buttons, see `Device::downgrade_to_classic`
+impl FromDevice for Classic {
+    const EXPECTED: DeviceId = DeviceId::Classic;
+
+    fn from_device(device: &Device) -> Option<Self> {
+        device.downgrade_to_classic()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Accessor = fn(&GamepadButtons) -> bool;
+
+    const ACCESSORS: [(Accessor, Buttons); 16] = [
+        (GamepadButtons::select, Buttons::SELECT),
+        (GamepadButtons::l3, Buttons::L3),
+        (GamepadButtons::r3, Buttons::R3),
+        (GamepadButtons::start, Buttons::START),
+        (GamepadButtons::up, Buttons::UP),
+        (GamepadButtons::right, Buttons::RIGHT),
+        (GamepadButtons::down, Buttons::DOWN),
+        (GamepadButtons::left, Buttons::LEFT),
+        (GamepadButtons::l2, Buttons::L2),
+        (GamepadButtons::r2, Buttons::R2),
+        (GamepadButtons::l1, Buttons::L1),
+        (GamepadButtons::r1, Buttons::R1),
+        (GamepadButtons::triangle, Buttons::TRIANGLE),
+        (GamepadButtons::circle, Buttons::CIRCLE),
+        (GamepadButtons::cross, Buttons::CROSS),
+        (GamepadButtons::square, Buttons::SQUARE),
+    ];
+
+    #[test]
+    fn pressed_set_is_active_high() {
+        // Cross and L1 held
+        let buttons = GamepadButtons { data: !(0x4000 | 0x0400) };
+
+        assert!(buttons.pressed_set().contains(Buttons::CROSS | Buttons::L1));
+        assert!(buttons.pressed_set().intersects(Buttons::L1 | Buttons::R1));
+        assert!(!buttons.pressed_set().contains(Buttons::CROSS | Buttons::R1));
+        assert_eq!(buttons.pressed_set(), Buttons::CROSS | Buttons::L1);
+
+        let released = GamepadButtons { data: 0xffff };
+        assert!(released.pressed_set().is_empty());
+    }
+
+    #[test]
+    fn accessors_match_flags() {
+        for &(_, flag) in ACCESSORS.iter() {
+            let buttons = GamepadButtons { data: !flag.bits() };
+
+            for &(accessor, other_flag) in ACCESSORS.iter() {
+                assert_eq!(accessor(&buttons), other_flag == flag);
+            }
+        }
+    }
+
+    #[test]
+    fn button_enum_matches_accessors() {
+        for (&button, &(accessor, flag)) in Button::ALL.iter().zip(ACCESSORS.iter()) {
+            assert_eq!(button.mask(), flag.bits());
+
+            let buttons = GamepadButtons { data: !button.mask() };
+            for &other in Button::ALL.iter() {
+                assert_eq!(buttons.is_pressed(other), other == button);
+            }
+            assert!(accessor(&buttons));
+        }
+    }
+
+    #[test]
+    fn pressed_iterator() {
+        let cases: [(u16, &[Button]); 4] = [
+            (0xffff, &[]),
+            (!0x0001, &[Button::Select]),
+            (!(0x8000 | 0x0010 | 0x0400), &[Button::Up, Button::L1, Button::Square]),
+            (0x0000, &Button::ALL),
+        ];
+
+        for &(data, expected) in cases.iter() {
+            let buttons = GamepadButtons { data };
+
+            assert!(buttons.pressed().eq(expected.iter().cloned()));
+        }
+    }
+
+    #[test]
+    fn changes_between_frames() {
+        // Cross and Up held, then Cross let go, Up still held and L1 pressed
+        let before = GamepadButtons { data: !(0x4000 | 0x0010) };
+        let after = GamepadButtons { data: !(0x0010 | 0x0400 | 0x0800) };
+
+        let changes = after.changes_since(&before);
+
+        assert!(changes.just_released(Button::Cross));
+        assert!(!changes.just_pressed(Button::Cross));
+        assert!(!changes.just_pressed(Button::Up));
+        assert!(!changes.just_released(Button::Up));
+        assert!(changes.just_pressed(Button::L1));
+        assert!(changes.just_pressed(Button::R1));
+        assert!(changes.pressed().eq([Button::L1, Button::R1].iter().cloned()));
+        assert!(changes.released().eq([Button::Cross].iter().cloned()));
+
+        assert!(after.changes_since(&after).is_empty());
+    }
+
+    #[test]
+    fn aggregates() {
+        let cases = [
+            (0xffff, 0, false),
+            (0xfffe, 1, true),
+            (0xbfef, 2, true),
+            (0x5aa5, 8, true),
+            (0x8000, 15, true),
+            (0x0000, 16, true),
+        ];
+
+        for &(data, count, any) in cases.iter() {
+            let buttons = GamepadButtons { data };
+
+            assert_eq!(buttons.pressed_count(), count);
+            assert_eq!(buttons.any_pressed(), any);
+            assert_eq!(buttons.none_pressed(), !any);
+        }
+    }
+
+    #[test]
+    fn dpad_directions() {
+        let cases = [
+            (Buttons::empty(), Direction::Neutral),
+            (Buttons::UP, Direction::Up),
+            (Buttons::UP | Buttons::RIGHT, Direction::UpRight),
+            (Buttons::RIGHT, Direction::Right),
+            (Buttons::DOWN | Buttons::RIGHT, Direction::DownRight),
+            (Buttons::DOWN, Direction::Down),
+            (Buttons::DOWN | Buttons::LEFT, Direction::DownLeft),
+            (Buttons::LEFT, Direction::Left),
+            (Buttons::UP | Buttons::LEFT, Direction::UpLeft),
+            (Buttons::CROSS | Buttons::START, Direction::Neutral),
+            (Buttons::UP | Buttons::DOWN, Direction::Neutral),
+            (Buttons::LEFT | Buttons::RIGHT, Direction::Neutral),
+            (Buttons::UP | Buttons::DOWN | Buttons::RIGHT, Direction::Right),
+            (Buttons::UP | Buttons::LEFT | Buttons::RIGHT, Direction::Up),
+            (Buttons::UP | Buttons::DOWN | Buttons::LEFT | Buttons::RIGHT, Direction::Neutral),
+        ];
+
+        for &(held, direction) in cases.iter() {
+            let buttons = GamepadButtons { data: !held.bits() };
+            assert_eq!(buttons.dpad(), direction);
+        }
+    }
+
+    #[test]
+    fn dpad_state_keeps_every_combination() {
+        let arrows = [Buttons::UP, Buttons::RIGHT, Buttons::DOWN, Buttons::LEFT];
+
+        for bits in 0..16u8 {
+            let held = arrows.iter()
+                .enumerate()
+                .filter(|&(i, _)| bits & 1 << i != 0)
+                .fold(Buttons::empty(), |held, (_, &arrow)| held | arrow);
+            let buttons = GamepadButtons { data: !(held | Buttons::CROSS).bits() };
+            let state = buttons.dpad_state();
+
+            assert_eq!(state.bits(), bits);
+            assert_eq!(DPadState::from_bits(bits), state);
+            assert_eq!((state.up, state.right, state.down, state.left), (buttons.up(), buttons.right(), buttons.down(), buttons.left()));
+            assert_eq!(state.has_opposites(), (state.up && state.down) || (state.left && state.right));
+
+            // Opposites cancel, leaving whatever else is held
+            let expected = DPadState {
+                up: state.up && !state.down,
+                down: state.down && !state.up,
+                left: state.left && !state.right,
+                right: state.right && !state.left,
+            };
+            assert!(!expected.has_opposites());
+            assert_eq!(buttons.dpad(), state.direction());
+            assert_eq!(state.direction(), expected.direction());
+        }
+
+        assert_eq!(DPadState::from_bits(0xff), DPadState::from_bits(0x0f));
+    }
+
+    #[test]
+    fn constructors() {
+        const CROSS: GamepadButtons = GamepadButtons::from_pressed(&[Button::Cross]);
+
+        for &(accessor, flag) in ACCESSORS.iter() {
+            assert_eq!(accessor(&CROSS), flag == Buttons::CROSS);
+        }
+
+        assert_eq!(CROSS, GamepadButtons::from_bits(0xbfff));
+        assert_eq!(GamepadButtons::from_pressed(&[]), GamepadButtons::all_released());
+        assert_eq!(GamepadButtons::from_pressed(&[Button::Up, Button::Square, Button::Up]).bits(), 0x7fef);
+        assert_eq!(GamepadButtons::from_bits(0x1234).bits(), 0x1234);
+    }
+
+    #[test]
+    fn builder() {
+        let classic = Classic::builder().press(Button::Start).press(Button::Left).build();
+
+        assert!(classic.buttons.start() && classic.buttons.left());
+        assert_eq!(classic.buttons.pressed_count(), 2);
+        assert_eq!(Classic::builder().build(), Classic::default());
+    }
+
+    #[test]
+    fn pressed_bits_are_inverted() {
+        let cases = [(0xffff, 0x0000), (0x0000, 0xffff), (0xbfff, 0x4000), (0x5aa5, 0xa55a), (0xfffe, 0x0001)];
+
+        for &(raw, pressed) in cases.iter() {
+            let buttons = GamepadButtons::from_bits(raw);
+            assert_eq!(buttons.bits(), raw);
+            assert_eq!(buttons.pressed_bits(), pressed);
+            assert_eq!(buttons.pressed_bits(), buttons.pressed_set().bits());
+
+            let buttons = GamepadButtons::from_pressed_bits(pressed);
+            assert_eq!(buttons.bits(), raw);
+            assert_eq!(buttons.pressed_bits(), pressed);
+        }
+
+        assert_eq!(GamepadButtons::from_pressed_bits(Button::Cross.mask()), GamepadButtons::from_pressed(&[Button::Cross]));
+    }
+
+    #[test]
+    fn button_names() {
+        let names = [
+            "Select", "L3", "R3", "Start", "Up", "Right", "Down", "Left",
+            "L2", "R2", "L1", "R1", "Triangle", "Circle", "Cross", "Square",
+        ];
+
+        let mut covered = 0u16;
+        for (&button, &name) in Button::ALL.iter().zip(names.iter()) {
+            assert_eq!(button.as_str(), name);
+            covered |= button.mask();
+        }
+        assert_eq!(covered, 0xffff);
+    }
+
+    #[test]
+    fn defaults_are_released() {
+        let buttons = GamepadButtons::default();
+
+        assert_eq!(buttons.bits(), 0xffff);
+        assert_eq!(buttons, GamepadButtons::all_released());
+        assert_eq!(buttons.pressed().count(), 0);
+        for &(accessor, _) in ACCESSORS.iter() {
+            assert!(!accessor(&buttons));
+        }
+
+        assert_eq!(Classic::default().buttons, buttons);
+    }
+}
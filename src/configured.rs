@@ -0,0 +1,196 @@
+//! Configured Ports
+//! ============================
+//! A `PlayStationPort` that remembers in its type how its controller was set
+//! up. Once `configure_dualshock2` has a DualShock 2 sending pressures, the
+//! `ConfiguredPort` it hands back reads `DualShock2`s straight out, with no
+//! `Device` to match on.
+//!
+//! Pulling the controller out (or someone pressing Analog) undoes the setup,
+//! so when anything else answers the plain port is handed back to be set up
+//! again.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use dualshock::DualShock2;
+use protocol::MESSAGE_MAX_LENGTH;
+use recovery::{FailFast, RecoveryPolicy};
+use super::{Error, PlayStationPort, PollCommand};
+
+/// A DualShock 2 sending button pressures, from `configure_dualshock2`
+#[derive(Copy, Clone, Debug)]
+pub struct DualShock2Mode;
+
+/// A port with its controller set up for mode `M`
+pub struct ConfiguredPort<SPI, CS, M, R = FailFast> {
+    port: PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R>,
+    mode: PhantomData<M>,
+}
+
+/// Why a port couldn't be set up or read, with the port handed back in
+/// whatever state it was left in
+pub enum ConfiguredError<SPI, CS, M, E, R = FailFast> {
+    /// The poll failed, but the controller should still be set up, so the
+    /// configured port comes back to try again
+    Failed(ConfiguredPort<SPI, CS, M, R>, Error<E>),
+    /// Nothing set up answered, so the plain port comes back to start over
+    Lost(PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R>, Error<E>),
+}
+
+/// What `configure_dualshock2` hands back
+type Configured<SPI, CS, E, R> = Result<ConfiguredPort<SPI, CS, DualShock2Mode, R>, DualShock2Error<SPI, CS, E, R>>;
+
+/// How a port set up by `configure_dualshock2` comes back
+type DualShock2Error<SPI, CS, E, R> = ConfiguredError<SPI, CS, DualShock2Mode, E, R>;
+
+impl<SPI, CS, M, E, R> ConfiguredError<SPI, CS, M, E, R> {
+    /// What went wrong, whichever port came back
+    pub fn error(&self) -> &Error<E> {
+        match self {
+            ConfiguredError::Failed(_, e) | ConfiguredError::Lost(_, e) => e,
+        }
+    }
+}
+
+/// Only the error is shown, since the ports have nothing to show
+impl<SPI, CS, M, E: fmt::Debug, R> fmt::Debug for ConfiguredError<SPI, CS, M, E, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfiguredError::Failed(_, e) => f.debug_tuple("Failed").field(e).finish(),
+            ConfiguredError::Lost(_, e) => f.debug_tuple("Lost").field(e).finish(),
+        }
+    }
+}
+
+impl<SPI, CS, M, R> ConfiguredPort<SPI, CS, M, R> {
+    /// Go back to a plain port, for talking to the controller any other way
+    pub fn degrade(self) -> PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R> {
+        self.port
+    }
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, R> PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Put a DualShock 2 into pressure mode with `enable_pressure`, then poll
+    /// it once to make sure it took. If anything but a DualShock 2 sending
+    /// pressures answers that poll, the port comes back as
+    /// `ConfiguredError::Lost`.
+    // Handing the port back in the error is the point, however big it is
+    #[allow(clippy::result_large_err)]
+    pub fn configure_dualshock2(mut self) -> Configured<SPI, CS, E, R> {
+        if let Err(e) = self.enable_pressure() {
+            return Err(ConfiguredError::Lost(self, e));
+        }
+
+        match self.read_input_as::<DualShock2>(None) {
+            Ok(_) => Ok(ConfiguredPort {
+                port: self,
+                mode: PhantomData,
+            }),
+            Err(e) => Err(ConfiguredError::Lost(self, e)),
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, R> ConfiguredPort<SPI, CS, DualShock2Mode, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Poll the DualShock 2, handing the port back along with it. A poll that
+    /// fails the way polls sometimes do leaves the port configured
+    /// (`ConfiguredError::Failed`), while any other controller answering,
+    /// or none, hands back the plain port (`ConfiguredError::Lost`).
+    #[allow(clippy::result_large_err)]
+    pub fn read_input(mut self, command: Option<&dyn PollCommand>) -> Result<(DualShock2, Self), DualShock2Error<SPI, CS, E, R>> {
+        match self.port.read_input_as::<DualShock2>(command) {
+            Ok(x) => Ok((x, self)),
+            Err(e @ Error::WrongDevice { .. }) => Err(ConfiguredError::Lost(self.port, e)),
+            Err(e) => Err(ConfiguredError::Failed(self, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::Button;
+    use mock::{self, MockPin};
+    use protocol::DeviceId;
+    use testutil::SimulatedDualShock2;
+
+    #[test]
+    fn reads_dualshock2s_straight_out() {
+        let mut controller = SimulatedDualShock2::new();
+        controller.state = DualShock2::builder().press(Button::Circle).pressure(Button::Circle, 0x90).build();
+        let port = PlayStationPort::<_, MockPin>::new(controller, None);
+
+        let port = port.configure_dualshock2().unwrap();
+        let (first, port) = port.read_input(None).unwrap();
+        assert_eq!(first, controller.state);
+        assert_eq!(first.pressure_circle(), 0x90);
+
+        // Someone presses Analog, and the controller drops back to digital
+        let mut port = port.degrade();
+        port.dev.press_analog_button();
+        port.dev.press_analog_button();
+        let port = port.configure_dualshock2().unwrap();
+        let (_, mut port) = port.read_input(None).unwrap();
+        port.port.dev.press_analog_button();
+
+        match port.read_input(None) {
+            Err(ConfiguredError::Lost(mut port, error)) => {
+                assert_eq!(error, Error::WrongDevice { expected: DeviceId::DualShockPressure, got: DeviceId::DualShockDigital });
+
+                // Set it up again and carry on
+                port.dev.press_analog_button();
+                let port = port.configure_dualshock2().unwrap();
+                assert!(port.read_input(None).is_ok());
+            },
+            x => panic!("expected to lose the configuration, got {:?}", x.map(|x| x.0)),
+        }
+    }
+
+    #[test]
+    fn unplugging_hands_back_the_port() {
+        let mut pressure = [0u8; 21];
+        pressure[..9].copy_from_slice(&[0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80]);
+        let stalled: &[u8] = &[0xff, 0x79, 0x00];
+        let unplugged: &[u8] = &[0xff, 0xff, 0xff];
+
+        // Seven commands to set it up, then the poll checking it took
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut responses = [config; 11];
+        responses[7] = &pressure;
+        responses[8] = stalled;
+        responses[9] = &pressure;
+        responses[10] = unplugged;
+
+        let port = mock::port(&responses).configure_dualshock2().unwrap();
+
+        // A stall doesn't undo anything
+        let port = match port.read_input(None) {
+            Err(ConfiguredError::Failed(port, Error::Timeout { bytes_completed: 2 })) => port,
+            x => panic!("expected a stall, got {:?}", x.map(|x| x.0)),
+        };
+        let (_, port) = port.read_input(None).unwrap();
+
+        let error = port.read_input(None).map(|x| x.0).unwrap_err();
+        assert_eq!(error.error(), &Error::WrongDevice { expected: DeviceId::DualShockPressure, got: DeviceId::NotPresent });
+        assert!(matches!(error, ConfiguredError::Lost(..)));
+
+        // And there's nothing to set up
+        assert!(matches!(mock::port(&[]).configure_dualshock2(), Err(ConfiguredError::Lost(..))));
+    }
+}
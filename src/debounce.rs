@@ -0,0 +1,103 @@
+//! Button Debouncing
+//! ============================
+//! Worn out pads (and dodgy cables) sometimes drop a held button for a single
+//! frame, which looks like a release and a press to anything watching for
+//! edges. The `Debouncer` here only lets a button change state once it has
+//! read the same way for several polls in a row.
+
+use classic::GamepadButtons;
+
+/// Filters out button changes that don't last for `N` polls in a row. A real
+/// press or release comes through `N - 1` polls late.
+#[derive(Debug)]
+pub struct Debouncer<const N: usize> {
+    history: [u16; N],
+    next: usize,
+    stable: u16,
+}
+
+impl<const N: usize> Debouncer<N> {
+    /// Create a debouncer where everything starts out released
+    pub const fn new() -> Self {
+        Self {
+            history: [0xffff; N],
+            next: 0,
+            stable: 0xffff,
+        }
+    }
+
+    /// Feed in the latest poll and get back the debounced buttons
+    pub fn update(&mut self, buttons: &GamepadButtons) -> GamepadButtons {
+        if N == 0 {
+            return *buttons;
+        }
+
+        if let Some(slot) = self.history.get_mut(self.next) {
+            *slot = buttons.data;
+        }
+        self.next = (self.next + 1) % N;
+
+        // Buttons are active low, so a bit that's zero in every frame has been
+        // held throughout and one that's set in every frame has been released
+        let mut held = 0xffff;
+        let mut released = 0xffff;
+        for &frame in self.history.iter() {
+            held &= !frame;
+            released &= frame;
+        }
+
+        self.stable = (self.stable | released) & !held;
+
+        self.stable()
+    }
+
+    /// The debounced buttons as of the last update
+    pub fn stable(&self) -> GamepadButtons {
+        GamepadButtons { data: self.stable }
+    }
+}
+
+impl<const N: usize> Default for Debouncer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CROSS: u16 = 0x4000;
+
+    fn frame(data: u16) -> GamepadButtons {
+        GamepadButtons { data }
+    }
+
+    #[test]
+    fn single_frame_dropout_is_filtered() {
+        let mut debouncer = Debouncer::<3>::new();
+        let held = !CROSS;
+
+        // Press, hold through a one frame dropout, keep holding
+        let input = [held, held, held, 0xffff, held, held];
+        let expected = [false, false, true, true, true, true];
+
+        for (&raw, &cross) in input.iter().zip(expected.iter()) {
+            assert_eq!(debouncer.update(&frame(raw)).cross(), cross);
+        }
+    }
+
+    #[test]
+    fn genuine_changes_come_through_late() {
+        let mut debouncer = Debouncer::<2>::new();
+
+        // A one frame blip never makes it through
+        assert!(!debouncer.update(&frame(!CROSS)).cross());
+        assert!(!debouncer.update(&frame(0xffff)).cross());
+
+        assert!(!debouncer.update(&frame(!CROSS)).cross());
+        assert!(debouncer.update(&frame(!CROSS)).cross());
+        assert!(debouncer.update(&frame(0xffff)).cross());
+        assert!(!debouncer.update(&frame(0xffff)).cross());
+    }
+}
@@ -0,0 +1,166 @@
+//! Device Diffs
+//! ============================
+//! Works out what changed between two snapshots of a controller, for sending
+//! only the changes over a slow link or logging them. Everything is fixed size
+//! so a diff can be built and thrown away every poll.
+
+use core::mem;
+
+use classic::{ButtonChanges, GamepadButtons};
+use super::Device;
+
+/// What changed between two snapshots of a controller. Created by
+/// `Device::diff`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DeviceDiff {
+    /// Whether a different kind of controller answered, like a DualShock
+    /// switching to analog mode. When it did, nothing else is filled in since
+    /// the two snapshots can't be lined up.
+    pub kind_changed: bool,
+    /// Buttons pressed and released in between, for controllers with the
+    /// standard buttons
+    pub buttons: ButtonChanges,
+    /// How far each stick axis moved, in the order of `sticks::Axis`. Positive
+    /// is right or down. Always zero for controllers without sticks.
+    pub sticks: [i16; 4],
+    /// How far each button pressure moved, in the same order as
+    /// `DualShock2::pressures`. Always zero for anything but a DualShock 2.
+    pub pressures: [i16; 12],
+}
+
+impl DeviceDiff {
+    fn unchanged() -> Self {
+        let released = GamepadButtons::all_released();
+
+        Self {
+            kind_changed: false,
+            buttons: released.changes_since(&released),
+            sticks: [0; 4],
+            pressures: [0; 12],
+        }
+    }
+
+    /// Whether nothing changed at all
+    pub fn is_empty(&self) -> bool {
+        !self.kind_changed
+            && self.buttons.is_empty()
+            && self.sticks == [0; 4]
+            && self.pressures == [0; 12]
+    }
+
+    /// The index of each pressure that moved, in order
+    pub fn changed_pressures(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pressures.iter()
+            .enumerate()
+            .filter(|&(_, &x)| x != 0)
+            .map(|(index, _)| index)
+    }
+}
+
+pub(crate) fn sticks(device: &Device) -> Option<[u8; 4]> {
+    match device {
+        Device::AnalogJoystick(x) | Device::DualShock(x) => Some([x.lx, x.ly, x.rx, x.ry]),
+        Device::DualShock2(x) => Some([x.lx, x.ly, x.rx, x.ry]),
+        _ => None,
+    }
+}
+
+fn deltas<const N: usize>(now: &[u8; N], before: &[u8; N]) -> [i16; N] {
+    let mut deltas = [0; N];
+    for (delta, (&now, &before)) in deltas.iter_mut().zip(now.iter().zip(before.iter())) {
+        *delta = i16::from(now) - i16::from(before);
+    }
+
+    deltas
+}
+
+impl Device {
+    /// What changed since `previous`. Comparing two different kinds of
+    /// controller only reports the change of kind.
+    pub fn diff(&self, previous: &Device) -> DeviceDiff {
+        let mut diff = DeviceDiff::unchanged();
+
+        if mem::discriminant(self) != mem::discriminant(previous) {
+            diff.kind_changed = true;
+            return diff;
+        }
+
+        if let (Some(now), Some(before)) = (self.buttons(), previous.buttons()) {
+            diff.buttons = now.changes_since(&before);
+        }
+
+        if let (Some(now), Some(before)) = (sticks(self), sticks(previous)) {
+            diff.sticks = deltas(&now, &before);
+        }
+
+        if let (Device::DualShock2(now), Device::DualShock2(before)) = (self, previous) {
+            diff.pressures = deltas(&now.pressures, &before.pressures);
+        }
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::{Button, Classic};
+    use dualshock::{DualShock, DualShock2};
+
+    #[test]
+    fn same_kind() {
+        let before = DualShock::builder().press(Button::Cross).stick_left(0x80, 0x80).stick_right(0x10, 0xf0).build();
+        let now = DualShock::builder().press(Button::Circle).stick_left(0x90, 0x80).stick_right(0x00, 0xff).build();
+        let diff = Device::DualShock(now).diff(&Device::DualShock(before));
+
+        assert!(!diff.kind_changed);
+        assert!(diff.buttons.just_pressed(Button::Circle));
+        assert!(diff.buttons.just_released(Button::Cross));
+        assert_eq!(diff.sticks, [0x10, 0, -0x10, 0x0f]);
+        assert_eq!(diff.changed_pressures().count(), 0);
+        assert!(!diff.is_empty());
+
+        let device = Device::DualShock(now);
+        assert!(device.diff(&device).is_empty());
+    }
+
+    #[test]
+    fn pressures() {
+        let before = DualShock2::builder().pressure(Button::Cross, 0x40).build();
+        let now = DualShock2::builder()
+            .press(Button::Cross)
+            .pressure(Button::Cross, 0xff)
+            .pressure(Button::Up, 0x20)
+            .build();
+        let diff = Device::DualShock2(now).diff(&Device::DualShock2(before));
+
+        assert!(diff.buttons.just_pressed(Button::Cross));
+        assert_eq!(diff.sticks, [0; 4]);
+
+        let mut changed = [0; 12];
+        let mut count = 0;
+        for index in diff.changed_pressures() {
+            changed[count] = index;
+            count += 1;
+        }
+        assert_eq!(changed[..count], [2, 6]);
+        assert_eq!((diff.pressures[2], diff.pressures[6]), (0x20, 0xbf));
+    }
+
+    #[test]
+    fn different_kinds() {
+        let classic = Classic::builder().press(Button::Start).build();
+        let dualshock = DualShock::builder().stick_left(0x00, 0x00).build();
+        let diff = Device::DualShock(dualshock).diff(&Device::Classic(classic));
+
+        assert!(diff.kind_changed);
+        assert!(diff.buttons.is_empty());
+        assert_eq!(diff.sticks, [0; 4]);
+        assert!(!diff.is_empty());
+
+        // The flight stick sends the same data as a DualShock, but is still a
+        // different kind of controller
+        let diff = Device::AnalogJoystick(dualshock).diff(&Device::DualShock(dualshock));
+        assert!(diff.kind_changed);
+    }
+}
@@ -0,0 +1,162 @@
+//! Two Ports
+//! ============================
+//! A console polls both controller ports back to back, once a frame. Doing
+//! the same with two `PlayStationPort`s one `read_input` after the other
+//! leaves all the parsing and bookkeeping of the first poll in between the
+//! two transfers, which is that much more time between when each player's
+//! buttons were read.
+//!
+//! `DualPort::read_both` clocks both frames first and only then parses them,
+//! so nothing but the second port's transfer setup sits in between. Each
+//! port keeps its own state, and a poll that fails on one port has no effect
+//! on the other.
+//!
+//! The two ports can be on SPI buses of their own, or share one through
+//! something like `shared-bus`, each with its own select pin.
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use super::{Device, Error, PlayStationPort, MESSAGE_MAX_LENGTH};
+
+/// Two controller ports polled together
+pub struct DualPort<SPI1, CS1, SPI2, CS2> {
+    first: PlayStationPort<SPI1, CS1>,
+    second: PlayStationPort<SPI2, CS2>,
+}
+
+#[allow(deprecated)]
+impl<E, SPI1, CS1, SPI2, CS2> DualPort<SPI1, CS1, SPI2, CS2>
+where
+    SPI1: spi::Transfer<u8, Error = E>,
+    CS1: OutputPin,
+    SPI2: spi::Transfer<u8, Error = E>,
+    CS2: OutputPin {
+
+    /// Poll `first` and `second` together. Set up each one beforehand, say
+    /// with `enable_pressure`, or through `first` and `second` later.
+    pub fn new(first: PlayStationPort<SPI1, CS1>, second: PlayStationPort<SPI2, CS2>) -> Self {
+        Self { first, second }
+    }
+
+    /// The first port, player one on a console
+    pub fn first(&mut self) -> &mut PlayStationPort<SPI1, CS1> {
+        &mut self.first
+    }
+
+    /// The second port
+    pub fn second(&mut self) -> &mut PlayStationPort<SPI2, CS2> {
+        &mut self.second
+    }
+
+    /// Give back both ports
+    pub fn release(self) -> (PlayStationPort<SPI1, CS1>, PlayStationPort<SPI2, CS2>) {
+        (self.first, self.second)
+    }
+
+    /// Poll both ports, the first then straight away the second, the same as
+    /// `read_input` on each. Each port's poll succeeds or fails on its own.
+    pub fn read_both(&mut self) -> (Result<Device, Error<E>>, Result<Device, Error<E>>) {
+        let mut first = [0u8; MESSAGE_MAX_LENGTH];
+        let mut second = [0u8; MESSAGE_MAX_LENGTH];
+
+        let first_len = self.first.clock_input(None, &mut first);
+        let second_len = self.second.clock_input(None, &mut second);
+
+        (
+            first_len.and_then(|(len, _)| Ok(self.first.parse_and_record(&first, len)?.0.to_owned())),
+            second_len.and_then(|(len, _)| Ok(self.second.parse_and_record(&second, len)?.0.to_owned())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::cell::RefCell;
+    use self::std::vec::Vec;
+    use super::*;
+    use hal::blocking::spi::Transfer;
+    use mock::MockSpi;
+
+    type Log<'a> = &'a RefCell<Vec<&'static str>>;
+
+    /// Notes each transfer, so the order across both ports shows
+    struct Spi<'a>(MockSpi, Log<'a>, &'static str);
+
+    impl<'a> Transfer<u8> for Spi<'a> {
+        type Error = ();
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+            self.1.borrow_mut().push(self.2);
+            self.0.transfer(words)
+        }
+    }
+
+    struct Pin;
+
+    #[allow(deprecated)]
+    impl OutputPin for Pin {
+        fn set_low(&mut self) {}
+
+        fn set_high(&mut self) {}
+    }
+
+    const DUALSHOCK: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+    const CLASSIC: &[u8] = &[0xff, 0x41, 0x5a, 0xfe, 0xff];
+
+    fn ports<'a>(log: Log<'a>, first: &[&[u8]], second: &[&[u8]]) -> DualPort<Spi<'a>, Pin, Spi<'a>, Pin> {
+        DualPort::new(
+            PlayStationPort::new(Spi(MockSpi::new(first), log, "first"), Some(Pin)),
+            PlayStationPort::new(Spi(MockSpi::new(second), log, "second"), Some(Pin)),
+        )
+    }
+
+    #[test]
+    fn both_ports_are_read() {
+        let log = RefCell::new(Vec::new());
+        let mut dual = ports(&log, &[DUALSHOCK, DUALSHOCK], &[CLASSIC]);
+
+        let (first, second) = dual.read_both();
+        assert!(matches!(first, Ok(Device::DualShock(_))));
+        assert!(matches!(second, Ok(Device::Classic(x)) if x.buttons.select()));
+        assert_eq!(*log.borrow(), ["first", "second"]);
+
+        // The second controller is pulled out, which takes a second, longer
+        // poll to be sure of, without holding the first port back
+        log.borrow_mut().clear();
+        let (first, second) = dual.read_both();
+        assert!(matches!(first, Ok(Device::DualShock(_))));
+        assert_eq!(second, Ok(Device::None));
+        assert_eq!(*log.borrow(), ["first", "second", "second"]);
+        assert!(dual.first().last_input().is_some());
+        assert!(dual.second().last_input().is_none());
+    }
+
+    #[test]
+    fn a_failure_stays_on_its_own_port() {
+        let garbled: &[u8] = &[0x00, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let log = RefCell::new(Vec::new());
+        let mut dual = ports(&log, &[DUALSHOCK, DUALSHOCK], &[garbled, CLASSIC]);
+
+        dual.first().dev.0.fail = true;
+        let (first, second) = dual.read_both();
+        assert_eq!(first, Err(Error::Spi(())));
+        assert!(matches!(second, Err(Error::Garbage { .. })));
+
+        // Neither failure is left behind for the next poll
+        dual.first().dev.0.fail = false;
+        let (first, second) = dual.read_both();
+        assert!(matches!(first, Ok(Device::DualShock(_))));
+        assert!(matches!(second, Ok(Device::Classic(_))));
+        assert_eq!(dual.first().last_error(), None);
+        assert_eq!(dual.second().last_error(), None);
+
+        let (first, second) = dual.release();
+        assert_eq!(first.stats().spi_errors, 1);
+        assert_eq!(second.stats().garbage, 1);
+        assert_eq!(second.stats().spi_errors, 0);
+    }
+}
@@ -6,14 +6,45 @@
 //! This also maps for the the Dual Analog (precursor to the Dual Shock) and
 //! the Analog controller (flight stick) as they both have the same buttons
 
-use classic::GamepadButtons;
+use classic::{Button, Classic, GamepadButtons};
+use protocol::{fill, leading};
+use sticks::{self, Axis, StickCalibration};
 use super::{
+    Device,
+    DeviceId,
+    FromDevice,
+    HasAnalogSticks,
     HasStandardButtons,
     PollCommand
 };
 
+/// Where an analog stick sits when nobody is touching it
+pub const STICK_CENTER: u8 = 0x80;
+
+/// Shift a raw stick byte so the center sits on zero. This is a straight shift
+/// with no clamping, so 0x80 becomes 0, 0x00 becomes -128 and 0xff becomes 127.
+/// That makes the negative side one step longer than the positive side. The
+/// axes aren't flipped either, so up and left are still the negative ends.
+pub fn signed(raw: u8) -> i8 {
+    (raw ^ STICK_CENTER) as i8
+}
+
+/// Scale a raw stick byte to the range -1.0 to 1.0 using
+/// `(raw - 128) / 127`, clamped. The center (0x80) comes out as exactly 0.0
+/// and 0xff as exactly 1.0. Both sides use the same scale, so the one extra
+/// step on the negative side means 0x00 and 0x01 both come out as -1.0.
+#[cfg(feature = "float")]
+pub fn normalized(raw: u8) -> f32 {
+    let value = f32::from(signed(raw)) / 127.0;
+
+    if value < -1.0 { -1.0 } else { value }
+}
+
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the DualShock 1 controller
 pub struct DualShock {
     /// Standard buttons (Cross, Circle, L3, Start, etc)
@@ -29,14 +60,153 @@ pub struct DualShock {
     pub ly: u8,
 }
 
+impl DualShock {
+    /// Read a DualShock from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        DualShockRef::new(frame).map(|x| x.to_owned())
+    }
+
+    /// Write a DualShock back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.bits().to_le_bytes();
+        fill(frame, &[low, high, self.rx, self.ry, self.lx, self.ly]);
+    }
+
+    /// Start building a controller state by hand, with nothing held and the
+    /// sticks centered
+    pub fn builder() -> DualShockBuilder {
+        DualShockBuilder { value: DualShock::default() }
+    }
+
+    /// Right stick left and right, centered on zero. See `signed` for the mapping.
+    pub fn rx_signed(&self) -> i8 {
+        signed(self.rx)
+    }
+
+    /// Right stick up and down, centered on zero. See `signed` for the mapping.
+    pub fn ry_signed(&self) -> i8 {
+        signed(self.ry)
+    }
+
+    /// Left stick left and right, centered on zero. See `signed` for the mapping.
+    pub fn lx_signed(&self) -> i8 {
+        signed(self.lx)
+    }
+
+    /// Left stick up and down, centered on zero. See `signed` for the mapping.
+    pub fn ly_signed(&self) -> i8 {
+        signed(self.ly)
+    }
+
+    /// Right stick left and right from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn rx_f32(&self) -> f32 {
+        normalized(self.rx)
+    }
+
+    /// Right stick up and down from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn ry_f32(&self) -> f32 {
+        normalized(self.ry)
+    }
+
+    /// Left stick left and right from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn lx_f32(&self) -> f32 {
+        normalized(self.lx)
+    }
+
+    /// Left stick up and down from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn ly_f32(&self) -> f32 {
+        normalized(self.ly)
+    }
+
+    /// Angle and distance of the left stick. See `sticks::stick_polar`.
+    pub fn left_polar(&self) -> (i16, u8) {
+        sticks::stick_polar(self.lx_signed(), self.ly_signed())
+    }
+
+    /// Angle and distance of the right stick. See `sticks::stick_polar`.
+    pub fn right_polar(&self) -> (i16, u8) {
+        sticks::stick_polar(self.rx_signed(), self.ry_signed())
+    }
+
+    /// The controller's bytes as they came off the wire, without the header
+    pub fn as_bytes(&self) -> [u8; 6] {
+        let mut bytes = [0u8; 6];
+        self.write_frame(&mut bytes);
+
+        bytes
+    }
+
+    /// Whether all four axes are within `tolerance` of center. See
+    /// `HasAnalogSticks::sticks_neutral`.
+    pub fn sticks_neutral(&self, tolerance: u8) -> bool {
+        HasAnalogSticks::sticks_neutral(self, tolerance)
+    }
+
+    /// A copy with the sticks corrected by `calibration`
+    pub fn calibrated(&self, calibration: &StickCalibration) -> Self {
+        Self {
+            rx: calibration.apply(self.rx, Axis::RightX),
+            ry: calibration.apply(self.ry, Axis::RightY),
+            lx: calibration.apply(self.lx, Axis::LeftX),
+            ly: calibration.apply(self.ly, Axis::LeftY),
+            ..*self
+        }
+    }
+}
+
+/// Nothing held and both sticks centered at `STICK_CENTER`
+impl Default for DualShock {
+    fn default() -> Self {
+        Self {
+            buttons: GamepadButtons::all_released(),
+            rx: STICK_CENTER,
+            ry: STICK_CENTER,
+            lx: STICK_CENTER,
+            ly: STICK_CENTER,
+        }
+    }
+}
+
+impl HasAnalogSticks for DualShock {
+    fn left_stick(&self) -> (u8, u8) {
+        (self.lx, self.ly)
+    }
+
+    fn right_stick(&self) -> (u8, u8) {
+        (self.rx, self.ry)
+    }
+}
+
 impl HasStandardButtons for DualShock {
-    fn buttons(&self) -> GamepadButtons {
-        self.buttons.clone()
+    fn buttons(&self) -> &GamepadButtons {
+        &self.buttons
+    }
+}
+
+/// A DualShock, the flight stick (which sends the same thing) or a DualShock
+/// 2 without its pressures
+impl FromDevice for DualShock {
+    const EXPECTED: DeviceId = DeviceId::DualShockAnalog;
+
+    fn from_device(device: &Device) -> Option<Self> {
+        match device {
+            Device::DualShock(x) | Device::AnalogJoystick(x) => Some(*x),
+            Device::DualShock2(x) => Some((*x).into()),
+            _ => None,
+        }
     }
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the DualShock 2 controller
 pub struct DualShock2 {
     /// Standard buttons (Cross, Circle, L3, Start, etc)
@@ -51,19 +221,495 @@ pub struct DualShock2 {
     /// Left analog stick, up and down
     pub ly: u8,
 
-    /// List of possible pressure readings from the buttons
-    /// Note that these are configurable length
-    pub pressures: [u8; 8],
+    /// Pressure readings from the buttons, 0 when let go. In full pressure
+    /// mode these are right, left, up, down, triangle, circle, cross, square,
+    /// L1, R1, L2 and R2. The named accessors are easier than remembering that.
+    pub pressures: [u8; 12],
+}
+
+impl DualShock2 {
+    /// Read a DualShock 2 from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        DualShock2Ref::new(frame).map(|x| x.to_owned())
+    }
+
+    /// Write a DualShock 2 back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        DualShock::from(*self).write_frame(frame);
+        if let Some(pressures) = frame.get_mut(6..) {
+            fill(pressures, &self.pressures);
+        }
+    }
+
+    /// Start building a controller state by hand, with nothing held, the
+    /// sticks centered and no pressure on anything
+    pub fn builder() -> DualShock2Builder {
+        DualShock2Builder { value: DualShock2::default() }
+    }
+
+    /// Right stick left and right, centered on zero. See `signed` for the mapping.
+    pub fn rx_signed(&self) -> i8 {
+        signed(self.rx)
+    }
+
+    /// Right stick up and down, centered on zero. See `signed` for the mapping.
+    pub fn ry_signed(&self) -> i8 {
+        signed(self.ry)
+    }
+
+    /// Left stick left and right, centered on zero. See `signed` for the mapping.
+    pub fn lx_signed(&self) -> i8 {
+        signed(self.lx)
+    }
+
+    /// Left stick up and down, centered on zero. See `signed` for the mapping.
+    pub fn ly_signed(&self) -> i8 {
+        signed(self.ly)
+    }
+
+    /// Right stick left and right from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn rx_f32(&self) -> f32 {
+        normalized(self.rx)
+    }
+
+    /// Right stick up and down from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn ry_f32(&self) -> f32 {
+        normalized(self.ry)
+    }
+
+    /// Left stick left and right from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn lx_f32(&self) -> f32 {
+        normalized(self.lx)
+    }
+
+    /// Left stick up and down from -1.0 to 1.0. See `normalized` for the mapping.
+    #[cfg(feature = "float")]
+    pub fn ly_f32(&self) -> f32 {
+        normalized(self.ly)
+    }
+
+    /// How hard Right is pressed, 0 when let go
+    pub fn pressure_right(&self) -> u8 {
+        self.pressures[0]
+    }
+
+    /// How hard Left is pressed, 0 when let go
+    pub fn pressure_left(&self) -> u8 {
+        self.pressures[1]
+    }
+
+    /// How hard Up is pressed, 0 when let go
+    pub fn pressure_up(&self) -> u8 {
+        self.pressures[2]
+    }
+
+    /// How hard Down is pressed, 0 when let go
+    pub fn pressure_down(&self) -> u8 {
+        self.pressures[3]
+    }
+
+    /// How hard Triangle is pressed, 0 when let go
+    pub fn pressure_triangle(&self) -> u8 {
+        self.pressures[4]
+    }
+
+    /// How hard Circle is pressed, 0 when let go
+    pub fn pressure_circle(&self) -> u8 {
+        self.pressures[5]
+    }
+
+    /// How hard Cross is pressed, 0 when let go
+    pub fn pressure_cross(&self) -> u8 {
+        self.pressures[6]
+    }
+
+    /// How hard Square is pressed, 0 when let go
+    pub fn pressure_square(&self) -> u8 {
+        self.pressures[7]
+    }
+
+    /// How hard L1 is pressed, 0 when let go
+    pub fn pressure_l1(&self) -> u8 {
+        self.pressures[8]
+    }
+
+    /// How hard R1 is pressed, 0 when let go
+    pub fn pressure_r1(&self) -> u8 {
+        self.pressures[9]
+    }
+
+    /// How hard L2 is pressed, 0 when let go
+    pub fn pressure_l2(&self) -> u8 {
+        self.pressures[10]
+    }
+
+    /// How hard R2 is pressed, 0 when let go
+    pub fn pressure_r2(&self) -> u8 {
+        self.pressures[11]
+    }
+
+    /// Work out which buttons are held from their pressures instead of the
+    /// digital bits, which some clone pads don't keep in step. A button goes
+    /// down once its pressure reaches `press_at` and comes back up once it
+    /// drops to `release_at` or below. In between it stays the way it was in
+    /// `prev`, the result from the last poll, so a press hovering around one
+    /// value doesn't flicker. `press_at` should be above `release_at`.
+    ///
+    /// Select, Start, L3 and R3 have no pressure reading and just use their
+    /// digital bits.
+    pub fn buttons_from_pressure(&self, press_at: u8, release_at: u8, prev: &GamepadButtons) -> GamepadButtons {
+        let mut buttons = self.buttons;
+
+        for &button in Button::ALL.iter() {
+            let pressure = match pressure_index(button).and_then(|x| self.pressures.get(x)) {
+                Some(&x) => x,
+                None => continue,
+            };
+
+            let held = if pressure >= press_at {
+                true
+            } else if pressure <= release_at {
+                false
+            } else {
+                prev.is_pressed(button)
+            };
+
+            if held {
+                buttons.data &= !button.mask();
+            } else {
+                buttons.data |= button.mask();
+            }
+        }
+
+        buttons
+    }
+
+    /// Angle and distance of the left stick. See `sticks::stick_polar`.
+    pub fn left_polar(&self) -> (i16, u8) {
+        sticks::stick_polar(self.lx_signed(), self.ly_signed())
+    }
+
+    /// Angle and distance of the right stick. See `sticks::stick_polar`.
+    pub fn right_polar(&self) -> (i16, u8) {
+        sticks::stick_polar(self.rx_signed(), self.ry_signed())
+    }
+
+    /// The controller's bytes as they came off the wire, without the header
+    pub fn as_bytes(&self) -> [u8; 18] {
+        let mut bytes = [0u8; 18];
+        self.write_frame(&mut bytes);
+
+        bytes
+    }
+
+    /// Whether all four axes are within `tolerance` of center. See
+    /// `HasAnalogSticks::sticks_neutral`.
+    pub fn sticks_neutral(&self, tolerance: u8) -> bool {
+        HasAnalogSticks::sticks_neutral(self, tolerance)
+    }
+
+    /// A copy with the sticks corrected by `calibration`
+    pub fn calibrated(&self, calibration: &StickCalibration) -> Self {
+        Self {
+            rx: calibration.apply(self.rx, Axis::RightX),
+            ry: calibration.apply(self.ry, Axis::RightY),
+            lx: calibration.apply(self.lx, Axis::LeftX),
+            ly: calibration.apply(self.ly, Axis::LeftY),
+            ..*self
+        }
+    }
+}
+
+/// Nothing held, both sticks centered at `STICK_CENTER` and no pressure on
+/// any button
+impl Default for DualShock2 {
+    fn default() -> Self {
+        Self {
+            buttons: GamepadButtons::all_released(),
+            rx: STICK_CENTER,
+            ry: STICK_CENTER,
+            lx: STICK_CENTER,
+            ly: STICK_CENTER,
+            pressures: [0; 12],
+        }
+    }
+}
+
+impl HasAnalogSticks for DualShock2 {
+    fn left_stick(&self) -> (u8, u8) {
+        (self.lx, self.ly)
+    }
+
+    fn right_stick(&self) -> (u8, u8) {
+        (self.rx, self.ry)
+    }
 }
 
 impl HasStandardButtons for DualShock2 {
-    fn buttons(&self) -> GamepadButtons {
-        self.buttons.clone()
+    fn buttons(&self) -> &GamepadButtons {
+        &self.buttons
+    }
+}
+
+impl FromDevice for DualShock2 {
+    const EXPECTED: DeviceId = DeviceId::DualShockPressure;
+
+    fn from_device(device: &Device) -> Option<Self> {
+        device.as_dualshock2().copied()
+    }
+}
+
+/// A `DualShock` read straight out of a poll buffer as it's asked for, rather
+/// than copied out up front
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DualShockRef<'a> {
+    frame: &'a [u8; 6],
+}
+
+impl<'a> DualShockRef<'a> {
+    /// View the bytes that follow a frame's header as a DualShock. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn new(frame: &'a [u8]) -> Option<Self> {
+        Some(Self { frame: leading(frame)? })
+    }
+
+    /// Standard buttons (Cross, Circle, L3, Start, etc)
+    pub fn buttons(&self) -> GamepadButtons {
+        GamepadButtons::from_bits(u16::from_le_bytes([self.frame[0], self.frame[1]]))
+    }
+
+    /// Right analog stick, left and right
+    pub fn rx(&self) -> u8 {
+        self.frame[2]
+    }
+
+    /// Right analog stick, up and down
+    pub fn ry(&self) -> u8 {
+        self.frame[3]
+    }
+
+    /// Left analog stick, left and right
+    pub fn lx(&self) -> u8 {
+        self.frame[4]
+    }
+
+    /// Left analog stick, up and down
+    pub fn ly(&self) -> u8 {
+        self.frame[5]
+    }
+
+    /// Copy the controller out of the buffer
+    pub fn to_owned(&self) -> DualShock {
+        DualShock {
+            buttons: self.buttons(),
+            rx: self.rx(),
+            ry: self.ry(),
+            lx: self.lx(),
+            ly: self.ly(),
+        }
+    }
+}
+
+/// A `DualShock2` read straight out of a poll buffer as it's asked for, rather
+/// than copied out up front
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DualShock2Ref<'a> {
+    dualshock: DualShockRef<'a>,
+    pressures: &'a [u8; 12],
+}
+
+impl<'a> DualShock2Ref<'a> {
+    /// View the bytes that follow a frame's header as a DualShock 2. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn new(frame: &'a [u8]) -> Option<Self> {
+        Some(Self {
+            dualshock: DualShockRef::new(frame)?,
+            pressures: leading(frame.get(6..)?)?,
+        })
+    }
+
+    /// The sticks and buttons, which sit where a DualShock's do
+    pub fn dualshock(&self) -> DualShockRef<'a> {
+        self.dualshock
+    }
+
+    /// Standard buttons (Cross, Circle, L3, Start, etc)
+    pub fn buttons(&self) -> GamepadButtons {
+        self.dualshock().buttons()
+    }
+
+    /// Right analog stick, left and right
+    pub fn rx(&self) -> u8 {
+        self.dualshock().rx()
+    }
+
+    /// Right analog stick, up and down
+    pub fn ry(&self) -> u8 {
+        self.dualshock().ry()
+    }
+
+    /// Left analog stick, left and right
+    pub fn lx(&self) -> u8 {
+        self.dualshock().lx()
+    }
+
+    /// Left analog stick, up and down
+    pub fn ly(&self) -> u8 {
+        self.dualshock().ly()
+    }
+
+    /// The pressure readings, in the same order as `DualShock2::pressures`
+    pub fn pressures(&self) -> &'a [u8] {
+        self.pressures
+    }
+
+    /// Copy the controller out of the buffer
+    pub fn to_owned(&self) -> DualShock2 {
+        let mut pressures = [0; 12];
+        pressures.copy_from_slice(self.pressures());
+
+        DualShock2 {
+            buttons: self.buttons(),
+            rx: self.rx(),
+            ry: self.ry(),
+            lx: self.lx(),
+            ly: self.ly(),
+            pressures,
+        }
+    }
+}
+
+/// Builds a `DualShock` by hand, for tests and simulators. Created by
+/// `DualShock::builder()`.
+#[derive(Copy, Clone, Debug)]
+pub struct DualShockBuilder {
+    value: DualShock,
+}
+
+impl DualShockBuilder {
+    /// Hold a button
+    pub fn press(mut self, button: Button) -> Self {
+        self.value.buttons.data &= !button.mask();
+        self
+    }
+
+    /// Set where the left stick is, as raw bytes
+    pub fn stick_left(mut self, x: u8, y: u8) -> Self {
+        self.value.lx = x;
+        self.value.ly = y;
+        self
+    }
+
+    /// Set where the right stick is, as raw bytes
+    pub fn stick_right(mut self, x: u8, y: u8) -> Self {
+        self.value.rx = x;
+        self.value.ry = y;
+        self
+    }
+
+    /// Finish building
+    pub fn build(self) -> DualShock {
+        self.value
+    }
+}
+
+/// Builds a `DualShock2` by hand, for tests and simulators. Created by
+/// `DualShock2::builder()`.
+#[derive(Copy, Clone, Debug)]
+pub struct DualShock2Builder {
+    value: DualShock2,
+}
+
+impl DualShock2Builder {
+    /// Hold a button. This doesn't set any pressure, see `pressure`.
+    pub fn press(mut self, button: Button) -> Self {
+        self.value.buttons.data &= !button.mask();
+        self
+    }
+
+    /// Set where the left stick is, as raw bytes
+    pub fn stick_left(mut self, x: u8, y: u8) -> Self {
+        self.value.lx = x;
+        self.value.ly = y;
+        self
+    }
+
+    /// Set where the right stick is, as raw bytes
+    pub fn stick_right(mut self, x: u8, y: u8) -> Self {
+        self.value.rx = x;
+        self.value.ry = y;
+        self
+    }
+
+    /// Set how hard a button is pressed. This doesn't hold the button, see
+    /// `press`. Select, Start, L3 and R3 have no pressure reading, so they're
+    /// ignored.
+    pub fn pressure(mut self, button: Button, value: u8) -> Self {
+        if let Some(pressure) = pressure_index(button).and_then(|x| self.value.pressures.get_mut(x)) {
+            *pressure = value;
+        }
+        self
+    }
+
+    /// Finish building
+    pub fn build(self) -> DualShock2 {
+        self.value
+    }
+}
+
+/// Where a button's reading sits in `DualShock2::pressures`
+pub(crate) fn pressure_index(button: Button) -> Option<usize> {
+    match button {
+        Button::Right => Some(0),
+        Button::Left => Some(1),
+        Button::Up => Some(2),
+        Button::Down => Some(3),
+        Button::Triangle => Some(4),
+        Button::Circle => Some(5),
+        Button::Cross => Some(6),
+        Button::Square => Some(7),
+        Button::L1 => Some(8),
+        Button::R1 => Some(9),
+        Button::L2 => Some(10),
+        Button::R2 => Some(11),
+        Button::Select | Button::L3 | Button::R3 | Button::Start => None,
+    }
+}
+
+/// Drops the pressure readings
+impl From<DualShock2> for DualShock {
+    fn from(ds2: DualShock2) -> Self {
+        Self {
+            buttons: ds2.buttons,
+            rx: ds2.rx,
+            ry: ds2.ry,
+            lx: ds2.lx,
+            ly: ds2.ly,
+        }
+    }
+}
+
+/// Keeps only the buttons
+impl From<DualShock> for Classic {
+    fn from(ds: DualShock) -> Self {
+        Self { buttons: ds.buttons }
+    }
+}
+
+/// Keeps only the buttons
+impl From<DualShock2> for Classic {
+    fn from(ds2: DualShock2) -> Self {
+        Self { buttons: ds2.buttons }
     }
 }
 
 /// Command for controlling the vibration motors in the
 /// dual shock controllers
+#[derive(Debug)]
 pub struct ControlDS {
     /// Whether to turn on the small motor
     pub little: bool,
@@ -75,8 +721,8 @@ impl ControlDS {
     /// Create a new one of thes newfangled control commands
     pub fn new(little: bool, big: u8) -> Self {
         Self {
-            little: little,
-            big: big,
+            little,
+            big,
         }
     }
 }
@@ -86,7 +732,223 @@ impl ControlDS {
 impl PollCommand for ControlDS {
     /// Sets the command for the rumble motoros on the DualShock
     fn set_command(&self, command: &mut [u8]) {
-        command[0] = if self.little { 0xff } else { 0x00 };
-        command[1] = self.big;
+        fill(command, &[if self.little { 0xff } else { 0x00 }, self.big]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_neutral() {
+        let ds = DualShock::default();
+
+        assert_eq!(ds.buttons, GamepadButtons::all_released());
+        assert_eq!([ds.lx, ds.ly, ds.rx, ds.ry], [0x80; 4]);
+
+        let ds2 = DualShock2::default();
+
+        assert_eq!(ds2.buttons, GamepadButtons::all_released());
+        assert_eq!([ds2.lx, ds2.ly, ds2.rx, ds2.ry], [0x80; 4]);
+        assert!(ds2.pressures.iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn signed_sticks() {
+        let cases = [(0x00, -128), (0x7f, -1), (0x80, 0), (0x81, 1), (0xff, 127)];
+
+        for &(raw, expected) in cases.iter() {
+            let ds = DualShock { rx: raw, ry: raw, lx: raw, ly: raw, ..Default::default() };
+            let ds2 = DualShock2 { rx: raw, ry: raw, lx: raw, ly: raw, ..Default::default() };
+
+            assert_eq!(signed(raw), expected);
+            assert_eq!([ds.rx_signed(), ds.ry_signed(), ds.lx_signed(), ds.ly_signed()], [expected; 4]);
+            assert_eq!([ds2.rx_signed(), ds2.ry_signed(), ds2.lx_signed(), ds2.ly_signed()], [expected; 4]);
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn normalized_sticks() {
+        let cases = [(0x00, -1.0), (0x7f, -1.0 / 127.0), (0x80, 0.0), (0x81, 1.0 / 127.0), (0xff, 1.0)];
+
+        for &(raw, expected) in cases.iter() {
+            let ds = DualShock { rx: raw, ry: raw, lx: raw, ly: raw, ..Default::default() };
+            let ds2 = DualShock2 { rx: raw, ry: raw, lx: raw, ly: raw, ..Default::default() };
+
+            for &value in [ds.rx_f32(), ds.ry_f32(), ds.lx_f32(), ds.ly_f32()].iter() {
+                assert!((value - expected).abs() < 1e-6);
+            }
+            for &value in [ds2.rx_f32(), ds2.ry_f32(), ds2.lx_f32(), ds2.ly_f32()].iter() {
+                assert!((value - expected).abs() < 1e-6);
+            }
+        }
+
+        assert_eq!(normalized(0x80), 0.0);
+        assert_eq!(normalized(0x01), -1.0);
+    }
+
+    #[test]
+    fn polar_sticks() {
+        let ds = DualShock { lx: 0x80, ly: 0x00, rx: 0xff, ry: 0x80, ..Default::default() };
+        assert_eq!(ds.left_polar(), (-90, 128));
+        assert_eq!(ds.right_polar(), (0, 127));
+
+        let ds2 = DualShock2 { lx: 0x00, ly: 0x80, ..Default::default() };
+        assert_eq!(ds2.left_polar(), (180, 128));
+        assert_eq!(ds2.right_polar(), (0, 0));
+    }
+
+    #[test]
+    fn downgrades() {
+        let ds2 = DualShock2 {
+            buttons: GamepadButtons { data: 0x5aa5 },
+            rx: 0x01,
+            ry: 0x23,
+            lx: 0x45,
+            ly: 0x67,
+            pressures: [0xff; 12],
+        };
+
+        let ds = DualShock::from(ds2);
+        assert_eq!(ds.buttons.bits(), 0x5aa5);
+        assert_eq!([ds.rx, ds.ry, ds.lx, ds.ly], [0x01, 0x23, 0x45, 0x67]);
+
+        assert_eq!(Classic::from(ds).buttons.bits(), 0x5aa5);
+        assert_eq!(Classic::from(ds2).buttons.bits(), 0x5aa5);
+        assert_eq!(Classic::from(ds), Classic::from(ds2));
+    }
+
+    #[test]
+    fn builders() {
+        let ds = DualShock::builder()
+            .press(Button::L1)
+            .stick_left(0x40, 0xc0)
+            .stick_right(0x00, 0xff)
+            .build();
+
+        assert!(ds.buttons.l1());
+        assert_eq!(ds.buttons.pressed_count(), 1);
+        assert_eq!([ds.lx, ds.ly, ds.rx, ds.ry], [0x40, 0xc0, 0x00, 0xff]);
+        assert_eq!(DualShock::builder().build(), DualShock::default());
+
+        let ds2 = DualShock2::builder()
+            .press(Button::Cross)
+            .stick_left(0x40, 0xc0)
+            .pressure(Button::Cross, 200)
+            .pressure(Button::R2, 10)
+            .pressure(Button::Start, 99)
+            .build();
+
+        assert!(ds2.buttons.cross() && !ds2.buttons.r2());
+        assert_eq!(ds2.buttons.pressed_count(), 1);
+        assert_eq!([ds2.lx, ds2.ly, ds2.rx, ds2.ry], [0x40, 0xc0, 0x80, 0x80]);
+        assert_eq!(ds2.pressure_cross(), 200);
+        assert_eq!(ds2.pressure_r2(), 10);
+        assert_eq!(ds2.pressures.iter().filter(|&&x| x != 0).count(), 2);
+    }
+
+    #[test]
+    fn pressure_indexes_match_accessors() {
+        let buttons = [
+            Button::Right, Button::Left, Button::Up, Button::Down,
+            Button::Triangle, Button::Circle, Button::Cross, Button::Square,
+            Button::L1, Button::R1, Button::L2, Button::R2,
+        ];
+
+        for (i, &button) in buttons.iter().enumerate() {
+            let ds2 = DualShock2::builder().pressure(button, 1).build();
+            assert_eq!(pressure_index(button), Some(i));
+            assert_eq!(ds2.pressures[i], 1);
+        }
+    }
+
+    #[test]
+    fn pressure_hysteresis() {
+        let (press_at, release_at) = (0x80, 0x40);
+        let with = |pressure| DualShock2::builder().pressure(Button::Cross, pressure).build();
+        let held = GamepadButtons::from_pressed(&[Button::Cross]);
+        let released = GamepadButtons::all_released();
+
+        // Past either threshold the previous state doesn't matter
+        for prev in [held, released].iter() {
+            assert_eq!(with(0x80).buttons_from_pressure(press_at, release_at, prev), held);
+            assert_eq!(with(0xff).buttons_from_pressure(press_at, release_at, prev), held);
+            assert_eq!(with(0x40).buttons_from_pressure(press_at, release_at, prev), released);
+            assert_eq!(with(0x00).buttons_from_pressure(press_at, release_at, prev), released);
+        }
+
+        // In between it sticks
+        for &pressure in [0x41, 0x60, 0x7f].iter() {
+            assert_eq!(with(pressure).buttons_from_pressure(press_at, release_at, &held), held);
+            assert_eq!(with(pressure).buttons_from_pressure(press_at, release_at, &released), released);
+        }
+
+        // Pressing down then letting go through the band
+        let mut state = released;
+        let mut trace = [false; 7];
+        for (i, &pressure) in [0x30, 0x70, 0x90, 0x70, 0x50, 0x40, 0x70].iter().enumerate() {
+            state = with(pressure).buttons_from_pressure(press_at, release_at, &state);
+            trace[i] = state.cross();
+        }
+        assert_eq!(trace, [false, false, true, true, true, false, false]);
+    }
+
+    #[test]
+    fn pressure_ignores_digital_bits() {
+        // The digital bits say Square and Start are held, but Square has no
+        // pressure behind it. Start has no pressure reading, so its bit stays.
+        let ds2 = DualShock2::builder()
+            .press(Button::Square)
+            .press(Button::Start)
+            .pressure(Button::R1, 0xff)
+            .build();
+
+        let buttons = ds2.buttons_from_pressure(0x80, 0x40, &GamepadButtons::all_released());
+        assert_eq!(buttons, GamepadButtons::from_pressed(&[Button::Start, Button::R1]));
+    }
+
+    #[test]
+    fn neutral_sticks() {
+        assert!(DualShock::default().sticks_neutral(0));
+        assert!(DualShock2::default().sticks_neutral(0));
+
+        for axis in 0..4 {
+            for &(raw, tolerance, neutral) in [(0x85, 5, true), (0x86, 5, false), (0x7b, 5, true), (0x7a, 5, false), (0x00, 127, false), (0x00, 128, true)].iter() {
+                let mut sticks = [0x80; 4];
+                sticks[axis] = raw;
+
+                let ds = DualShock::builder().stick_left(sticks[0], sticks[1]).stick_right(sticks[2], sticks[3]).build();
+                let ds2 = DualShock2::builder().stick_left(sticks[0], sticks[1]).stick_right(sticks[2], sticks[3]).build();
+
+                assert_eq!(ds.sticks_neutral(tolerance), neutral);
+                assert_eq!(ds2.sticks_neutral(tolerance), neutral);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn neutral_after_calibration() {
+        let resting = DualShock { lx: 0x7a, ly: 0x86, rx: 0x81, ry: 0x7f, ..Default::default() };
+        let mut calibration = StickCalibration::default();
+        calibration.learn_center(&resting);
+
+        assert!(!resting.sticks_neutral(2));
+        assert!(resting.calibrated(&calibration).sticks_neutral(0));
+    }
+
+    #[test]
+    fn calibrated_sticks() {
+        let mut calibration = StickCalibration::default();
+        let resting = DualShock { lx: 0x7a, ly: 0x86, rx: 0x81, ry: 0x7f, ..Default::default() };
+        calibration.learn_center(&resting);
+
+        let ds = resting.calibrated(&calibration);
+        assert_eq!([ds.lx, ds.ly, ds.rx, ds.ry], [0x80; 4]);
+
+        let ds2 = DualShock2 { lx: 0x7a, ly: 0x86, rx: 0x81, ry: 0x7f, ..Default::default() };
+        let ds2 = ds2.calibrated(&calibration);
+        assert_eq!([ds2.lx, ds2.ly, ds2.rx, ds2.ry], [0x80; 4]);
+    }
+}
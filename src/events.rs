@@ -0,0 +1,286 @@
+//! Input Events
+//! ============================
+//! Rather than comparing each poll with the last, `EventPump` polls an
+//! `InputSource` and hands back what changed as a list of `Event`s: buttons
+//! going down and up, sticks moving, and controllers coming and going.
+//!
+//! Within one poll the events always come in the same order:
+//!
+//! 1. `ButtonReleased`, in wire bit order (see `classic::Button`)
+//! 2. `Disconnected` or `Connected`
+//! 3. `ButtonPressed`, in wire bit order
+//! 4. `StickMoved`, left stick then right
+//!
+//! So everything held is let go before a controller is reported gone, and a
+//! controller is reported before anything held on it. Nothing is stuck held
+//! across an unplug.
+//!
+//! A controller changing kind, like a DualShock switching to analog mode, is
+//! `Connected` again with its new kind but not `Disconnected` first, and its
+//! buttons carry over. Controllers without the standard buttons have nothing
+//! held, and controllers without sticks have them centered, the same as
+//! `GenericGamepad`.
+
+use classic::{Button, GamepadButtons};
+use diff;
+use dualshock::STICK_CENTER;
+use probe::ControllerKind;
+use super::{Error, InputSource};
+
+/// The most events one poll can make: every button released and pressed, a
+/// new controller, and both sticks
+pub const MAX_EVENTS: usize = 2 * 16 + 1 + 2;
+
+/// One of the analog sticks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Stick {
+    /// The left stick
+    Left,
+    /// The right stick
+    Right,
+}
+
+/// Something that changed between polls
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// A button went down
+    ButtonPressed(Button),
+    /// A button came up
+    ButtonReleased(Button),
+    /// A stick moved at least the pump's threshold from where it was last
+    /// reported, now at the raw (`x`, `y`)
+    StickMoved {
+        /// Which stick moved
+        stick: Stick,
+        /// Raw left and right position
+        x: u8,
+        /// Raw up and down position
+        y: u8,
+    },
+    /// A controller was plugged in, or changed kind
+    Connected(ControllerKind),
+    /// The controller was unplugged
+    Disconnected,
+}
+
+/// Writes events into the caller's buffer, dropping any past the end
+struct Events<'a> {
+    out: &'a mut [Event],
+    len: usize,
+}
+
+impl<'a> Events<'a> {
+    fn push(&mut self, event: Event) {
+        if let Some(slot) = self.out.get_mut(self.len) {
+            *slot = event;
+            self.len += 1;
+        }
+    }
+}
+
+const CENTERED: [(u8, u8); 2] = [(STICK_CENTER, STICK_CENTER); 2];
+
+/// Polls an `InputSource` and turns each poll into `Event`s
+#[derive(Debug)]
+pub struct EventPump<S> {
+    source: S,
+    threshold: u8,
+    kind: ControllerKind,
+    buttons: GamepadButtons,
+    /// Where each stick was last reported, as (x, y)
+    sticks: [(u8, u8); 2],
+}
+
+impl<S: InputSource> EventPump<S> {
+    /// Poll `source` for events. A stick is only reported once it's moved at
+    /// least `threshold` steps on either axis from where it was last
+    /// reported, or on any change when `threshold` is 0 or 1.
+    pub fn new(source: S, threshold: u8) -> Self {
+        Self {
+            source,
+            threshold,
+            kind: ControllerKind::None,
+            buttons: GamepadButtons::all_released(),
+            sticks: CENTERED,
+        }
+    }
+
+    /// The source being polled
+    pub fn source(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Give back the source
+    pub fn release(self) -> S {
+        self.source
+    }
+
+    /// What kind of controller the last poll found
+    pub fn kind(&self) -> ControllerKind {
+        self.kind
+    }
+
+    /// Poll once and write what changed since the last poll into `out`,
+    /// returning how many events there were. A buffer of `MAX_EVENTS` always
+    /// fits them all, while a shorter one loses whatever doesn't fit. A poll
+    /// that fails makes no events and changes nothing.
+    pub fn next_events(&mut self, out: &mut [Event]) -> Result<usize, Error<S::SpiError>> {
+        let device = self.source.read_input()?;
+        let mut events = Events { out, len: 0 };
+
+        let kind = device.kind();
+        let buttons = device.buttons().unwrap_or_default();
+        let changes = buttons.changes_since(&self.buttons);
+
+        for button in changes.released() {
+            events.push(Event::ButtonReleased(button));
+        }
+
+        if kind != self.kind {
+            if kind == ControllerKind::None {
+                events.push(Event::Disconnected);
+            } else {
+                events.push(Event::Connected(kind));
+            }
+        }
+
+        for button in changes.pressed() {
+            events.push(Event::ButtonPressed(button));
+        }
+
+        if kind == ControllerKind::None {
+            // The next controller starts from centered sticks, without
+            // reporting these ones going back there
+            self.sticks = CENTERED;
+        } else {
+            let sticks = diff::sticks(&device).map_or(CENTERED, |x| [(x[0], x[1]), (x[2], x[3])]);
+            let threshold = self.threshold.max(1);
+
+            for (&stick, (&now, last)) in [Stick::Left, Stick::Right].iter().zip(sticks.iter().zip(self.sticks.iter_mut())) {
+                if now.0.abs_diff(last.0) >= threshold || now.1.abs_diff(last.1) >= threshold {
+                    *last = now;
+                    events.push(Event::StickMoved { stick, x: now.0, y: now.1 });
+                }
+            }
+        }
+
+        self.kind = kind;
+        self.buttons = buttons;
+
+        Ok(events.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use classic::Classic;
+    use dualshock::DualShock;
+    use replay::{LoopMode, ReplaySource};
+    use Device;
+
+    fn events<S: InputSource>(pump: &mut EventPump<S>) -> Vec<Event>
+    where
+        S::SpiError: ::core::fmt::Debug {
+
+        let mut out = [Event::Disconnected; MAX_EVENTS];
+        let len = pump.next_events(&mut out).unwrap();
+
+        out[..len].to_vec()
+    }
+
+    #[test]
+    fn scripted_session() {
+        let frames = [
+            Device::Classic(Classic::builder().press(Button::Start).build()),
+            Device::Classic(Classic::builder().press(Button::Start).press(Button::Cross).build()),
+            Device::Classic(Classic::builder().press(Button::Up).build()),
+            // Switched to analog with Up still held and the left stick pushed
+            Device::DualShock(DualShock::builder().press(Button::Up).stick_left(0x80, 0x00).build()),
+            Device::DualShock(DualShock::builder().press(Button::Up).stick_left(0x80, 0x00).build()),
+            Device::None,
+        ];
+        let mut pump = EventPump::new(ReplaySource::new(&frames, LoopMode::Stop), 8);
+
+        assert_eq!(events(&mut pump), [Event::Connected(ControllerKind::Classic), Event::ButtonPressed(Button::Start)]);
+        assert_eq!(events(&mut pump), [Event::ButtonPressed(Button::Cross)]);
+        assert_eq!(events(&mut pump), [
+            Event::ButtonReleased(Button::Start),
+            Event::ButtonReleased(Button::Cross),
+            Event::ButtonPressed(Button::Up),
+        ]);
+        assert_eq!(events(&mut pump), [
+            Event::Connected(ControllerKind::DualShock),
+            Event::StickMoved { stick: Stick::Left, x: 0x80, y: 0x00 },
+        ]);
+        assert_eq!(events(&mut pump), []);
+        assert_eq!(pump.kind(), ControllerKind::DualShock);
+
+        // Unplugged with Up held, so it's let go first
+        assert_eq!(events(&mut pump), [Event::ButtonReleased(Button::Up), Event::Disconnected]);
+        assert_eq!(events(&mut pump), []);
+        assert_eq!(pump.kind(), ControllerKind::None);
+    }
+
+    #[test]
+    fn sticks_move_past_the_threshold() {
+        let at = |x, y| Device::DualShock(DualShock::builder().stick_right(x, y).build());
+        let frames = [at(0x80, 0x80), at(0x84, 0x7c), at(0x88, 0x80), at(0x8a, 0x80), at(0x90, 0x80), at(0x90, 0x80)];
+        let mut pump = EventPump::new(ReplaySource::new(&frames, LoopMode::Stop), 8);
+
+        assert_eq!(events(&mut pump), [Event::Connected(ControllerKind::DualShock)]);
+        assert_eq!(events(&mut pump), []);
+        assert_eq!(events(&mut pump), [Event::StickMoved { stick: Stick::Right, x: 0x88, y: 0x80 }]);
+        // Measured from where it was reported, not from the last poll
+        assert_eq!(events(&mut pump), []);
+        assert_eq!(events(&mut pump), [Event::StickMoved { stick: Stick::Right, x: 0x90, y: 0x80 }]);
+
+        // Without a threshold every change counts
+        let mut pump = EventPump::new(ReplaySource::new(&frames, LoopMode::Stop), 0);
+        assert_eq!(events(&mut pump).len(), 1);
+        assert_eq!(events(&mut pump), [Event::StickMoved { stick: Stick::Right, x: 0x84, y: 0x7c }]);
+        pump.source().rewind();
+        assert_eq!(events(&mut pump), [Event::StickMoved { stick: Stick::Right, x: 0x80, y: 0x80 }]);
+    }
+
+    #[test]
+    fn every_event_at_once_fits() {
+        let everything = Classic::builder();
+        let everything = Button::ALL.iter().fold(everything, |builder, &button| builder.press(button)).build();
+        let nothing = DualShock::builder().stick_left(0x00, 0x00).stick_right(0xff, 0xff).build();
+        let frames = [Device::Classic(everything), Device::DualShock(nothing), Device::Classic(everything)];
+        let mut pump = EventPump::new(ReplaySource::new(&frames, LoopMode::Stop), 8);
+
+        assert_eq!(events(&mut pump).len(), 1 + 16);
+        assert_eq!(events(&mut pump).len(), 16 + 1 + 2);
+
+        // Every button, back on a controller with centered sticks
+        assert_eq!(events(&mut pump).len(), 1 + 16 + 2);
+
+        // A short buffer keeps what fits
+        let mut pump = EventPump::new(ReplaySource::new(&frames, LoopMode::Stop), 8);
+        let mut out = [Event::Disconnected; 2];
+        assert_eq!(pump.next_events(&mut out), Ok(2));
+        assert_eq!(out, [Event::Connected(ControllerKind::Classic), Event::ButtonPressed(Button::Select)]);
+    }
+
+    #[test]
+    fn failed_polls_change_nothing() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let mut port = ::mock::port(&[dualshock]);
+        port.dev.fail = true;
+        let mut pump = EventPump::new(port, 8);
+
+        let mut out = [Event::Disconnected; MAX_EVENTS];
+        assert_eq!(pump.next_events(&mut out), Err(Error::Spi(())));
+        assert_eq!(pump.kind(), ControllerKind::None);
+
+        pump.source().dev.fail = false;
+        assert_eq!(events(&mut pump), [Event::Connected(ControllerKind::DualShock), Event::ButtonPressed(Button::Select)]);
+    }
+}
@@ -0,0 +1,204 @@
+//! Generic Gamepad
+//! ============================
+//! Most programs don't care exactly which controller is plugged in, only that
+//! it has buttons and maybe some sticks. `GenericGamepad` gives every
+//! controller with the standard buttons the same shape.
+//!
+//! How each `Device` maps:
+//!
+//! * `Classic` - its buttons, with both sticks centered
+//! * `AnalogJoystick` and `DualShock` - its buttons and sticks
+//! * `DualShock2` - its buttons, sticks and pressures
+//! * `GuitarHero` - Start, Select and the strum bar (see `Device::buttons`),
+//!   with both sticks centered. The frets, star power and whammy bar are
+//!   dropped.
+//! * `JogCon` - its buttons, with both sticks centered. The wheel is dropped.
+//! * `Mouse`, `NegCon`, `GunCon` and `Baton` - nothing, since they don't have
+//!   the standard buttons
+//! * `None`, `Unknown` and `ConfigurationMode` - nothing
+//!
+//! The buttons are kept as they are, so a dance mat holding opposite arrows
+//! keeps all of them. Only `GamepadButtons::dpad` cancels them out, and
+//! `GamepadButtons::dpad_state` doesn't.
+
+use classic::{Button, GamepadButtons};
+use dualshock::{pressure_index, STICK_CENTER};
+use super::{
+    Device,
+    HasAnalogSticks,
+    HasStandardButtons,
+};
+
+/// Any controller with the standard buttons, boiled down to the same shape
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericGamepad {
+    /// Standard buttons (Cross, Circle, L3, Start, etc)
+    pub buttons: GamepadButtons,
+    /// Left stick as raw (x, y) bytes, centered if the controller has none
+    pub left: (u8, u8),
+    /// Right stick as raw (x, y) bytes, centered if the controller has none
+    pub right: (u8, u8),
+    /// Button pressures in the same order as `DualShock2::pressures`, if the
+    /// controller reports them
+    pub pressures: Option<[u8; 12]>,
+}
+
+impl GenericGamepad {
+    /// A gamepad with these buttons, centered sticks and no pressures
+    pub const fn from_buttons(buttons: GamepadButtons) -> Self {
+        Self {
+            buttons,
+            left: (STICK_CENTER, STICK_CENTER),
+            right: (STICK_CENTER, STICK_CENTER),
+            pressures: None,
+        }
+    }
+
+    /// How hard a button is pressed, if the controller reports pressures and
+    /// the button has one
+    pub fn pressure(&self, button: Button) -> Option<u8> {
+        let pressures = self.pressures?;
+
+        pressure_index(button).and_then(|x| pressures.get(x).cloned())
+    }
+}
+
+impl<'a> From<&'a Device> for Option<GenericGamepad> {
+    fn from(device: &'a Device) -> Self {
+        let buttons = device.buttons()?;
+        let mut gamepad = GenericGamepad::from_buttons(buttons);
+
+        match device {
+            Device::AnalogJoystick(x) | Device::DualShock(x) => {
+                gamepad.left = x.left_stick();
+                gamepad.right = x.right_stick();
+            },
+            Device::DualShock2(x) => {
+                gamepad.left = x.left_stick();
+                gamepad.right = x.right_stick();
+                gamepad.pressures = Some(x.pressures);
+            },
+            _ => (),
+        }
+
+        Some(gamepad)
+    }
+}
+
+impl HasStandardButtons for GenericGamepad {
+    fn buttons(&self) -> &GamepadButtons {
+        &self.buttons
+    }
+}
+
+impl HasAnalogSticks for GenericGamepad {
+    fn left_stick(&self) -> (u8, u8) {
+        self.left
+    }
+
+    fn right_stick(&self) -> (u8, u8) {
+        self.right
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mouse::Mouse;
+    use classic::{Classic, DPadState};
+    use dualshock::{DualShock, DualShock2};
+    use negcon::NegCon;
+    use jogcon::JogCon;
+    use guncon::GunCon;
+    use guitarhero::GuitarHero;
+    use baton::Baton;
+
+    fn generic(device: Device) -> Option<GenericGamepad> {
+        Option::from(&device)
+    }
+
+    fn held(buttons: &[Button]) -> GamepadButtons {
+        GamepadButtons::from_pressed(buttons)
+    }
+
+    #[test]
+    fn nothing_to_map() {
+        let frame = [0x00; 6];
+
+        assert_eq!(generic(Device::None), None);
+        assert_eq!(generic(Device::Unknown), None);
+        assert_eq!(generic(Device::ConfigurationMode), None);
+        assert_eq!(generic(Device::Mouse(Mouse::from_frame(&frame).unwrap())), None);
+        assert_eq!(generic(Device::NegCon(NegCon::from_frame(&frame).unwrap())), None);
+        assert_eq!(generic(Device::GunCon(GunCon::from_frame(&frame).unwrap())), None);
+        assert_eq!(generic(Device::Baton(Baton::from_frame(&frame).unwrap())), None);
+    }
+
+    #[test]
+    fn digital_controllers_get_centered_sticks() {
+        let classic = Classic::builder().press(Button::Circle).build();
+        assert_eq!(generic(Device::Classic(classic)), Some(GenericGamepad {
+            buttons: held(&[Button::Circle]),
+            left: (0x80, 0x80),
+            right: (0x80, 0x80),
+            pressures: None,
+        }));
+
+        // Start and strum up land on Start and Up, and the green fret isn't R2
+        let guitar = GuitarHero::builder().start().strum_up().fret_green().whammy(0x40).build();
+        assert_eq!(
+            generic(Device::GuitarHero(guitar)),
+            Some(GenericGamepad::from_buttons(held(&[Button::Start, Button::Up])))
+        );
+
+        let mut frame = [0x00; 5];
+        frame[0..2].copy_from_slice(&held(&[Button::L1]).bits().to_le_bytes());
+        let jogcon = JogCon::from_frame(&frame).unwrap();
+        assert_eq!(generic(Device::JogCon(jogcon)), Some(GenericGamepad::from_buttons(held(&[Button::L1]))));
+    }
+
+    #[test]
+    fn analog_controllers_keep_their_sticks() {
+        let dualshock = DualShock::builder().press(Button::R3).stick_left(0x01, 0x02).stick_right(0x03, 0x04).build();
+        let expected = GenericGamepad {
+            buttons: held(&[Button::R3]),
+            left: (0x01, 0x02),
+            right: (0x03, 0x04),
+            pressures: None,
+        };
+
+        assert_eq!(generic(Device::DualShock(dualshock)), Some(expected));
+        assert_eq!(generic(Device::AnalogJoystick(dualshock)), Some(expected));
+    }
+
+    #[test]
+    fn dualshock2_keeps_its_pressures() {
+        let dualshock2 = DualShock2::builder()
+            .press(Button::Cross)
+            .pressure(Button::Cross, 0xa0)
+            .stick_right(0xff, 0x00)
+            .build();
+        let gamepad = generic(Device::DualShock2(dualshock2)).unwrap();
+
+        assert_eq!(gamepad.buttons, held(&[Button::Cross]));
+        assert_eq!((gamepad.left, gamepad.right), ((0x80, 0x80), (0xff, 0x00)));
+        assert_eq!(gamepad.pressures, Some(dualshock2.pressures));
+        assert_eq!(gamepad.pressure(Button::Cross), Some(0xa0));
+        assert_eq!(gamepad.pressure(Button::Start), None);
+        assert_eq!(GenericGamepad::from_buttons(held(&[])).pressure(Button::Cross), None);
+    }
+
+    #[test]
+    fn dance_mats_keep_every_arrow() {
+        for bits in 0..16u8 {
+            let state = DPadState::from_bits(bits);
+            let buttons = GamepadButtons::from_pressed_bits(u16::from(bits) << 4);
+            let gamepad = generic(Device::Classic(Classic { buttons })).unwrap();
+
+            assert_eq!(gamepad.buttons.dpad_state(), state);
+            assert_eq!(gamepad.buttons.dpad(), state.direction());
+        }
+    }
+}
@@ -2,14 +2,65 @@
 //! ============================
 //! Made for a single game, from a software point of view it's identical
 //! to a DualShock1 controller. There's no way to tell it apart yet, so
-//! the design of this library doesn't make it easy to use yet
+//! `read_input` hands it back as a DualShock and `probe` finds a DualShock.
+//! Ask for one with `read_input_as` instead.
+
+use core::fmt;
+use classic::GamepadButtons;
+use protocol::{fill, leading};
+use super::{Device, DeviceId, FromDevice, HasStandardButtons};
 
 /// Buttons on the Guitar Hero guitar. Reference material:
 /// https://strategywiki.org/wiki/Guitar_Hero_II/Controls
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "GamepadButtons", into = "GamepadButtons"))]
 pub struct GuitarButtons {
-    data: u16
+    // The word as it came off the wire, laid out like a DualShock's
+    pub(crate) data: GamepadButtons,
+    // The same word with the frets and star power released, handed out as
+    // the standard buttons
+    standard: GamepadButtons,
+}
+
+/// Keeps the word as it came off the wire
+#[cfg(feature = "serde")]
+impl From<GamepadButtons> for GuitarButtons {
+    fn from(data: GamepadButtons) -> Self {
+        GuitarButtons::new(data.data)
+    }
+}
+
+/// The word as it came off the wire
+#[cfg(feature = "serde")]
+impl From<GuitarButtons> for GamepadButtons {
+    fn from(buttons: GuitarButtons) -> Self {
+        buttons.data
+    }
+}
+
+/// Shows the raw active low word
+impl fmt::Debug for GuitarButtons {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GuitarButtons").field("data", &self.data.data).finish()
+    }
+}
+
+/// Shows the raw active low word, the same as the `Debug` output
+#[cfg(feature = "defmt")]
+impl defmt::Format for GuitarButtons {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "GuitarButtons {{ data: {} }}", self.data.data);
+    }
+}
+
+/// Shows the raw active low word, the same as the `Debug` output
+#[cfg(feature = "ufmt")]
+impl ufmt::uDebug for GuitarButtons {
+    fn fmt<W: ufmt::uWrite + ?Sized>(&self, f: &mut ufmt::Formatter<W>) -> Result<(), W::Error> {
+        f.debug_struct("GuitarButtons")?.field("data", &self.data.data)?.finish()
+    }
 }
 
 impl GuitarButtons {
@@ -27,74 +78,320 @@ impl GuitarButtons {
     const FRET_BLUE: u16 = 0x4000;
     const FRET_ORANGE: u16 = 0x8000;
 
+    /// Where a DualShock has buttons the guitar doesn't
+    const GUITAR_ONLY: u16 = Self::STAR_POWER | Self::FRET_GREEN | Self::FRET_RED | Self::FRET_YELLOW |
+        Self::FRET_BLUE | Self::FRET_ORANGE;
+
+    /// The buttons from their active low word
+    pub(crate) const fn new(data: u16) -> Self {
+        Self {
+            data: GamepadButtons { data },
+            standard: GamepadButtons { data: data | Self::GUITAR_ONLY },
+        }
+    }
+
     /// A button on the controller
     pub fn select(&self) -> bool {
-        self.data & Self::PS_SELECT == 0
+        self.data.data & Self::PS_SELECT == 0
     }
 
     /// A button on the controller
     pub fn start(&self) -> bool {
-        self.data & Self::PS_START == 0
+        self.data.data & Self::PS_START == 0
     }
 
     /// A button on the controller
     pub fn strum_up(&self) -> bool {
-        self.data & Self::STRUM_UP == 0
+        self.data.data & Self::STRUM_UP == 0
     }
 
     /// A button on the controller
     pub fn strum_down(&self) -> bool {
-        self.data & Self::STRUM_DOWN == 0
+        self.data.data & Self::STRUM_DOWN == 0
     }
 
     /// A button on the controller
     pub fn fret_green(&self) -> bool {
-        self.data & Self::FRET_GREEN == 0
+        self.data.data & Self::FRET_GREEN == 0
     }
 
     /// A button on the controller
     pub fn fret_red(&self) -> bool {
-        self.data & Self::FRET_RED == 0
+        self.data.data & Self::FRET_RED == 0
     }
 
     /// A button on the controller
     pub fn fret_yellow(&self) -> bool {
-        self.data & Self::FRET_YELLOW == 0
+        self.data.data & Self::FRET_YELLOW == 0
     }
 
     /// A button on the controller
     pub fn fret_blue(&self) -> bool {
-        self.data & Self::FRET_BLUE == 0
+        self.data.data & Self::FRET_BLUE == 0
     }
 
     /// A button on the controller
     pub fn fret_orange(&self) -> bool {
-        self.data & Self::FRET_ORANGE == 0
+        self.data.data & Self::FRET_ORANGE == 0
     }
 
     /// A button on the controller
     pub fn star_power(&self) -> bool {
-        self.data & Self::STAR_POWER == 0
+        self.data.data & Self::STAR_POWER == 0
     }
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents a Guitar Hero controller
 pub struct GuitarHero {
     /// The buttons
     pub buttons: GuitarButtons,
 
     // Lazily pad bytes
+    #[cfg_attr(feature = "serde", serde(skip))]
     padding: [u8; 3],
 
     /// The whammy bar's current position
     pub whammy: u8,
 }
 
+/// Nothing held and the whammy bar at rest. The whammy sits on one of the
+/// stick axes so its rest value is the usual stick center (0x80).
+impl Default for GuitarHero {
+    fn default() -> Self {
+        Self {
+            buttons: GuitarButtons::new(0xffff),
+            padding: [0x80; 3],
+            whammy: 0x80,
+        }
+    }
+}
+
 impl GuitarHero {
+    /// Read a Guitar Hero controller from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        let &[low, high, a, b, c, whammy] = leading(frame)?;
+
+        Some(Self {
+            buttons: GuitarButtons::new(u16::from_le_bytes([low, high])),
+            padding: [a, b, c],
+            whammy,
+        })
+    }
+
+    /// Write a Guitar Hero controller back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.data.data.to_le_bytes();
+        let [a, b, c] = self.padding;
+        fill(frame, &[low, high, a, b, c, self.whammy]);
+    }
+
+    /// Start building a controller state by hand, with nothing held and the
+    /// whammy bar at rest
+    pub fn builder() -> GuitarHeroBuilder {
+        GuitarHeroBuilder { value: GuitarHero::default() }
+    }
+
     /// Get a copy of the buttons that were pressed
     pub fn buttons(&self) -> GuitarButtons {
-        self.buttons.clone()
+        self.buttons
+    }
+
+    /// The controller's bytes as they came off the wire, without the header
+    pub fn as_bytes(&self) -> [u8; 6] {
+        let mut bytes = [0u8; 6];
+        self.write_frame(&mut bytes);
+
+        bytes
+    }
+}
+
+/// Builds a `GuitarHero` by hand, for tests and simulators. Created by
+/// `GuitarHero::builder()`.
+#[derive(Copy, Clone, Debug)]
+pub struct GuitarHeroBuilder {
+    value: GuitarHero,
+}
+
+impl GuitarHeroBuilder {
+    fn press(mut self, mask: u16) -> Self {
+        self.value.buttons = GuitarButtons::new(self.value.buttons.data.data & !mask);
+        self
+    }
+
+    /// Hold Select
+    pub fn select(self) -> Self {
+        self.press(GuitarButtons::PS_SELECT)
+    }
+
+    /// Hold Start
+    pub fn start(self) -> Self {
+        self.press(GuitarButtons::PS_START)
+    }
+
+    /// Push the strum bar up
+    pub fn strum_up(self) -> Self {
+        self.press(GuitarButtons::STRUM_UP)
+    }
+
+    /// Push the strum bar down
+    pub fn strum_down(self) -> Self {
+        self.press(GuitarButtons::STRUM_DOWN)
+    }
+
+    /// Hold the green fret
+    pub fn fret_green(self) -> Self {
+        self.press(GuitarButtons::FRET_GREEN)
+    }
+
+    /// Hold the red fret
+    pub fn fret_red(self) -> Self {
+        self.press(GuitarButtons::FRET_RED)
+    }
+
+    /// Hold the yellow fret
+    pub fn fret_yellow(self) -> Self {
+        self.press(GuitarButtons::FRET_YELLOW)
+    }
+
+    /// Hold the blue fret
+    pub fn fret_blue(self) -> Self {
+        self.press(GuitarButtons::FRET_BLUE)
+    }
+
+    /// Hold the orange fret
+    pub fn fret_orange(self) -> Self {
+        self.press(GuitarButtons::FRET_ORANGE)
+    }
+
+    /// Tilt the guitar for star power
+    pub fn star_power(self) -> Self {
+        self.press(GuitarButtons::STAR_POWER)
+    }
+
+    /// Set where the whammy bar is
+    pub fn whammy(mut self, whammy: u8) -> Self {
+        self.value.whammy = whammy;
+        self
+    }
+
+    /// Finish building
+    pub fn build(self) -> GuitarHero {
+        self.value
+    }
+}
+
+/// The guitar's buttons sit where a DualShock's would, so Start and Select
+/// land on themselves and the strum bar on Up and Down. Star power and the
+/// frets come over on L2, R2 and the face buttons, but they aren't those
+/// buttons, so they're left released here and only read through
+/// `GuitarButtons`. Buttons the guitar doesn't have are never held, the same
+/// as L3 and R3 on the original controller.
+impl HasStandardButtons for GuitarHero {
+    fn buttons(&self) -> &GamepadButtons {
+        &self.buttons.standard
+    }
+}
+
+/// Anything that answers as a DualShock is read as the guitar, since that's
+/// what the guitar answers as
+impl FromDevice for GuitarHero {
+    const EXPECTED: DeviceId = DeviceId::DualShockAnalog;
+
+    fn from_device(device: &Device) -> Option<Self> {
+        match device {
+            Device::GuitarHero(x) => Some(*x),
+            Device::DualShock(x) => {
+                let mut frame = [0u8; 6];
+                x.write_frame(&mut frame);
+                GuitarHero::from_frame(&frame)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The padding is whatever the guitar left on the wire, so it's not compared
+impl PartialEq for GuitarHero {
+    fn eq(&self, other: &GuitarHero) -> bool {
+        self.buttons == other.buttons && self.whammy == other.whammy
+    }
+}
+
+impl Eq for GuitarHero {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_released() {
+        let guitar = GuitarHero::default();
+        let buttons = guitar.buttons();
+
+        assert!(!buttons.select() && !buttons.start());
+        assert!(!buttons.strum_up() && !buttons.strum_down() && !buttons.star_power());
+        assert!(!buttons.fret_green() && !buttons.fret_red() && !buttons.fret_yellow());
+        assert!(!buttons.fret_blue() && !buttons.fret_orange());
+        assert_eq!(guitar.whammy, 0x80);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn builder() {
+        let guitar = GuitarHero::builder().fret_green().fret_orange().strum_down().whammy(0xff).build();
+        let buttons = guitar.buttons();
+
+        assert!(buttons.fret_green() && buttons.fret_orange() && buttons.strum_down());
+        assert!(!buttons.fret_red() && !buttons.fret_yellow() && !buttons.fret_blue());
+        assert!(!buttons.select() && !buttons.start() && !buttons.strum_up() && !buttons.star_power());
+        assert_eq!(guitar.whammy, 0xff);
+
+        let guitar = GuitarHero::builder().select().start().strum_up().star_power().fret_red().fret_yellow().fret_blue().build();
+        let buttons = guitar.buttons();
+
+        assert!(buttons.select() && buttons.start() && buttons.strum_up() && buttons.star_power());
+        assert!(buttons.fret_red() && buttons.fret_yellow() && buttons.fret_blue());
+        assert!(!buttons.fret_green() && !buttons.fret_orange() && !buttons.strum_down());
+        assert_eq!(guitar.whammy, 0x80);
+    }
+
+    #[test]
+    fn standard_buttons() {
+        use classic::Button;
+
+        let guitar = GuitarHero::builder().select().start().build();
+        let buttons = guitar.buttons_owned();
+        assert!(buttons.select() && buttons.start());
+        assert_eq!(buttons.pressed_count(), 2);
+
+        let guitar = GuitarHero::builder()
+            .strum_up()
+            .star_power()
+            .fret_green()
+            .fret_red()
+            .fret_yellow()
+            .fret_blue()
+            .fret_orange()
+            .whammy(0x00)
+            .build();
+        // Only the strum bar comes through. The frets and star power stay
+        // released, along with L1, R1, L3 and R3
+        assert_eq!(*HasStandardButtons::buttons(&guitar), GamepadButtons::from_pressed(&[Button::Up]));
+        assert!(!GuitarHero::default().buttons_owned().any_pressed());
+
+        // The guitar's own buttons still see them, from the wire and from
+        // the builder alike
+        let frame = guitar.as_bytes();
+        for guitar in [guitar, GuitarHero::from_frame(&frame).unwrap()].iter() {
+            let buttons = guitar.buttons();
+            assert!(buttons.star_power() && buttons.fret_green() && buttons.fret_red());
+            assert!(buttons.fret_yellow() && buttons.fret_blue() && buttons.fret_orange());
+            assert_eq!(guitar.buttons_owned(), GamepadButtons::from_pressed(&[Button::Up]));
+        }
+    }
+}
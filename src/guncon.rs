@@ -6,11 +6,16 @@ use byteorder::{
     ByteOrder,
     LittleEndian
 };
+use protocol::{fill, leading};
 
 /// The buttons found on the generation 1 GunCon. Once I find a GC2
 /// I'll fill this out better
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct GunconButtons {
     data: u16
 }
@@ -37,7 +42,10 @@ impl GunconButtons {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the classic Controller
 pub struct GunCon {
     /// Standard buttons (Cross, Circle, L3, Start)
@@ -49,6 +57,24 @@ pub struct GunCon {
 }
 
 impl GunCon {
+    /// Read a GunCon from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        let &[low, high, x_low, x_high, y_low, y_high] = leading(frame)?;
+
+        Some(Self {
+            buttons: GunconButtons { data: u16::from_le_bytes([low, high]) },
+            x: [x_low, x_high],
+            y: [y_low, y_high],
+        })
+    }
+
+    /// Write a GunCon back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.data.to_le_bytes();
+        fill(frame, &[low, high, self.x[0], self.x[1], self.y[0], self.y[1]]);
+    }
+
     /// Return the X position of the GunCon's aim on the screen
     pub fn x(&self) -> u16 {
         LittleEndian::read_u16(&self.x)
@@ -58,4 +84,198 @@ impl GunCon {
     pub fn y(&self) -> u16 {
         LittleEndian::read_u16(&self.y)
     }
-}
\ No newline at end of file
+}
+
+/// How one axis of a `GunConCalibration` turns raw counts into pixels:
+/// `(raw * num + offset) / den`, rounded to the nearest pixel with halves
+/// rounding up. `den` is always positive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisScale {
+    /// Pixels covered between the two samples
+    pub num: i32,
+    /// Raw counts covered between the two samples
+    pub den: i32,
+    /// Puts the first sample's raw count on its pixel, in units of `1 / den`
+    pub offset: i64,
+}
+
+impl AxisScale {
+    /// The line through both samples, or `None` if they're at the same raw
+    /// count
+    fn through(raw: (u16, u16), screen: (u16, u16)) -> Option<Self> {
+        let (num, den) = (i32::from(screen.1) - i32::from(screen.0), i32::from(raw.1) - i32::from(raw.0));
+        let (num, den) = match den {
+            0 => return None,
+            x if x < 0 => (-num, -den),
+            _ => (num, den),
+        };
+
+        Some(Self {
+            num,
+            den,
+            offset: i64::from(screen.0) * i64::from(den) - i64::from(raw.0) * i64::from(num),
+        })
+    }
+
+    /// Where `raw` lands, which may be off either end of the screen
+    pub fn apply(&self, raw: u16) -> i64 {
+        let den = i64::from(self.den);
+        let scaled = i64::from(raw) * i64::from(self.num) + self.offset;
+
+        (2 * scaled + den).div_euclid(2 * den)
+    }
+}
+
+/// Where the GunCon read a target and where it was drawn, as (raw, screen)
+type Sample = ((u16, u16), (u16, u16));
+
+/// Turns the GunCon's raw counters into screen pixels. The counters come
+/// from the video timing, so they differ between consoles, video modes and
+/// TVs, and have to be matched up to the picture by aiming at two targets.
+///
+/// Show a target near one corner of the screen and `add_sample` where the
+/// GunCon read it along with where it was drawn, then the same near the
+/// opposite corner, and `solve`. The further apart the targets, the better.
+/// Mapping is all integer math, so it hits both targets exactly and lands
+/// within half a pixel of the line between them everywhere else.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GunConCalibration {
+    width: u16,
+    height: u16,
+    /// The two latest samples, oldest first
+    samples: [Option<Sample>; 2],
+    /// Set by `solve`, for X then Y
+    scale: Option<(AxisScale, AxisScale)>,
+}
+
+impl GunConCalibration {
+    /// The GunCon reports an X of 1 when it didn't see the picture, with Y
+    /// saying why
+    const NO_LIGHT_X: u16 = 1;
+
+    /// Start calibrating for a screen `width` by `height` pixels
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            samples: [None; 2],
+            scale: None,
+        }
+    }
+
+    /// Note that the GunCon read `raw` aimed at the pixel `screen`, both as
+    /// (x, y). Only the two latest samples are kept, and nothing changes
+    /// until the next `solve`.
+    pub fn add_sample(&mut self, raw: (u16, u16), screen: (u16, u16)) {
+        self.samples = [self.samples[1], Some((raw, screen))];
+    }
+
+    /// Work out the scale and offset for each axis, as (X, Y), from the two
+    /// latest samples. Fails, keeping whatever was solved before, without two
+    /// samples or if both were read at the same raw count on either axis.
+    pub fn solve(&mut self) -> Option<(AxisScale, AxisScale)> {
+        let (first, second) = match self.samples {
+            [Some(first), Some(second)] => (first, second),
+            _ => return None,
+        };
+
+        let x = AxisScale::through(((first.0).0, (second.0).0), ((first.1).0, (second.1).0))?;
+        let y = AxisScale::through(((first.0).1, (second.0).1), ((first.1).1, (second.1).1))?;
+        self.scale = Some((x, y));
+
+        self.scale
+    }
+
+    /// The pixel the GunCon is aimed at, as (x, y). `None` before `solve`,
+    /// when the GunCon didn't see the picture, or when it's aimed off the
+    /// edge of the screen.
+    pub fn map(&self, raw: (u16, u16)) -> Option<(u16, u16)> {
+        let (x, y) = self.scale?;
+        if raw.0 == Self::NO_LIGHT_X {
+            return None;
+        }
+
+        let on_screen = |pixel: i64, size: u16| if pixel >= 0 && pixel < i64::from(size) { Some(pixel as u16) } else { None };
+
+        Some((on_screen(x.apply(raw.0), self.width)?, on_screen(y.apply(raw.1), self.height)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts the way an NTSC console might: 77 to 461 across 320 pixels,
+    /// and 25 to 265 down 240 lines
+    fn ntsc(pixel: (u16, u16)) -> (u16, u16) {
+        (77 + pixel.0 * 6 / 5, 25 + pixel.1)
+    }
+
+    #[test]
+    fn calibration_hits_its_targets() {
+        let mut calibration = GunConCalibration::new(320, 240);
+        assert_eq!(calibration.map(ntsc((160, 120))), None);
+
+        calibration.add_sample(ntsc((32, 24)), (32, 24));
+        assert_eq!(calibration.solve(), None);
+        calibration.add_sample(ntsc((288, 216)), (288, 216));
+        let (x, y) = calibration.solve().unwrap();
+        assert_eq!((x.num, x.den), (256, 307));
+        assert_eq!((y.num, y.den), (192, 192));
+
+        assert_eq!(calibration.map(ntsc((32, 24))), Some((32, 24)));
+        assert_eq!(calibration.map(ntsc((288, 216))), Some((288, 216)));
+
+        // In between, and past the targets out to the edges
+        for &pixel in [(0, 0), (100, 50), (160, 120), (250, 200), (319, 239)].iter() {
+            let (mx, my) = calibration.map(ntsc(pixel)).unwrap();
+            assert!((i32::from(mx) - i32::from(pixel.0)).abs() <= 1, "{:?} mapped to {}", pixel, mx);
+            assert_eq!(my, pixel.1);
+        }
+    }
+
+    #[test]
+    fn calibration_rounds_to_the_nearest_pixel() {
+        // Two counts a pixel, so odd counts land halfway
+        let mut calibration = GunConCalibration::new(100, 100);
+        calibration.add_sample((10, 10), (0, 0));
+        calibration.add_sample((30, 30), (10, 10));
+        calibration.solve().unwrap();
+
+        assert_eq!(calibration.map((12, 13)), Some((1, 2)));
+        assert_eq!(calibration.map((14, 15)), Some((2, 3)));
+
+        // Samples taken right to left solve the same
+        let mut reversed = GunConCalibration::new(100, 100);
+        reversed.add_sample((30, 30), (10, 10));
+        reversed.add_sample((10, 10), (0, 0));
+        assert_eq!(reversed.solve(), calibration.solve());
+    }
+
+    #[test]
+    fn off_screen_readings_map_to_nothing() {
+        let mut calibration = GunConCalibration::new(320, 240);
+        calibration.add_sample(ntsc((32, 24)), (32, 24));
+        calibration.add_sample(ntsc((288, 216)), (288, 216));
+        calibration.solve().unwrap();
+
+        // No light, and past each edge
+        assert_eq!(calibration.map((1, 10)), None);
+        assert_eq!(calibration.map((1, 5)), None);
+        assert_eq!(calibration.map((70, 100)), None);
+        assert_eq!(calibration.map((470, 100)), None);
+        assert_eq!(calibration.map((200, 20)), None);
+        assert_eq!(calibration.map((200, 270)), None);
+
+        // Samples at the same count can't be solved, and don't undo the last
+        // solution
+        calibration.add_sample((100, 100), (0, 0));
+        calibration.add_sample((100, 200), (50, 50));
+        assert_eq!(calibration.solve(), None);
+        assert_eq!(calibration.map(ntsc((32, 24))), Some((32, 24)));
+    }
+}
@@ -0,0 +1,257 @@
+//! USB HID Gamepad Reports
+//! ============================
+//! Turns a controller into a USB HID gamepad report, for building adapters.
+//! `REPORT_DESCRIPTOR` describes the report to the host, and `to_hid_report`
+//! fills one in. The report is 7 bytes:
+//!
+//! * Bytes 0-1: 16 buttons, little endian, 1 when held. In order they're
+//!   Cross, Circle, Square, Triangle, L1, R1, L2, R2, Select, Start, L3, R3,
+//!   Up, Down, Left and Right, which is the order browsers and SDL use for
+//!   standard gamepads.
+//! * Bytes 2-5: X, Y, Z and Rz axes from -127 to 127, being the left stick's
+//!   X and Y then the right stick's X and Y. Like the controller (and like
+//!   HID expects), left and up are negative.
+//! * Byte 6: a hat switch from the d-pad in the low four bits, 0 for up and
+//!   counting clockwise in 45 degree steps to 7 for up and left. Nothing held
+//!   is 8, which hosts read as centered. Opposite directions, which a dance
+//!   mat can hold together, cancel out the same as `GamepadButtons::dpad`,
+//!   so the hat can be centered with arrows held. The button bits still have
+//!   every arrow.
+//!
+//! Controllers without sticks report them centered, and controllers without
+//! standard buttons report nothing held.
+
+use classic::{Button, Direction, GamepadButtons};
+use dualshock::signed;
+use super::Device;
+
+/// The HID report descriptor for `GamepadReport`
+pub const REPORT_DESCRIPTOR: [u8; 66] = [
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x05,       // Usage (Game Pad)
+    0xa1, 0x01,       // Collection (Application)
+    0x05, 0x09,       //   Usage Page (Button)
+    0x19, 0x01,       //   Usage Minimum (1)
+    0x29, 0x10,       //   Usage Maximum (16)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x25, 0x01,       //   Logical Maximum (1)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x10,       //   Report Count (16)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute)
+    0x05, 0x01,       //   Usage Page (Generic Desktop)
+    0x09, 0x30,       //   Usage (X)
+    0x09, 0x31,       //   Usage (Y)
+    0x09, 0x32,       //   Usage (Z)
+    0x09, 0x35,       //   Usage (Rz)
+    0x15, 0x81,       //   Logical Minimum (-127)
+    0x25, 0x7f,       //   Logical Maximum (127)
+    0x75, 0x08,       //   Report Size (8)
+    0x95, 0x04,       //   Report Count (4)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute)
+    0x09, 0x39,       //   Usage (Hat Switch)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x25, 0x07,       //   Logical Maximum (7)
+    0x35, 0x00,       //   Physical Minimum (0)
+    0x46, 0x3b, 0x01, //   Physical Maximum (315)
+    0x65, 0x14,       //   Unit (Degrees)
+    0x75, 0x04,       //   Report Size (4)
+    0x95, 0x01,       //   Report Count (1)
+    0x81, 0x42,       //   Input (Data, Variable, Absolute, Null State)
+    0x65, 0x00,       //   Unit (None)
+    0x81, 0x03,       //   Input (Constant), the top four bits of the hat byte
+    0xc0,             // End Collection
+];
+
+/// The hat switch value for nothing held
+pub const HAT_CENTERED: u8 = 8;
+
+/// Which report button bit each controller button lands on
+const BUTTON_ORDER: [Button; 16] = [
+    Button::Cross,
+    Button::Circle,
+    Button::Square,
+    Button::Triangle,
+    Button::L1,
+    Button::R1,
+    Button::L2,
+    Button::R2,
+    Button::Select,
+    Button::Start,
+    Button::L3,
+    Button::R3,
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+];
+
+/// One gamepad report, laid out as described by `REPORT_DESCRIPTOR`. The
+/// button word is in the machine's byte order, so use `to_bytes` to get what
+/// goes on the wire.
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GamepadReport {
+    /// One bit per button, 1 when held
+    pub buttons: u16,
+    /// Left stick, left and right
+    pub x: i8,
+    /// Left stick, up and down
+    pub y: i8,
+    /// Right stick, left and right
+    pub z: i8,
+    /// Right stick, up and down
+    pub rz: i8,
+    /// The d-pad as a hat switch, see `HAT_CENTERED`
+    pub hat: u8,
+}
+
+impl GamepadReport {
+    /// The report as it's sent to the host
+    pub fn to_bytes(&self) -> [u8; 7] {
+        let buttons = self.buttons.to_le_bytes();
+
+        [buttons[0], buttons[1], self.x as u8, self.y as u8, self.z as u8, self.rz as u8, self.hat]
+    }
+}
+
+/// Build the report for a controller
+pub fn to_hid_report(device: &Device) -> GamepadReport {
+    let buttons = device.buttons().unwrap_or_default();
+    let (lx, ly, rx, ry) = match device {
+        Device::AnalogJoystick(x) | Device::DualShock(x) => (x.lx, x.ly, x.rx, x.ry),
+        Device::DualShock2(x) => (x.lx, x.ly, x.rx, x.ry),
+        _ => (0x80, 0x80, 0x80, 0x80),
+    };
+
+    GamepadReport {
+        buttons: button_bits(&buttons),
+        x: axis(lx),
+        y: axis(ly),
+        z: axis(rx),
+        rz: axis(ry),
+        hat: hat(buttons.dpad()),
+    }
+}
+
+fn button_bits(buttons: &GamepadButtons) -> u16 {
+    BUTTON_ORDER.iter()
+        .enumerate()
+        .filter(|&(_, &button)| buttons.is_pressed(button))
+        .fold(0, |bits, (index, _)| bits | 1 << index)
+}
+
+/// The raw stick range is one step longer on the negative side, so the end of
+/// it is pulled in to fit the descriptor's -127
+fn axis(raw: u8) -> i8 {
+    signed(raw).max(-127)
+}
+
+fn hat(direction: Direction) -> u8 {
+    match direction {
+        Direction::Up => 0,
+        Direction::UpRight => 1,
+        Direction::Right => 2,
+        Direction::DownRight => 3,
+        Direction::Down => 4,
+        Direction::DownLeft => 5,
+        Direction::Left => 6,
+        Direction::UpLeft => 7,
+        Direction::Neutral => HAT_CENTERED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::{Classic, DPadState};
+    use dualshock::{DualShock, DualShock2};
+
+    #[test]
+    fn nothing_held() {
+        let report = to_hid_report(&Device::DualShock(DualShock::default()));
+
+        assert_eq!(report.to_bytes(), [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08]);
+        assert_eq!(to_hid_report(&Device::None).to_bytes(), report.to_bytes());
+    }
+
+    #[test]
+    fn classic_buttons_and_hat() {
+        let classic = Classic::builder()
+            .press(Button::Cross)
+            .press(Button::Start)
+            .press(Button::Right)
+            .press(Button::Down)
+            .build();
+
+        assert_eq!(to_hid_report(&Device::Classic(classic)).to_bytes(), [0x01, 0xa2, 0x00, 0x00, 0x00, 0x00, 0x03]);
+    }
+
+    #[test]
+    fn sticks_keep_their_direction() {
+        // Left stick pushed up and left, right stick down and right
+        let dualshock = DualShock::builder().stick_left(0x00, 0x00).stick_right(0xff, 0xc0).build();
+        let report = to_hid_report(&Device::DualShock(dualshock));
+
+        assert_eq!(report.to_bytes(), [0x00, 0x00, 0x81, 0x81, 0x7f, 0x40, 0x08]);
+    }
+
+    #[test]
+    fn hat_directions() {
+        let cases = [
+            (&[Button::Up][..], 0),
+            (&[Button::Up, Button::Right][..], 1),
+            (&[Button::Right][..], 2),
+            (&[Button::Down, Button::Right][..], 3),
+            (&[Button::Down][..], 4),
+            (&[Button::Down, Button::Left][..], 5),
+            (&[Button::Left][..], 6),
+            (&[Button::Up, Button::Left][..], 7),
+            (&[Button::Up, Button::Down][..], 8),
+        ];
+
+        for &(held, expected) in cases.iter() {
+            let buttons = GamepadButtons::from_pressed(held);
+
+            assert_eq!(to_hid_report(&Device::Classic(Classic { buttons })).hat, expected);
+        }
+    }
+
+    #[test]
+    fn every_dpad_combination() {
+        for bits in 0..16u8 {
+            let state = DPadState::from_bits(bits);
+            let buttons = GamepadButtons::from_pressed_bits(u16::from(bits) << 4);
+            let report = to_hid_report(&Device::Classic(Classic { buttons }));
+
+            assert_eq!(report.hat, hat(state.direction()));
+
+            // Up, Down, Left and Right sit at bits 12 to 15
+            let arrows = (report.buttons >> 12) as u8;
+            let held = [state.up, state.down, state.left, state.right];
+            assert_eq!(arrows, held.iter().rev().fold(0, |bits, &x| bits << 1 | x as u8));
+        }
+
+        // Everything held points nowhere, but is all still there
+        let buttons = GamepadButtons::from_pressed_bits(0x00f0);
+        let report = to_hid_report(&Device::Classic(Classic { buttons }));
+        assert_eq!((report.hat, report.buttons), (HAT_CENTERED, 0xf000));
+    }
+
+    #[test]
+    fn every_button_has_its_own_bit() {
+        for (index, &button) in BUTTON_ORDER.iter().enumerate() {
+            let dualshock2 = DualShock2::builder().press(button).build();
+            let buttons = to_hid_report(&Device::DualShock2(dualshock2)).buttons;
+
+            assert_eq!(buttons, 1 << index);
+        }
+    }
+
+    #[test]
+    fn descriptor_adds_up() {
+        // 16 one bit buttons, four bytes of axes and a four bit hat with four
+        // bits of padding
+        assert_eq!(16 + 4 * 8 + 4 + 4, 8 * ::core::mem::size_of::<GamepadReport>());
+        assert_eq!(REPORT_DESCRIPTOR[REPORT_DESCRIPTOR.len() - 1], 0xc0);
+    }
+}
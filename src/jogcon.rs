@@ -10,6 +10,7 @@ use byteorder::{
     ByteOrder,
     LittleEndian
 };
+use protocol::{fill, leading};
 use super::{
     HasStandardButtons,
     PollCommand,
@@ -17,7 +18,7 @@ use super::{
 
 /// What we want the JogCon's wheel to do after we
 /// poll it
-#[derive(Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum JogControl {
     /// Stop the motor
     Stop = 0x00,
@@ -38,6 +39,7 @@ pub enum JogControl {
 }
 
 /// What state the JogCon's wheel was in last poll
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum JogState {
     /// The wheel was turned left
     TurnedLeft,
@@ -48,7 +50,10 @@ pub enum JogState {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the Namco JogCon controller
 pub struct JogCon {
     // TODO: Implement an endian-safe accessor for jog_position
@@ -65,6 +70,25 @@ pub struct JogCon {
 }
 
 impl JogCon {
+    /// Read a JogCon from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        let &[low, high, position_low, position_high, jog_state] = leading(frame)?;
+
+        Some(Self {
+            buttons: GamepadButtons::from_bits(u16::from_le_bytes([low, high])),
+            jog_position: [position_low, position_high],
+            jog_state,
+        })
+    }
+
+    /// Write a JogCon back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.data.to_le_bytes();
+        let [position_low, position_high] = self.jog_position;
+        fill(frame, &[low, high, position_low, position_high, self.jog_state]);
+    }
+
     /// The absolute position of the jog wheel
     pub fn jog_position(&self) -> i16 {
         LittleEndian::read_i16(&self.jog_position)
@@ -72,12 +96,13 @@ impl JogCon {
 }
 
 impl HasStandardButtons for JogCon {
-    fn buttons(&self) -> GamepadButtons {
-        self.buttons.clone()
+    fn buttons(&self) -> &GamepadButtons {
+        &self.buttons
     }
 }
 
 /// Command for controlling the wheel on the JogCon
+#[derive(Debug)]
 pub struct ControlJC {
     /// The mode the wheel should be in (move left, move right, etc)
 	pub mode: JogControl,
@@ -89,8 +114,8 @@ impl ControlJC {
     /// Create a new one of thes newfangled control commands
     pub fn new(mode: JogControl, strength: u8) -> Self {
         Self {
-            mode: mode,
-            strength: strength,
+            mode,
+            strength,
         }
     }
 }
@@ -99,7 +124,6 @@ impl ControlJC {
 impl PollCommand for ControlJC {
     /// Sets the command for the wheel on the JogCon
     fn set_command(&self, command: &mut [u8]) {
-        command[0] = self.mode.clone() as u8;
-        command[0] |= self.strength & 0x0f;
+        fill(command, &[self.mode as u8 | (self.strength & 0x0f)]);
     }
 }
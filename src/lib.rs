@@ -36,6 +36,17 @@
 //! driven by the 7.5v cd-rom voltage. Testing using the 5v line on of the Raspberry Pi
 //! technically works, but the result is much weaker than the original console.
 //! 
+//! Sharing the SPI bus
+//! -----------------------
+//! 
+//! The port only needs something implementing `Transfer<u8>`, so the proxies handed
+//! out by bus sharing crates like `shared-bus` can be passed to `new` as they are.
+//! Every transfer is a complete select cycle: the select pin goes low just before it
+//! and back high straight after, even when the transfer fails, so nothing else on the
+//! bus gets talked over. Commands that take several transfers (like `enable_pressure`)
+//! can have other bus traffic land in between them, which the controller doesn't mind
+//! as long as it isn't selected. `release` hands the bus and pin back when you're done.
+//! 
 //! Bibliography
 //! -----------------------
 //! Here is the list of the great bits of documentation that helped get this project started
@@ -50,7 +61,10 @@
 
 #![no_std]
 #![deny(missing_docs)]
+// Whatever a device sends back, a short buffer is an error and never a panic
+#![cfg_attr(not(test), deny(clippy::indexing_slicing))]
 
+pub mod protocol;
 pub mod mouse;
 pub mod classic;
 pub mod dualshock;
@@ -59,143 +73,321 @@ pub mod jogcon;
 pub mod guncon;
 pub mod guitarhero;
 pub mod baton;
+pub mod debounce;
+pub mod sticks;
+pub mod pressure;
+pub mod recorder;
+pub mod replay;
+pub mod wire;
+pub mod generic;
+pub mod sequence;
+pub mod action;
+pub mod rumble;
+pub mod diff;
+pub mod configured;
+pub mod probe;
+pub mod memcard;
+pub mod bus;
+pub mod recovery;
+pub mod dual;
+pub mod selftest;
+pub mod events;
+#[cfg(feature = "hid")]
+pub mod hid;
+#[cfg(feature = "softspi")]
+pub mod softspi;
+#[cfg(any(feature = "testutil", test))]
+pub mod testutil;
+
+#[cfg(test)]
+mod mock;
 
-extern crate bit_reverse;
+#[macro_use]
 extern crate bitflags;
 extern crate byteorder;
+#[cfg(feature = "critical-section")]
+extern crate critical_section;
+#[cfg(feature = "defmt")]
+extern crate defmt;
 extern crate embedded_hal as hal;
+#[cfg(any(feature = "float", test))]
+extern crate libm;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "ufmt")]
+extern crate ufmt;
 
-use bit_reverse::ParallelReverse;
 use core::fmt;
+use core::mem;
 use hal::blocking::spi;
+#[allow(deprecated)]
 use hal::digital::OutputPin;
 
+use protocol::*;
+
+pub use protocol::{
+    build_poll_frame,
+    decode_frame,
+    parse_response,
+    DeviceId,
+    FrameMeta,
+    MultitapPort,
+    MESSAGE_MAX_LENGTH,
+};
+
 use mouse::Mouse;
-use classic::{Classic, GamepadButtons};
-use dualshock::{DualShock, DualShock2};
+use classic::{Button, Classic, ClassicRef, GamepadButtons};
+use dualshock::{signed, ControlDS, DualShock, DualShock2, DualShock2Ref, DualShockRef};
 use negcon::NegCon;
 use jogcon::JogCon;
 use guncon::GunCon;
 use guitarhero::GuitarHero;
 use baton::Baton;
+use probe::ControllerKind;
+use recovery::{Action, FailFast, RecoveryPolicy};
 
-/// The maximum length of a message from a controller
-const MESSAGE_MAX_LENGTH: usize = 32;
-/// Acknoweldgement byte for header commnad
-//const ACK_BYTE: u8 = 0x5a;
-/// Length of the command header
-const HEADER_LEN: usize = 3;
-
-/// Controller missing
-const CONTROLLER_NOT_PRESENT: u8 = 0xff;
-/// PlayStation mouse, SCPH-1030
-const CONTROLLER_MOUSE: u8 = 0x12;
-/// Original controller, SCPH-1080
-const CONTROLLER_CLASSIC: u8 = 0xc1;
-/// Analog Controller, SCPH-1110 (flightstick looking thing)
-const CONTROLLER_ANALOG_JOYSTICK: u8 = 0x53;
-/// DualShock in Digital mode
-const CONTROLLER_DUALSHOCK_DIGITAL: u8 = 0x41;
-/// DualShock
-const CONTROLLER_DUALSHOCK_ANALOG: u8 = 0x73;
-/// DuakShock 2
-const CONTROLLER_DUALSHOCK_PRESSURE: u8 = 0x79;
-/// JogCon
-const CONTROLLER_JOGCON: u8 = 0xe3;
-/// NegCon
-const CONTROLLER_NEGCON: u8 = 0x23;
-/// NegCon
-const CONTROLLER_GUNCON: u8 = 0x63;
-/// Configuration Mode
-const CONTROLLER_CONFIGURATION: u8 = 0xf3;
-
-/// Command to poll buttons
-const CMD_POLL: &[u8] = &[0x00, 0x42, 0x00];
-/// Command to enter escape mode
-const CMD_ENTER_ESCAPE_MODE: &[u8] = &[0x00, 0x43, 0x00, 0x01, 0x00];
-/// Command to exit escape mode
-const CMD_EXIT_ESCAPE_MODE: &[u8] = &[0x00, 0x43, 0x00, 0x00, 0x00];
-/// Command to set response format. Right now asks for all data
-const CMD_RESPONSE_FORMAT: &[u8] = &[0x00, 0x4F, 0x00, 0xFF, 0xFF, 0x03, 0x00, 0x00, 0x00];
-/// Command to initialize / customize pressure
-const CMD_INIT_PRESSURE: &[u8] = &[0x00, 0x40, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00];
-/// Command to set major mode (DualShock = 1 / Digital = 0)
-const CMD_SET_MODE: &[u8] = &[0x00, 0x44, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00];
-/// Command to read extended status
-const CMD_READ_STATUS: &[u8] = &[0x00, 0x45, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
-/// Command to read constant 1 at address 00
-const CMD_READ_CONST1A: &[u8] = &[0x00, 0x46, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
-/// Command to read constant 1 at address 01
-const CMD_READ_CONST1B: &[u8] = &[0x00, 0x46, 0x00, 0x01, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
-/// Command to read constant 2 at address 00
-const CMD_READ_CONST2: &[u8] = &[0x00, 0x47, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
-/// Command to read constant 3 at address 00
-const CMD_READ_CONST3A: &[u8] = &[0x00, 0x4C, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
-/// Command to read constant 3 at address 01
-const CMD_READ_CONST3B: &[u8] = &[0x00, 0x4C, 0x00, 0x01, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
-/// Command to enable DualShock motors
-const CMD_MOTOR_DUALSHOCK: &[u8] = &[0x00, 0x4D, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xff];
-/// Command to enable JogCon motor
-const CMD_MOTOR_JOGCON: &[u8] = &[0x00, 0x4D, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff];
-
-#[repr(C)]
-#[derive(Copy, Clone)]
-/// The poll command returns a series of bytes. This union allows us to interact with
-/// those bytes with some functions. It's nice sugar that allows us to use the data
-/// in an obvious way without needing to copy the bytes around
-pub union ControllerData {
+/// How many extra polls to spend waiting out a mode change
+const TRANSITION_RETRIES: u8 = 3;
+
+/// The bytes a controller sent back, without the frame's header. Use the
+/// `from_frame` functions on the controller types to read them as a
+/// particular controller. `N` is the size of the port that read them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ControllerData<const N: usize = { MESSAGE_MAX_LENGTH }> {
     /// The raw data representing the buttons. Kind of unwieldy
-    pub data: [u8; MESSAGE_MAX_LENGTH],
-    /// Maps the underlying bytes to buttons and the whammy bar of the Guitar Hero controller
-    pub gh: GuitarHero,
-    /// Maps the bytes to the Mad Maestro baton
-    pub b: Baton,
-    pm: Mouse,
-    classic: Classic,
-    ds: DualShock,
-    ds2: DualShock2,
-    jc: JogCon,
-    nc: NegCon,
-    gc: GunCon,
-}
-
-/// The active port to set on the Multitap
-#[derive(Clone, PartialEq)]
-pub enum MultitapPort {
-    /// The first port on the multi-tap and also the port when no tap
-    /// is present
-    A = 0x01,
-    /// The second port on the multi-top
-    B = 0x02,
-    /// The third port on the multi-top
-    C = 0x03,
-    /// The fourth port on the multi-top
-    D = 0x04,
-    /// Some unknown id when booting the PS2. Memory card?
-    M = 0x61,
-    /// This may be for the multitap itself
-    X = 0xff,
+    pub data: [u8; N],
+}
+
+/// A controller's state along with when it was read, from `read_input_stamped`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputFrame<T> {
+    /// The controller that answered and what it said
+    pub device: Device,
+    /// Whatever the clock handed to `read_input_stamped` returned
+    pub timestamp: T,
+}
+
+/// Everything one poll found out, from `poll`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PollResponse {
+    /// The controller that answered and what it said
+    pub device: Device,
+    /// What the frame's header said, and how the poll went
+    pub meta: FrameMeta,
+}
+
+/// Counts of how polls and configuration have gone since the port was set up
+/// (or since `reset_stats`), for keeping an eye on a link without a
+/// debugger. Counts stop at `u32::MAX` rather than wrapping.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortStats {
+    /// Polls sent by the `read_input` family, `read_raw` and `poll_buttons`,
+    /// retries included
+    pub polls: u32,
+    /// Polls where the device stopped answering partway (`Error::Timeout`)
+    pub timeouts: u32,
+    /// Polls with a byte that can't be right (`Error::Garbage`)
+    pub garbage: u32,
+    /// Frames too short for the device they came from (`Error::BadResponse`)
+    pub bad_responses: u32,
+    /// Polls the SPI bus itself failed
+    pub spi_errors: u32,
+    /// Polls repeated while riding out a mode change (see
+    /// `smooth_transitions`) or at a `RecoveryPolicy`'s asking
+    pub retries: u32,
+    /// Times a controller that had been answering was pulled out
+    pub disconnects: u32,
+    /// Configuration that failed, like `enable_pressure`, `read_config` or
+    /// `is_analog_active`, including controllers that don't have an escape
+    /// mode to answer it
+    pub config_failures: u32,
+}
+
+fn bump(count: &mut u32) {
+    *count = count.saturating_add(1);
+}
+
+impl PortStats {
+    fn record_error<E>(&mut self, error: &Error<E>) {
+        match error {
+            Error::Timeout { .. } => bump(&mut self.timeouts),
+            Error::Garbage { .. } => bump(&mut self.garbage),
+            Error::BadResponse => bump(&mut self.bad_responses),
+            Error::Spi(_) => bump(&mut self.spi_errors),
+            _ => (),
+        }
+    }
+}
+
+/// A poll that failed partway through `poll_n`
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BurstError<E> {
+    /// Which poll failed, counting from 0. This many polls worked before it.
+    pub index: usize,
+    /// What went wrong
+    pub error: Error<E>,
 }
 
 /// Errors that can arrise from trying to communicate with the controller
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
 pub enum Error<E> {
     /// Late collision
     LateCollision,
     /// Something responded badly
     BadResponse,
+    /// A device answered but stopped responding partway through the frame:
+    /// it left out the ACK byte, or the SPI device cut the transfer short.
+    /// This usually points to wiring trouble rather than an empty port.
+    Timeout {
+        /// How many bytes of the frame arrived before the device went quiet
+        bytes_completed: u8,
+    },
+    /// A byte came back with a value that can't be right, like one that never
+    /// changes for this device or an id claiming more than a frame can hold
+    Garbage {
+        /// Position of the byte within the frame
+        offset: u8,
+        /// The value that was read instead
+        byte: u8,
+    },
+    /// A different kind of controller answered partway through a burst of
+    /// polls, like when a DualShock switches between digital and analog
+    DeviceChanged,
+    /// The device didn't answer a command it would need to understand, like
+    /// an original controller asked for its status
+    UnsupportedDevice,
+    /// A different controller answered than the one asked for with
+    /// `read_input_as`
+    WrongDevice {
+        /// The controller asked for
+        expected: DeviceId,
+        /// The id of the one that answered
+        got: DeviceId,
+    },
+    /// The memory card was swapped (or plugged in for the first time) since
+    /// `MemoryCard::acknowledge_insertion`, so anything read from the old
+    /// one, like its directory, is out of date
+    CardChanged,
+    /// A buffer was too short for what had to go in it or be read out of it,
+    /// like a `send_command` result shorter than the command
+    BufferTooSmall {
+        /// How many bytes it needed, or `u16::MAX` for anything longer
+        needed: u16,
+    },
+    /// The data line didn't read idle with select released, so the transfer
+    /// wasn't started (see `verify_bus_idle`). Either another device is
+    /// partway through a transfer of its own, or the line is stuck low.
+    BusBusy,
+    /// A poll came sooner after the last one than `set_poll_interval`
+    /// allows, and wasn't sent
+    TooSoon,
     /// SPI error
     Spi(E),
 }
 
-impl<E> From<E> for Error<E> {
-    fn from(e: E) -> Self {
-        Error::Spi(e)
+impl<E> Error<E> {
+    /// Whether polling again stands a fair chance of working. Collisions,
+    /// stalls, mangled bytes, controllers switching modes and other devices
+    /// using the bus all tend to clear up by the next poll. A device that
+    /// keeps sending frames too short for what it claims to be, one that
+    /// can't do what was asked, a swapped memory card, or the SPI bus itself
+    /// failing, won't.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::LateCollision
+            | Error::Timeout { .. }
+            | Error::Garbage { .. }
+            | Error::DeviceChanged
+            | Error::BusBusy
+            | Error::TooSoon => true,
+            Error::BadResponse
+            | Error::UnsupportedDevice
+            | Error::WrongDevice { .. }
+            | Error::CardChanged
+            | Error::BufferTooSmall { .. }
+            | Error::Spi(_) => false,
+        }
+    }
+
+    /// The same error with any SPI error swapped for `()`, so it can be kept
+    /// around without the SPI error type having to be `Clone`
+    fn without_spi(&self) -> Error<()> {
+        match *self {
+            Error::LateCollision => Error::LateCollision,
+            Error::BadResponse => Error::BadResponse,
+            Error::Timeout { bytes_completed } => Error::Timeout { bytes_completed },
+            Error::Garbage { offset, byte } => Error::Garbage { offset, byte },
+            Error::DeviceChanged => Error::DeviceChanged,
+            Error::UnsupportedDevice => Error::UnsupportedDevice,
+            Error::WrongDevice { expected, got } => Error::WrongDevice { expected, got },
+            Error::CardChanged => Error::CardChanged,
+            Error::BufferTooSmall { needed } => Error::BufferTooSmall { needed },
+            Error::BusBusy => Error::BusBusy,
+            Error::TooSoon => Error::TooSoon,
+            Error::Spi(_) => Error::Spi(()),
+        }
     }
 }
 
-impl<E> fmt::Debug for Error<E> {
+/// A short description of what went wrong, with whatever the error knows
+/// about where
+impl<E: fmt::Debug> fmt::Display for Error<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error")
+        match self {
+            Error::LateCollision => f.write_str("late collision on the bus"),
+            Error::BadResponse => f.write_str("frame too short for the device it came from"),
+            Error::Timeout { bytes_completed } => {
+                write!(f, "device stopped responding after {} bytes", bytes_completed)
+            },
+            Error::Garbage { offset, byte } => {
+                write!(f, "unexpected byte {:02x} at offset {}", byte, offset)
+            },
+            Error::DeviceChanged => f.write_str("a different device answered partway through"),
+            Error::UnsupportedDevice => f.write_str("the device doesn't support that command"),
+            Error::WrongDevice { expected, got } => write!(f, "expected {:?} but {:?} answered", expected, got),
+            Error::CardChanged => f.write_str("the memory card was changed"),
+            Error::BufferTooSmall { needed } => write!(f, "buffer too small, {} bytes needed", needed),
+            Error::BusBusy => f.write_str("the bus wasn't idle"),
+            Error::TooSoon => f.write_str("polled too soon after the last poll"),
+            Error::Spi(e) => write!(f, "SPI error: {:?}", e),
+        }
+    }
+}
+
+/// The same as the `Display` output
+#[cfg(feature = "ufmt")]
+impl<E: ufmt::uDebug> ufmt::uDisplay for Error<E> {
+    fn fmt<W: ufmt::uWrite + ?Sized>(&self, f: &mut ufmt::Formatter<W>) -> Result<(), W::Error> {
+        match self {
+            Error::LateCollision => f.write_str("late collision on the bus"),
+            Error::BadResponse => f.write_str("frame too short for the device it came from"),
+            Error::Timeout { bytes_completed } => {
+                ufmt::uwrite!(f, "device stopped responding after {} bytes", bytes_completed)
+            },
+            Error::Garbage { offset, byte } => {
+                ufmt::uwrite!(f, "unexpected byte {:02x} at offset {}", *byte, offset)
+            },
+            Error::DeviceChanged => f.write_str("a different device answered partway through"),
+            Error::UnsupportedDevice => f.write_str("the device doesn't support that command"),
+            Error::WrongDevice { expected, got } => ufmt::uwrite!(f, "expected {:?} but {:?} answered", expected, got),
+            Error::CardChanged => f.write_str("the memory card was changed"),
+            Error::BufferTooSmall { needed } => ufmt::uwrite!(f, "buffer too small, {} bytes needed", needed),
+            Error::BusBusy => f.write_str("the bus wasn't idle"),
+            Error::TooSoon => f.write_str("polled too soon after the last poll"),
+            Error::Spi(e) => ufmt::uwrite!(f, "SPI error: {:?}", e),
+        }
+    }
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::Spi(e)
     }
 }
 
@@ -204,7 +396,7 @@ pub trait PollCommand {
     /// Re-write the provided slice starting from index 0. This command
     /// is called by read_input() which will provide a sub-slice of the
     /// controller's command bytes.
-    fn set_command(&self, &mut [u8]);
+    fn set_command(&self, command: &mut [u8]);
 }
 
 /// Many controllers have the same set of buttons (Square, Circle, L3, R1, etc).
@@ -212,13 +404,59 @@ pub trait PollCommand {
 /// Controller not having L3 and R3, they are brought out regardless and just considered
 /// unpressable.
 pub trait HasStandardButtons {
-    /// This does require a clone operation of the bytes inside the controller.
-    /// To save yourself the copy, you can access the button data directly via `buttons`
-    fn buttons(&self) -> GamepadButtons;
+    /// The buttons, borrowed straight from the controller data
+    fn buttons(&self) -> &GamepadButtons;
+
+    /// A copy of the buttons, for when a borrow gets in the way
+    fn buttons_owned(&self) -> GamepadButtons {
+        *self.buttons()
+    }
+}
+
+/// Controllers with a pair of analog sticks, read as raw bytes
+pub trait HasAnalogSticks {
+    /// The left stick's (x, y) position
+    fn left_stick(&self) -> (u8, u8);
+
+    /// The right stick's (x, y) position
+    fn right_stick(&self) -> (u8, u8);
+
+    /// Whether all four axes are within `tolerance` of center, for telling
+    /// when nobody is touching the sticks. This looks at the values as they
+    /// are, so calibrate first (see `StickCalibration`) for worn sticks.
+    fn sticks_neutral(&self, tolerance: u8) -> bool {
+        let (lx, ly) = self.left_stick();
+        let (rx, ry) = self.right_stick();
+
+        [lx, ly, rx, ry].iter().all(|&x| signed(x).unsigned_abs() <= tolerance)
+    }
+}
+
+/// Controllers `read_input_as` can hand back. A controller that sends more
+/// than the one asked for will do, with the extra dropped, so a DualShock 2
+/// can be read as a DualShock or an original controller.
+pub trait FromDevice: Sized {
+    /// The id of the controller asked for, for `Error::WrongDevice`
+    const EXPECTED: DeviceId;
+
+    /// Get this controller out of whatever answered, if it can be
+    fn from_device(device: &Device) -> Option<Self>;
+}
+
+/// Anywhere controller input can come from. Write application code against
+/// this instead of `PlayStationPort` and it can be fed recorded input (see
+/// `replay::ReplaySource`) when there's no controller around.
+pub trait InputSource {
+    /// The SPI error the source can run into, or `Infallible` if it can't fail
+    type SpiError;
+
+    /// The next controller state
+    fn read_input(&mut self) -> Result<Device, Error<Self::SpiError>>;
 }
 
 /// Holds information about the controller's configuration and constants
-#[derive(Default)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ControllerConfiguration {
     /// The controller's current status and *perhaps* its generation
     pub status: [u8; 6],
@@ -232,6 +470,139 @@ pub struct ControllerConfiguration {
     pub const3a: [u8; 5],
     /// Unknown constant
     pub const3b: [u8; 5],
+    /// How many bytes of each answer arrived, see `lengths`
+    lengths: [u8; 6],
+}
+
+/// Every answer in full, and all zeroes
+impl Default for ControllerConfiguration {
+    fn default() -> Self {
+        Self::new([0; 6], [0; 5], [0; 5], [0; 5], [0; 5], [0; 5])
+    }
+}
+
+impl ControllerConfiguration {
+    /// A configuration where every answer arrived in full
+    pub const fn new(status: [u8; 6], const1a: [u8; 5], const1b: [u8; 5], const2: [u8; 5], const3a: [u8; 5], const3b: [u8; 5]) -> Self {
+        Self { status, const1a, const1b, const2, const3a, const3b, lengths: [6, 5, 5, 5, 5, 5] }
+    }
+
+    /// How many bytes of each answer the controller actually sent, in the
+    /// order of `raw`. Not every controller answers in full, and anything
+    /// without an escape mode doesn't answer at all. Whatever didn't arrive
+    /// is left zeroed.
+    pub fn lengths(&self) -> [u8; 6] {
+        self.lengths
+    }
+
+    /// Whether the status looks like one we know how to read. Sony's pads
+    /// always send 0x02 as the second byte.
+    fn status_known(&self) -> bool {
+        self.status[1] == 0x02
+    }
+
+    /// Whether this is a DualShock 2 (`Some(true)`) or a DualShock
+    /// (`Some(false)`), from the first status byte. Anything else, including
+    /// most third party pads, is `None`.
+    pub fn is_dualshock2(&self) -> Option<bool> {
+        if !self.status_known() {
+            return None;
+        }
+
+        match self.status[0] {
+            0x03 => Some(true),
+            0x01 => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Whether analog mode (and its LED) was on. Unknown status reads as off.
+    pub fn analog_enabled(&self) -> bool {
+        self.status_known() && self.status[2] == 0x01
+    }
+
+    /// How many vibration motors the controller says it has. Unknown status
+    /// reads as none.
+    pub fn actuators(&self) -> u8 {
+        if self.status_known() { self.status[3] } else { 0 }
+    }
+
+    /// Every response in the order they were read: status, then constants
+    /// 1a, 1b, 2, 3a and 3b, each only as long as what arrived (see
+    /// `lengths`)
+    pub fn raw(&self) -> [&[u8]; 6] {
+        let full: [&[u8]; 6] = [&self.status, &self.const1a, &self.const1b, &self.const2, &self.const3a, &self.const3b];
+        let mut raw = full;
+        for (x, (&full, &len)) in raw.iter_mut().zip(full.iter().zip(self.lengths.iter())) {
+            *x = full.get(..usize::from(len)).unwrap_or(full);
+        }
+
+        raw
+    }
+}
+
+/// Prints what arrived of each response as hex bytes, since none of them are
+/// really numbers
+impl fmt::Debug for ControllerConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [status, const1a, const1b, const2, const3a, const3b] = self.raw();
+
+        f.debug_struct("ControllerConfiguration")
+            .field("status", &HexBytes(status))
+            .field("const1a", &HexBytes(const1a))
+            .field("const1b", &HexBytes(const1b))
+            .field("const2", &HexBytes(const2))
+            .field("const3a", &HexBytes(const3a))
+            .field("const3b", &HexBytes(const3b))
+            .finish()
+    }
+}
+
+/// A mode set up by `enable_pressure` or `enable_jogcon`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AppliedConfig {
+    /// DualShock 2 mode, sending pressures
+    Pressure,
+    /// JogCon wheel mode
+    JogCon,
+}
+
+impl AppliedConfig {
+    /// The id a controller polls with while it's in this mode
+    pub const fn device_id(self) -> DeviceId {
+        match self {
+            AppliedConfig::Pressure => DeviceId::DualShockPressure,
+            AppliedConfig::JogCon => DeviceId::JogCon,
+        }
+    }
+}
+
+/// What a configuration call ended up doing
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigOutcome {
+    /// The whole escape mode sequence was sent
+    Applied,
+    /// The controller was already set up that way, so only a poll was sent
+    /// to check
+    AlreadyApplied,
+}
+
+/// Formats bytes as `[03, 5a]`
+struct HexBytes<'a>(&'a [u8]);
+
+impl<'a> fmt::Debug for HexBytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        f.write_str("]")
+    }
 }
 
 /// Possible devices that can be returned by the poll command to the controller.
@@ -243,6 +614,10 @@ pub struct ControllerConfiguration {
 /// Guitar Hero controller reports in every way that it is a DualShock 1 controller.
 /// Other devices, like the DVD remote don't even support escape mode so this
 /// is the best I can do until we find a better way to get creative.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Device {
     /// If pulling the device type didn't work
     None,
@@ -278,242 +653,3597 @@ pub enum Device {
     Baton(Baton),
 }
 
-/// The main event! Create a port using an SPI bus and start commanding
-/// controllers!
-pub struct PlayStationPort<SPI, CS> {
-    dev: SPI,
-    select: Option<CS>,
-    multitap_port: MultitapPort,
-}
+impl Device {
+    /// The standard buttons, for any controller that has them. The Guitar
+    /// Hero controller's buttons sit in the same places as a DualShock's, so
+    /// Start, Select and the strum bar line up, but the frets and star power
+    /// aren't the face and shoulder buttons they sit on and are left out.
+    pub fn buttons(&self) -> Option<GamepadButtons> {
+        match self {
+            Device::Classic(x) => Some(x.buttons),
+            Device::AnalogJoystick(x) | Device::DualShock(x) => Some(x.buttons),
+            Device::DualShock2(x) => Some(x.buttons),
+            Device::GuitarHero(x) => Some(x.buttons_owned()),
+            Device::JogCon(x) => Some(x.buttons),
+            Device::None | Device::Unknown | Device::ConfigurationMode |
+            Device::Mouse(_) | Device::NegCon(_) | Device::GunCon(_) | Device::Baton(_) => None,
+        }
+    }
 
-impl<E, SPI, CS> PlayStationPort<SPI, CS>
-where
-    SPI: spi::Transfer<u8, Error = E>,
-    CS: OutputPin {
+    /// Treat any controller with standard buttons as an original controller,
+    /// dropping everything but the buttons. See `buttons` for which ones.
+    pub fn downgrade_to_classic(&self) -> Option<Classic> {
+        self.buttons().map(|buttons| Classic { buttons })
+    }
 
-    /// Create a new device to talk over the PlayStation's controller
-    /// port
-    pub fn new(spi: SPI, mut select: Option<CS>) -> Self {
-        // If a select pin was provided, disable the controller for now
-        if let Some(ref mut x) = select {
-            x.set_high();
+    /// The controller's data if it's a mouse
+    #[inline]
+    pub fn as_mouse(&self) -> Option<&Mouse> {
+        match self {
+            Device::Mouse(x) => Some(x),
+            _ => None,
         }
+    }
 
-        Self {
-            dev: spi,
-            select,
-            multitap_port: MultitapPort::A,
+    /// The controller's data if it's a mouse, consuming the device
+    #[inline]
+    pub fn into_mouse(self) -> Option<Mouse> {
+        match self {
+            Device::Mouse(x) => Some(x),
+            _ => None,
         }
     }
 
-    fn flip(bytes: &mut [u8]) {
-        for byte in bytes.iter_mut() {
-		    *byte = byte.swap_bits();
-	    }
+    /// The controller's data if it's an original controller
+    #[inline]
+    pub fn as_classic(&self) -> Option<&Classic> {
+        match self {
+            Device::Classic(x) => Some(x),
+            _ => None,
+        }
     }
 
-    /// Set the active port on the multi-tap. If no tap is being used, anything
-    /// other than `A` will fail to return anything. Or so I assume! Setting this
-    /// will mean any commands send will be directed towards that port indefinitely.
-    pub fn set_multitap_port(&mut self, port: MultitapPort) {
-        self.multitap_port = port;
+    /// The controller's data if it's an original controller, consuming the device
+    #[inline]
+    pub fn into_classic(self) -> Option<Classic> {
+        match self {
+            Device::Classic(x) => Some(x),
+            _ => None,
+        }
     }
 
-    /// Sends commands to the underlying hardware and provides responses
-    pub fn send_command(&mut self, command: &[u8], result: &mut [u8]) -> Result<(), E> {
-        // Pack in bytes for the command we'll be sending
-        result[..command.len()].copy_from_slice(command);
-        result[0] = self.multitap_port.clone() as u8;
+    /// The controller's data if it's an analog joystick
+    #[inline]
+    pub fn as_analog_joystick(&self) -> Option<&DualShock> {
+        match self {
+            Device::AnalogJoystick(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        // Because not all hardware supports LSB mode for SPI, we flip
-        // the bits ourselves
-        Self::flip(result);
+    /// The controller's data if it's an analog joystick, consuming the device
+    #[inline]
+    pub fn into_analog_joystick(self) -> Option<DualShock> {
+        match self {
+            Device::AnalogJoystick(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        if let Some(ref mut x) = self.select {
-            x.set_low();
+    /// The controller's data if it's a DualShock
+    #[inline]
+    pub fn as_dualshock(&self) -> Option<&DualShock> {
+        match self {
+            Device::DualShock(x) => Some(x),
+            _ => None,
         }
+    }
 
-        self.dev.transfer(result)?;
+    /// The controller's data if it's a DualShock, consuming the device
+    #[inline]
+    pub fn into_dualshock(self) -> Option<DualShock> {
+        match self {
+            Device::DualShock(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        if let Some(ref mut x) = self.select {
-            x.set_high();
+    /// The controller's data if it's a DualShock 2
+    #[inline]
+    pub fn as_dualshock2(&self) -> Option<&DualShock2> {
+        match self {
+            Device::DualShock2(x) => Some(x),
+            _ => None,
         }
+    }
 
-        Self::flip(result);
+    /// The controller's data if it's a DualShock 2, consuming the device
+    #[inline]
+    pub fn into_dualshock2(self) -> Option<DualShock2> {
+        match self {
+            Device::DualShock2(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        Ok(())
+    /// The controller's data if it's a Guitar Hero controller
+    #[inline]
+    pub fn as_guitar_hero(&self) -> Option<&GuitarHero> {
+        match self {
+            Device::GuitarHero(x) => Some(x),
+            _ => None,
+        }
     }
 
-    /// Configure the controller to set it to DualShock2 mode. This will also
-    /// enable analog mode on DualShock1 controllers.
-    pub fn enable_pressure(&mut self) -> Result<(), E> {
-        // TODO: Redefine this to allow input parameters. Right now they're are hard coded
-        // TODO: Detect and return actual protocol errors
+    /// The controller's data if it's a Guitar Hero controller, consuming the device
+    #[inline]
+    pub fn into_guitar_hero(self) -> Option<GuitarHero> {
+        match self {
+            Device::GuitarHero(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+    /// The controller's data if it's a JogCon
+    #[inline]
+    pub fn as_jogcon(&self) -> Option<&JogCon> {
+        match self {
+            Device::JogCon(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        // Wake up the controller if needed
-        self.send_command(CMD_POLL, &mut buffer)?;
+    /// The controller's data if it's a JogCon, consuming the device
+    #[inline]
+    pub fn into_jogcon(self) -> Option<JogCon> {
+        match self {
+            Device::JogCon(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        self.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer)?;
-        self.send_command(CMD_SET_MODE, &mut buffer)?;
-        self.send_command(CMD_MOTOR_DUALSHOCK, &mut buffer)?;
-        self.send_command(CMD_INIT_PRESSURE, &mut buffer)?;
-        self.send_command(CMD_RESPONSE_FORMAT, &mut buffer)?;
-        self.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer)?;
+    /// The controller's data if it's a NegCon
+    #[inline]
+    pub fn as_negcon(&self) -> Option<&NegCon> {
+        match self {
+            Device::NegCon(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        Ok(())
+    /// The controller's data if it's a NegCon, consuming the device
+    #[inline]
+    pub fn into_negcon(self) -> Option<NegCon> {
+        match self {
+            Device::NegCon(x) => Some(x),
+            _ => None,
+        }
     }
 
-    /// Configure the JogCon for wheel control.
-    /// 
-    /// If no digital buttons are pressed in this mode for 60 seconds, the
-    /// JogCon will go to sleep until buttons are pressed. If no polling is
-    /// done for 10 seconds, it will drop out of this mode and revert to
-    /// the standard Controller mode
-    pub fn enable_jogcon(&mut self) -> Result<(), E> {
-        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+    /// The controller's data if it's a GunCon
+    #[inline]
+    pub fn as_guncon(&self) -> Option<&GunCon> {
+        match self {
+            Device::GunCon(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        // Wake up the controller if needed
-        self.send_command(CMD_POLL, &mut buffer)?;
+    /// The controller's data if it's a GunCon, consuming the device
+    #[inline]
+    pub fn into_guncon(self) -> Option<GunCon> {
+        match self {
+            Device::GunCon(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        self.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer)?;
-        self.send_command(CMD_SET_MODE, &mut buffer)?;
-        self.send_command(CMD_MOTOR_JOGCON, &mut buffer)?;
-        self.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer)?;
+    /// The controller's data if it's a baton
+    #[inline]
+    pub fn as_baton(&self) -> Option<&Baton> {
+        match self {
+            Device::Baton(x) => Some(x),
+            _ => None,
+        }
+    }
 
-        Ok(())
+    /// The controller's data if it's a baton, consuming the device
+    #[inline]
+    pub fn into_baton(self) -> Option<Baton> {
+        match self {
+            Device::Baton(x) => Some(x),
+            _ => None,
+        }
     }
+}
 
-    /// Read various parameters from the controller including its current
-    /// status.
-    pub fn read_config(&mut self) -> Result<ControllerConfiguration, E> {
-        let mut config: ControllerConfiguration = Default::default();
-        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+/// A device read out of a poll buffer without copying. The common
+/// controllers are views that read from the buffer as they're asked, while
+/// the rest are small enough that they're just parsed like in `Device`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeviceRef<'a> {
+    /// If pulling the device type didn't work
+    None,
+    /// A new controller type we haven't seen before
+    Unknown,
+    /// The controller is waiting for configuration data
+    ConfigurationMode,
+    /// Original controller, or a DualShock in digital mode
+    Classic(ClassicRef<'a>),
+    /// Sony's flight stick, which sends the same data as a DualShock
+    AnalogJoystick(DualShockRef<'a>),
+    /// Controller with two analog sticks
+    DualShock(DualShockRef<'a>),
+    /// Controller with two analog sticks and pressure sensitive buttons
+    DualShock2(DualShock2Ref<'a>),
+    /// Any other controller, already parsed
+    Other(Device),
+}
 
-        self.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer)?;
+impl<'a> DeviceRef<'a> {
+    /// Work out which device sent a frame, header included, and view it.
+    /// Returns `None` if the frame is shorter than the device it claims to
+    /// come from.
+    pub fn parse(frame: &'a [u8]) -> Option<Self> {
+        let id = *frame.get(1)?;
+        let payload = frame.get(HEADER_LEN..frame_len(id).min(frame.len()))?;
 
-        self.send_command(CMD_READ_STATUS, &mut buffer)?;
-        config.status.copy_from_slice(&buffer[HEADER_LEN..9]);
+        match DeviceId::from_byte(id) {
+            DeviceId::NotPresent => Some(DeviceRef::None),
+            DeviceId::Configuration => Some(DeviceRef::ConfigurationMode),
+            DeviceId::Mouse => Mouse::from_frame(payload).map(|x| DeviceRef::Other(Device::Mouse(x))),
+            DeviceId::Classic => ClassicRef::new(payload).map(DeviceRef::Classic),
+            DeviceId::AnalogJoystick => DualShockRef::new(payload).map(DeviceRef::AnalogJoystick),
+            DeviceId::DualShockDigital => ClassicRef::new(payload).map(DeviceRef::Classic),
+            DeviceId::DualShockAnalog => DualShockRef::new(payload).map(DeviceRef::DualShock),
+            DeviceId::DualShockPressure => DualShock2Ref::new(payload).map(DeviceRef::DualShock2),
+            DeviceId::JogCon => JogCon::from_frame(payload).map(|x| DeviceRef::Other(Device::JogCon(x))),
+            DeviceId::NegCon => NegCon::from_frame(payload).map(|x| DeviceRef::Other(Device::NegCon(x))),
+            DeviceId::GunCon => GunCon::from_frame(payload).map(|x| DeviceRef::Other(Device::GunCon(x))),
+            DeviceId::Other(_) => Some(DeviceRef::Unknown),
+        }
+    }
+
+    /// Copy the device out of the buffer
+    pub fn to_owned(&self) -> Device {
+        match self {
+            DeviceRef::None => Device::None,
+            DeviceRef::Unknown => Device::Unknown,
+            DeviceRef::ConfigurationMode => Device::ConfigurationMode,
+            DeviceRef::Classic(x) => Device::Classic(x.to_owned()),
+            DeviceRef::AnalogJoystick(x) => Device::AnalogJoystick(x.to_owned()),
+            DeviceRef::DualShock(x) => Device::DualShock(x.to_owned()),
+            DeviceRef::DualShock2(x) => Device::DualShock2(x.to_owned()),
+            DeviceRef::Other(x) => *x,
+        }
+    }
+}
 
-        self.send_command(CMD_READ_CONST1A, &mut buffer)?;
-        config.const1a.copy_from_slice(&buffer[4..9]);
+/// Which way bytes went in a transfer, for a `TransferObserver`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransferDirection {
+    /// Bytes sent to the controller
+    Sent,
+    /// Bytes the controller sent back
+    Received,
+}
 
-        self.send_command(CMD_READ_CONST1B, &mut buffer)?;
-        config.const1b.copy_from_slice(&buffer[4..9]);
+/// Gets a look at every transfer a port makes, for logging what's on the
+/// wire. The bytes are in their usual bit order rather than flipped for SPI.
+/// A plain function rather than a closure so it works anywhere, which means
+/// anything it records has to go somewhere static. `GapDelay` and
+/// `TickSource` are plain functions for the same reason.
+pub type TransferObserver = fn(TransferDirection, &[u8]);
 
-        self.send_command(CMD_READ_CONST2, &mut buffer)?;
-        config.const2.copy_from_slice(&buffer[4..9]);
+/// Waits the given number of microseconds, for the gaps in
+/// `CsMode::PulsePerByte`, see `TransferObserver`
+pub type GapDelay = fn(u32);
 
-        self.send_command(CMD_READ_CONST3A, &mut buffer)?;
-        config.const3a.copy_from_slice(&buffer[4..9]);
+/// Reads a free running tick counter, for `PollInterval`. The ticks can be
+/// any length, and wrapping around is fine. See `TransferObserver`.
+pub type TickSource = fn() -> u32;
 
-        self.send_command(CMD_READ_CONST3B, &mut buffer)?;
-        config.const3b.copy_from_slice(&buffer[4..9]);
+/// What a poll does when it comes too soon, see `PollInterval`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IntervalMode {
+    /// Wait, spinning on the tick source, until it's been long enough
+    Block,
+    /// Don't poll, and hand back `Error::TooSoon`
+    NonBlocking,
+}
 
-        self.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer)?;
+/// Keeps polls a minimum time apart, see `set_poll_interval`
+#[derive(Copy, Clone, Debug)]
+pub struct PollInterval {
+    /// Reads the time
+    pub now: TickSource,
+    /// The least time between the start of one poll and the next, in ticks
+    pub min_ticks: u32,
+    /// What to do about a poll that comes sooner
+    pub mode: IntervalMode,
+}
 
-        Ok(config)
-    }
+/// How the select (attention) line is driven through a transfer
+#[derive(Copy, Clone, Debug)]
+pub enum CsMode {
+    /// Held low for the whole transfer. Genuine Sony controllers never need
+    /// anything else.
+    Continuous,
+    /// Let go after every byte, the way some consoles do, for clone receivers
+    /// and long cables that drop bytes otherwise. Each byte is a transfer of
+    /// its own, with select high for `gap_us` in between.
+    PulsePerByte {
+        /// How long select stays high between bytes, in microseconds
+        gap_us: u32,
+        /// Waits out the gap
+        delay: GapDelay,
+    },
+}
 
-    fn read_port(&mut self, command: Option<&PollCommand>) -> Result<[u8; MESSAGE_MAX_LENGTH], Error<E>> {
-        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
-        let mut data = [0u8; MESSAGE_MAX_LENGTH];
+/// Which bit of each byte the SPI bus sends first. The controller wants the
+/// least significant bit first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitOrder {
+    /// The most significant bit goes first, so the port reverses every byte
+    /// itself. This is all a lot of SPI hardware can do.
+    MsbFirst,
+    /// The bus already sends the least significant bit first, like `SoftSpi`
+    /// does, so bytes go out as they are
+    LsbFirst,
+}
 
-        data[..CMD_POLL.len()].copy_from_slice(CMD_POLL);
+/// The main event! Create a port using an SPI bus and start commanding
+/// controllers!
+///
+/// `N` is the longest frame the port will clock, and the size of every buffer
+/// it polls into. The default of `MESSAGE_MAX_LENGTH` fits any one
+/// controller, but a product that only ever sees digital pads can get by with
+/// 5 bytes (see `PortBuilder::frame_size`), and a multi-tap answering for all
+/// its slots at once needs more. Frames longer than `N` fail with
+/// `Error::BadResponse`, and calls that need longer answers than `N` allows,
+/// like `enable_pressure` on a port shorter than a DualShock 2's 21 bytes,
+/// don't compile:
+///
+/// ```compile_fail,E0080
+/// # extern crate embedded_hal as hal;
+/// # extern crate pscontroller_rs;
+/// # use pscontroller_rs::PlayStationPort;
+/// # struct Spi;
+/// # impl hal::blocking::spi::Transfer<u8> for Spi {
+/// #     type Error = ();
+/// #     fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> { Ok(words) }
+/// # }
+/// # struct Pin;
+/// # #[allow(deprecated)]
+/// # impl hal::digital::OutputPin for Pin {
+/// #     fn set_low(&mut self) {}
+/// #     fn set_high(&mut self) {}
+/// # }
+/// let mut port = PlayStationPort::builder(Spi, None::<Pin>).frame_size::<9>().build();
+/// port.enable_pressure();
+/// ```
+///
+/// `R` decides what to do about errors, see `RecoveryPolicy`. The default,
+/// `FailFast`, hands every error straight back.
+pub struct PlayStationPort<SPI, CS, const N: usize = { MESSAGE_MAX_LENGTH }, R = FailFast> {
+    dev: SPI,
+    select: Option<CS>,
+    multitap_port: MultitapPort,
+    /// How many bytes to clock for the next poll, based on what the device
+    /// claimed last time
+    poll_len: usize,
+    /// Whether to re-poll through frames sent while the controller changes modes
+    smooth_transitions: bool,
+    /// The last state `poll_changed` handed back
+    reported: Option<Device>,
+    /// How far a stick or pressure has to move for `poll_changed` to notice
+    change_tolerance: u8,
+    /// The last controller successfully read, cleared when it goes away
+    last_input: Option<Device>,
+    /// What went wrong with the last poll, if anything
+    last_error: Option<Error<()>>,
+    /// Whether each transfer runs inside a critical section
+    #[cfg(feature = "critical-section")]
+    critical_section: bool,
+    /// Shown every transfer, if set
+    observer: Option<TransferObserver>,
+    /// Whether select is let go between bytes
+    cs_mode: CsMode,
+    /// Whether bytes need reversing for the bus
+    bit_order: BitOrder,
+    /// Whether to check the bus is idle before each transfer
+    verify_bus_idle: bool,
+    /// How far apart polls are kept, if at all
+    poll_interval: Option<PollInterval>,
+    /// When the last poll started, by `poll_interval`'s clock
+    last_poll: Option<u32>,
+    /// Motor strengths to send with every poll, as (small, large)
+    rumble: (bool, u8),
+    /// How many polls a `set_rumble` lasts, or 0 for as long as it takes
+    rumble_ttl: u16,
+    /// How many polls the current rumble has left
+    rumble_left: u16,
+    /// What `probe` found plugged in, cleared when it goes away
+    probed: Option<ControllerKind>,
+    /// Whether the last poll found a controller, for counting disconnects
+    connected: bool,
+    stats: PortStats,
+    /// Asked what to do about errors
+    recovery: R,
+    /// What the last `enable_pressure` or `enable_jogcon` set up
+    setup: Option<AppliedConfig>,
+    /// The same, until the controller is unplugged
+    applied: Option<AppliedConfig>,
+}
 
-        // Overlay the command to send with the poll...
-        if let Some(x) = command {
-            x.set_command(&mut data[HEADER_LEN..]);
-        }
+#[allow(deprecated)]
+impl<E, SPI, CS> PlayStationPort<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin {
 
-        self.send_command(&data, &mut buffer)?;
+    /// Create a new device to talk over the PlayStation's controller
+    /// port
+    pub fn new(spi: SPI, select: Option<CS>) -> Self {
+        Self::sized(spi, select, FailFast)
+    }
 
-        // Device polling will return `ACK_BYTE` in the third byte if the command
-        // was properly understood
-        /*
-        match device {
-            Device::None => {},
-            _ => {
-                if buffer[2] != ACK_BYTE {
-                    return Err(Error::BadResponse);
-                }
-            }
+    /// Start setting up a port with more than the defaults. `new` is the same
+    /// as building straight away.
+    pub fn builder(spi: SPI, select: Option<CS>) -> PortBuilder<SPI, CS> {
+        PortBuilder {
+            spi,
+            select,
+            multitap_port: MultitapPort::A,
+            smooth_transitions: false,
+            change_tolerance: 0,
+            #[cfg(feature = "critical-section")]
+            critical_section: false,
+            observer: None,
+            cs_mode: CsMode::Continuous,
+            bit_order: BitOrder::MsbFirst,
+            verify_bus_idle: false,
+            poll_interval: None,
+            rumble_ttl: 0,
+            recovery: FailFast,
         }
-        */
-
-        Ok(buffer)
     }
+}
 
-    /// Get the raw data from polling for a controller. You can use this to cooerce the data into
-    /// some controller that can't be safely identified by `read_input`, but you should rely on that
-    /// function if you can.
-    pub fn read_raw(&mut self, command: Option<&PollCommand>) -> Result<ControllerData, Error<E>> {
-        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
-        let data = self.read_port(command)?;
+#[allow(deprecated)]
+impl<E, SPI, CS, const N: usize, R> PlayStationPort<SPI, CS, N, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// `new`, for any frame size and recovery policy
+    fn sized(spi: SPI, mut select: Option<CS>, recovery: R) -> Self {
+        const {
+            assert!(N >= frame_len(CONTROLLER_CLASSIC), "a port needs at least 5 bytes, for a digital pad");
+        }
 
-        // Shift the controller data over because we don't need the header anymore
-        buffer[0 .. MESSAGE_MAX_LENGTH - 3].copy_from_slice(&data[HEADER_LEN..]);
+        // If a select pin was provided, disable the controller for now
+        if let Some(ref mut x) = select {
+            x.set_high();
+        }
 
-        // FIXME: This doesn't zero out the end so we could theoretically have
-        //        repeated data
+        Self {
+            dev: spi,
+            select,
+            multitap_port: MultitapPort::A,
+            poll_len: N,
+            smooth_transitions: false,
+            reported: None,
+            change_tolerance: 0,
+            last_input: None,
+            last_error: None,
+            #[cfg(feature = "critical-section")]
+            critical_section: false,
+            observer: None,
+            cs_mode: CsMode::Continuous,
+            bit_order: BitOrder::MsbFirst,
+            verify_bus_idle: false,
+            poll_interval: None,
+            last_poll: None,
+            rumble: (false, 0),
+            rumble_ttl: 0,
+            rumble_left: 0,
+            probed: None,
+            connected: false,
+            stats: PortStats::default(),
+            recovery,
+            setup: None,
+            applied: None,
+        }
+    }
 
-        Ok(ControllerData { data: buffer })
+    /// Give back the SPI bus and select pin
+    pub fn release(self) -> (SPI, Option<CS>) {
+        (self.dev, self.select)
     }
 
-    /// Ask the controller for input states. Different contoller types will be returned automatically
-    /// for you. If you'd like to cooerce a controller yourself, use `read_raw`.
-    pub fn read_input(&mut self, command: Option<&PollCommand>) -> Result<Device, Error<E>> {
-        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
-        let data = self.read_port(command)?;
-
-        // Shift the controller data over because we don't need the header anymore
-        buffer[0 .. MESSAGE_MAX_LENGTH - 3].copy_from_slice(&data[HEADER_LEN..]);
-
-        let controller = ControllerData { data: buffer };
-        let device;
-
-        unsafe {
-            device = match data[1] {
-                CONTROLLER_NOT_PRESENT => Device::None,
-                CONTROLLER_CONFIGURATION => Device::ConfigurationMode,
-                CONTROLLER_MOUSE => Device::Mouse(controller.pm),
-                CONTROLLER_CLASSIC => Device::Classic(controller.classic),
-                CONTROLLER_ANALOG_JOYSTICK => Device::AnalogJoystick(controller.ds),
-                CONTROLLER_DUALSHOCK_DIGITAL => Device::Classic(controller.classic),                
-                CONTROLLER_DUALSHOCK_ANALOG => Device::DualShock(controller.ds),
-                CONTROLLER_DUALSHOCK_PRESSURE => Device::DualShock2(controller.ds2),
-                CONTROLLER_JOGCON => Device::JogCon(controller.jc),
-                CONTROLLER_NEGCON => Device::NegCon(controller.nc),
-                CONTROLLER_GUNCON => Device::GunCon(controller.gc),
-                _ => Device::Unknown,
-            }
+    /// The same port with a different select pin, handing back the old one.
+    /// Everything else the port keeps track of carries over.
+    fn swap_select<CS2: OutputPin>(self, mut select: Option<CS2>) -> (PlayStationPort<SPI, CS2, N, R>, Option<CS>) {
+        if let Some(ref mut x) = select {
+            x.set_high();
         }
 
-        Ok(device)
+        self.rebuild(select, |x| x)
     }
-}
 
-mod tests {
-    #[test]
-    fn union_test() {
-        // Again, buttons are active low, hence 'fe' and '7f'
-        let controller = ControllerData {
-            data: [
-                0xfe,
-                0x7f,
-                0x00,
-                0x00,
-                0x00,
-                0xff
-            ],
-        };
+    /// The same port with the recovery policy swapped for `policy`, see
+    /// `RecoveryPolicy`. Everything else the port keeps track of carries
+    /// over.
+    pub fn with_recovery_policy<P: RecoveryPolicy>(mut self, policy: P) -> PlayStationPort<SPI, CS, N, P> {
+        let select = self.select.take();
 
-        unsafe {
-            assert!(controller.ds.buttons.select() == true);
-            assert!(controller.ds.buttons.square() == true);
-            assert!(controller.ds.lx == 0);
-            assert!(controller.ds.ly == 255);
+        self.rebuild(select, |_| policy).0
+    }
+
+    /// The recovery policy, for changing its settings
+    pub fn recovery_policy(&mut self) -> &mut R {
+        &mut self.recovery
+    }
+
+    /// Move everything over to a port with `select` and whatever `recovery`
+    /// makes of the policy, handing back the old select pin
+    fn rebuild<CS2, P>(self, select: Option<CS2>, recovery: impl FnOnce(R) -> P) -> (PlayStationPort<SPI, CS2, N, P>, Option<CS>) {
+        let port = PlayStationPort {
+            dev: self.dev,
+            select,
+            multitap_port: self.multitap_port,
+            poll_len: self.poll_len,
+            smooth_transitions: self.smooth_transitions,
+            reported: self.reported,
+            change_tolerance: self.change_tolerance,
+            last_input: self.last_input,
+            last_error: self.last_error,
+            #[cfg(feature = "critical-section")]
+            critical_section: self.critical_section,
+            observer: self.observer,
+            cs_mode: self.cs_mode,
+            bit_order: self.bit_order,
+            verify_bus_idle: self.verify_bus_idle,
+            poll_interval: self.poll_interval,
+            last_poll: self.last_poll,
+            rumble: self.rumble,
+            rumble_ttl: self.rumble_ttl,
+            rumble_left: self.rumble_left,
+            probed: self.probed,
+            connected: self.connected,
+            stats: self.stats,
+            recovery: recovery(self.recovery),
+            setup: self.setup,
+            applied: self.applied,
+        };
+
+        (port, self.select)
+    }
+
+    /// Set the active port on the multi-tap. If no tap is being used, anything
+    /// other than `A` will fail to return anything. Or so I assume! Setting this
+    /// will mean any commands send will be directed towards that port indefinitely.
+    pub fn set_multitap_port(&mut self, port: MultitapPort) {
+        self.multitap_port = port;
+    }
+
+    /// When the controller changes modes (someone pressed Analog, or after
+    /// `enable_pressure`) it answers a poll or two in configuration mode or
+    /// with a mangled frame. With this on, `read_input` quietly polls again
+    /// a few times instead of handing those frames back. Off by default.
+    pub fn smooth_transitions(&mut self, enable: bool) {
+        self.smooth_transitions = enable;
+    }
+
+    /// Run each transfer, select pin and all, inside `critical_section::with`
+    /// so an interrupt using the same bus can't land in the middle of a frame.
+    /// Off by default.
+    ///
+    /// Interrupts stay off for a whole transfer, which adds up: a DualShock 2
+    /// frame is 21 bytes, close to 700µs at 250 kHz, and configuration
+    /// commands that take several transfers let interrupts back in between
+    /// each one. Only turn this on if something else really does use the bus
+    /// from an interrupt.
+    #[cfg(feature = "critical-section")]
+    pub fn use_critical_section(&mut self, enable: bool) {
+        self.critical_section = enable;
+    }
+
+    /// Show every transfer to `observer` once it's finished, first the bytes
+    /// sent and then the bytes that came back. Transfers that fail aren't
+    /// shown. One longer than the port's frame size, like a memory card read
+    /// or write, or a long `send_command`, is shown a piece of that size at a
+    /// time, so a failure partway through may come after the first few
+    /// pieces. `None` turns it off again.
+    pub fn set_observer(&mut self, observer: Option<TransferObserver>) {
+        self.observer = observer;
+    }
+
+    /// How select is driven through each transfer, see `CsMode`.
+    /// `CsMode::Continuous` by default.
+    pub fn set_cs_mode(&mut self, mode: CsMode) {
+        self.cs_mode = mode;
+    }
+
+    /// Which bit the SPI bus sends first, see `BitOrder`.
+    /// `BitOrder::MsbFirst` by default.
+    pub fn set_bit_order(&mut self, order: BitOrder) {
+        self.bit_order = order;
+    }
+
+    /// Before each transfer, clock one byte with select released and make
+    /// sure it reads idle (0xff). If it doesn't, another device on a shared
+    /// bus is partway through its own transfer, or the data line is stuck
+    /// low, and the transfer is refused with `Error::BusBusy` rather than
+    /// reading garbage. Nothing is listening with select released, so the
+    /// check doesn't disturb anything. Off by default.
+    ///
+    /// This costs a byte's time on every transfer. Ports without a select pin
+    /// can't release it, so they never check.
+    pub fn verify_bus_idle(&mut self, enable: bool) {
+        self.verify_bus_idle = enable;
+    }
+
+    /// Keep polls through the `read_input` family (`poll_n` included) at
+    /// least `interval.min_ticks` apart. Some pads, wireless clones most of
+    /// all, send stale or mangled frames when polled every few milliseconds
+    /// or faster, which is easy to do from a busy loop. A poll that comes
+    /// too soon either waits or fails with `Error::TooSoon`, going by
+    /// `interval.mode`. Retries within one call aren't held back, and neither
+    /// are configuration commands. `None`, the default, polls as fast as
+    /// asked.
+    pub fn set_poll_interval(&mut self, interval: Option<PollInterval>) {
+        self.poll_interval = interval;
+    }
+
+    /// How far a stick or pressure reading has to move before `poll_changed`
+    /// counts it as a change, so jitter doesn't. 0 by default, which counts
+    /// every movement.
+    pub fn set_change_tolerance(&mut self, tolerance: u8) {
+        self.change_tolerance = tolerance;
+    }
+
+    /// Keep the motors running at these strengths, sending them along with
+    /// every poll that isn't given a command of its own. This way whatever
+    /// decides on rumble doesn't have to be the same code that polls.
+    ///
+    /// The motors only listen once `enable_pressure` has mapped them, and
+    /// until then nothing extra is sent. Pulling the controller out clears
+    /// the setting and the mapping both.
+    pub fn set_rumble(&mut self, small: bool, large: u8) {
+        self.rumble = (small, large);
+        self.rumble_left = self.rumble_ttl;
+    }
+
+    /// Turn the motors off by themselves `polls` polls after the last
+    /// `set_rumble`, the way games keep refreshing rumble every frame. Then a
+    /// hung program or a forgotten `stop_rumble` can't leave a motor running
+    /// (and burning out). 0 keeps the motors going until told otherwise,
+    /// which is the default.
+    pub fn set_rumble_ttl(&mut self, polls: u16) {
+        self.rumble_ttl = polls;
+        self.rumble_left = polls;
+    }
+
+    /// The motor strengths from `set_rumble`, as `(small, large)`
+    pub fn rumble(&self) -> (bool, u8) {
+        self.rumble
+    }
+
+    /// Turn both motors off from the next poll on
+    pub fn stop_rumble(&mut self) {
+        self.rumble = (false, 0);
+    }
+
+    /// What to poll with when nothing else was asked for. Each call is
+    /// another poll as far as `set_rumble_ttl` is concerned.
+    fn stored_rumble(&mut self) -> Option<ControlDS> {
+        if self.rumble_ttl != 0 {
+            if self.rumble_left == 0 {
+                self.stop_rumble();
+            } else {
+                self.rumble_left -= 1;
+            }
+        }
+
+        // Only `enable_pressure` maps the motors to the poll's bytes
+        if self.applied == Some(AppliedConfig::Pressure) {
+            Some(ControlDS::new(self.rumble.0, self.rumble.1))
+        } else {
+            None
+        }
+    }
+
+    /// The controller went away, and whatever it was set up for with it
+    fn forget_controller(&mut self) {
+        if self.connected {
+            bump(&mut self.stats.disconnects);
+        }
+
+        self.connected = false;
+        self.applied = None;
+        self.probed = None;
+        self.stop_rumble();
+    }
+
+    /// How polls and configuration have gone, see `PortStats`
+    pub fn stats(&self) -> &PortStats {
+        &self.stats
+    }
+
+    /// Start every count in `stats` over from zero
+    pub fn reset_stats(&mut self) {
+        self.stats = PortStats::default();
+    }
+
+    /// Run one of the configuration paths, counting each time it fails and
+    /// running it again for as long as the recovery policy says to
+    fn configure<T, X, F>(&mut self, mut f: F) -> Result<T, X>
+    where
+        F: FnMut(&mut Self) -> Result<T, X> {
+
+        let mut attempt = 0u8;
+        loop {
+            let result = f(self);
+            if result.is_ok() {
+                return result;
+            }
+
+            bump(&mut self.stats.config_failures);
+            attempt = attempt.saturating_add(1);
+            if self.config_action(attempt) == Action::Fail {
+                return result;
+            }
+        }
+    }
+
+    /// The controller from the last successful poll through any of the
+    /// `read_input` family (`poll_changed` and friends included). It's kept
+    /// through failed polls, but cleared as soon as a poll finds nothing
+    /// plugged in, so input from a controller that's been pulled out can't
+    /// keep driving anything. `read_raw` and `poll_buttons` don't parse the
+    /// whole controller and leave this alone.
+    pub fn last_input(&self) -> Option<&Device> {
+        self.last_input.as_ref()
+    }
+
+    /// The error from the last poll through the `read_input` family, or
+    /// `None` if it succeeded. SPI errors are kept as `Error::Spi(())`.
+    pub fn last_error(&self) -> Option<&Error<()>> {
+        self.last_error.as_ref()
+    }
+
+    /// Sends commands to the underlying hardware and provides responses. Only
+    /// as many bytes as there are in `command` are clocked out, so pad it
+    /// if you're expecting a longer response. A `result` shorter than
+    /// `command` is `Error::BufferTooSmall`, and nothing is sent.
+    pub fn send_command(&mut self, command: &[u8], result: &mut [u8]) -> Result<(), Error<E>> {
+        let result = field_mut(result, 0..command.len())?;
+
+        // Pack in bytes for the command we'll be sending
+        result.copy_from_slice(command);
+        if let Some(x) = result.first_mut() {
+            *x = self.multitap_port as u8;
+        }
+
+        self.exchange(result)
+    }
+
+    /// Clock `bytes` out exactly as they are, first byte included, and leave
+    /// what came back in their place
+    fn exchange(&mut self, bytes: &mut [u8]) -> Result<(), Error<E>> {
+        #[cfg(feature = "critical-section")]
+        let transferred = if self.critical_section {
+            critical_section::with(|_| self.idle_then_transfer(bytes))
+        } else {
+            self.idle_then_transfer(bytes)
+        };
+        #[cfg(not(feature = "critical-section"))]
+        let transferred = self.idle_then_transfer(bytes);
+
+        transferred
+    }
+
+    /// `transfer_pieces` with select held as `cs_mode` says, checking the bus
+    /// is idle first if `verify_bus_idle` asks. A transfer the SPI device
+    /// cuts short, because it watches the ACK line and the device stopped
+    /// answering, is `Error::Timeout`, with the rest of `bytes` reading as an
+    /// idle line.
+    fn idle_then_transfer(&mut self, bytes: &mut [u8]) -> Result<(), Error<E>> {
+        if self.verify_bus_idle && self.select.is_some() && !self.bus_idle::<1>()? {
+            return Err(Error::BusBusy);
+        }
+
+        let continuous = matches!(self.cs_mode, CsMode::Continuous);
+        if continuous {
+            self.set_selected(true);
+        }
+
+        let completed = self.transfer_pieces(bytes);
+
+        // Let go of the controller even if the transfer failed, so another
+        // device on the bus isn't talked over
+        if continuous {
+            self.set_selected(false);
+        }
+
+        let completed = completed?;
+        if let Some(rest) = bytes.get_mut(completed..).filter(|x| !x.is_empty()) {
+            rest.fill(LINE_IDLE);
+            return Err(Error::Timeout { bytes_completed: completed.min(u8::MAX as usize) as u8 });
+        }
+
+        Ok(())
+    }
+
+    /// Flip and clock out `bytes`, showing each piece to the observer as it
+    /// finishes. Returns how many bytes made it across before the SPI device
+    /// stopped.
+    fn transfer_pieces(&mut self, bytes: &mut [u8]) -> Result<usize, E> {
+        // What goes out is overwritten by what comes back, so the observer's
+        // copy is kept here, and something longer than that is shown a piece
+        // at a time
+        let piece = match self.observer {
+            Some(_) => N,
+            None => bytes.len(),
+        };
+
+        // Because not all hardware supports LSB mode for SPI, we flip
+        // the bits ourselves
+        let reverse = self.bit_order == BitOrder::MsbFirst;
+
+        let mut completed = 0;
+        for bytes in bytes.chunks_mut(piece.max(1)) {
+            let mut sent = [0u8; N];
+            let observed = match sent.get_mut(..bytes.len()) {
+                Some(x) if self.observer.is_some() => {
+                    x.copy_from_slice(bytes);
+                    Some(x)
+                },
+                _ => None,
+            };
+
+            if reverse {
+                flip(bytes);
+            }
+            let clocked = self.clock(bytes, completed)?;
+            if reverse {
+                flip(bytes);
+            }
+
+            completed += clocked;
+            if clocked < bytes.len() {
+                break;
+            }
+
+            if let (Some(observe), Some(sent)) = (self.observer, observed) {
+                observe(TransferDirection::Sent, sent);
+                observe(TransferDirection::Received, bytes);
+            }
+        }
+
+        Ok(completed)
+    }
+
+    /// Clock out `bytes`, which start `offset` bytes into the transfer, and
+    /// return how many made it across
+    fn clock(&mut self, bytes: &mut [u8], offset: usize) -> Result<usize, E> {
+        match self.cs_mode {
+            CsMode::Continuous => self.dev.transfer(bytes).map(|x| x.len()),
+            CsMode::PulsePerByte { gap_us, delay } => {
+                for (i, byte) in bytes.chunks_mut(1).enumerate() {
+                    if offset + i > 0 {
+                        delay(gap_us);
+                    }
+
+                    self.set_selected(true);
+                    let clocked = self.dev.transfer(byte).map(|x| x.len());
+                    self.set_selected(false);
+
+                    if clocked? == 0 {
+                        return Ok(i);
+                    }
+                }
+
+                Ok(bytes.len())
+            },
+        }
+    }
+
+    /// Whether `L` bytes clocked with select released read back as an idle
+    /// line. Nothing is listening then, so what goes out doesn't matter.
+    pub(crate) fn bus_idle<const L: usize>(&mut self) -> Result<bool, E> {
+        let mut idle = [LINE_IDLE; L];
+        self.dev.transfer(&mut idle)?;
+
+        Ok(idle.iter().all(|&x| x == LINE_IDLE))
+    }
+
+    /// `transfer_pieces` without touching select, whatever `cs_mode` says
+    pub(crate) fn released_transfer(&mut self, bytes: &mut [u8]) -> Result<usize, E> {
+        let select = self.select.take();
+        let completed = self.transfer_pieces(bytes);
+        self.select = select;
+
+        completed
+    }
+
+    fn set_selected(&mut self, selected: bool) {
+        match self.select {
+            Some(ref mut x) if selected => x.set_low(),
+            Some(ref mut x) => x.set_high(),
+            None => {},
+        }
+    }
+
+    /// Configure the controller to set it to DualShock2 mode. This will also
+    /// enable analog mode on DualShock1 controllers.
+    ///
+    /// If this port already set that up and the controller hasn't been
+    /// unplugged since, a single poll checks it's still sending pressures and
+    /// the escape mode sequence is skipped. That poll counts like any other,
+    /// so it's recorded in `last_input` and `stats` and sends the rumble.
+    pub fn enable_pressure(&mut self) -> Result<ConfigOutcome, Error<E>> {
+        // TODO: Redefine this to allow input parameters. Right now they're are hard coded
+        // TODO: Detect and return actual protocol errors
+
+        Self::fits_pressure();
+
+        if self.still_applied(AppliedConfig::Pressure) {
+            return Ok(ConfigOutcome::AlreadyApplied);
+        }
+
+        self.setup_pressure()?;
+
+        Ok(ConfigOutcome::Applied)
+    }
+
+    /// `enable_pressure`, sending the escape mode sequence without checking
+    /// first. Say after changing the controller's mode with `send_command`.
+    pub fn reapply_pressure(&mut self) -> Result<(), Error<E>> {
+        Self::fits_pressure();
+        self.setup_pressure()
+    }
+
+    /// Stop the build for ports too short for a frame with pressures
+    fn fits_pressure() {
+        const {
+            assert!(N >= frame_len(CONTROLLER_DUALSHOCK_PRESSURE), "pressures need a port of at least 21 bytes");
+        }
+    }
+
+    /// `enable_pressure` without the size check, so `reconfigure` still
+    /// compiles on ports too small to have ever set up pressures
+    pub(crate) fn setup_pressure(&mut self) -> Result<(), Error<E>> {
+        self.configure(|port| {
+            let mut buffer = [0u8; N];
+
+            // Wake up the controller if needed
+            port.send_command(CMD_POLL, &mut buffer)?;
+
+            port.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer)?;
+            port.send_command(CMD_SET_MODE, &mut buffer)?;
+            port.send_command(CMD_MOTOR_DUALSHOCK, &mut buffer)?;
+            port.send_command(CMD_INIT_PRESSURE, &mut buffer)?;
+            port.send_command(CMD_RESPONSE_FORMAT, &mut buffer)?;
+            port.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer)?;
+            port.setup = Some(AppliedConfig::Pressure);
+            port.applied = port.setup;
+
+            Ok(())
+        })
+    }
+
+    /// Configure the JogCon for wheel control.
+    /// 
+    /// If no digital buttons are pressed in this mode for 60 seconds, the
+    /// JogCon will go to sleep until buttons are pressed. If no polling is
+    /// done for 10 seconds, it will drop out of this mode and revert to
+    /// the standard Controller mode, which the check `enable_pressure`
+    /// describes will notice.
+    pub fn enable_jogcon(&mut self) -> Result<ConfigOutcome, Error<E>> {
+        Self::fits_escape_mode();
+
+        if self.still_applied(AppliedConfig::JogCon) {
+            return Ok(ConfigOutcome::AlreadyApplied);
+        }
+
+        self.setup_jogcon()?;
+
+        Ok(ConfigOutcome::Applied)
+    }
+
+    /// `enable_jogcon`, sending the escape mode sequence without checking
+    /// first
+    pub fn reapply_jogcon(&mut self) -> Result<(), Error<E>> {
+        Self::fits_escape_mode();
+        self.setup_jogcon()
+    }
+
+    /// `enable_jogcon` without the size check
+    pub(crate) fn setup_jogcon(&mut self) -> Result<(), Error<E>> {
+        self.configure(|port| {
+            let mut buffer = [0u8; N];
+
+            // Wake up the controller if needed
+            port.send_command(CMD_POLL, &mut buffer)?;
+
+            port.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer)?;
+            port.send_command(CMD_SET_MODE, &mut buffer)?;
+            port.send_command(CMD_MOTOR_JOGCON, &mut buffer)?;
+            port.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer)?;
+
+            // The JogCon reads the same bytes as its own force feedback, so
+            // rumble is no longer sent
+            port.setup = Some(AppliedConfig::JogCon);
+            port.applied = port.setup;
+
+            Ok(())
+        })
+    }
+
+    /// Whether `config` is what this port last set up, and one poll finds the
+    /// controller still in that mode. A poll that fails counts as no.
+    fn still_applied(&mut self, config: AppliedConfig) -> bool {
+        if self.applied != Some(config) {
+            return false;
+        }
+
+        let mut buffer = [0u8; N];
+        match self.read_frame(None, &mut buffer) {
+            Ok((_, meta)) => DeviceId::from_byte(meta.id) == config.device_id(),
+            Err(_) => false,
+        }
+    }
+
+    /// What `enable_pressure` or `enable_jogcon` last set up, or `None` if
+    /// neither has since the controller was last unplugged
+    pub fn applied_config(&self) -> Option<AppliedConfig> {
+        self.applied
+    }
+
+    /// Escape mode answers with full 9 byte frames, which a port built for
+    /// nothing but digital pads has no room for
+    fn fits_escape_mode() {
+        const {
+            assert!(N >= frame_len(CONTROLLER_CONFIGURATION), "escape mode needs a port of at least 9 bytes");
+        }
+    }
+
+    /// Read various parameters from the controller including its current
+    /// status.
+    pub fn read_config(&mut self) -> Result<ControllerConfiguration, Error<E>> {
+        Self::fits_escape_mode();
+        self.read_config_answered().map(|(config, _)| config)
+    }
+
+    /// `read_config`, along with whether the controller answered the status
+    /// request from escape mode. Controllers that don't have one answer it
+    /// like any other poll.
+    fn read_config_answered(&mut self) -> Result<(ControllerConfiguration, bool), Error<E>> {
+        self.configure(|port| {
+            port.escape_status(|port, config, buffer| {
+                port.send_command(CMD_READ_CONST1A, buffer)?;
+                config.lengths[1] = copy_answer(&mut config.const1a, buffer, 4)?;
+
+                port.send_command(CMD_READ_CONST1B, buffer)?;
+                config.lengths[2] = copy_answer(&mut config.const1b, buffer, 4)?;
+
+                port.send_command(CMD_READ_CONST2, buffer)?;
+                config.lengths[3] = copy_answer(&mut config.const2, buffer, 4)?;
+
+                port.send_command(CMD_READ_CONST3A, buffer)?;
+                config.lengths[4] = copy_answer(&mut config.const3a, buffer, 4)?;
+
+                port.send_command(CMD_READ_CONST3B, buffer)?;
+                config.lengths[5] = copy_answer(&mut config.const3b, buffer, 4)?;
+
+                Ok(())
+            })
+        })
+    }
+
+    /// Enter escape mode, read the controller's status, let `more` ask for
+    /// anything else while it's there, and leave again. Also returns whether
+    /// the status came from escape mode.
+    fn escape_status<F>(&mut self, more: F) -> Result<(ControllerConfiguration, bool), Error<E>>
+    where
+        F: FnOnce(&mut Self, &mut ControllerConfiguration, &mut [u8; N]) -> Result<(), Error<E>> {
+
+        let mut config: ControllerConfiguration = Default::default();
+        let mut buffer = [0u8; N];
+
+        self.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer)?;
+
+        self.send_command(CMD_READ_STATUS, &mut buffer)?;
+        let answered = byte(&buffer, 1)? == CONTROLLER_CONFIGURATION && byte(&buffer, 2)? == ACK_BYTE;
+        config.lengths[0] = copy_answer(&mut config.status, &buffer, HEADER_LEN)?;
+
+        more(self, &mut config, &mut buffer)?;
+
+        // Harmless for anything that didn't enter escape mode, it's just
+        // another poll to them
+        self.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer)?;
+
+        Ok((config, answered))
+    }
+
+    /// Whether the Analog LED is lit, meaning the controller is sending its
+    /// sticks. Players can switch it whenever the mode isn't locked, so
+    /// this is worth checking now and then for showing on screen.
+    ///
+    /// This asks for the controller's status the same way `read_config`
+    /// does, and reads it the same way as `analog_enabled`. Controllers with
+    /// no analog mode to speak of, like the original controller or the
+    /// mouse, don't answer and give `Error::UnsupportedDevice`, as does an
+    /// empty port.
+    pub fn is_analog_active(&mut self) -> Result<bool, Error<E>> {
+        Self::fits_escape_mode();
+
+        self.configure(|port| {
+            let (config, answered) = port.escape_status(|_, _, _| Ok(()))?;
+            if !answered {
+                return Err(Error::UnsupportedDevice);
+            }
+
+            Ok(config.analog_enabled())
+        })
+    }
+
+    /// Poll the controller, leaving the whole frame (header included) in
+    /// `buffer`. Anything past the end of the frame is left alone. Returns
+    /// how many bytes were clocked, which is as far as the frame can be
+    /// trusted.
+    fn read_port(&mut self, command: Option<&dyn PollCommand>, buffer: &mut [u8; N]) -> Result<usize, Error<E>> {
+        bump(&mut self.stats.polls);
+
+        let result = self.clock_frame(command, buffer);
+        if let Err(ref e) = result {
+            self.stats.record_error(e);
+        }
+
+        result
+    }
+
+    fn clock_frame(&mut self, command: Option<&dyn PollCommand>, buffer: &mut [u8; N]) -> Result<usize, Error<E>> {
+        let rumble = self.stored_rumble();
+        let command = poll_command(command.or(rumble.as_ref().map(|x| x as &dyn PollCommand)));
+
+        // Anything past the end of the command is zeroes
+        let mut data = [0u8; N];
+        for (x, &command) in data.iter_mut().zip(command.iter()) {
+            *x = command;
+        }
+
+        let mut len = self.poll_len;
+        self.send_poll(field(&data, 0..len)?, buffer)?;
+
+        // Only clock what the device says it has next time. If it has switched
+        // to a longer mode since the last poll, ask again for the whole frame.
+        // Never more than fits, though; a frame longer than that fails below
+        self.poll_len = Self::claimed_len(byte(buffer, 1)?);
+        if self.poll_len > len {
+            len = self.poll_len;
+            self.send_poll(field(&data, 0..len)?, buffer)?;
+
+            // It can change its mind again in between, in which case the
+            // frame is too short and the next poll gets it right
+            self.poll_len = Self::claimed_len(byte(buffer, 1)?);
+        }
+
+        // A frame that can't fit in any port is garbage, while one that
+        // only doesn't fit in this one is too short
+        check_frame(field(buffer, 0..len)?, N.max(MESSAGE_MAX_LENGTH))?;
+
+        Ok(len)
+    }
+
+    /// `send_command` for a poll. An SPI device watching the ACK line gives
+    /// up after the first byte when nothing is plugged in to answer, which
+    /// is no more an error than the idle line it leaves in `buffer`.
+    fn send_poll(&mut self, command: &[u8], buffer: &mut [u8]) -> Result<(), Error<E>> {
+        match self.send_command(command, buffer) {
+            Err(Error::Timeout { bytes_completed: 0..=1 }) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// How much of a frame with this id fits in the port. Unlike
+    /// `frame_len`, this doesn't stop at `MESSAGE_MAX_LENGTH`, so a port
+    /// long enough for a multi-tap's frames reads all of them.
+    fn claimed_len(id: u8) -> usize {
+        full_frame_len(id).min(N)
+    }
+
+    /// Read just the digital buttons using the shortest poll the controller
+    /// accepts. The button word sits in the same spot whatever mode the
+    /// controller is in, so this works on digital, analog and pressure pads
+    /// alike. Returns `None` when nothing is plugged in. Anything else, like
+    /// a mouse, a GunCon or a controller stuck in escape mode, doesn't send
+    /// that word and gives `Error::WrongDevice`.
+    pub fn poll_buttons(&mut self) -> Result<Option<GamepadButtons>, Error<E>> {
+        let mut buffer = [0u8; N];
+
+        // The motor bytes are part of even the shortest poll
+        let rumble = self.stored_rumble();
+        let data = poll_command(rumble.as_ref().map(|x| x as &dyn PollCommand));
+        bump(&mut self.stats.polls);
+        if let Err(e) = self.send_command(field(&data, 0..CMD_POLL_BUTTONS.len())?, &mut buffer) {
+            self.stats.record_error(&e);
+            return Err(e);
+        }
+
+        if byte(&buffer, 1)? == CONTROLLER_NOT_PRESENT {
+            self.forget_controller();
+            return Ok(None);
+        }
+
+        if byte(&buffer, 2)? != ACK_BYTE {
+            bump(&mut self.stats.timeouts);
+            return Err(Error::Timeout { bytes_completed: 2 });
+        }
+
+        self.connected = true;
+
+        let id = DeviceId::from_byte(byte(&buffer, 1)?);
+        let pad = matches!(id, DeviceId::Classic | DeviceId::DualShockDigital | DeviceId::DualShockAnalog |
+            DeviceId::DualShockPressure | DeviceId::AnalogJoystick);
+        if !pad {
+            return Err(Error::WrongDevice { expected: DeviceId::DualShockDigital, got: id });
+        }
+
+        Ok(Classic::from_frame(field(&buffer, HEADER_LEN..CMD_POLL_BUTTONS.len())?).map(|x| x.buttons))
+    }
+
+    /// Get the raw data from polling for a controller. You can use this to cooerce the data into
+    /// some controller that can't be safely identified by `read_input`, but you should rely on that
+    /// function if you can.
+    pub fn read_raw(&mut self, command: Option<&dyn PollCommand>) -> Result<ControllerData<N>, Error<E>> {
+        // Bytes the transfer doesn't reach are read back out below, so this
+        // has to start zeroed
+        let mut frame = [0u8; N];
+        self.read_port(command, &mut frame)?;
+
+        // Drop the header because we don't need it anymore. Everything past
+        // the end of the frame stays zeroed
+        let mut data = [0u8; N];
+        for (out, x) in data.iter_mut().zip(frame.iter().skip(HEADER_LEN)) {
+            *out = *x;
+        }
+
+        Ok(ControllerData { data })
+    }
+
+    /// Ask the controller for input states. Different contoller types will be returned automatically
+    /// for you. If you'd like to cooerce a controller yourself, use `read_raw`.
+    pub fn read_input(&mut self, command: Option<&dyn PollCommand>) -> Result<Device, Error<E>> {
+        Ok(self.poll(command)?.device)
+    }
+
+    /// The same as `read_input`, for when only one kind of controller will
+    /// do. Anything that sends at least as much as `T` is read as one (see
+    /// `FromDevice`), and anything else is `Error::WrongDevice`.
+    pub fn read_input_as<T: FromDevice>(&mut self, command: Option<&dyn PollCommand>) -> Result<T, Error<E>> {
+        let response = self.poll(command)?;
+
+        T::from_device(&response.device).ok_or(Error::WrongDevice {
+            expected: T::EXPECTED,
+            got: response.meta.device_id(),
+        })
+    }
+
+    /// The same as `read_input`, but along with the controller comes what
+    /// the frame's header said about itself and how many retries it took
+    pub fn poll(&mut self, command: Option<&dyn PollCommand>) -> Result<PollResponse, Error<E>> {
+        // Parsing only looks inside the frame the transfer just filled, but
+        // the buffer is zeroed anyway since that costs next to nothing
+        let mut buffer = [0u8; N];
+        let (device, meta) = self.read_frame(command, &mut buffer)?;
+
+        Ok(PollResponse {
+            device: device.to_owned(),
+            meta,
+        })
+    }
+
+    /// The same as `read_input` but also hands back what the frame's header
+    /// said about itself. Handy when working out what an unknown device is.
+    pub fn read_input_meta(&mut self, command: Option<&dyn PollCommand>) -> Result<(Device, FrameMeta), Error<E>> {
+        let response = self.poll(command)?;
+
+        Ok((response.device, response.meta))
+    }
+
+    /// The same as `read_input`, but the controller is left in `buffer` and
+    /// read from there as it's asked for instead of being copied out
+    pub fn read_input_ref<'a>(&mut self, command: Option<&dyn PollCommand>, buffer: &'a mut [u8; N]) -> Result<DeviceRef<'a>, Error<E>> {
+        let (device, _) = self.read_frame(command, buffer)?;
+
+        Ok(device)
+    }
+
+    /// The same as `read_input`, but the frame is tagged with the time from
+    /// `now`, which can be any clock at all (a tick counter, an RTC, ...).
+    ///
+    /// The clock is read as soon as the last transfer finishes and before the
+    /// frame is parsed, so the timestamp is as close as we can get to when the
+    /// controller answered. If the controller was retried while it left
+    /// configuration mode, it's the final transfer that counts. With a
+    /// `RecoveryPolicy` installed, the frame is checked over before the clock
+    /// is read, so the policy can ask for another poll.
+    pub fn read_input_stamped<T>(&mut self, command: Option<&dyn PollCommand>, now: impl FnOnce() -> T) -> Result<InputFrame<T>, Error<E>> {
+        let mut buffer = [0u8; N];
+        let (len, _) = self.clock_input(command, &mut buffer)?;
+        let timestamp = now();
+
+        let (device, _) = self.parse_and_record(&buffer, len)?;
+
+        Ok(InputFrame {
+            device: device.to_owned(),
+            timestamp,
+        })
+    }
+
+    /// Poll the controller `samples` times in a row (at least once) and
+    /// average the results, for knocking down noise on the sticks. Sticks
+    /// and pressures are averaged and rounded, and a button counts as held if
+    /// it was held in more than half of the polls.
+    ///
+    /// Only the original controller, analog joystick, DualShock and DualShock
+    /// 2 are averaged. Anything else comes back as it was on the last poll.
+    /// If a different kind of controller answers partway through, this stops
+    /// with `Error::DeviceChanged` rather than mixing them up.
+    pub fn read_input_averaged(&mut self, command: Option<&dyn PollCommand>, samples: u8) -> Result<Device, Error<E>> {
+        let samples = samples.max(1);
+        let mut totals = SampleTotals::default();
+
+        let mut last = PlayStationPort::read_input(self, command)?;
+        totals.add(&last);
+
+        for _ in 1..samples {
+            let device = PlayStationPort::read_input(self, command)?;
+            if mem::discriminant(&device) != mem::discriminant(&last) {
+                return Err(Error::DeviceChanged);
+            }
+
+            totals.add(&device);
+            last = device;
+        }
+
+        Ok(totals.average(last, samples))
+    }
+
+    /// Poll the controller, but only hand back the result if it's different
+    /// from the last one this returned. A different kind of controller or
+    /// any change to the buttons counts, as does a stick or pressure moving
+    /// further than the change tolerance (see `set_change_tolerance`). For
+    /// other controllers any change at all counts.
+    ///
+    /// Movements are measured from the last state handed back rather than
+    /// the last poll, so a stick creeping along a step at a time still gets
+    /// reported once it has gone far enough.
+    pub fn poll_changed(&mut self, command: Option<&dyn PollCommand>) -> Result<Option<Device>, Error<E>> {
+        let device = PlayStationPort::read_input(self, command)?;
+
+        let changed = match self.reported {
+            Some(ref previous) => differs(previous, &device, self.change_tolerance),
+            None => true,
+        };
+        if !changed {
+            return Ok(None);
+        }
+
+        self.reported = Some(device);
+
+        Ok(Some(device))
+    }
+
+    /// Poll `N` times back to back, as fast as the bus allows, for things
+    /// like measuring input latency. The polls share one buffer and each
+    /// clocks the frame length the last one found, so there's nothing in
+    /// between them but the transfers. Stops at the first poll that fails,
+    /// saying which one it was.
+    pub fn poll_n<const COUNT: usize>(&mut self, command: Option<&dyn PollCommand>) -> Result<[Device; COUNT], BurstError<E>> {
+        let mut devices = [Device::None; COUNT];
+        let mut buffer = [0u8; N];
+
+        for (index, device) in devices.iter_mut().enumerate() {
+            match self.read_frame(command, &mut buffer) {
+                Ok((x, _)) => *device = x.to_owned(),
+                Err(error) => return Err(BurstError { index, error }),
+            }
+        }
+
+        Ok(devices)
+    }
+
+    fn read_frame<'a>(&mut self, command: Option<&dyn PollCommand>, buffer: &'a mut [u8; N]) -> Result<(DeviceRef<'a>, FrameMeta), Error<E>> {
+        let (len, retries) = self.clock_input(command, buffer)?;
+
+        let (device, mut meta) = self.parse_and_record(buffer, len)?;
+        meta.retries = retries;
+
+        Ok((device, meta))
+    }
+
+    /// Everything in a poll up to parsing the frame, which `parse_and_record`
+    /// picks up from. Returns how many bytes were clocked and how many
+    /// retries it took.
+    pub(crate) fn clock_input(&mut self, command: Option<&dyn PollCommand>, buffer: &mut [u8; N]) -> Result<(usize, u8), Error<E>> {
+        self.wait_for_interval()?;
+        self.recover_frame(command, buffer)
+    }
+
+    /// Hold a poll back until `poll_interval` allows it, and note when it
+    /// started
+    fn wait_for_interval(&mut self) -> Result<(), Error<E>> {
+        let interval = match self.poll_interval {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        if let Some(last) = self.last_poll {
+            while (interval.now)().wrapping_sub(last) < interval.min_ticks {
+                match interval.mode {
+                    IntervalMode::Block => core::hint::spin_loop(),
+                    IntervalMode::NonBlocking => return Err(Error::TooSoon),
+                }
+            }
+        }
+        self.last_poll = Some((interval.now)());
+
+        Ok(())
+    }
+
+    /// `transfer_frame`, polling again for as long as the recovery policy
+    /// says to. Every error the policy can help with is caught by the
+    /// transfer's checks, so the frame is left for the caller to parse.
+    fn recover_frame(&mut self, command: Option<&dyn PollCommand>, buffer: &mut [u8; N]) -> Result<(usize, u8), Error<E>> {
+        let mut attempt = 0u8;
+        loop {
+            let error = match self.transfer_frame(command, buffer) {
+                Ok(x) => return Ok(x),
+                Err(e) => e,
+            };
+
+            attempt = attempt.saturating_add(1);
+            match self.poll_action(&error, attempt) {
+                Action::Fail => return Err(error),
+                Action::Retry => (),
+                Action::Reconfigure => self.reconfigure()?,
+            }
+            bump(&mut self.stats.retries);
+        }
+    }
+
+    /// Poll, retrying through mode changes if that's turned on. Returns how
+    /// many bytes were clocked and how many retries it took.
+    fn transfer_frame(&mut self, command: Option<&dyn PollCommand>, buffer: &mut [u8; N]) -> Result<(usize, u8), Error<E>> {
+        let mut result = self.read_port(command, buffer);
+        let mut retries = 0;
+
+        if self.smooth_transitions {
+            while retries < TRANSITION_RETRIES {
+                match result {
+                    Ok(_) if buffer.get(1) == Some(&CONTROLLER_CONFIGURATION) => (),
+                    Err(Error::Garbage { .. }) => (),
+                    _ => break,
+                }
+
+                result = self.read_port(command, buffer);
+                retries += 1;
+                bump(&mut self.stats.retries);
+            }
+        }
+
+        match result {
+            Ok(len) => Ok((len, retries)),
+            Err(e) => {
+                self.last_error = Some(e.without_spi());
+                Err(e)
+            },
+        }
+    }
+
+    /// Parse a frame and keep track of how it went for `last_input` and
+    /// `last_error`
+    pub(crate) fn parse_and_record<'a>(&mut self, buffer: &'a [u8; N], len: usize) -> Result<(DeviceRef<'a>, FrameMeta), Error<E>> {
+        match field(buffer, 0..len).and_then(Self::parse_frame) {
+            Ok((device, meta)) => {
+                match device {
+                    DeviceRef::None => {
+                        self.last_input = None;
+                        self.forget_controller();
+                    },
+                    _ => {
+                        self.last_input = Some(device.to_owned());
+                        self.connected = true;
+                    },
+                }
+                self.last_error = None;
+
+                Ok((device, meta))
+            },
+            Err(e) => {
+                self.stats.record_error(&e);
+                self.last_error = Some(e.without_spi());
+
+                Err(e)
+            },
+        }
+    }
+
+    fn parse_frame(frame: &[u8]) -> Result<(DeviceRef<'_>, FrameMeta), Error<E>> {
+        // The frame has already been checked against the length its id
+        // claims, so this is only a backstop
+        let device = DeviceRef::parse(frame).ok_or(Error::BadResponse)?;
+
+        // Which also means there's a whole header
+        let id = byte(frame, 1)?;
+        let meta = FrameMeta {
+            id,
+            half_words: id & 0x0f,
+            ack_ok: byte(frame, 2)? == ACK_BYTE,
+            retries: 0,
+        };
+
+        Ok((device, meta))
+    }
+}
+
+/// Sets up a `PlayStationPort` with options besides the defaults. Created by
+/// `PlayStationPort::builder()`.
+pub struct PortBuilder<SPI, CS, const N: usize = { MESSAGE_MAX_LENGTH }, R = FailFast> {
+    spi: SPI,
+    select: Option<CS>,
+    multitap_port: MultitapPort,
+    smooth_transitions: bool,
+    change_tolerance: u8,
+    #[cfg(feature = "critical-section")]
+    critical_section: bool,
+    observer: Option<TransferObserver>,
+    cs_mode: CsMode,
+    bit_order: BitOrder,
+    verify_bus_idle: bool,
+    poll_interval: Option<PollInterval>,
+    rumble_ttl: u16,
+    recovery: R,
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, const N: usize, R> PortBuilder<SPI, CS, N, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// The longest frame the port clocks, and so how much stack each poll
+    /// takes, see `PlayStationPort`. `MESSAGE_MAX_LENGTH` by default. Sizes
+    /// under 5 don't compile.
+    pub fn frame_size<const M: usize>(self) -> PortBuilder<SPI, CS, M, R> {
+        self.convert(|x| x)
+    }
+
+    /// What to do about errors, see `RecoveryPolicy`. Errors are handed
+    /// straight back by default.
+    pub fn recovery_policy<P: RecoveryPolicy>(self, policy: P) -> PortBuilder<SPI, CS, N, P> {
+        self.convert(|_| policy)
+    }
+
+    /// Carry every setting over to a builder of another frame size or
+    /// policy
+    fn convert<const M: usize, P>(self, recovery: impl FnOnce(R) -> P) -> PortBuilder<SPI, CS, M, P> {
+        PortBuilder {
+            spi: self.spi,
+            select: self.select,
+            multitap_port: self.multitap_port,
+            smooth_transitions: self.smooth_transitions,
+            change_tolerance: self.change_tolerance,
+            #[cfg(feature = "critical-section")]
+            critical_section: self.critical_section,
+            observer: self.observer,
+            cs_mode: self.cs_mode,
+            bit_order: self.bit_order,
+            verify_bus_idle: self.verify_bus_idle,
+            poll_interval: self.poll_interval,
+            rumble_ttl: self.rumble_ttl,
+            recovery: recovery(self.recovery),
+        }
+    }
+
+    /// Which multi-tap port to talk to, see `set_multitap_port`. `A` by
+    /// default.
+    pub fn multitap_port(mut self, port: MultitapPort) -> Self {
+        self.multitap_port = port;
+        self
+    }
+
+    /// Whether to poll through mode changes, see `smooth_transitions`. Off by
+    /// default.
+    pub fn smooth_transitions(mut self, enable: bool) -> Self {
+        self.smooth_transitions = enable;
+        self
+    }
+
+    /// How far sticks and pressures move before `poll_changed` notices, see
+    /// `set_change_tolerance`. 0 by default.
+    pub fn change_tolerance(mut self, tolerance: u8) -> Self {
+        self.change_tolerance = tolerance;
+        self
+    }
+
+    /// Whether transfers run inside a critical section, see
+    /// `use_critical_section`. Off by default.
+    #[cfg(feature = "critical-section")]
+    pub fn critical_section(mut self, enable: bool) -> Self {
+        self.critical_section = enable;
+        self
+    }
+
+    /// Something to show every transfer to, see `set_observer`. None by
+    /// default.
+    pub fn observer(mut self, observer: TransferObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// How select is driven through each transfer, see `set_cs_mode`.
+    /// `CsMode::Continuous` by default.
+    pub fn cs_mode(mut self, mode: CsMode) -> Self {
+        self.cs_mode = mode;
+        self
+    }
+
+    /// Which bit the SPI bus sends first, see `set_bit_order`.
+    /// `BitOrder::MsbFirst` by default.
+    pub fn bit_order(mut self, order: BitOrder) -> Self {
+        self.bit_order = order;
+        self
+    }
+
+    /// Whether to check the bus is idle before each transfer, see
+    /// `verify_bus_idle`. Off by default.
+    pub fn verify_bus_idle(mut self, enable: bool) -> Self {
+        self.verify_bus_idle = enable;
+        self
+    }
+
+    /// How far apart polls are kept, see `set_poll_interval`. As fast as
+    /// they're asked for by default.
+    pub fn poll_interval(mut self, interval: PollInterval) -> Self {
+        self.poll_interval = Some(interval);
+        self
+    }
+
+    /// How many polls rumble lasts without a fresh `set_rumble`, see
+    /// `set_rumble_ttl`. 0, for no limit, by default.
+    pub fn rumble_ttl(mut self, polls: u16) -> Self {
+        self.rumble_ttl = polls;
+        self
+    }
+
+    /// Finish setting up the port. This is when the select pin is first set.
+    pub fn build(self) -> PlayStationPort<SPI, CS, N, R> {
+        let mut port = PlayStationPort::sized(self.spi, self.select, self.recovery);
+        port.set_multitap_port(self.multitap_port);
+        port.smooth_transitions(self.smooth_transitions);
+        port.set_change_tolerance(self.change_tolerance);
+        #[cfg(feature = "critical-section")]
+        port.use_critical_section(self.critical_section);
+        port.set_observer(self.observer);
+        port.set_cs_mode(self.cs_mode);
+        port.set_bit_order(self.bit_order);
+        port.verify_bus_idle(self.verify_bus_idle);
+        port.set_poll_interval(self.poll_interval);
+        port.set_rumble_ttl(self.rumble_ttl);
+
+        port
+    }
+}
+
+/// Whether two polls are different enough for `poll_changed`
+fn differs(previous: &Device, current: &Device, tolerance: u8) -> bool {
+    let moved = |a: &[u8], b: &[u8]| {
+        a.iter().zip(b.iter()).any(|(&a, &b)| (i16::from(a) - i16::from(b)).unsigned_abs() > u16::from(tolerance))
+    };
+
+    match (previous, current) {
+        (Device::AnalogJoystick(a), Device::AnalogJoystick(b)) | (Device::DualShock(a), Device::DualShock(b)) => {
+            a.buttons != b.buttons || moved(&[a.lx, a.ly, a.rx, a.ry], &[b.lx, b.ly, b.rx, b.ry])
+        },
+        (Device::DualShock2(a), Device::DualShock2(b)) => {
+            a.buttons != b.buttons ||
+                moved(&[a.lx, a.ly, a.rx, a.ry], &[b.lx, b.ly, b.rx, b.ry]) ||
+                moved(&a.pressures, &b.pressures)
+        },
+        _ => previous != current,
+    }
+}
+
+/// Running totals for `read_input_averaged`
+#[derive(Default)]
+struct SampleTotals {
+    /// Stick bytes in the order left X, left Y, right X, right Y
+    sticks: [u32; 4],
+    pressures: [u32; 12],
+    /// How many polls each button was held in, in the order of `Button::ALL`
+    held: [u16; 16],
+}
+
+impl SampleTotals {
+    fn add(&mut self, device: &Device) {
+        if let Some(buttons) = device.buttons() {
+            for (held, &button) in self.held.iter_mut().zip(Button::ALL.iter()) {
+                if buttons.is_pressed(button) {
+                    *held += 1;
+                }
+            }
+        }
+
+        let sticks = match device {
+            Device::AnalogJoystick(x) | Device::DualShock(x) => [x.lx, x.ly, x.rx, x.ry],
+            Device::DualShock2(x) => [x.lx, x.ly, x.rx, x.ry],
+            _ => return,
+        };
+        for (total, &value) in self.sticks.iter_mut().zip(sticks.iter()) {
+            *total += u32::from(value);
+        }
+
+        if let Device::DualShock2(x) = device {
+            for (total, &value) in self.pressures.iter_mut().zip(x.pressures.iter()) {
+                *total += u32::from(value);
+            }
+        }
+    }
+
+    fn average(&self, last: Device, samples: u8) -> Device {
+        let samples = u32::from(samples);
+        let mean = |total: u32| ((total + samples / 2) / samples) as u8;
+
+        let mut pressed = 0;
+        for (&held, &button) in self.held.iter().zip(Button::ALL.iter()) {
+            if u32::from(held) * 2 > samples {
+                pressed |= button.mask();
+            }
+        }
+        let buttons = GamepadButtons::from_pressed_bits(pressed);
+        let dualshock = DualShock {
+            buttons,
+            lx: mean(self.sticks[0]),
+            ly: mean(self.sticks[1]),
+            rx: mean(self.sticks[2]),
+            ry: mean(self.sticks[3]),
+        };
+
+        match last {
+            Device::Classic(_) => Device::Classic(Classic { buttons }),
+            Device::AnalogJoystick(_) => Device::AnalogJoystick(dualshock),
+            Device::DualShock(_) => Device::DualShock(dualshock),
+            Device::DualShock2(_) => {
+                let mut pressures = [0; 12];
+                for (pressure, &total) in pressures.iter_mut().zip(self.pressures.iter()) {
+                    *pressure = mean(total);
+                }
+
+                Device::DualShock2(DualShock2 {
+                    buttons,
+                    lx: dualshock.lx,
+                    ly: dualshock.ly,
+                    rx: dualshock.rx,
+                    ry: dualshock.ry,
+                    pressures,
+                })
+            },
+            other => other,
+        }
+    }
+}
+
+/// Polls without sending a command along
+#[allow(deprecated)]
+impl<E, SPI, CS, const N: usize, R> InputSource for PlayStationPort<SPI, CS, N, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    type SpiError = E;
+
+    fn read_input(&mut self) -> Result<Device, Error<E>> {
+        PlayStationPort::read_input(self, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use mock;
+
+    #[test]
+    fn frame_test() {
+        // Again, buttons are active low, hence 'fe' and '7f'
+        let ds = DualShock::from_frame(&[
+            0xfe,
+            0x7f,
+            0x00,
+            0x00,
+            0x00,
+            0xff
+        ]).unwrap();
+
+        assert!(ds.buttons.select());
+        assert!(ds.buttons.square());
+        assert!(ds.lx == 0);
+        assert!(ds.ly == 255);
+    }
+
+    #[test]
+    fn every_type_parses_at_fixed_offsets() {
+        let mut frame = [0u8; MESSAGE_MAX_LENGTH];
+        for (i, byte) in frame.iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+
+        let mouse = Mouse::from_frame(&frame).unwrap();
+        assert_eq!((mouse.y, mouse.x), (3, 4));
+
+        assert_eq!(Classic::from_frame(&frame).unwrap().buttons.bits(), 0x0201);
+
+        let ds = DualShock::from_frame(&frame).unwrap();
+        assert_eq!(ds.buttons.bits(), 0x0201);
+        assert_eq!([ds.rx, ds.ry, ds.lx, ds.ly], [3, 4, 5, 6]);
+
+        let ds2 = DualShock2::from_frame(&frame).unwrap();
+        assert_eq!(ds2.buttons.bits(), 0x0201);
+        assert_eq!([ds2.rx, ds2.ry, ds2.lx, ds2.ly], [3, 4, 5, 6]);
+        assert_eq!(ds2.pressures, [7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18]);
+
+        assert_eq!(GuitarHero::from_frame(&frame).unwrap().whammy, 6);
+
+        let jogcon = JogCon::from_frame(&frame).unwrap();
+        assert_eq!(jogcon.buttons.bits(), 0x0201);
+        assert_eq!((jogcon.jog_position(), jogcon.jog_state), (0x0403, 5));
+
+        let negcon = NegCon::from_frame(&frame).unwrap();
+        assert_eq!([negcon.twist, negcon.switchi, negcon.switchii, negcon.switchl], [3, 4, 5, 6]);
+
+        let guncon = GunCon::from_frame(&frame).unwrap();
+        assert_eq!((guncon.x(), guncon.y()), (0x0403, 0x0605));
+
+        let baton = Baton::from_frame(&frame).unwrap();
+        assert_eq!([baton.z, baton.x, baton.y, baton.a], [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn short_frames_are_rejected() {
+        let frame = [0u8; MESSAGE_MAX_LENGTH];
+
+        assert!(Mouse::from_frame(&frame[..3]).is_none());
+        assert!(Classic::from_frame(&frame[..1]).is_none());
+        assert!(DualShock::from_frame(&frame[..5]).is_none());
+        assert!(DualShock2::from_frame(&frame[..17]).is_none());
+        assert!(GuitarHero::from_frame(&frame[..5]).is_none());
+        assert!(JogCon::from_frame(&frame[..4]).is_none());
+        assert!(NegCon::from_frame(&frame[..5]).is_none());
+        assert!(GunCon::from_frame(&frame[..5]).is_none());
+        assert!(Baton::from_frame(&frame[..5]).is_none());
+
+        assert!(Mouse::from_frame(&frame[..4]).is_some());
+        assert!(Classic::from_frame(&frame[..2]).is_some());
+        assert!(DualShock::from_frame(&frame[..6]).is_some());
+        assert!(DualShock2::from_frame(&frame[..18]).is_some());
+        assert!(GuitarHero::from_frame(&frame[..6]).is_some());
+        assert!(JogCon::from_frame(&frame[..5]).is_some());
+        assert!(NegCon::from_frame(&frame[..6]).is_some());
+        assert!(GunCon::from_frame(&frame[..6]).is_some());
+        assert!(Baton::from_frame(&frame[..6]).is_some());
+    }
+
+    #[test]
+    fn absent_controller_is_not_an_error() {
+        let mut psp = mock::port(&[]);
+
+        assert!(matches!(psp.read_input(None), Ok(Device::None)));
+    }
+
+    #[test]
+    fn stall_after_header_times_out() {
+        // A DualShock that answered the header and then went quiet
+        let mut psp = mock::port(&[&[0xff, 0x73, 0x5a]]);
+        psp.dev.stall = true;
+
+        assert!(matches!(psp.read_input(None), Err(Error::Timeout { bytes_completed: 3 })));
+
+        // Going quiet before the id is an empty port
+        let mut psp = mock::port(&[&[0xff]]);
+        psp.dev.stall = true;
+
+        assert!(matches!(psp.read_input(None), Ok(Device::None)));
+    }
+
+    #[test]
+    fn missing_ack_times_out() {
+        let mut psp = mock::port(&[&[0xff, 0x73]]);
+
+        assert!(matches!(psp.read_input(None), Err(Error::Timeout { bytes_completed: 2 })));
+    }
+
+    #[test]
+    fn idle_digital_pad_is_not_a_stall() {
+        let mut psp = mock::port(&[&[0xff, 0x41, 0x5a, 0xff, 0xff]]);
+
+        assert!(matches!(psp.read_input(None), Ok(Device::Classic(_))));
+    }
+
+    #[test]
+    fn valid_dualshock2_frame_passes() {
+        let frame = [
+            0xff, 0x79, 0x5a, 0xff, 0xbf, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut psp = mock::port(&[&frame]);
+
+        assert!(matches!(psp.read_input(None), Ok(Device::DualShock2(_))));
+    }
+
+    #[test]
+    fn dualshock2_pressure_layout() {
+        // Full pressure mode holding up, cross and R2
+        let frame = [
+            0xff, 0x79, 0x5a, 0xef, 0xbd, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x9e, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0xff,
+        ];
+        let mut psp = mock::port(&[&frame]);
+
+        let ds2 = match psp.read_input(None) {
+            Ok(Device::DualShock2(x)) => x,
+            other => panic!("Expected a DualShock 2, got {:?}", other),
+        };
+
+        assert!(ds2.buttons.up() && ds2.buttons.cross() && ds2.buttons.r2());
+        assert_eq!(ds2.pressure_up(), 0x9e);
+        assert_eq!(ds2.pressure_cross(), 0xc8);
+        assert_eq!(ds2.pressure_r2(), 0xff);
+        assert_eq!(
+            [
+                ds2.pressure_right(), ds2.pressure_left(), ds2.pressure_down(),
+                ds2.pressure_triangle(), ds2.pressure_circle(), ds2.pressure_square(),
+                ds2.pressure_l1(), ds2.pressure_r1(), ds2.pressure_l2(),
+            ],
+            [0; 9]
+        );
+    }
+
+    #[test]
+    fn corrupt_dualshock2_frame_fails() {
+        let frame = [
+            0xfe, 0x79, 0x5a, 0xff, 0xbf, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut psp = mock::port(&[&frame]);
+
+        assert!(matches!(psp.read_input(None), Err(Error::Garbage { offset: 0, byte: 0xfe })));
+    }
+
+    #[test]
+    fn poll_length_follows_device() {
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xff];
+        let mut psp = mock::port(&[classic, classic]);
+
+        psp.read_input(None).unwrap();
+        psp.read_input(None).unwrap();
+
+        let lengths: Vec<usize> = psp.dev.sent.iter().map(|x| x.len()).collect();
+        assert_eq!(lengths, [MESSAGE_MAX_LENGTH, 5]);
+
+        let dualshock2: &[u8] = &[
+            0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut psp = mock::port(&[dualshock2, dualshock2]);
+
+        psp.read_input(None).unwrap();
+        psp.read_input(None).unwrap();
+
+        let lengths: Vec<usize> = psp.dev.sent.iter().map(|x| x.len()).collect();
+        assert_eq!(lengths, [MESSAGE_MAX_LENGTH, 21]);
+    }
+
+    #[test]
+    fn longer_mode_is_polled_again() {
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xff];
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let mut psp = mock::port(&[classic, classic, dualshock, dualshock]);
+
+        psp.read_input(None).unwrap();
+        psp.read_input(None).unwrap();
+        assert!(matches!(psp.read_input(None), Ok(Device::DualShock(_))));
+
+        let lengths: Vec<usize> = psp.dev.sent.iter().map(|x| x.len()).collect();
+        assert_eq!(lengths, [MESSAGE_MAX_LENGTH, 5, 5, 9]);
+    }
+
+    #[test]
+    fn frames_cut_short_are_rejected() {
+        let digital: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xff];
+        let analog: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff];
+        let pressure: &[u8] = &[0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let mut full = [0u8; 21];
+        full[..9].copy_from_slice(pressure);
+
+        // The re-poll for the analog frame is only nine bytes long, but by
+        // then the controller has moved on to pressures
+        let mut port = mock::port(&[digital, analog, pressure, &full]);
+        port.read_input(None).unwrap();
+
+        let mut buffer = [0x11; MESSAGE_MAX_LENGTH];
+        assert_eq!(port.read_input_ref(None, &mut buffer), Err(Error::BadResponse));
+        assert_eq!(port.last_error(), Some(&Error::BadResponse));
+
+        // The next poll asks for the whole frame
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock2(_))));
+        assert_eq!(port.dev.sent.last().unwrap().len(), 21);
+    }
+
+    #[test]
+    fn poll_buttons_in_every_mode() {
+        // Cross held (active low) in digital, analog and pressure modes, and
+        // on the original controller and the flight stick
+        let frames: [&[u8]; 5] = [
+            &[0xff, 0x41, 0x5a, 0xff, 0xbf],
+            &[0xff, 0x73, 0x5a, 0xff, 0xbf, 0x80, 0x80, 0x80, 0x80],
+            &[0xff, 0x79, 0x5a, 0xff, 0xbf, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00],
+            &[0xff, 0xc1, 0x5a, 0xff, 0xbf],
+            &[0xff, 0x53, 0x5a, 0xff, 0xbf, 0x80, 0x80, 0x80, 0x80],
+        ];
+
+        for frame in frames.iter() {
+            let mut psp = mock::port(&[frame]);
+            let buttons = psp.poll_buttons().unwrap().unwrap();
+
+            assert!(buttons.cross());
+            assert!(!buttons.circle());
+            assert_eq!(psp.dev.sent[0].len(), 5);
+        }
+
+        let mut psp = mock::port(&[]);
+        assert!(psp.poll_buttons().unwrap().is_none());
+
+        // Nothing else has the button word where a pad does
+        let others: [&[u8]; 3] = [
+            &[0xff, 0x12, 0x5a, 0xff, 0xfc, 0x00, 0x00],
+            &[0xff, 0x63, 0x5a, 0xff, 0xdf, 0x01, 0x00, 0x0a, 0x00],
+            &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        ];
+
+        for frame in others.iter() {
+            let got = DeviceId::from_byte(frame[1]);
+            let error = Error::WrongDevice { expected: DeviceId::DualShockDigital, got };
+            assert_eq!(mock::port(&[frame]).poll_buttons(), Err(error));
+        }
+    }
+
+    #[test]
+    fn dualshock2_frame_meta() {
+        let frame = [
+            0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut psp = mock::port(&[&frame]);
+
+        let (device, meta) = psp.read_input_meta(None).unwrap();
+
+        assert!(matches!(device, Device::DualShock2(_)));
+        assert!(meta == FrameMeta { id: 0x79, half_words: 9, ack_ok: true, retries: 0 });
+    }
+
+    #[test]
+    fn transition_frames_are_skipped() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let dualshock2: &[u8] = &[
+            0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let mut psp = mock::port(&[dualshock, config, dualshock2, dualshock2]);
+        psp.smooth_transitions(true);
+
+        assert!(matches!(psp.read_input(None), Ok(Device::DualShock(_))));
+        assert!(matches!(psp.read_input(None), Ok(Device::DualShock2(_))));
+
+        // Without smoothing the configuration frame comes straight through
+        let mut psp = mock::port(&[dualshock, config, dualshock2, dualshock2]);
+
+        assert!(matches!(psp.read_input(None), Ok(Device::DualShock(_))));
+        assert!(matches!(psp.read_input(None), Ok(Device::ConfigurationMode)));
+    }
+
+    #[test]
+    fn read_as_one_kind() {
+        use guitarhero::GuitarHero;
+
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xbf];
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x10, 0x20, 0x30, 0x40];
+        let mut dualshock2 = [0u8; 21];
+        dualshock2[..9].copy_from_slice(&[0xff, 0x79, 0x5a, 0xff, 0xbf, 0x10, 0x20, 0x30, 0x40]);
+        dualshock2[15] = 0xff;
+
+        let device = mock::port(&[&dualshock2]).read_input(None).unwrap();
+        let exact = mock::port(&[&dualshock2]).read_input_as::<DualShock2>(None).unwrap();
+        assert_eq!(Device::DualShock2(exact), device);
+        assert_eq!(exact.pressure_cross(), 0xff);
+
+        // A DualShock 2 will do for anything it has everything of
+        let downgraded = mock::port(&[&dualshock2]).read_input_as::<DualShock>(None).unwrap();
+        assert_eq!(DualShock::from(exact), downgraded);
+        assert_eq!(downgraded.lx, 0x30);
+        let downgraded = mock::port(&[&dualshock2]).read_input_as::<Classic>(None).unwrap();
+        assert!(downgraded.buttons.cross());
+
+        // The guitar sends the same frame as a DualShock
+        let guitar = mock::port(&[dualshock]).read_input_as::<GuitarHero>(None).unwrap();
+        assert!(guitar.buttons.select());
+
+        // But not the other way around
+        assert_eq!(mock::port(&[classic]).read_input_as::<DualShock>(None), Err(Error::WrongDevice {
+            expected: DeviceId::DualShockAnalog,
+            got: DeviceId::DualShockDigital,
+        }));
+        assert_eq!(mock::port(&[dualshock]).read_input_as::<DualShock2>(None), Err(Error::WrongDevice {
+            expected: DeviceId::DualShockPressure,
+            got: DeviceId::DualShockAnalog,
+        }));
+        assert_eq!(mock::port(&[]).read_input_as::<Classic>(None), Err(Error::WrongDevice {
+            expected: DeviceId::Classic,
+            got: DeviceId::NotPresent,
+        }));
+    }
+
+    #[test]
+    fn poll_responses_count_retries() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let garbled: &[u8] = &[0x00, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+
+        let mut psp = mock::port(&[dualshock, config, garbled, dualshock]);
+        psp.smooth_transitions(true);
+
+        let response = psp.poll(None).unwrap();
+        assert!(matches!(response.device, Device::DualShock(_)));
+        assert_eq!(response.meta, FrameMeta { id: 0x73, half_words: 3, ack_ok: true, retries: 0 });
+        assert_eq!(response.meta.frame_len(), 9);
+        assert_eq!(response.meta.device_id(), DeviceId::DualShockAnalog);
+
+        let response = psp.poll(None).unwrap();
+        assert!(matches!(response.device, Device::DualShock(_)));
+        assert_eq!(response.meta.retries, 2);
+
+        // Running out of retries hands back what the last one got
+        let mut psp = mock::port(&[config, config, config, config]);
+        psp.smooth_transitions(true);
+        let response = psp.poll(None).unwrap();
+        assert_eq!(response.device, Device::ConfigurationMode);
+        assert_eq!(response.meta.retries, TRANSITION_RETRIES);
+
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xff];
+        let response = mock::port(&[classic]).poll(None).unwrap();
+        assert_eq!(Ok((response.device, response.meta)), mock::port(&[classic]).read_input_meta(None));
+    }
+
+    #[test]
+    fn stats_count_every_path() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let stalled: &[u8] = &[0xff, 0x73, 0x5a];
+        let garbled: &[u8] = &[0x00, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let unplugged: &[u8] = &[0xff, 0xff, 0xff];
+        let digital: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xff];
+        let analog: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff];
+        let pressure: &[u8] = &[0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        let mut port = mock::port(&[
+            dualshock, stalled, garbled, unplugged, unplugged,
+            digital, analog, pressure,
+            config, dualshock, unplugged, unplugged, unplugged,
+        ]);
+        assert_eq!(port.stats(), &PortStats::default());
+
+        assert!(port.read_input(None).is_ok());
+        port.dev.stall = true;
+        assert_eq!(port.read_input(None), Err(Error::Timeout { bytes_completed: 3 }));
+        port.dev.stall = false;
+        assert!(matches!(port.read_input(None), Err(Error::Garbage { .. })));
+
+        // An empty port answers 0xff for the whole frame, so it's asked again
+        // at full length
+        assert_eq!(port.read_input(None), Ok(Device::None));
+
+        // Plugged back in, and the mode changes under the re-poll
+        port.read_input(None).unwrap();
+        assert_eq!(port.read_input(None), Err(Error::BadResponse));
+
+        port.smooth_transitions(true);
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+
+        port.dev.fail = true;
+        assert_eq!(port.read_input(None), Err(Error::Spi(())));
+        assert!(port.enable_pressure().is_err());
+
+        assert_eq!(port.stats(), &PortStats {
+            polls: 9,
+            timeouts: 1,
+            garbage: 1,
+            bad_responses: 1,
+            spi_errors: 1,
+            retries: 1,
+            disconnects: 1,
+            config_failures: 1,
+        });
+
+        // Pulling it out again counts, as long as it had answered since
+        port.dev.fail = false;
+        assert_eq!(port.read_input(None), Ok(Device::None));
+        assert_eq!(port.read_input(None), Ok(Device::None));
+        assert_eq!(port.stats().disconnects, 2);
+
+        port.reset_stats();
+        assert_eq!(port.stats(), &PortStats::default());
+
+        // Counts stop at the top instead of wrapping
+        port.stats.polls = u32::MAX;
+        assert_eq!(port.poll_buttons(), Ok(None));
+        assert_eq!(port.stats().polls, u32::MAX);
+    }
+
+    #[test]
+    fn debug_formatting() {
+        use mock::FixedBuffer;
+
+        let mut data = [0u8; MESSAGE_MAX_LENGTH];
+        data[..6].copy_from_slice(&[0xfe, 0xbf, 0x7f, 0x80, 0x00, 0xff]);
+
+        let classic = Classic::from_frame(&data).unwrap();
+        let dualshock = DualShock::from_frame(&data).unwrap();
+        let dualshock2 = DualShock2::from_frame(&data).unwrap();
+
+        assert_eq!(FixedBuffer::debug(&classic.buttons).as_str(), "{Select, Cross}");
+        assert_eq!(FixedBuffer::display(&classic.buttons).as_str(), "Select, Cross");
+        assert_eq!(FixedBuffer::display(&GamepadButtons::all_released()).as_str(), "");
+        assert_eq!(FixedBuffer::display(&classic::Button::L2).as_str(), "L2");
+        assert_eq!(
+            FixedBuffer::debug(&classic).as_str(),
+            "Classic { buttons: {Select, Cross} }");
+        assert_eq!(
+            FixedBuffer::debug(&dualshock).as_str(),
+            "DualShock { buttons: {Select, Cross}, rx: 127, ry: 128, lx: 0, ly: 255 }");
+        assert!(FixedBuffer::debug(&dualshock2).as_str().starts_with("DualShock2 { buttons: {Select, Cross}"));
+        assert_eq!(
+            FixedBuffer::debug(&Device::Classic(classic)).as_str(),
+            "Classic(Classic { buttons: {Select, Cross} })");
+        assert_eq!(FixedBuffer::debug(&Device::None).as_str(), "None");
+
+        let error: Error<()> = Error::Timeout { bytes_completed: 3 };
+        assert_eq!(FixedBuffer::debug(&error).as_str(), "Timeout { bytes_completed: 3 }");
+        assert_eq!(FixedBuffer::debug(&Error::Spi(())).as_str(), "Spi(())");
+
+        let config: ControllerConfiguration = Default::default();
+        assert!(FixedBuffer::debug(&config).as_str().starts_with("ControllerConfiguration { status: [00, 00, 00, 00, 00, 00], const1a: [00, 00, 00, 00, 00]"));
+    }
+
+    #[test]
+    fn error_messages() {
+        use mock::FixedBuffer;
+
+        let cases: [(Error<u8>, &str, bool); 12] = [
+            (Error::LateCollision, "late collision on the bus", true),
+            (Error::BadResponse, "frame too short for the device it came from", false),
+            (Error::Timeout { bytes_completed: 3 }, "device stopped responding after 3 bytes", true),
+            (Error::Garbage { offset: 4, byte: 0x5a }, "unexpected byte 5a at offset 4", true),
+            (Error::DeviceChanged, "a different device answered partway through", true),
+            (Error::UnsupportedDevice, "the device doesn't support that command", false),
+            (Error::WrongDevice { expected: DeviceId::DualShockPressure, got: DeviceId::Classic },
+                "expected DualShockPressure but Classic answered", false),
+            (Error::CardChanged, "the memory card was changed", false),
+            (Error::BufferTooSmall { needed: 9 }, "buffer too small, 9 bytes needed", false),
+            (Error::BusBusy, "the bus wasn't idle", true),
+            (Error::TooSoon, "polled too soon after the last poll", true),
+            (Error::Spi(7), "SPI error: 7", false),
+        ];
+
+        for (error, message, transient) in cases.iter() {
+            assert_eq!(FixedBuffer::display(error).as_str(), *message);
+            assert_eq!(error.is_transient(), *transient);
+        }
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn defmt_format_implemented() {
+        use guitarhero::GuitarHero;
+
+        fn assert_format<T: defmt::Format>() {}
+
+        assert_format::<Device>();
+        assert_format::<GamepadButtons>();
+        assert_format::<DualShock>();
+        assert_format::<DualShock2>();
+        assert_format::<Classic>();
+        assert_format::<GuitarHero>();
+        assert_format::<ControllerConfiguration>();
+        assert_format::<Error<u8>>();
+    }
+
+    #[cfg(feature = "ufmt")]
+    #[test]
+    fn ufmt_matches_core_fmt() {
+        use guitarhero::GuitarHero;
+        use mock::FixedBuffer;
+
+        fn same_debug<T: fmt::Debug + ufmt::uDebug>(value: T) {
+            assert_eq!(FixedBuffer::udebug(&value).as_str(), FixedBuffer::debug(&value).as_str());
+        }
+
+        let buttons = GamepadButtons::from_bits(!0x4001);
+        let dualshock = DualShock::builder().press(Button::Select).stick_left(0x12, 0xf2).build();
+        let mut dualshock2 = DualShock2::builder().press(Button::Cross).build();
+        dualshock2.pressures[3] = 0x7f;
+
+        same_debug(buttons);
+        same_debug(GamepadButtons::default());
+        same_debug(dualshock);
+        same_debug(dualshock2);
+        same_debug(Device::DualShock(dualshock));
+        same_debug(Device::DualShock2(dualshock2));
+        same_debug(Device::Classic(Classic { buttons }));
+        same_debug(Device::None);
+        for frame in [&[0xff, 0x41, 0x5a, 0xfe, 0xff][..], &[0xff, 0x12, 0x5a, 0xff, 0xfc, 0x05, 0xfb]] {
+            same_debug(decode_frame(frame).unwrap());
+        }
+        same_debug(GuitarHero::from_frame(&[0xff, 0x7f, 0x00, 0x00, 0x00, 0x00]).unwrap());
+        same_debug(Error::WrongDevice::<()> { expected: DeviceId::DualShockPressure, got: DeviceId::Other(0x12) });
+        same_debug(Error::Spi(7u8));
+
+        assert_eq!(FixedBuffer::udisplay(&buttons).as_str(), FixedBuffer::display(&buttons).as_str());
+        for error in [
+            Error::Garbage { offset: 1, byte: 0x0a },
+            Error::WrongDevice { expected: DeviceId::DualShockPressure, got: DeviceId::Other(0x12) },
+            Error::BufferTooSmall { needed: 21 },
+            Error::Spi(()),
+        ] {
+            assert_eq!(FixedBuffer::udisplay(&error).as_str(), FixedBuffer::display(&error).as_str());
+        }
+    }
+
+    /// Push a value through postcard and back, checking nothing was lost
+    #[cfg(feature = "serde")]
+    fn round_trip<T>(value: &T) -> Vec<u8>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+        let mut again = [0u8; MESSAGE_MAX_LENGTH];
+
+        let bytes = postcard::to_slice(value, &mut buffer).unwrap();
+        let decoded: T = postcard::from_bytes(bytes).unwrap();
+
+        assert_eq!(postcard::to_slice(&decoded, &mut again).unwrap(), &bytes[..]);
+        bytes.to_vec()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut data = [0u8; MESSAGE_MAX_LENGTH];
+        data[..9].copy_from_slice(&[0xfe, 0xbf, 0x7f, 0x80, 0x00, 0xff, 0x00, 0x00, 0xc8]);
+
+        let classic = Classic::from_frame(&data).unwrap();
+        let dualshock = DualShock::from_frame(&data).unwrap();
+        let dualshock2 = DualShock2::from_frame(&data).unwrap();
+        let guitar = GuitarHero::from_frame(&data).unwrap();
+
+        // Buttons are the raw word and sticks are plain bytes
+        assert_eq!(round_trip(&classic.buttons), [0xfe, 0xff, 0x02]);
+        assert_eq!(round_trip(&classic), [0xfe, 0xff, 0x02]);
+        assert_eq!(round_trip(&dualshock), [0xfe, 0xff, 0x02, 0x7f, 0x80, 0x00, 0xff]);
+        round_trip(&dualshock2);
+        round_trip(&guitar);
+
+        let devices = [
+            Device::None,
+            Device::Unknown,
+            Device::ConfigurationMode,
+            Device::Mouse(Mouse::from_frame(&data).unwrap()),
+            Device::Classic(classic),
+            Device::AnalogJoystick(dualshock),
+            Device::DualShock(dualshock),
+            Device::DualShock2(dualshock2),
+            Device::GuitarHero(guitar),
+            Device::JogCon(JogCon::from_frame(&data).unwrap()),
+            Device::NegCon(NegCon::from_frame(&data).unwrap()),
+            Device::GunCon(GunCon::from_frame(&data).unwrap()),
+            Device::Baton(Baton::from_frame(&data).unwrap()),
+        ];
+
+        for (i, device) in devices.iter().enumerate() {
+            // The variant index leads so old captures keep decoding
+            assert_eq!(round_trip(device)[0] as usize, i);
+        }
+    }
+
+    #[test]
+    fn device_accessors() {
+        let ds2 = DualShock2::default();
+        let device = Device::DualShock2(ds2);
+
+        assert_eq!(device.as_dualshock2(), Some(&ds2));
+        assert_eq!(device.into_dualshock2(), Some(ds2));
+        assert!(device.as_dualshock().is_none());
+        assert!(device.as_classic().is_none());
+        assert!(device.into_guitar_hero().is_none());
+
+        // The analog joystick carries DualShock data, but is its own variant
+        let joystick = Device::AnalogJoystick(DualShock::default());
+        assert_eq!(joystick.as_analog_joystick(), Some(&DualShock::default()));
+        assert!(joystick.as_dualshock().is_none());
+
+        let classic = Device::Classic(Classic::default());
+        assert_eq!(classic.into_classic(), Some(Classic::default()));
+        assert!(classic.into_mouse().is_none());
+
+        assert!(Device::None.as_classic().is_none());
+        assert!(Device::Unknown.into_baton().is_none());
+    }
+
+    #[test]
+    fn device_buttons() {
+        let held = GamepadButtons { data: !0x4009 };
+        let data = [0xff; MESSAGE_MAX_LENGTH];
+        let mouse = Mouse::from_frame(&data).unwrap();
+        let negcon = NegCon::from_frame(&data).unwrap();
+        let guncon = GunCon::from_frame(&data).unwrap();
+        let baton = Baton::from_frame(&data).unwrap();
+        let mut jogcon = JogCon::from_frame(&data).unwrap();
+        jogcon.buttons = held;
+
+        let with_buttons = [
+            Device::Classic(Classic { buttons: held }),
+            Device::AnalogJoystick(DualShock { buttons: held, ..Default::default() }),
+            Device::DualShock(DualShock { buttons: held, ..Default::default() }),
+            Device::DualShock2(DualShock2 { buttons: held, ..Default::default() }),
+            Device::JogCon(jogcon),
+        ];
+        for device in with_buttons.iter() {
+            assert_eq!(device.buttons(), Some(held));
+            assert_eq!(device.downgrade_to_classic(), Some(Classic { buttons: held }));
+        }
+
+        let guitar = GuitarHero::builder().start().select().fret_green().build();
+        let buttons = Device::GuitarHero(guitar).buttons().unwrap();
+        assert!(buttons.start() && buttons.select());
+        assert_eq!(buttons.pressed().count(), 2);
+
+        let without_buttons = [
+            Device::None,
+            Device::Unknown,
+            Device::ConfigurationMode,
+            Device::Mouse(mouse),
+            Device::NegCon(negcon),
+            Device::GunCon(guncon),
+            Device::Baton(baton),
+        ];
+        for device in without_buttons.iter() {
+            assert_eq!(device.buttons(), None);
+            assert_eq!(device.downgrade_to_classic(), None);
+        }
+    }
+
+    #[test]
+    fn standard_buttons_are_borrowed() {
+        fn both<T: HasStandardButtons>(controller: &T) -> (&GamepadButtons, &GamepadButtons) {
+            (controller.buttons(), controller.buttons())
+        }
+
+        let ds2 = DualShock2 { buttons: GamepadButtons { data: !0x0008 }, ..Default::default() };
+        let (first, second) = both(&ds2);
+
+        assert!(core::ptr::eq(first, &ds2.buttons));
+        assert!(core::ptr::eq(first, second));
+        assert!(first.start());
+        assert_eq!(ds2.buttons_owned(), ds2.buttons);
+
+        let classic = Classic::default();
+        assert!(core::ptr::eq(HasStandardButtons::buttons(&classic), &classic.buttons));
+    }
+
+    #[test]
+    fn byte_views_match_frames() {
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xef, 0xbd];
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xef, 0xbd, 0x12, 0x34, 0x56, 0x78];
+        let dualshock2: &[u8] = &[
+            0xff, 0x79, 0x5a, 0xef, 0xbd, 0x12, 0x34, 0x56, 0x78,
+            0x00, 0x00, 0x9e, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0xff,
+        ];
+        // Separate ports, so none of them re-poll when the length grows
+        match mock::port(&[classic]).read_input(None).unwrap() {
+            Device::Classic(x) => assert_eq!(x.as_bytes(), &classic[3..]),
+            other => panic!("Expected a Classic, got {:?}", other),
+        }
+        match mock::port(&[dualshock]).read_input(None).unwrap() {
+            Device::DualShock(x) => assert_eq!(x.as_bytes(), &dualshock[3..]),
+            other => panic!("Expected a DualShock, got {:?}", other),
+        }
+        match mock::port(&[dualshock2]).read_input(None).unwrap() {
+            Device::DualShock2(x) => assert_eq!(x.as_bytes(), &dualshock2[3..]),
+            other => panic!("Expected a DualShock 2, got {:?}", other),
+        }
+
+        let raw = mock::port(&[dualshock]).read_raw(None).unwrap();
+        let guitar = GuitarHero::from_frame(&raw.data).unwrap();
+        assert_eq!(guitar.as_bytes(), &dualshock[3..]);
+
+        // The wire sends the button word low byte first, whatever order the
+        // target keeps it in
+        let classic = Classic::builder().press(Button::Select).press(Button::Square).build();
+        assert_eq!(classic.buttons.bits(), 0x7ffe);
+        assert_eq!(classic.as_bytes(), [0xfe, 0x7f]);
+        assert_eq!(Classic::from_frame(&classic.as_bytes()), Some(classic));
+    }
+
+    /// A frame from each kind of controller, in various states
+    const CAPTURED_FRAMES: &[&[u8]] = &[
+        &[0xff, 0x41, 0x5a, 0xff, 0xff],
+        &[0xff, 0x41, 0x5a, 0xef, 0xbf],
+        &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x7b, 0x83, 0x80, 0x7f],
+        &[0xff, 0x73, 0x5a, 0xfe, 0x7f, 0x00, 0x00, 0x00, 0xff],
+        &[0xff, 0x53, 0x5a, 0xff, 0xfb, 0x80, 0x80, 0x12, 0xe0],
+        &[
+            0xff, 0x79, 0x5a, 0xef, 0xbd, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x9e, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0xff,
+        ],
+        &[0xff, 0x12, 0x5a, 0xff, 0xfb, 0x03, 0xfe],
+        &[0xff, 0x23, 0x5a, 0xff, 0xf7, 0x84, 0x00, 0x40, 0x00],
+        &[0xff, 0xe3, 0x5a, 0xff, 0xbf, 0x10, 0x00, 0x01, 0x00],
+        &[0xff, 0x63, 0x5a, 0xff, 0xdf, 0x9a, 0x00, 0x40, 0x00],
+    ];
+
+    #[test]
+    fn poll_frames_match_the_port() {
+        use dualshock::ControlDS;
+        use jogcon::{ControlJC, JogControl};
+
+        let rumble = ControlDS::new(true, 0x80);
+        let wheel = ControlJC::new(JogControl::Hold, 0x07);
+        let commands: [Option<&dyn PollCommand>; 3] = [None, Some(&rumble), Some(&wheel)];
+
+        for &command in commands.iter() {
+            let mut port = mock::port(&[]);
+            port.set_multitap_port(MultitapPort::B);
+            port.read_raw(command).unwrap();
+
+            let mut tx = [0u8; MESSAGE_MAX_LENGTH];
+            assert_eq!(build_poll_frame(MultitapPort::B, command, &mut tx), MESSAGE_MAX_LENGTH);
+            flip(&mut tx);
+            assert_eq!(port.dev.sent[0], tx);
+        }
+
+        let mut tx = [0u8; 5];
+        assert_eq!(build_poll_frame(MultitapPort::A, Some(&rumble), &mut tx), 5);
+        assert_eq!(tx, [0x80, 0x42, 0x00, 0xff, 0x01]);
+
+        let mut tx = [0u8; 40];
+        assert_eq!(build_poll_frame(MultitapPort::A, None, &mut tx), MESSAGE_MAX_LENGTH);
+        assert_eq!(build_poll_frame(MultitapPort::A, None, &mut []), 0);
+    }
+
+    #[test]
+    fn responses_parse_like_the_port() {
+        for &frame in CAPTURED_FRAMES.iter() {
+            let mut rx = [0u8; MESSAGE_MAX_LENGTH];
+            rx[..frame.len()].copy_from_slice(frame);
+            flip(&mut rx[..frame.len()]);
+
+            let expected = mock::port(&[frame]).read_input(None).unwrap();
+            assert_eq!(parse_response(&rx[..frame.len()]), Ok(expected));
+        }
+
+        // Nothing plugged in, a missing ack, and frames cut short
+        assert_eq!(parse_response(&[0xff; 5]), Ok(Device::None));
+        assert_eq!(parse_response(&[0xff, 0x82, 0x00]), Err(Error::Timeout { bytes_completed: 2 }));
+        assert_eq!(parse_response(&[0xff, 0x82, 0x5a, 0xff]), Err(Error::BadResponse));
+        assert_eq!(parse_response(&[]), Err(Error::BadResponse));
+    }
+
+    #[test]
+    fn stamped_frames_carry_the_clock() {
+        let frame: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x7f, 0x10, 0xf0];
+        let expected = mock::port(&[frame]).read_input(None).unwrap();
+
+        let mut ticks = 41u32;
+        let stamped = mock::port(&[frame]).read_input_stamped(None, || { ticks += 1; ticks }).unwrap();
+
+        assert_eq!(stamped, InputFrame { device: expected, timestamp: 42 });
+        assert_eq!(ticks, 42);
+    }
+
+    #[test]
+    fn port_is_an_input_source() {
+        fn read<S: InputSource>(source: &mut S) -> Result<Device, Error<S::SpiError>> {
+            source.read_input()
         }
+
+        let frame: &[u8] = &[0xff, 0x41, 0x5a, 0xbf, 0xff];
+        let expected = mock::port(&[frame]).read_input(None);
+
+        assert_eq!(read(&mut mock::port(&[frame])), expected);
+    }
+
+    #[test]
+    fn averaged_polls() {
+        // Sticks wobble by a few counts. Select is held in three of the four
+        // polls and Cross only shows up in one.
+        let mut port = mock::port(&[
+            &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x7f, 0x10, 0xf0],
+            &[0xff, 0x73, 0x5a, 0xff, 0xbf, 0x82, 0x7e, 0x11, 0xf2],
+            &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x84, 0x7f, 0x13, 0xf1],
+            &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x83, 0x80, 0x12, 0xf3],
+        ]);
+
+        let expected = DualShock::builder().press(Button::Select).stick_right(0x82, 0x7f).stick_left(0x12, 0xf2).build();
+        assert_eq!(port.read_input_averaged(None, 4), Ok(Device::DualShock(expected)));
+    }
+
+    #[test]
+    fn averaged_dualshock2() {
+        let mut frames: [[u8; 21]; 3] = [[0; 21]; 3];
+        for (i, frame) in frames.iter_mut().enumerate() {
+            frame[..9].copy_from_slice(&[0xff, 0x79, 0x5a, 0xff, 0xbf, 0x80, 0x80, 0x80, 0x80]);
+            frame[9 + 6] = 0x40 + i as u8 * 0x10;
+        }
+        let mut port = mock::port(&[&frames[0], &frames[1], &frames[2]]);
+
+        let averaged = port.read_input_averaged(None, 3).unwrap();
+        let ds2 = averaged.as_dualshock2().unwrap();
+        assert_eq!(ds2.pressure_cross(), 0x50);
+        assert!(ds2.buttons.cross());
+    }
+
+    #[test]
+    fn averaging_stops_when_the_device_changes() {
+        let mut port = mock::port(&[
+            &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80],
+            &[0xff, 0x41, 0x5a, 0xff, 0xff],
+        ]);
+
+        assert_eq!(port.read_input_averaged(None, 4), Err(Error::DeviceChanged));
+
+        // A single sample is the same as a plain read, and so is none
+        let frame: &[u8] = &[0xff, 0x41, 0x5a, 0xef, 0xff];
+        let expected = mock::port(&[frame]).read_input(None);
+        assert_eq!(mock::port(&[frame]).read_input_averaged(None, 1), expected);
+        assert_eq!(mock::port(&[frame]).read_input_averaged(None, 0), expected);
+    }
+
+    #[test]
+    fn burst_polls_in_order() {
+        let frames: [&[u8]; 3] = [
+            &[0xff, 0x41, 0x5a, 0xff, 0xbf],
+            &[0xff, 0x41, 0x5a, 0xff, 0xff],
+            &[0xff, 0x41, 0x5a, 0xfe, 0xff],
+        ];
+
+        let mut port = mock::port(&frames);
+        let devices = port.poll_n::<3>(None).unwrap();
+        for (device, frame) in devices.iter().zip(frames.iter()) {
+            assert_eq!(Ok(*device), decode_frame(frame));
+        }
+        assert_ne!(devices[0], devices[1]);
+        assert_ne!(devices[1], devices[2]);
+        assert_eq!(port.last_input(), Some(&devices[2]));
+
+        // A stall stops the burst where it happened
+        let stalled: &[u8] = &[0xff, 0x41, 0x00];
+        let mut port = mock::port(&[frames[0], stalled, frames[2]]);
+        assert_eq!(port.poll_n::<3>(None), Err(BurstError { index: 1, error: Error::Timeout { bytes_completed: 2 } }));
+        assert_eq!(port.dev.sent.len(), 2);
+
+        assert_eq!(mock::port(&[]).poll_n::<0>(None), Ok([]));
+    }
+
+    #[test]
+    fn stored_rumble_goes_out_with_every_poll() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let unplugged: &[u8] = &[0xff, 0xff, 0xff];
+        let motors = |port: &PlayStationPort<mock::MockSpi, mock::MockPin>| {
+            let sent = port.dev.sent.last().unwrap();
+            (sent[3], sent[4])
+        };
+
+        let mut port = mock::port(&[dualshock]);
+        port.set_rumble(true, 0x80);
+        assert_eq!(port.rumble(), (true, 0x80));
+
+        // The motors aren't mapped yet, so the poll goes out plain
+        port.read_input(None).unwrap();
+        assert_eq!(motors(&port), (0x00, 0x00));
+
+        port.enable_pressure().unwrap();
+        port.dev.responses.extend([dualshock, dualshock, dualshock, dualshock, dualshock].iter().map(|x| x.to_vec()));
+
+        port.read_input(None).unwrap();
+        assert_eq!(motors(&port), (0xff, 0x80));
+        port.read_raw(None).unwrap();
+        assert_eq!(motors(&port), (0xff, 0x80));
+        port.poll_buttons().unwrap();
+        assert_eq!(motors(&port), (0xff, 0x80));
+
+        // A command of its own wins
+        port.read_input(Some(&ControlDS::new(false, 0x20))).unwrap();
+        assert_eq!(motors(&port), (0x00, 0x20));
+
+        port.stop_rumble();
+        assert_eq!(port.rumble(), (false, 0));
+        port.read_input(None).unwrap();
+        assert_eq!(motors(&port), (0x00, 0x00));
+
+        // Pulling the controller out forgets the setting and the mapping
+        port.set_rumble(true, 0xff);
+        port.dev.responses.extend([unplugged, unplugged, dualshock].iter().map(|x| x.to_vec()));
+        assert_eq!(port.read_input(None), Ok(Device::None));
+        assert_eq!(port.rumble(), (false, 0));
+
+        port.set_rumble(true, 0xff);
+        port.read_input(None).unwrap();
+        assert_eq!(motors(&port), (0x00, 0x00));
+    }
+
+    #[test]
+    fn rumble_runs_out_without_a_refresh() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+
+        let mut port = PlayStationPort::<_, mock::MockPin>::builder(mock::MockSpi::new(&[]), None)
+            .rumble_ttl(3)
+            .build();
+        port.enable_pressure().unwrap();
+        port.dev.responses.extend((0..10).map(|_| dualshock.to_vec()));
+
+        fn motors(port: &mut PlayStationPort<mock::MockSpi, mock::MockPin>) -> (u8, u8) {
+            port.read_input(None).unwrap();
+            let sent = port.dev.sent.last().unwrap();
+            (sent[3], sent[4])
+        }
+
+        port.set_rumble(true, 0xc0);
+        assert_eq!([motors(&mut port), motors(&mut port), motors(&mut port)], [(0xff, 0xc0); 3]);
+        assert_eq!(motors(&mut port), (0x00, 0x00));
+        assert_eq!(port.rumble(), (false, 0));
+
+        // Setting it again starts the count over
+        port.set_rumble(false, 0x40);
+        assert_eq!([motors(&mut port), motors(&mut port)], [(0x00, 0x40); 2]);
+        port.set_rumble(false, 0x40);
+        assert_eq!([motors(&mut port), motors(&mut port), motors(&mut port)], [(0x00, 0x40); 3]);
+        assert_eq!(motors(&mut port), (0x00, 0x00));
+
+        // And 0 lets it run
+        port.set_rumble_ttl(0);
+        port.set_rumble(true, 0xff);
+        port.dev.responses.extend((0..20).map(|_| dualshock.to_vec()));
+        for _ in 0..20 {
+            assert_eq!(motors(&mut port), (0xff, 0xff));
+        }
+    }
+
+    #[test]
+    fn only_changes_are_reported() {
+        let still: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let jitter: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x82, 0x7e, 0x80, 0x81];
+        let creep: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x83, 0x80, 0x80, 0x80];
+        let pressed: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xbf, 0x83, 0x80, 0x80, 0x80];
+        let digital: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xbf];
+
+        let mut port = mock::port(&[still, still, jitter, creep, creep, pressed, pressed, digital, digital]);
+        port.set_change_tolerance(2);
+        let mut changes = [false; 9];
+        for changed in changes.iter_mut() {
+            *changed = port.poll_changed(None).unwrap().is_some();
+        }
+        assert_eq!(changes, [true, false, false, true, false, true, false, true, false]);
+
+        // With no tolerance every wobble counts, and what comes back is the
+        // new state
+        let mut port = mock::port(&[still, jitter]);
+        port.poll_changed(None).unwrap();
+        assert_eq!(port.poll_changed(None), mock::port(&[jitter]).read_input(None).map(Some));
+    }
+
+    #[test]
+    fn last_input_is_cached() {
+        let cross: &[u8] = &[0xff, 0x41, 0x5a, 0xff, 0xbf];
+        let stalled: &[u8] = &[0xff, 0x41, 0x00];
+        let unplugged: &[u8] = &[0xff, 0xff, 0xff];
+        let select: &[u8] = &[0xff, 0x41, 0x5a, 0xfe, 0xff];
+
+        // The empty port claims a longer frame, so it's polled twice
+        let mut port = mock::port(&[cross, stalled, unplugged, unplugged, select]);
+        assert_eq!(port.last_input(), None);
+        assert_eq!(port.last_error(), None);
+
+        let device = port.read_input(None).unwrap();
+        assert_eq!(port.last_input(), Some(&device));
+        assert_eq!(port.last_error(), None);
+
+        // A failed poll keeps the last good input around
+        assert!(port.read_input(None).is_err());
+        assert_eq!(port.last_input(), Some(&device));
+        assert_eq!(port.last_error(), Some(&Error::Timeout { bytes_completed: 2 }));
+
+        // But nothing plugged in clears it
+        assert_eq!(port.read_input(None), Ok(Device::None));
+        assert_eq!(port.last_input(), None);
+        assert_eq!(port.last_error(), None);
+
+        let device = port.read_input(None).unwrap();
+        assert!(device.buttons().unwrap().select());
+        assert_eq!(port.last_input(), Some(&device));
+    }
+
+    #[test]
+    fn builder_options_apply() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let jitter: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x81, 0x80, 0x80, 0x80];
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        let mut port = PlayStationPort::builder(mock::MockSpi::new(&[dualshock, config, jitter]), Some(mock::MockPin::default()))
+            .multitap_port(MultitapPort::C)
+            .smooth_transitions(true)
+            .change_tolerance(2)
+            .build();
+        assert!(port.select.as_ref().unwrap().high);
+
+        assert!(port.poll_changed(None).unwrap().is_some());
+        // The configuration frame is polled through, and the wobble after it
+        // is inside the tolerance
+        assert_eq!(port.poll_changed(None), Ok(None));
+        assert!(port.dev.sent.iter().all(|x| x[0] == MultitapPort::C as u8));
+        assert_eq!(port.dev.sent.len(), 3);
+
+        // With nothing set the builder matches `new`
+        let built = PlayStationPort::<_, mock::MockPin>::builder(mock::MockSpi::new(&[]), None).build();
+        let new = mock::port(&[]);
+        assert_eq!(built.multitap_port, new.multitap_port);
+        assert_eq!(built.smooth_transitions, new.smooth_transitions);
+        assert_eq!(built.change_tolerance, new.change_tolerance);
+    }
+
+    #[test]
+    fn busy_bus_is_left_alone() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let idle: &[u8] = &[0xff];
+        let stuck: &[u8] = &[0x00];
+
+        let mut port = PlayStationPort::builder(mock::MockSpi::new(&[idle, dualshock, stuck]), Some(mock::MockPin::default()))
+            .verify_bus_idle(true)
+            .build();
+
+        // One byte clocked with select still released, then the poll
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+        assert_eq!(port.dev.sent[0], [LINE_IDLE]);
+        assert_eq!(port.dev.sent.len(), 2);
+        assert_eq!(port.select.as_ref().unwrap().levels, [true, false, true]);
+
+        // Something else drives the line low, so the poll never starts
+        assert_eq!(port.read_input(None), Err(Error::BusBusy));
+        assert_eq!(port.dev.sent.len(), 3);
+        assert_eq!(port.select.as_ref().unwrap().levels, [true, false, true]);
+        assert_eq!(port.last_error(), Some(&Error::BusBusy));
+
+        // Off again, or without a select pin to release, there's no check
+        port.verify_bus_idle(false);
+        port.dev.responses.push_back(dualshock.to_vec());
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+        assert_eq!(port.dev.sent.len(), 4);
+
+        let mut port = mock::port(&[dualshock]);
+        port.verify_bus_idle(true);
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+        assert_eq!(port.dev.sent.len(), 1);
+    }
+
+    #[test]
+    fn blocking_poll_interval_spaces_polls() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        // Every look at the clock moves it on a tick
+        static NOW: AtomicU32 = AtomicU32::new(0);
+        fn now() -> u32 {
+            NOW.fetch_add(1, Ordering::SeqCst)
+        }
+
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let interval = PollInterval { now, min_ticks: 10, mode: IntervalMode::Block };
+        let mut port = PlayStationPort::builder(mock::MockSpi::new(&[dualshock; 3]), Some(mock::MockPin::default()))
+            .poll_interval(interval)
+            .build();
+
+        let stamps: [u32; 3] = core::array::from_fn(|_| {
+            port.read_input_stamped(None, || NOW.load(Ordering::SeqCst)).unwrap().timestamp
+        });
+        assert_eq!(port.dev.sent.len(), 3);
+        assert!(stamps.windows(2).all(|x| x[1] - x[0] >= 10), "{:?}", stamps);
+
+        // Without an interval nothing waits
+        port.set_poll_interval(None);
+        let before = NOW.load(Ordering::SeqCst);
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(NOW.load(Ordering::SeqCst), before);
+    }
+
+    #[test]
+    fn non_blocking_poll_interval_says_too_soon() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        static NOW: AtomicU32 = AtomicU32::new(u32::MAX - 4);
+        fn now() -> u32 {
+            NOW.load(Ordering::SeqCst)
+        }
+
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let mut port = mock::port(&[dualshock; 3]);
+        port.set_poll_interval(Some(PollInterval { now, min_ticks: 10, mode: IntervalMode::NonBlocking }));
+
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(port.read_input(None), Err(Error::TooSoon));
+
+        // Still too soon, and nothing was sent or recorded against the
+        // controller
+        NOW.store(4, Ordering::SeqCst);
+        assert_eq!(port.read_input(None), Err(Error::TooSoon));
+        assert_eq!(port.dev.sent.len(), 1);
+        assert_eq!(port.last_error(), None);
+        assert_eq!(port.stats().polls, 1);
+
+        // Ten ticks on, across the wrap
+        NOW.store(5, Ordering::SeqCst);
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(port.read_input(None), Err(Error::TooSoon));
+        NOW.store(15, Ordering::SeqCst);
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(port.dev.sent.len(), 3);
+    }
+
+    #[test]
+    fn cs_mode_sets_select_edges_per_frame() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        static WAITED: AtomicU32 = AtomicU32::new(0);
+        fn wait(us: u32) {
+            WAITED.fetch_add(us, Ordering::SeqCst);
+        }
+
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xfe, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let edges = |port: &PlayStationPort<mock::MockSpi, mock::MockPin>| port.select.as_ref().unwrap().levels.len();
+
+        // One select cycle a frame, whatever its length
+        let mut port = PlayStationPort::new(mock::MockSpi::new(&[dualshock, dualshock]), Some(mock::MockPin::default()));
+        assert!(port.read_input(None).is_ok());
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(edges(&port), 1 + 2 * 2);
+
+        // One a byte, each byte answered on its own. The first poll clocks a
+        // whole `MESSAGE_MAX_LENGTH` bytes
+        let mut first = [0xff; MESSAGE_MAX_LENGTH];
+        first[..dualshock.len()].copy_from_slice(dualshock);
+        let bytes: Vec<&[u8]> = first.chunks(1).chain(dualshock.chunks(1)).collect();
+        let mut port = PlayStationPort::builder(mock::MockSpi::new(&bytes), Some(mock::MockPin::default()))
+            .cs_mode(CsMode::PulsePerByte { gap_us: 10, delay: wait })
+            .build();
+
+        let device = port.read_input(None).unwrap();
+        assert!(device.buttons().unwrap().select());
+        assert_eq!(edges(&port), 1 + 2 * MESSAGE_MAX_LENGTH);
+        assert_eq!(WAITED.load(Ordering::SeqCst), 10 * (MESSAGE_MAX_LENGTH as u32 - 1));
+
+        // The second poll only clocks the frame's 9 bytes
+        assert_eq!(port.read_input(None), Ok(device));
+        assert_eq!(edges(&port), 1 + 2 * (MESSAGE_MAX_LENGTH + 9));
+        assert!(port.dev.sent.iter().all(|x| x.len() == 1));
+
+        // A failed byte still lets go of select
+        port.dev.fail = true;
+        assert_eq!(port.read_input(None), Err(Error::Spi(())));
+        assert!(port.select.as_ref().unwrap().high);
+        assert_eq!(WAITED.load(Ordering::SeqCst), 10 * (MESSAGE_MAX_LENGTH as u32 - 1 + 8));
+    }
+
+    #[test]
+    fn frame_size_limits_polls() {
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xfe, 0xff];
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let mut dualshock2 = [0u8; 21];
+        dualshock2[..3].copy_from_slice(&[0xff, 0x79, 0x5a]);
+        let dualshock2: &[u8] = &dualshock2;
+
+        // Each switch to a longer mode is polled again
+        let responses = [classic, dualshock, dualshock, dualshock2];
+        let mut small = PlayStationPort::<_, mock::MockPin>::builder(mock::MockSpi::new(&responses), None)
+            .frame_size::<9>()
+            .build();
+        assert!(matches!(small.read_input(None), Ok(Device::Classic(_))));
+        assert!(matches!(small.read_input(None), Ok(Device::DualShock(_))));
+
+        // Asked again for the whole frame, but only as much of it as fits
+        assert_eq!(small.read_input(None), Err(Error::BadResponse));
+        assert_eq!(small.dev.sent.iter().map(|x| x.len()).collect::<Vec<_>>(), [9, 5, 9, 9]);
+        assert_eq!(small.poll_len, 9);
+
+        let mut full = mock::port(&[dualshock, dualshock2, dualshock2]);
+        assert!(matches!(full.read_input(None), Ok(Device::DualShock(_))));
+        assert!(matches!(full.read_input(None), Ok(Device::DualShock2(_))));
+        assert_eq!(full.poll_len, 21);
+    }
+
+    #[test]
+    fn ports_can_be_longer_than_one_controller() {
+        // A multi-tap's id doesn't say how long its frame is, so it gets the
+        // whole port
+        let mut multitap = [0x5au8; 35];
+        multitap[..3].copy_from_slice(&[0xff, 0x80, 0x5a]);
+        multitap[34] = 0x11;
+        let multitap: &[u8] = &multitap;
+
+        let mut port = PlayStationPort::<_, mock::MockPin>::builder(mock::MockSpi::new(&[multitap, multitap]), None)
+            .frame_size::<35>()
+            .build();
+        let raw = port.read_raw(None).unwrap();
+        assert_eq!(&raw.data[..32], &multitap[3..]);
+        assert_eq!(raw.data[31], 0x11);
+        assert_eq!(port.poll_len, 35);
+        assert_eq!(port.read_input(None), Ok(Device::Unknown));
+        assert_eq!(port.dev.sent.iter().map(|x| x.len()).collect::<Vec<_>>(), [35, 35]);
+
+        // The usual port can't fit it, and takes it for a mangled id
+        let mut port = mock::port(&[multitap]);
+        assert_eq!(port.read_raw(None), Err(Error::Garbage { offset: 1, byte: 0x80 }));
+        assert_eq!(port.dev.sent[0].len(), MESSAGE_MAX_LENGTH);
+    }
+
+    #[test]
+    fn shared_bus_transfers_are_whole_select_cycles() {
+        use core::cell::{Cell, RefCell};
+        use hal::blocking::spi::Transfer;
+        use mock::MockSpi;
+
+        // A bus proxy in the style of `shared-bus`, locking the real bus for
+        // each transfer and checking the select pin while it holds it
+        struct Proxy<'a> {
+            bus: &'a RefCell<MockSpi>,
+            selected: &'a Cell<bool>,
+            locks: &'a Cell<u32>,
+            unlocks: &'a Cell<u32>,
+            fail: bool,
+        }
+
+        impl<'a> Transfer<u8> for Proxy<'a> {
+            type Error = ();
+
+            fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+                let mut bus = self.bus.try_borrow_mut().expect("bus already locked");
+                self.locks.set(self.locks.get() + 1);
+                assert!(self.selected.get());
+
+                let result = if self.fail { Err(()) } else { bus.transfer(words) };
+                self.unlocks.set(self.unlocks.get() + 1);
+
+                result
+            }
+        }
+
+        struct Pin<'a>(&'a Cell<bool>);
+
+        #[allow(deprecated)]
+        impl<'a> OutputPin for Pin<'a> {
+            fn set_low(&mut self) {
+                self.0.set(true);
+            }
+
+            fn set_high(&mut self) {
+                self.0.set(false);
+            }
+        }
+
+        let dualshock2: &[u8] = &[
+            0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let bus = RefCell::new(MockSpi::new(&[]));
+        let (selected, locks, unlocks) = (Cell::new(false), Cell::new(0), Cell::new(0));
+        let proxy = |fail| Proxy { bus: &bus, selected: &selected, locks: &locks, unlocks: &unlocks, fail };
+
+        let mut port = PlayStationPort::new(proxy(false), Some(Pin(&selected)));
+        port.enable_pressure().unwrap();
+        assert!(!selected.get());
+
+        // Someone else uses the bus in between
+        bus.borrow_mut().transfer(&mut [0u8; 4]).unwrap();
+        bus.borrow_mut().responses.push_back(dualshock2.to_vec());
+
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock2(_))));
+        assert!(!selected.get());
+        assert_eq!((locks.get(), unlocks.get()), (8, 8));
+        assert_eq!(bus.borrow().sent.len(), 9);
+
+        // A failed transfer still deselects the controller
+        let (proxy, pin) = port.release();
+        assert_eq!(proxy.locks.get(), 8);
+        let mut port = PlayStationPort::new(Proxy { fail: true, ..proxy }, pin);
+        assert_eq!(port.read_input(None), Err(Error::Spi(())));
+        assert!(!selected.get());
+        assert_eq!((locks.get(), unlocks.get()), (9, 9));
+    }
+
+    #[cfg(feature = "critical-section")]
+    #[test]
+    fn transfers_run_inside_critical_sections() {
+        use core::cell::RefCell;
+        use hal::blocking::spi::Transfer;
+        use mock::{critical, MockSpi};
+
+        // Notes whether each pin change and transfer is in a critical section
+        struct Spi<'a>(MockSpi, &'a RefCell<Vec<(&'static str, bool)>>);
+
+        impl<'a> Transfer<u8> for Spi<'a> {
+            type Error = ();
+
+            fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+                self.1.borrow_mut().push(("transfer", critical::held()));
+                self.0.transfer(words)
+            }
+        }
+
+        struct Pin<'a>(&'a RefCell<Vec<(&'static str, bool)>>);
+
+        #[allow(deprecated)]
+        impl<'a> OutputPin for Pin<'a> {
+            fn set_low(&mut self) {
+                self.0.borrow_mut().push(("select", critical::held()));
+            }
+
+            fn set_high(&mut self) {
+                self.0.borrow_mut().push(("deselect", critical::held()));
+            }
+        }
+
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let log = RefCell::new(Vec::new());
+        let mut port = PlayStationPort::builder(Spi(MockSpi::new(&[dualshock, dualshock]), &log), Some(Pin(&log)))
+            .critical_section(true)
+            .build();
+        log.borrow_mut().clear();
+
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(*log.borrow(), [("select", true), ("transfer", true), ("deselect", true)]);
+        assert!(!critical::held());
+
+        log.borrow_mut().clear();
+        port.use_critical_section(false);
+        assert!(port.read_input(None).is_ok());
+        assert_eq!(*log.borrow(), [("select", false), ("transfer", false), ("deselect", false)]);
+    }
+
+    #[test]
+    fn observer_sees_every_transfer() {
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static SEEN: RefCell<Vec<(TransferDirection, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        fn record(direction: TransferDirection, bytes: &[u8]) {
+            SEEN.with(|x| x.borrow_mut().push((direction, bytes.to_vec())));
+        }
+
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let responses = [dualshock, config, config, config, config, config, config];
+        let mut port = PlayStationPort::builder(mock::MockSpi::new(&responses), None::<mock::MockPin>)
+            .observer(record)
+            .build();
+        port.enable_pressure().unwrap();
+
+        let commands = [
+            CMD_POLL, CMD_ENTER_ESCAPE_MODE, CMD_SET_MODE, CMD_MOTOR_DUALSHOCK,
+            CMD_INIT_PRESSURE, CMD_RESPONSE_FORMAT, CMD_EXIT_ESCAPE_MODE,
+        ];
+        let seen = SEEN.with(|x| x.replace(Vec::new()));
+        assert_eq!(seen.len(), commands.len() * 2);
+
+        for (i, (pair, command)) in seen.chunks(2).zip(commands.iter()).enumerate() {
+            let mut sent = command.to_vec();
+            sent[0] = MultitapPort::A as u8;
+
+            assert_eq!(pair[0], (TransferDirection::Sent, sent));
+            assert_eq!(pair[1].0, TransferDirection::Received);
+            assert_eq!(pair[1].1, responses[i][..command.len().min(responses[i].len())]);
+            assert_eq!(port.dev.sent[i], pair[0].1);
+        }
+
+        // Turned off, nothing more is seen
+        port.set_observer(None);
+        port.read_raw(None).unwrap();
+        assert!(SEEN.with(|x| x.borrow().is_empty()));
+    }
+
+    #[test]
+    fn device_ids() {
+        let known = [
+            (0xff, DeviceId::NotPresent),
+            (0x12, DeviceId::Mouse),
+            (0xc1, DeviceId::Classic),
+            (0x53, DeviceId::AnalogJoystick),
+            (0x41, DeviceId::DualShockDigital),
+            (0x73, DeviceId::DualShockAnalog),
+            (0x79, DeviceId::DualShockPressure),
+            (0xe3, DeviceId::JogCon),
+            (0x23, DeviceId::NegCon),
+            (0x63, DeviceId::GunCon),
+            (0xf3, DeviceId::Configuration),
+        ];
+
+        for &(byte, id) in known.iter() {
+            assert_eq!(DeviceId::from_byte(byte), id);
+            assert_eq!(u8::from(id), byte);
+        }
+
+        assert_eq!(DeviceId::from(0x5f), DeviceId::Other(0x5f));
+        assert_eq!(DeviceId::Other(0x5f).to_byte(), 0x5f);
+
+        assert_eq!(DeviceId::DualShockPressure.kind_nibble(), 0x7);
+        assert_eq!(DeviceId::DualShockPressure.half_words(), 9);
+        assert_eq!(DeviceId::Classic.kind_nibble(), 0xc);
+        assert_eq!(DeviceId::Classic.half_words(), 1);
+
+        let (_, meta) = mock::port(&[&[0xff, 0x41, 0x5a, 0xff, 0xff]]).read_input_meta(None).unwrap();
+        assert_eq!(meta.device_id(), DeviceId::DualShockDigital);
+    }
+
+    #[test]
+    fn single_buffer_parsing_matches_copying() {
+        for &frame in CAPTURED_FRAMES.iter() {
+            // How the frame used to be parsed: copy everything after the
+            // header into its own zeroed buffer, then read the controller out
+            // of that. The first poll clocks the longest frame, so the bus
+            // idles high past the end of the real one.
+            let mut wire = [0xff; MESSAGE_MAX_LENGTH];
+            wire[..frame.len()].copy_from_slice(frame);
+            let mut copy = [0u8; MESSAGE_MAX_LENGTH];
+            copy[..MESSAGE_MAX_LENGTH - HEADER_LEN].copy_from_slice(&wire[HEADER_LEN..]);
+            let expected = match frame[1] {
+                CONTROLLER_MOUSE => Device::Mouse(Mouse::from_frame(&copy).unwrap()),
+                CONTROLLER_CLASSIC | CONTROLLER_DUALSHOCK_DIGITAL => Device::Classic(Classic::from_frame(&copy).unwrap()),
+                CONTROLLER_ANALOG_JOYSTICK => Device::AnalogJoystick(DualShock::from_frame(&copy).unwrap()),
+                CONTROLLER_DUALSHOCK_ANALOG => Device::DualShock(DualShock::from_frame(&copy).unwrap()),
+                CONTROLLER_DUALSHOCK_PRESSURE => Device::DualShock2(DualShock2::from_frame(&copy).unwrap()),
+                CONTROLLER_JOGCON => Device::JogCon(JogCon::from_frame(&copy).unwrap()),
+                CONTROLLER_NEGCON => Device::NegCon(NegCon::from_frame(&copy).unwrap()),
+                CONTROLLER_GUNCON => Device::GunCon(GunCon::from_frame(&copy).unwrap()),
+                _ => Device::Unknown,
+            };
+
+            assert_eq!(mock::port(&[frame]).read_input(None), Ok(expected));
+            assert_eq!(mock::port(&[frame]).read_raw(None), Ok(ControllerData { data: copy }));
+        }
+    }
+
+    #[test]
+    fn views_match_owned() {
+        for &frame in CAPTURED_FRAMES.iter() {
+            let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+            let owned = mock::port(&[frame]).read_input(None).unwrap();
+            let view = mock::port(&[frame]).read_input_ref(None, &mut buffer).unwrap();
+
+            assert_eq!(view.to_owned(), owned);
+
+            match (view, owned) {
+                (DeviceRef::Classic(view), Device::Classic(owned)) => {
+                    assert_eq!(view.buttons(), owned.buttons);
+                },
+                (DeviceRef::AnalogJoystick(view), Device::AnalogJoystick(owned)) |
+                (DeviceRef::DualShock(view), Device::DualShock(owned)) => {
+                    assert_eq!(view.buttons(), owned.buttons);
+                    assert_eq!([view.rx(), view.ry(), view.lx(), view.ly()], [owned.rx, owned.ry, owned.lx, owned.ly]);
+                },
+                (DeviceRef::DualShock2(view), Device::DualShock2(owned)) => {
+                    assert_eq!(view.buttons(), owned.buttons);
+                    assert_eq!([view.rx(), view.ry(), view.lx(), view.ly()], [owned.rx, owned.ry, owned.lx, owned.ly]);
+                    assert_eq!(view.pressures(), &owned.pressures[..]);
+                },
+                (DeviceRef::Other(view), owned) => assert_eq!(view, owned),
+                (view, owned) => panic!("{:?} doesn't match {:?}", view, owned),
+            }
+        }
+    }
+
+    #[test]
+    fn views_reject_short_frames() {
+        assert_eq!(DeviceRef::parse(&[0xff, 0x73]), None);
+        assert_eq!(DeviceRef::parse(&[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80]), None);
+        assert_eq!(DeviceRef::parse(&[0xff, 0xff, 0xff]), Some(DeviceRef::None));
+        assert!(matches!(
+            DeviceRef::parse(&[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80]),
+            Some(DeviceRef::DualShock(_))));
+    }
+
+    fn config(status: [u8; 6]) -> ControllerConfiguration {
+        ControllerConfiguration::new(
+            status,
+            [0x00, 0x01, 0x02, 0x00, 0x0a],
+            [0x00, 0x01, 0x01, 0x01, 0x14],
+            [0x00, 0x02, 0x00, 0x01, 0x00],
+            [0x00, 0x00, 0x04, 0x00, 0x00],
+            [0x00, 0x00, 0x07, 0x00, 0x00],
+        )
+    }
+
+    #[test]
+    fn configuration_interpretation() {
+        let dualshock = config([0x01, 0x02, 0x00, 0x02, 0x01, 0x00]);
+        assert_eq!(dualshock.is_dualshock2(), Some(false));
+        assert!(!dualshock.analog_enabled());
+        assert_eq!(dualshock.actuators(), 2);
+
+        let dualshock2 = config([0x03, 0x02, 0x01, 0x02, 0x01, 0x00]);
+        assert_eq!(dualshock2.is_dualshock2(), Some(true));
+        assert!(dualshock2.analog_enabled());
+        assert_eq!(dualshock2.actuators(), 2);
+
+        // A third party pad that answers the commands with nothing useful
+        let clone = config([0x00, 0x00, 0x01, 0x02, 0x00, 0x00]);
+        assert_eq!(clone.is_dualshock2(), None);
+        assert!(!clone.analog_enabled());
+        assert_eq!(clone.actuators(), 0);
+
+        assert_eq!(dualshock2.raw()[0], &[0x03, 0x02, 0x01, 0x02, 0x01, 0x00]);
+        assert_eq!(dualshock2.raw()[5], &[0x00, 0x00, 0x07, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn send_command_flips_and_selects() {
+        let response: &[u8] = &[0xff, 0x41, 0x5a, 0xfe, 0x7f];
+        let mut port = PlayStationPort::new(mock::MockSpi::new(&[response, response]), Some(mock::MockPin::default()));
+        assert_eq!(port.select.as_ref().unwrap().levels, [true]);
+
+        let mut result = [0u8; MESSAGE_MAX_LENGTH];
+        port.send_command(&[0x00, 0x42, 0x00, 0x00, 0x00], &mut result).unwrap();
+
+        // Only the command's length is clocked, least significant bit first,
+        // with the multi-tap port in the first byte
+        assert_eq!(port.dev.wire, [[0x80, 0x42, 0x00, 0x00, 0x00]]);
+        assert_eq!(&result[..5], response);
+        assert_eq!(&result[5..], &[0u8; MESSAGE_MAX_LENGTH - 5][..]);
+        assert_eq!(port.select.as_ref().unwrap().levels, [true, false, true]);
+
+        port.set_multitap_port(MultitapPort::B);
+        port.send_command(&[0x00, 0x43, 0x00, 0x01, 0x00], &mut result).unwrap();
+        assert_eq!(port.dev.wire[1], [0x40, 0xc2, 0x00, 0x80, 0x00]);
+        assert_eq!(port.select.as_ref().unwrap().levels, [true, false, true, false, true]);
+    }
+
+    #[test]
+    fn short_buffers_are_errors() {
+        let mut port = mock::port(&[]);
+
+        // Nothing goes out when the answer has nowhere to go
+        let mut result = [0u8; 4];
+        assert_eq!(port.send_command(&[0x01, 0x42, 0x00, 0x00, 0x00], &mut result), Err(Error::BufferTooSmall { needed: 5 }));
+        assert!(port.dev.sent.is_empty());
+        assert_eq!(port.send_command(&[], &mut []), Ok(()));
+
+        // Frames shorter than a header don't parse
+        type Port = PlayStationPort<mock::MockSpi, mock::MockPin>;
+        for len in 0..HEADER_LEN {
+            assert!(matches!(Port::parse_frame(&[0xff, 0x41, 0x5a][..len]), Err(Error::BadResponse)));
+        }
+        assert!(DeviceRef::parse(&[0xff, 0x41]).is_none());
+    }
+
+    #[test]
+    fn configuration_command_order() {
+        let sent = |commands: &[&[u8]]| -> Vec<Vec<u8>> {
+            commands.iter().map(|x| {
+                let mut x = x.to_vec();
+                x[0] = MultitapPort::A as u8;
+                x
+            }).collect()
+        };
+
+        let mut port = PlayStationPort::new(mock::MockSpi::new(&[]), Some(mock::MockPin::default()));
+        port.enable_pressure().unwrap();
+        assert_eq!(port.dev.sent, sent(&[
+            CMD_POLL, CMD_ENTER_ESCAPE_MODE, CMD_SET_MODE, CMD_MOTOR_DUALSHOCK,
+            CMD_INIT_PRESSURE, CMD_RESPONSE_FORMAT, CMD_EXIT_ESCAPE_MODE,
+        ]));
+
+        // Every command gets a select cycle of its own
+        let levels = &port.select.as_ref().unwrap().levels;
+        assert_eq!(levels.len(), 1 + 7 * 2);
+        assert!(levels[1..].chunks(2).all(|x| x == [false, true]));
+
+        let mut port = mock::port(&[]);
+        port.enable_jogcon().unwrap();
+        assert_eq!(port.dev.sent, sent(&[
+            CMD_POLL, CMD_ENTER_ESCAPE_MODE, CMD_SET_MODE, CMD_MOTOR_JOGCON, CMD_EXIT_ESCAPE_MODE,
+        ]));
+    }
+
+    #[test]
+    fn read_config_fills_each_field() {
+        let escape: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let responses: [&[u8]; 8] = [
+            escape,
+            &[0xff, 0xf3, 0x5a, 0x03, 0x02, 0x01, 0x02, 0x01, 0x00],
+            &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x01, 0x02, 0x00, 0x0a],
+            &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x01, 0x01, 0x01, 0x14],
+            &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00],
+            &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00],
+            &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00],
+            escape,
+        ];
+
+        let mut port = mock::port(&responses);
+        assert_eq!(port.read_config(), Ok(config([0x03, 0x02, 0x01, 0x02, 0x01, 0x00])));
+
+        let commands = [
+            CMD_ENTER_ESCAPE_MODE, CMD_READ_STATUS, CMD_READ_CONST1A, CMD_READ_CONST1B,
+            CMD_READ_CONST2, CMD_READ_CONST3A, CMD_READ_CONST3B, CMD_EXIT_ESCAPE_MODE,
+        ];
+        for (sent, command) in port.dev.sent.iter().zip(commands.iter()) {
+            assert_eq!(sent[1..], command[1..]);
+        }
+        assert_eq!(port.dev.sent.len(), commands.len());
+
+        // Something without an escape mode answers each request as a poll,
+        // which isn't kept
+        let classic: &[u8] = &[0xff, 0x41, 0x5a, 0xfe, 0xff];
+        let config = mock::port(&[classic; 8]).read_config().unwrap();
+        assert_eq!(config.lengths(), [0; 6]);
+        assert_eq!(config.raw(), [&[][..]; 6]);
+        assert_eq!(config.status, [0; 6]);
+    }
+
+    #[test]
+    fn configuration_already_applied_is_skipped() {
+        let mut pressure = [0u8; 21];
+        pressure[..3].copy_from_slice(&[0xff, 0x79, 0x5a]);
+        let pressure: &[u8] = &pressure;
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let jogcon: &[u8] = &[0xff, 0xe3, 0x5a, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00];
+
+        let mut port = mock::port(&[]);
+        assert_eq!(port.enable_pressure(), Ok(ConfigOutcome::Applied));
+        assert_eq!(port.dev.sent.len(), 7);
+        assert_eq!(port.applied_config(), Some(AppliedConfig::Pressure));
+
+        // Still sending pressures, so a poll is all it takes
+        port.dev.responses.push_back(pressure.to_vec());
+        assert_eq!(port.enable_pressure(), Ok(ConfigOutcome::AlreadyApplied));
+        assert_eq!(port.dev.sent.len(), 7 + 1);
+
+        // Unless it's asked for again
+        assert_eq!(port.reapply_pressure(), Ok(()));
+        assert_eq!(port.dev.sent.len(), 8 + 7);
+
+        // The controller dropped back to analog, so it's set up again
+        port.dev.responses.push_back(dualshock.to_vec());
+        assert_eq!(port.enable_pressure(), Ok(ConfigOutcome::Applied));
+        assert_eq!(port.dev.sent.len(), 15 + 1 + 7);
+
+        // Asking for something else doesn't check first
+        assert_eq!(port.enable_jogcon(), Ok(ConfigOutcome::Applied));
+        assert_eq!(port.dev.sent.len(), 23 + 5);
+        port.dev.responses.push_back(jogcon.to_vec());
+        assert_eq!(port.enable_jogcon(), Ok(ConfigOutcome::AlreadyApplied));
+        assert_eq!(port.dev.sent.len(), 28 + 1);
+
+        // Nor does anything after the controller was unplugged
+        assert_eq!(port.read_input(None), Ok(Device::None));
+        assert_eq!(port.applied_config(), None);
+        let sent = port.dev.sent.len();
+        assert_eq!(port.enable_jogcon(), Ok(ConfigOutcome::Applied));
+        assert_eq!(port.dev.sent.len(), sent + 5);
+
+        // The checking poll is a poll like any other, so it notices the
+        // controller going away
+        port.dev.responses.push_back(dualshock.to_vec());
+        assert_eq!(port.read_input(None), Ok(Device::DualShock(DualShock::default())));
+        let polls = port.stats().polls;
+        assert_eq!(port.enable_jogcon(), Ok(ConfigOutcome::Applied));
+        assert_eq!(port.last_input(), None);
+        assert_eq!(port.stats().polls, polls + 1);
+    }
+
+    #[test]
+    fn analog_led() {
+        use testutil::SimulatedDualShock2;
+
+        let mut port = PlayStationPort::<_, mock::MockPin>::new(SimulatedDualShock2::new(), None);
+        assert_eq!(port.is_analog_active(), Ok(false));
+
+        port.dev.press_analog_button();
+        assert_eq!(port.is_analog_active(), Ok(true));
+        assert!(!port.dev.in_escape_mode());
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+
+        let classic: &[u8] = &[0xff, 0xc1, 0x5a, 0xff, 0xff];
+        let mut port = mock::port(&[classic, classic, classic]);
+        assert_eq!(port.is_analog_active(), Err(Error::UnsupportedDevice));
+        assert_eq!(port.dev.sent.len(), 3);
+
+        assert_eq!(mock::port(&[]).is_analog_active(), Err(Error::UnsupportedDevice));
+    }
+
+    #[test]
+    fn configuration_debug_is_hex() {
+        use mock::FixedBuffer;
+
+        let dualshock2 = config([0x03, 0x02, 0x01, 0x02, 0x01, 0x00]);
+
+        assert_eq!(
+            FixedBuffer::debug(&dualshock2).as_str(),
+            "ControllerConfiguration { status: [03, 02, 01, 02, 01, 00], \
+             const1a: [00, 01, 02, 00, 0a], const1b: [00, 01, 01, 01, 14], \
+             const2: [00, 02, 00, 01, 00], const3a: [00, 00, 04, 00, 00], \
+             const3b: [00, 00, 07, 00, 00] }");
+    }
+
+    #[test]
+    fn device_equality() {
+        let mut data = [0u8; MESSAGE_MAX_LENGTH];
+        data[..8].copy_from_slice(&[0xfe, 0xbf, 0x7f, 0x80, 0x00, 0xff, 0x10, 0x20]);
+        let first = data;
+        data[3] = 0x01;
+        let second = data;
+
+        let devices = |c: &[u8]| {
+            [
+                Device::Mouse(Mouse::from_frame(c).unwrap()),
+                Device::Classic(Classic::from_frame(c).unwrap()),
+                Device::AnalogJoystick(DualShock::from_frame(c).unwrap()),
+                Device::DualShock(DualShock::from_frame(c).unwrap()),
+                Device::DualShock2(DualShock2::from_frame(c).unwrap()),
+                Device::GuitarHero(GuitarHero::from_frame(c).unwrap()),
+                Device::JogCon(JogCon::from_frame(c).unwrap()),
+                Device::NegCon(NegCon::from_frame(c).unwrap()),
+                Device::GunCon(GunCon::from_frame(c).unwrap()),
+                Device::Baton(Baton::from_frame(c).unwrap()),
+            ]
+        };
+
+        let first = devices(&first);
+        let second = devices(&second);
+
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a, a);
+
+            // The Classic only looks at the buttons and the guitar doesn't
+            // use the changed byte, everything else should see the difference
+            match *a {
+                Device::Classic(_) | Device::GuitarHero(_) => assert_eq!(a, b),
+                _ => assert_ne!(a, b),
+            }
+        }
+
+        // Same bytes, different variant
+        assert_ne!(first[2], first[3]);
+        assert_eq!(Device::None, Device::None);
+        assert_ne!(Device::None, Device::Unknown);
     }
 }
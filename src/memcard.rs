@@ -0,0 +1,795 @@
+//! Memory Cards
+//! ============================
+//! Memory cards sit on the same bus as the controller, and answer when the
+//! first byte is 0x81 instead of a controller's 0x01. A card holds 1024
+//! frames of 128 bytes, read and written a frame at a time, each one checked
+//! with an XOR checksum.
+//!
+//! Every answer starts with the card's flag byte. The card sets
+//! `FLAG_FRESH` when it's plugged in or powered up, and only clears it after
+//! a write. The BIOS uses it to tell when its copy of the directory is out of
+//! date, and so does `MemoryCard`: reads and writes fail with
+//! `Error::CardChanged` until `acknowledge_insertion` is called. That goes for
+//! the first access after power up too, since there's no telling which card
+//! was there before.
+//!
+//! Behind a multi-tap there are four cards, one next to each controller,
+//! picked with the same `MultitapPort` as the controllers (see
+//! `PlayStationPort::multitap_memory_card`).
+//!
+//! Each save starts with a header frame (see `SaveHeader`) holding its title
+//! and its icon's palette, followed by one to three frames of icon bitmaps
+//! (see `Icon`).
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use protocol::{byte, field, MultitapPort, MESSAGE_MAX_LENGTH};
+use recovery::{FailFast, RecoveryPolicy};
+use super::{Error, PlayStationPort};
+
+/// Bytes in a frame
+pub const FRAME_SIZE: usize = 128;
+/// Frames on a card
+pub const FRAME_COUNT: u16 = 1024;
+/// The last frame of the directory block, which the BIOS keeps as a copy of
+/// the first and writes to when it wants the flag cleared
+pub const WRITE_TEST_FRAME: u16 = 63;
+
+/// Set in the flag byte from the card being plugged in until it's written
+pub const FLAG_FRESH: u8 = 0x08;
+/// Set in the flag byte when the last write failed
+pub const FLAG_WRITE_ERROR: u8 = 0x04;
+
+/// Address byte for a memory card, in place of a controller's 0x01. Behind a
+/// multi-tap, cards B to D follow on from it just like the controllers do.
+const MEMCARD_ADDRESS: u8 = 0x81;
+const CMD_READ: u8 = 0x52;
+const CMD_WRITE: u8 = 0x57;
+
+/// The two bytes a card answers every command with after its flag
+const CARD_ID: [u8; 2] = [0x5a, 0x5d];
+/// The two bytes a card acknowledges a frame number or frame of data with
+const CARD_ACK: [u8; 2] = [0x5c, 0x5d];
+/// The last byte of a read or write that went well
+const END_GOOD: u8 = 0x47;
+
+/// Address and command, card id, frame number, acknowledgement, confirmed
+/// frame number, data, then checksum and end byte
+const READ_LEN: usize = 10 + FRAME_SIZE + 2;
+/// Address and command, card id, frame number, data, checksum, then
+/// acknowledgement and end byte
+const WRITE_LEN: usize = 6 + FRAME_SIZE + 4;
+
+/// What a save's header frame starts with
+const SAVE_MAGIC: [u8; 2] = *b"SC";
+/// Where the title sits in a header frame, in Shift-JIS
+const TITLE_OFFSET: usize = 0x04;
+/// The longest a title can be, in bytes
+pub const TITLE_LEN: usize = 64;
+/// Where the icon's palette sits in a header frame
+const PALETTE_OFFSET: usize = 0x60;
+/// Width and height of a save's icon, in pixels
+pub const ICON_SIZE: usize = 16;
+
+/// Reasons a frame isn't a save header
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SaveHeaderError {
+    /// The frame doesn't start with "SC"
+    BadMagic([u8; 2]),
+    /// The icon flag isn't 0x11, 0x12 or 0x13, for one to three frames
+    BadIconFlag(u8),
+}
+
+/// The first frame of a save, with its title and icon palette
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SaveHeader {
+    title: [u8; TITLE_LEN],
+    icon_frames: u8,
+    palette: [u16; 16],
+}
+
+impl SaveHeader {
+    /// Read the header out of a save's first frame
+    pub fn parse(frame: &[u8; FRAME_SIZE]) -> Result<Self, SaveHeaderError> {
+        if frame[..2] != SAVE_MAGIC {
+            return Err(SaveHeaderError::BadMagic([frame[0], frame[1]]));
+        }
+
+        let icon_frames = match frame[2] {
+            0x11..=0x13 => frame[2] & 0x0f,
+            x => return Err(SaveHeaderError::BadIconFlag(x)),
+        };
+
+        let mut title = [0u8; TITLE_LEN];
+        title.copy_from_slice(&frame[TITLE_OFFSET..TITLE_OFFSET + TITLE_LEN]);
+
+        let mut palette = [0u16; 16];
+        let palette_bytes = frame.get(PALETTE_OFFSET..).unwrap_or_default();
+        for (entry, bytes) in palette.iter_mut().zip(palette_bytes.chunks_exact(2)) {
+            if let &[low, high] = bytes {
+                *entry = u16::from_le_bytes([low, high]);
+            }
+        }
+
+        Ok(SaveHeader { title, icon_frames, palette })
+    }
+
+    /// The title as it's stored, in Shift-JIS, up to its terminating zero
+    pub fn title_raw(&self) -> &[u8] {
+        self.title.split(|&x| x == 0).next().unwrap_or_default()
+    }
+
+    /// The title with its full-width letters, digits and punctuation turned
+    /// into ASCII, written into `out`. Anything else, like kana, comes out as
+    /// `?`. Games mostly write their titles in full-width Latin, so this is
+    /// usually readable.
+    pub fn title_ascii<'b>(&self, out: &'b mut [u8; TITLE_LEN]) -> &'b str {
+        let raw = self.title_raw();
+        let mut len = 0;
+        let mut i = 0;
+
+        while let Some(&byte) = raw.get(i) {
+            let (ascii, width) = match byte {
+                x @ 0x20..=0x7e => (x, 1),
+                // Lead bytes of two byte characters
+                hi @ 0x81..=0x9f | hi @ 0xe0..=0xef => {
+                    let ascii = raw.get(i + 1).and_then(|&lo| full_width_to_ascii(hi, lo));
+                    (ascii.unwrap_or(b'?'), 2)
+                },
+                _ => (b'?', 1),
+            };
+
+            // The title is never longer than `out`, and each letter takes at
+            // least a byte of it
+            if let Some(slot) = out.get_mut(len) {
+                *slot = ascii;
+                len += 1;
+            }
+            i += width;
+        }
+
+        // Only ever ASCII went in
+        core::str::from_utf8(out.get(..len).unwrap_or_default()).unwrap_or("")
+    }
+
+    /// How many frames of icon follow the header, from one to three. More
+    /// than one means the icon is animated.
+    pub fn icon_frames(&self) -> u8 {
+        self.icon_frames
+    }
+
+    /// The icon's 16 colors as the PlayStation stores them: five bits each of
+    /// red, green and blue from the bottom up, and the top bit for
+    /// semi-transparency. 0x0000 is see-through.
+    pub fn palette(&self) -> &[u16; 16] {
+        &self.palette
+    }
+
+    /// A palette entry as 8 bit red, green and blue. Only the low four bits
+    /// of `index` are used.
+    // Four bits can't index past the 16 entries
+    #[allow(clippy::indexing_slicing)]
+    pub fn color(&self, index: u8) -> [u8; 3] {
+        let entry = self.palette[usize::from(index & 0x0f)];
+        let channel = |shift: u16| {
+            let x = ((entry >> shift) & 0x1f) as u8;
+            (x << 3) | (x >> 2)
+        };
+
+        [channel(0), channel(5), channel(10)]
+    }
+}
+
+/// The ASCII for a full-width Shift-JIS character, if there is one
+fn full_width_to_ascii(hi: u8, lo: u8) -> Option<u8> {
+    match (hi, lo) {
+        (0x82, 0x4f..=0x58) => Some(b'0' + lo - 0x4f),
+        (0x82, 0x60..=0x79) => Some(b'A' + lo - 0x60),
+        (0x82, 0x81..=0x9a) => Some(b'a' + lo - 0x81),
+        (0x81, _) => {
+            let ascii = match lo {
+                0x40 => b' ',
+                0x43 => b',',
+                0x44 => b'.',
+                0x46 => b':',
+                0x47 => b';',
+                0x48 => b'?',
+                0x49 => b'!',
+                0x50 => b'^',
+                0x51 => b'_',
+                0x5b | 0x5c | 0x5d | 0x7c => b'-',
+                0x5e => b'/',
+                0x5f => b'\\',
+                0x60 => b'~',
+                0x62 => b'|',
+                0x65 | 0x66 => b'\'',
+                0x67 | 0x68 => b'"',
+                0x69 => b'(',
+                0x6a => b')',
+                0x6d => b'[',
+                0x6e => b']',
+                0x6f => b'{',
+                0x70 => b'}',
+                0x7b => b'+',
+                0x81 => b'=',
+                0x83 => b'<',
+                0x84 => b'>',
+                0x90 => b'$',
+                0x93 => b'%',
+                0x94 => b'#',
+                0x95 => b'&',
+                0x96 => b'*',
+                0x97 => b'@',
+                _ => return None,
+            };
+
+            Some(ascii)
+        },
+        _ => None,
+    }
+}
+
+/// One frame of a save's icon: 16 by 16 pixels, each a four bit index into
+/// `SaveHeader::palette`
+#[derive(Copy, Clone, Debug)]
+pub struct Icon<'a> {
+    frame: &'a [u8; FRAME_SIZE],
+}
+
+impl<'a> Icon<'a> {
+    /// View an icon frame, one of the `SaveHeader::icon_frames` frames that
+    /// follow the header
+    pub fn new(frame: &'a [u8; FRAME_SIZE]) -> Self {
+        Icon { frame }
+    }
+
+    /// The palette index of the pixel `x` across and `y` down from the top
+    /// left. Both wrap at 16.
+    // 256 pixels at two a byte fill exactly the frame's 128 bytes
+    #[allow(clippy::indexing_slicing)]
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let index = (y % ICON_SIZE) * ICON_SIZE + x % ICON_SIZE;
+        let byte = self.frame[index / 2];
+
+        // The left pixel of each pair is in the low nybble
+        if index & 1 == 0 { byte & 0x0f } else { byte >> 4 }
+    }
+}
+
+/// The flag byte a card answered with
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MemoryCardStatus {
+    /// The flag byte as it came in
+    pub flag: u8,
+}
+
+impl MemoryCardStatus {
+    /// Whether the card has been plugged in (or powered up) since it was last
+    /// written, see `MemoryCard::acknowledge_insertion`
+    pub fn fresh_insertion(&self) -> bool {
+        self.flag & FLAG_FRESH != 0
+    }
+
+    /// Whether the last write failed
+    pub fn write_failed(&self) -> bool {
+        self.flag & FLAG_WRITE_ERROR != 0
+    }
+}
+
+/// The XOR of the frame number and every data byte, which cards send after
+/// the data both ways
+fn checksum(frame: u16, data: &[u8]) -> u8 {
+    let [msb, lsb] = frame.to_be_bytes();
+    data.iter().fold(msb ^ lsb, |sum, &x| sum ^ x)
+}
+
+/// Check that `bytes[offset..]` starts with `expected`
+fn expect<E>(bytes: &[u8], offset: usize, expected: &[u8]) -> Result<(), Error<E>> {
+    for (i, &x) in expected.iter().enumerate() {
+        let byte = byte(bytes, offset + i)?;
+        if byte != x {
+            return Err(Error::Garbage { offset: (offset + i) as u8, byte });
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a card answered at all, rather than an empty slot or a
+/// controller
+fn check_card<E>(bytes: &[u8]) -> Result<(), Error<E>> {
+    if field(bytes, 2..4)? != CARD_ID {
+        return Err(Error::UnsupportedDevice);
+    }
+
+    Ok(())
+}
+
+/// The memory card in the same slot as a port's controller. Created by
+/// `PlayStationPort::memory_card`.
+pub struct MemoryCard<'a, SPI, CS, R = FailFast> {
+    port: &'a mut PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R>,
+    address: u8,
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, R> PlayStationPort<SPI, CS, MESSAGE_MAX_LENGTH, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Talk to the memory card next to the controller. This is the card with
+    /// no multi-tap, or in slot A of one, whatever `set_multitap_port` says.
+    pub fn memory_card(&mut self) -> MemoryCard<'_, SPI, CS, R> {
+        MemoryCard { port: self, address: MEMCARD_ADDRESS }
+    }
+
+    /// Talk to the memory card in one of a multi-tap's four slots. Slot A is
+    /// the same card as `memory_card`. `MultitapPort::M` and `X` aren't slots,
+    /// and give `None`.
+    pub fn multitap_memory_card(&mut self, slot: MultitapPort) -> Option<MemoryCard<'_, SPI, CS, R>> {
+        let offset = match slot {
+            MultitapPort::A => 0,
+            MultitapPort::B => 1,
+            MultitapPort::C => 2,
+            MultitapPort::D => 3,
+            MultitapPort::M | MultitapPort::X => return None,
+        };
+
+        Some(MemoryCard { port: self, address: MEMCARD_ADDRESS + offset })
+    }
+}
+
+#[allow(deprecated)]
+impl<'a, E, SPI, CS, R> MemoryCard<'a, SPI, CS, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Read the card's flag byte. This starts a read and lets go partway
+    /// through, which cards don't mind. An empty slot gives
+    /// `Error::UnsupportedDevice`.
+    pub fn status(&mut self) -> Result<MemoryCardStatus, Error<E>> {
+        let mut bytes = [self.address, CMD_READ, 0x00, 0x00];
+        self.port.exchange(&mut bytes)?;
+        check_card(&bytes)?;
+
+        Ok(MemoryCardStatus { flag: bytes[1] })
+    }
+
+    /// Read `frame` into `buffer`. Fails with `Error::CardChanged` if the
+    /// card was swapped since `acknowledge_insertion`, though `buffer` is
+    /// still filled in from the new card.
+    pub fn read_frame(&mut self, frame: u16, buffer: &mut [u8; FRAME_SIZE]) -> Result<(), Error<E>> {
+        let status = self.read_unchecked(frame, buffer)?;
+        if status.fresh_insertion() {
+            return Err(Error::CardChanged);
+        }
+
+        Ok(())
+    }
+
+    /// Write `data` to `frame`. The flag is checked first, and nothing is
+    /// written to a card that was swapped since `acknowledge_insertion`.
+    pub fn write_frame(&mut self, frame: u16, data: &[u8; FRAME_SIZE]) -> Result<(), Error<E>> {
+        if self.status()?.fresh_insertion() {
+            return Err(Error::CardChanged);
+        }
+
+        self.write_unchecked(frame, data).map(|_| ())
+    }
+
+    /// Accept whatever card is plugged in, clearing its flag so reads and
+    /// writes work again. Only a write clears it, so this does what the BIOS
+    /// does: reads the first frame and writes it over `WRITE_TEST_FRAME`,
+    /// which is kept as a copy of it. Re-read anything cached from the old
+    /// card, like its directory, afterwards.
+    pub fn acknowledge_insertion(&mut self) -> Result<(), Error<E>> {
+        let mut header = [0u8; FRAME_SIZE];
+        self.read_unchecked(0, &mut header)?;
+        self.write_unchecked(WRITE_TEST_FRAME, &header)?;
+
+        Ok(())
+    }
+
+    fn read_unchecked(&mut self, frame: u16, buffer: &mut [u8; FRAME_SIZE]) -> Result<MemoryCardStatus, Error<E>> {
+        let [msb, lsb] = frame.to_be_bytes();
+        let mut bytes = [0u8; READ_LEN];
+        bytes[..6].copy_from_slice(&[self.address, CMD_READ, 0x00, 0x00, msb, lsb]);
+
+        self.port.exchange(&mut bytes)?;
+        check_card(&bytes)?;
+        expect(&bytes, 6, &CARD_ACK)?;
+
+        // A frame past the end of the card comes back as 0xffff
+        expect(&bytes, 8, &[msb, lsb])?;
+
+        buffer.copy_from_slice(&bytes[10..10 + FRAME_SIZE]);
+        expect(&bytes, 10 + FRAME_SIZE, &[checksum(frame, buffer), END_GOOD])?;
+
+        Ok(MemoryCardStatus { flag: bytes[1] })
+    }
+
+    fn write_unchecked(&mut self, frame: u16, data: &[u8; FRAME_SIZE]) -> Result<MemoryCardStatus, Error<E>> {
+        let [msb, lsb] = frame.to_be_bytes();
+        let mut bytes = [0u8; WRITE_LEN];
+        bytes[..6].copy_from_slice(&[self.address, CMD_WRITE, 0x00, 0x00, msb, lsb]);
+        bytes[6..6 + FRAME_SIZE].copy_from_slice(data);
+        bytes[6 + FRAME_SIZE] = checksum(frame, data);
+
+        self.port.exchange(&mut bytes)?;
+        check_card(&bytes)?;
+
+        // The end byte is 0x4e for a bad checksum and 0xff for a frame past
+        // the end of the card
+        expect(&bytes, 7 + FRAME_SIZE, &[CARD_ACK[0], CARD_ACK[1], END_GOOD])?;
+
+        Ok(MemoryCardStatus { flag: bytes[1] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use core::convert::Infallible;
+    use mock::{self, MockPin};
+    use protocol::flip;
+
+    /// A memory card with its first 64 frames, enough for the directory
+    struct Card {
+        frames: [[u8; FRAME_SIZE]; 64],
+        flag: u8,
+        /// The address byte it answers to
+        address: u8,
+        /// Send every read back with a bad checksum
+        corrupt: bool,
+    }
+
+    impl Card {
+        fn new() -> Self {
+            let mut frames = [[0u8; FRAME_SIZE]; 64];
+            for (i, frame) in frames.iter_mut().enumerate() {
+                frame[0] = i as u8;
+                frame[FRAME_SIZE - 1] = 0xa0 | i as u8;
+            }
+
+            Card { frames, flag: FLAG_FRESH, address: MEMCARD_ADDRESS, corrupt: false }
+        }
+
+        fn answer(&mut self, command: &[u8], reply: &mut [u8]) {
+            if command[0] != self.address || command.len() < 4 {
+                return;
+            }
+
+            reply[1] = self.flag;
+            reply[2..4].copy_from_slice(&CARD_ID);
+            if command.len() < 6 {
+                return;
+            }
+
+            let frame = u16::from_be_bytes([command[4], command[5]]);
+            let stored = self.frames.get_mut(frame as usize);
+
+            match command[1] {
+                CMD_READ => {
+                    reply[6..8].copy_from_slice(&CARD_ACK);
+                    match stored {
+                        Some(data) => {
+                            reply[8..10].copy_from_slice(&command[4..6]);
+                            reply[10..10 + FRAME_SIZE].copy_from_slice(data);
+                            reply[10 + FRAME_SIZE] = checksum(frame, data) ^ self.corrupt as u8;
+                            reply[11 + FRAME_SIZE] = END_GOOD;
+                        },
+                        None => reply[8..10].copy_from_slice(&[0xff, 0xff]),
+                    }
+                },
+                CMD_WRITE => {
+                    let data = &command[6..6 + FRAME_SIZE];
+                    reply[7 + FRAME_SIZE..9 + FRAME_SIZE].copy_from_slice(&CARD_ACK);
+                    reply[9 + FRAME_SIZE] = match stored {
+                        None => 0xff,
+                        Some(_) if checksum(frame, data) != command[6 + FRAME_SIZE] => 0x4e,
+                        Some(stored) => {
+                            stored.copy_from_slice(data);
+                            self.flag &= !FLAG_FRESH;
+                            END_GOOD
+                        },
+                    };
+                },
+                _ => (),
+            }
+        }
+    }
+
+    impl spi::Transfer<u8> for Card {
+        type Error = Infallible;
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+            flip(words);
+            let mut reply = [0xffu8; READ_LEN];
+            self.answer(words, &mut reply[..words.len()]);
+            words.copy_from_slice(&reply[..words.len()]);
+            flip(words);
+
+            Ok(words)
+        }
+    }
+
+    fn port() -> PlayStationPort<Card, MockPin> {
+        PlayStationPort::new(Card::new(), None)
+    }
+
+    #[test]
+    fn fresh_insertion() {
+        let mut port = port();
+        let mut buffer = [0u8; FRAME_SIZE];
+
+        let status = port.memory_card().status().unwrap();
+        assert!(status.fresh_insertion());
+        assert!(!status.write_failed());
+
+        // Nothing goes through until the card is acknowledged, though reads
+        // still fill in the buffer
+        assert_eq!(port.memory_card().read_frame(5, &mut buffer), Err(Error::CardChanged));
+        assert_eq!(buffer[0], 5);
+        assert_eq!(port.memory_card().write_frame(5, &[0x11; FRAME_SIZE]), Err(Error::CardChanged));
+        assert_eq!(port.dev.frames[5][0], 5);
+
+        port.memory_card().acknowledge_insertion().unwrap();
+        assert!(!port.memory_card().status().unwrap().fresh_insertion());
+        assert_eq!(port.dev.frames[WRITE_TEST_FRAME as usize], port.dev.frames[0]);
+
+        port.memory_card().write_frame(5, &[0x11; FRAME_SIZE]).unwrap();
+        port.memory_card().read_frame(5, &mut buffer).unwrap();
+        assert_eq!(buffer, [0x11; FRAME_SIZE]);
+
+        // Someone swaps the card mid-session
+        port.dev = Card::new();
+        assert_eq!(port.memory_card().read_frame(5, &mut buffer), Err(Error::CardChanged));
+        assert_eq!(port.memory_card().write_frame(6, &[0x22; FRAME_SIZE]), Err(Error::CardChanged));
+        assert_eq!(port.dev.frames[6][0], 6);
+
+        port.memory_card().acknowledge_insertion().unwrap();
+        port.memory_card().read_frame(5, &mut buffer).unwrap();
+        assert_eq!((buffer[0], buffer[FRAME_SIZE - 1]), (5, 0xa5));
+    }
+
+    #[test]
+    fn broken_transfers() {
+        let mut port = port();
+        let mut buffer = [0u8; FRAME_SIZE];
+        port.memory_card().acknowledge_insertion().unwrap();
+
+        // Past the end of what this card holds
+        assert_eq!(port.memory_card().read_frame(64, &mut buffer), Err(Error::Garbage { offset: 8, byte: 0xff }));
+        let end = (9 + FRAME_SIZE) as u8;
+        assert_eq!(port.memory_card().write_frame(64, &buffer), Err(Error::Garbage { offset: end, byte: 0xff }));
+
+        port.dev.corrupt = true;
+        let sum = checksum(3, &port.dev.frames[3]) ^ 1;
+        assert_eq!(port.memory_card().read_frame(3, &mut buffer), Err(Error::Garbage { offset: (10 + FRAME_SIZE) as u8, byte: sum }));
+
+        // Nothing in the slot
+        let mut empty = mock::port(&[]);
+        assert_eq!(empty.memory_card().status(), Err(Error::UnsupportedDevice));
+        assert_eq!(empty.memory_card().read_frame(0, &mut buffer), Err(Error::UnsupportedDevice));
+    }
+
+    /// A header frame laid out the way the BIOS writes them, for a save
+    /// titled "ＲＡＣＥ　２　ｐｔ．１　レース" with a two frame icon
+    fn header() -> [u8; FRAME_SIZE] {
+        let mut frame = [0u8; FRAME_SIZE];
+        frame[..4].copy_from_slice(&[b'S', b'C', 0x12, 0x01]);
+
+        let title = [
+            0x82, 0x71, 0x82, 0x60, 0x82, 0x62, 0x82, 0x64, 0x81, 0x40, 0x82, 0x51,
+            0x81, 0x40, 0x82, 0x90, 0x82, 0x94, 0x81, 0x44, 0x82, 0x50, 0x81, 0x40,
+            0x83, 0x8c, 0x81, 0x5b, 0x83, 0x58,
+        ];
+        frame[TITLE_OFFSET..TITLE_OFFSET + title.len()].copy_from_slice(&title);
+
+        // See-through, then pure red, green and blue, then white with
+        // semi-transparency
+        let palette: [u16; 5] = [0x0000, 0x001f, 0x03e0, 0x7c00, 0xffff];
+        for (i, entry) in palette.iter().enumerate() {
+            frame[PALETTE_OFFSET + i * 2..PALETTE_OFFSET + i * 2 + 2].copy_from_slice(&entry.to_le_bytes());
+        }
+
+        frame
+    }
+
+    #[test]
+    fn observer_sees_reads() {
+        use core::cell::RefCell;
+        use TransferDirection;
+        use mock::MockSpi;
+
+        std::thread_local! {
+            static SEEN: RefCell<Vec<(TransferDirection, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        fn record(direction: TransferDirection, bytes: &[u8]) {
+            SEEN.with(|x| x.borrow_mut().push((direction, bytes.to_vec())));
+        }
+
+        let mut command = [0u8; READ_LEN];
+        command[..6].copy_from_slice(&[MEMCARD_ADDRESS, CMD_READ, 0x00, 0x00, 0x00, 0x05]);
+        let mut reply = [0xffu8; READ_LEN];
+        let mut card = Card::new();
+        card.flag = 0;
+        card.answer(&command, &mut reply);
+
+        // Far longer than the port's frames, so it goes out in pieces with
+        // select held throughout
+        let pieces: Vec<&[u8]> = reply.chunks(MESSAGE_MAX_LENGTH).collect();
+        let mut port = PlayStationPort::builder(MockSpi::new(&pieces), Some(MockPin::default()))
+            .observer(record)
+            .build();
+
+        let mut buffer = [0u8; FRAME_SIZE];
+        port.memory_card().read_frame(5, &mut buffer).unwrap();
+        assert_eq!(buffer, card.frames[5]);
+        assert_eq!(port.select.as_ref().unwrap().levels, [true, false, true]);
+
+        let seen = SEEN.with(|x| x.replace(Vec::new()));
+        assert_eq!(seen.len(), pieces.len() * 2);
+
+        let (mut sent, mut received) = (Vec::new(), Vec::new());
+        for pair in seen.chunks(2) {
+            assert_eq!((pair[0].0, pair[1].0), (TransferDirection::Sent, TransferDirection::Received));
+            sent.extend_from_slice(&pair[0].1);
+            received.extend_from_slice(&pair[1].1);
+        }
+        assert_eq!(sent, command);
+        assert_eq!(received, reply);
+    }
+
+    #[test]
+    fn save_header() {
+        let parsed = SaveHeader::parse(&header()).unwrap();
+        assert_eq!(parsed.icon_frames(), 2);
+        assert_eq!(parsed.title_raw().len(), 30);
+        assert_eq!(&parsed.title_raw()[..4], &[0x82, 0x71, 0x82, 0x60]);
+
+        let mut title = [0u8; TITLE_LEN];
+        assert_eq!(parsed.title_ascii(&mut title), "RACE 2 pt.1 ?-?");
+
+        assert_eq!(parsed.palette()[..5], [0x0000, 0x001f, 0x03e0, 0x7c00, 0xffff]);
+        assert_eq!(parsed.color(0), [0, 0, 0]);
+        assert_eq!(parsed.color(1), [0xff, 0, 0]);
+        assert_eq!(parsed.color(2), [0, 0xff, 0]);
+        assert_eq!(parsed.color(3), [0, 0, 0xff]);
+        assert_eq!(parsed.color(0x14), [0xff, 0xff, 0xff]);
+
+        let mut bad = header();
+        bad[1] = b'D';
+        assert_eq!(SaveHeader::parse(&bad), Err(SaveHeaderError::BadMagic(*b"SD")));
+        assert_eq!(SaveHeader::parse(&[0; FRAME_SIZE]), Err(SaveHeaderError::BadMagic([0, 0])));
+
+        let mut bad = header();
+        bad[2] = 0x14;
+        assert_eq!(SaveHeader::parse(&bad), Err(SaveHeaderError::BadIconFlag(0x14)));
+    }
+
+    #[test]
+    fn titles() {
+        let title = |raw: &[u8]| {
+            let mut frame = header();
+            frame[TITLE_OFFSET..TITLE_OFFSET + TITLE_LEN].copy_from_slice(&[0; TITLE_LEN]);
+            frame[TITLE_OFFSET..TITLE_OFFSET + raw.len()].copy_from_slice(raw);
+
+            let mut out = [0u8; TITLE_LEN];
+            let header = SaveHeader::parse(&frame).unwrap();
+            let len = header.title_ascii(&mut out).len();
+            (out, len)
+        };
+
+        // Plain ASCII goes straight through, half-width kana don't, and a
+        // lead byte cut off by the end of the title is still one character
+        let (out, len) = title(b"Ab 9\xb1\x82");
+        assert_eq!(&out[..len], b"Ab 9??");
+
+        // A title with no room for its terminator
+        let (_, len) = title(&[b'x'; TITLE_LEN]);
+        assert_eq!(len, TITLE_LEN);
+
+        let (out, len) = title(&[0x82, 0x81, 0x82, 0x9a, 0x82, 0x4f, 0x82, 0x58, 0x81, 0x97, 0x88, 0x9f]);
+        assert_eq!(&out[..len], b"az09@?");
+    }
+
+    #[test]
+    fn icons() {
+        let mut frame = [0u8; FRAME_SIZE];
+        frame[0] = 0x21;
+        frame[7] = 0xf0;
+        frame[FRAME_SIZE - 1] = 0x30;
+        let icon = Icon::new(&frame);
+
+        assert_eq!((icon.pixel(0, 0), icon.pixel(1, 0)), (1, 2));
+        assert_eq!((icon.pixel(14, 0), icon.pixel(15, 0)), (0, 0xf));
+        assert_eq!(icon.pixel(15, 15), 3);
+        assert_eq!(icon.pixel(16, 16), 1);
+    }
+
+    #[test]
+    fn multitap_slots() {
+        // An empty port answers nothing, but everything sent is kept
+        let sent = |slot: Option<MultitapPort>| {
+            let mut port = mock::port(&[]);
+            port.set_multitap_port(MultitapPort::C);
+            let mut buffer = [0u8; FRAME_SIZE];
+
+            let mut card = match slot {
+                Some(slot) => port.multitap_memory_card(slot).unwrap(),
+                None => port.memory_card(),
+            };
+            assert_eq!(card.status(), Err(Error::UnsupportedDevice));
+            assert_eq!(card.read_frame(0x0123, &mut buffer), Err(Error::UnsupportedDevice));
+            assert_eq!(card.write_frame(0x0123, &[0x44; FRAME_SIZE]), Err(Error::UnsupportedDevice));
+
+            port.dev.sent
+        };
+
+        let plain = sent(None);
+        assert_eq!(plain.len(), 3);
+        assert_eq!(plain[0], [0x81, 0x52, 0x00, 0x00]);
+        assert_eq!(plain[1][..6], [0x81, 0x52, 0x00, 0x00, 0x01, 0x23]);
+        assert_eq!(plain[1].len(), READ_LEN);
+        assert_eq!(plain[2][..4], [0x81, 0x52, 0x00, 0x00]);
+
+        // Slot A is the same card, byte for byte
+        assert_eq!(sent(Some(MultitapPort::A)), plain);
+
+        for (slot, address) in [(MultitapPort::B, 0x82), (MultitapPort::C, 0x83), (MultitapPort::D, 0x84)].iter() {
+            let frames = sent(Some(*slot));
+            assert_eq!(frames.len(), plain.len());
+            for (frame, plain) in frames.iter().zip(plain.iter()) {
+                assert_eq!(frame[0], *address);
+                assert_eq!(frame[1..], plain[1..]);
+            }
+        }
+
+        // Each card only answers in its own slot
+        let slots = [MultitapPort::A, MultitapPort::B, MultitapPort::C, MultitapPort::D];
+        for (i, &slot) in slots.iter().enumerate() {
+            let mut port = port();
+            port.dev.address = 0x81 + i as u8;
+
+            let mut card = port.multitap_memory_card(slot).unwrap();
+            card.acknowledge_insertion().unwrap();
+            card.write_frame(5, &[i as u8; FRAME_SIZE]).unwrap();
+            assert_eq!(port.dev.frames[5], [i as u8; FRAME_SIZE]);
+
+            for &other in slots.iter().filter(|&&x| x != slot) {
+                let mut card = port.multitap_memory_card(other).unwrap();
+                assert_eq!(card.write_frame(5, &[0xee; FRAME_SIZE]), Err(Error::UnsupportedDevice));
+            }
+        }
+
+        let mut port = mock::port(&[]);
+        assert!(port.multitap_memory_card(MultitapPort::M).is_none());
+        assert!(port.multitap_memory_card(MultitapPort::X).is_none());
+    }
+
+    #[test]
+    fn short_answers_are_errors() {
+        assert_eq!(check_card::<()>(&[0xff, 0x08, 0x5a]), Err(Error::BufferTooSmall { needed: 4 }));
+        assert_eq!(expect::<()>(&[0x5c], 0, &CARD_ACK), Err(Error::BufferTooSmall { needed: 2 }));
+        assert_eq!(expect::<()>(&[0x5c, 0x5d], 0, &CARD_ACK), Ok(()));
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(checksum(0x0102, &[]), 0x03);
+        assert_eq!(checksum(0x0000, &[0xff, 0x0f]), 0xf0);
+    }
+}
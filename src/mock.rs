@@ -0,0 +1,190 @@
+//! Test doubles for the SPI bus, the select pin and friends
+//! ============================
+//! The mock bus answers each transfer with the next scripted frame. Frames are
+//! written the way they show up in the protocol notes (most significant bit
+//! first) and the mock does the flipping, so tests read like bus captures.
+
+extern crate std;
+
+use self::std::collections::VecDeque;
+use self::std::vec::Vec;
+
+use core::fmt;
+use core::str;
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use super::PlayStationPort;
+
+/// An SPI bus with a scripted controller on the other end
+#[derive(Default)]
+pub struct MockSpi {
+    /// Frames to answer with, one per transfer. Once these run out the bus
+    /// reads as though nothing is plugged in.
+    pub responses: VecDeque<Vec<u8>>,
+    /// Everything the port sent, one entry per transfer
+    pub sent: Vec<Vec<u8>>,
+    /// The same, but exactly as it was clocked out, before flipping back
+    pub wire: Vec<Vec<u8>>,
+    /// Fail every transfer with an SPI error while set, without using up a
+    /// response
+    pub fail: bool,
+    /// Cut each transfer short where its response runs out while set, the
+    /// way an SPI device watching the ACK line would
+    pub stall: bool,
+}
+
+impl MockSpi {
+    /// Create a bus that answers with the given frames in order
+    pub fn new(responses: &[&[u8]]) -> Self {
+        Self {
+            responses: responses.iter().map(|x| x.to_vec()).collect(),
+            sent: Vec::new(),
+            wire: Vec::new(),
+            fail: false,
+            stall: false,
+        }
+    }
+}
+
+impl spi::Transfer<u8> for MockSpi {
+    type Error = ();
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+        if self.fail {
+            return Err(());
+        }
+
+        self.wire.push(words.to_vec());
+        self.sent.push(words.iter().map(|x| x.reverse_bits()).collect());
+
+        let response = self.responses.pop_front().unwrap_or_default();
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = response.get(i).cloned().unwrap_or(0xff).reverse_bits();
+        }
+
+        if self.stall {
+            let completed = response.len().min(words.len());
+            return Ok(&words[..completed]);
+        }
+
+        Ok(words)
+    }
+}
+
+/// A select pin that remembers what it's been set to
+#[derive(Default)]
+pub struct MockPin {
+    /// Whether the pin is currently high
+    pub high: bool,
+    /// Every level it's been set to, in order, repeats included
+    pub levels: Vec<bool>,
+}
+
+#[allow(deprecated)]
+impl OutputPin for MockPin {
+    fn set_low(&mut self) {
+        self.high = false;
+        self.levels.push(false);
+    }
+
+    fn set_high(&mut self) {
+        self.high = true;
+        self.levels.push(true);
+    }
+}
+
+/// Peeking at the critical section std stands in with for the tests
+#[cfg(feature = "critical-section")]
+pub mod critical {
+    use critical_section::RawRestoreState;
+
+    // `set_impl!` defines these, and they're the only way to see whether a
+    // critical section is being re-entered
+    extern "Rust" {
+        fn _critical_section_1_0_acquire() -> RawRestoreState;
+        fn _critical_section_1_0_release(restore_state: RawRestoreState);
+    }
+
+    /// Whether this thread is inside a critical section right now
+    pub fn held() -> bool {
+        // The std implementation nests, answering `true` when the thread
+        // already holds it
+        unsafe {
+            let nested = _critical_section_1_0_acquire();
+            _critical_section_1_0_release(nested);
+            nested
+        }
+    }
+}
+
+/// Create a port talking to a scripted controller
+pub fn port(responses: &[&[u8]]) -> PlayStationPort<MockSpi, MockPin> {
+    PlayStationPort::new(MockSpi::new(responses), None)
+}
+
+/// Somewhere to format text without an allocator, like firmware would
+pub struct FixedBuffer {
+    data: [u8; 512],
+    len: usize,
+}
+
+impl FixedBuffer {
+    /// Format a value's `Debug` output into a new buffer
+    pub fn debug<T: fmt::Debug>(value: &T) -> Self {
+        let mut buffer = FixedBuffer { data: [0; 512], len: 0 };
+        fmt::write(&mut buffer, format_args!("{:?}", value)).unwrap();
+        buffer
+    }
+
+    /// Format a value's `Display` output into a new buffer
+    pub fn display<T: fmt::Display>(value: &T) -> Self {
+        let mut buffer = FixedBuffer { data: [0; 512], len: 0 };
+        fmt::write(&mut buffer, format_args!("{}", value)).unwrap();
+        buffer
+    }
+
+    /// Format a value's `uDebug` output into a new buffer
+    #[cfg(feature = "ufmt")]
+    pub fn udebug<T: ufmt::uDebug>(value: &T) -> Self {
+        let mut buffer = FixedBuffer { data: [0; 512], len: 0 };
+        ufmt::uwrite!(buffer, "{:?}", value).unwrap();
+        buffer
+    }
+
+    /// Format a value's `uDisplay` output into a new buffer
+    #[cfg(feature = "ufmt")]
+    pub fn udisplay<T: ufmt::uDisplay>(value: &T) -> Self {
+        let mut buffer = FixedBuffer { data: [0; 512], len: 0 };
+        ufmt::uwrite!(buffer, "{}", value).unwrap();
+        buffer
+    }
+
+    /// What's been written so far
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(&self.data[..self.len]).unwrap()
+    }
+}
+
+impl fmt::Write for FixedBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.data.len() {
+            return Err(fmt::Error);
+        }
+
+        self.data[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl ufmt::uWrite for FixedBuffer {
+    type Error = fmt::Error;
+
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        fmt::Write::write_str(self, s)
+    }
+}
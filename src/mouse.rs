@@ -4,9 +4,15 @@
 //! for the PlayStation. This was implemented from notes online and while it
 //! should be accurate, it has not been tested.
 
+use protocol::{fill, leading};
+
 /// The two buttons found on the mouse
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct MouseButtons {
     data: u16
 }
@@ -27,7 +33,10 @@ impl MouseButtons {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the classic Controller
 pub struct Mouse {
     /// Standard buttons
@@ -37,3 +46,175 @@ pub struct Mouse {
     /// Difference in X-Axis since last poll
     pub x: i8,
 }
+
+impl Mouse {
+    /// Read a mouse from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        let &[low, high, y, x] = leading(frame)?;
+
+        Some(Self {
+            buttons: MouseButtons { data: u16::from_le_bytes([low, high]) },
+            y: y as i8,
+            x: x as i8,
+        })
+    }
+
+    /// Write a mouse back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.data.to_le_bytes();
+        fill(frame, &[low, high, self.y as u8, self.x as u8]);
+    }
+}
+
+/// Sensitivity that moves the cursor a pixel for every count the mouse reports
+pub const SENSITIVITY_ONE: u16 = 0x100;
+
+/// Turns the mouse's movement since each poll into a cursor on a screen.
+///
+/// Movement is scaled by the sensitivity, in 256ths so `SENSITIVITY_ONE` is
+/// one pixel a count, and kept to a fraction of a pixel, so slow movement at a
+/// low sensitivity still adds up. The mouse reports at most 127 counts each
+/// way per poll and a fast swipe is spread over several, so `update` needs
+/// calling on every poll to keep up. The cursor stops at the screen's edges,
+/// and y grows downwards the same as the mouse reports it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MouseCursor {
+    /// Position in 256ths of a pixel, as (x, y)
+    position: (i32, i32),
+    /// Furthest the cursor can go in 256ths of a pixel, as (x, y)
+    limit: (i32, i32),
+    /// 256ths of a pixel per count
+    sensitivity: u16,
+}
+
+impl MouseCursor {
+    /// Create a cursor in the middle of a screen `width` by `height` pixels,
+    /// moving `sensitivity` 256ths of a pixel for each count
+    pub const fn new(width: u16, height: u16, sensitivity: u16) -> Self {
+        let limit = (Self::edge(width), Self::edge(height));
+
+        Self {
+            position: ((limit.0 >> 9) << 8, (limit.1 >> 9) << 8),
+            limit,
+            sensitivity,
+        }
+    }
+
+    /// The last pixel across `size`, in 256ths. An empty screen still has
+    /// somewhere to put the cursor.
+    const fn edge(size: u16) -> i32 {
+        if size == 0 { 0 } else { (size as i32 - 1) << 8 }
+    }
+
+    /// Move by however far `mouse` moved since the last poll
+    pub fn update(&mut self, mouse: &Mouse) {
+        let step = |position: i32, delta: i8, limit: i32| {
+            let moved = position + i32::from(delta) * i32::from(self.sensitivity);
+
+            // Whatever fraction was left over doesn't carry past an edge
+            if moved < 0 {
+                0
+            } else if moved > limit {
+                limit
+            } else {
+                moved
+            }
+        };
+
+        self.position = (
+            step(self.position.0, mouse.x, self.limit.0),
+            step(self.position.1, mouse.y, self.limit.1),
+        );
+    }
+
+    /// Where the cursor is, as (x, y) in pixels
+    pub fn position(&self) -> (u16, u16) {
+        ((self.position.0 >> 8) as u16, (self.position.1 >> 8) as u16)
+    }
+
+    /// Move the cursor to (x, y), stopping at the edges of the screen
+    pub fn set_position(&mut self, x: u16, y: u16) {
+        let place = |pixel: u16, limit: i32| (i32::from(pixel) << 8).min(limit);
+        self.position = (place(x, self.limit.0), place(y, self.limit.1));
+    }
+
+    /// 256ths of a pixel the cursor moves for each count
+    pub fn sensitivity(&self) -> u16 {
+        self.sensitivity
+    }
+
+    /// Change how far the cursor moves for each count, in 256ths of a pixel
+    pub fn set_sensitivity(&mut self, sensitivity: u16) {
+        self.sensitivity = sensitivity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mouse(x: i8, y: i8) -> Mouse {
+        Mouse { buttons: MouseButtons { data: 0xffff }, y, x }
+    }
+
+    #[test]
+    fn cursor_accumulates_movement() {
+        let mut cursor = MouseCursor::new(640, 480, SENSITIVITY_ONE);
+        assert_eq!(cursor.position(), (319, 239));
+
+        cursor.update(&mouse(10, -20));
+        cursor.update(&mouse(-3, 5));
+        assert_eq!(cursor.position(), (326, 224));
+
+        // A swipe faster than the mouse can report in one poll, spread over
+        // several at the most it can report
+        cursor.set_position(0, 0);
+        for _ in 0..4 {
+            cursor.update(&mouse(127, 0));
+        }
+        cursor.update(&mouse(-128, 0));
+        assert_eq!(cursor.position(), (380, 0));
+    }
+
+    #[test]
+    fn cursor_stops_at_the_edges() {
+        let mut cursor = MouseCursor::new(320, 240, SENSITIVITY_ONE);
+        for _ in 0..10 {
+            cursor.update(&mouse(-128, 127));
+        }
+        assert_eq!(cursor.position(), (0, 239));
+
+        // Straight back off the edge, with nothing built up past it
+        cursor.update(&mouse(5, -5));
+        assert_eq!(cursor.position(), (5, 234));
+
+        cursor.set_position(1000, 1000);
+        assert_eq!(cursor.position(), (319, 239));
+
+        let mut cursor = MouseCursor::new(0, 0, SENSITIVITY_ONE);
+        cursor.update(&mouse(100, 100));
+        assert_eq!(cursor.position(), (0, 0));
+    }
+
+    #[test]
+    fn cursor_scales_by_sensitivity() {
+        let mut cursor = MouseCursor::new(640, 480, SENSITIVITY_ONE * 2);
+        cursor.set_position(100, 100);
+        cursor.update(&mouse(10, -10));
+        assert_eq!(cursor.position(), (120, 80));
+
+        // A quarter pixel a count, with the fractions adding up across polls
+        cursor.set_sensitivity(SENSITIVITY_ONE / 4);
+        cursor.set_position(100, 100);
+        for _ in 0..3 {
+            cursor.update(&mouse(1, -1));
+        }
+        assert_eq!(cursor.position(), (100, 99));
+        cursor.update(&mouse(1, -1));
+        assert_eq!(cursor.position(), (101, 99));
+        assert_eq!(cursor.sensitivity(), 0x40);
+    }
+}
@@ -10,9 +10,15 @@
 //! compatibility mode. It will work this way if the 'mode' button is held when the
 //! controller is powered on or plugged in.
 
+use protocol::{fill, leading};
+
 /// The digital buttons of the Namco NegCon
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct NegconButtons {
     data: u16,
 }
@@ -86,7 +92,10 @@ impl NegconButtons {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the Namco NegCon controller
 pub struct NegCon {
     /// The NegCon's weird buttons (A, B, R, etc)
@@ -105,3 +114,25 @@ pub struct NegCon {
     pub switchl: u8
 }
 
+impl NegCon {
+    /// Read a NegCon from the bytes that follow the frame's header. Returns
+    /// `None` if there aren't enough bytes for one.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        let &[low, high, twist, switchi, switchii, switchl] = leading(frame)?;
+
+        Some(Self {
+            buttons: NegconButtons { data: u16::from_le_bytes([low, high]) },
+            twist,
+            switchi,
+            switchii,
+            switchl,
+        })
+    }
+
+    /// Write a NegCon back the way `from_frame` reads it, as much as fits
+    pub(crate) fn write_frame(&self, frame: &mut [u8]) {
+        let [low, high] = self.buttons.data.to_le_bytes();
+        fill(frame, &[low, high, self.twist, self.switchi, self.switchii, self.switchl]);
+    }
+}
+
@@ -0,0 +1,107 @@
+//! Button Pressure Helpers
+//! ============================
+//! The DualShock 2 reports how hard each button is pressed, but the readings
+//! are noisy. A finger resting on a button without pushing it reads anywhere
+//! from 5 to 15, and the top of the range depends on how worn the button is.
+//! `PressureCurve` cleans that up so the values can be used directly.
+
+/// Maps raw pressure readings onto the full 0 to 255 range, ignoring anything
+/// at or below `floor` and treating anything at or above `ceiling` as a full
+/// press
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PressureCurve {
+    /// Readings at or below this count as not pressed
+    pub floor: u8,
+    /// Readings at or above this count as fully pressed
+    pub ceiling: u8,
+}
+
+impl PressureCurve {
+    /// Create a curve with the given floor and ceiling
+    pub const fn new(floor: u8, ceiling: u8) -> Self {
+        Self { floor, ceiling }
+    }
+
+    /// Scale a raw reading so 0 is let go and 255 is pressed all the way
+    pub fn normalize(&self, raw: u8) -> u8 {
+        if raw <= self.floor {
+            return 0;
+        }
+        if raw >= self.ceiling {
+            return 0xff;
+        }
+
+        let span = u32::from(self.ceiling) - u32::from(self.floor);
+
+        ((u32::from(raw) - u32::from(self.floor)) * 0xff / span) as u8
+    }
+
+    /// Whether a raw reading counts as a press, once normalized, at the given
+    /// threshold. Nothing below the floor ever counts, even with a threshold
+    /// of zero.
+    pub fn is_effectively_pressed(&self, raw: u8, threshold: u8) -> bool {
+        let value = self.normalize(raw);
+
+        value > 0 && value >= threshold
+    }
+}
+
+/// Ignores resting fingers (up to 15 or so) and gives a little room at the top
+/// for buttons that have worn enough not to reach 255
+impl Default for PressureCurve {
+    fn default() -> Self {
+        Self::new(0x10, 0xf0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floor_and_ceiling() {
+        let curve = PressureCurve::new(0x10, 0xf0);
+
+        assert_eq!(curve.normalize(0x00), 0);
+        assert_eq!(curve.normalize(0x10), 0);
+        assert_eq!(curve.normalize(0x11), 1);
+        assert_eq!(curve.normalize(0xef), 0xfd);
+        assert_eq!(curve.normalize(0xf0), 0xff);
+        assert_eq!(curve.normalize(0xff), 0xff);
+    }
+
+    #[test]
+    fn curve_is_monotonic() {
+        for &curve in [PressureCurve::default(), PressureCurve::new(0, 0xff), PressureCurve::new(0x80, 0x81)].iter() {
+            let mut last = 0;
+            for raw in 0..=0xff {
+                let value = curve.normalize(raw);
+                assert!(value >= last);
+                last = value;
+            }
+            assert_eq!(last, 0xff);
+        }
+    }
+
+    #[test]
+    fn resting_fingers_are_ignored() {
+        let curve = PressureCurve::default();
+
+        for raw in 5..=15 {
+            assert_eq!(curve.normalize(raw), 0);
+            assert!(!curve.is_effectively_pressed(raw, 0));
+        }
+    }
+
+    #[test]
+    fn thresholds() {
+        let curve = PressureCurve::default();
+
+        assert!(!curve.is_effectively_pressed(0x40, 0x80));
+        assert!(curve.is_effectively_pressed(0x90, 0x80));
+        assert!(curve.is_effectively_pressed(0x11, 0));
+        assert!(curve.is_effectively_pressed(0xff, 0xff));
+    }
+}
@@ -0,0 +1,458 @@
+//! Probing
+//! ============================
+//! The id a controller polls with says what shape of frame it's sending, not
+//! what the controller is. A DualShock 2 looks just like a DualShock until
+//! pressures are turned on, and a Guitar Hero controller polls exactly like a
+//! DualShock the whole time.
+//!
+//! `PlayStationPort::probe` asks the controller about itself in escape mode
+//! and looks the answers up in a table of controllers we've seen. Controllers
+//! without an escape mode (the original controller, the mouse and most of
+//! the Namco ones) are identified by their id alone.
+//!
+//! `ControllerKind` is also how to talk about what's plugged in without
+//! holding on to its input, see `Device::kind` and `PlayStationPort::kind`.
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use protocol::*;
+use recovery::RecoveryPolicy;
+use super::{ControllerConfiguration, Device, Error, PlayStationPort};
+
+/// What kind of controller is plugged in, without any of its input
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ControllerKind {
+    /// Nothing is plugged in
+    None,
+    /// PlayStation mouse
+    Mouse,
+    /// Original controller
+    Classic,
+    /// Sony's analog flight stick
+    AnalogJoystick,
+    /// A DualShock or DualShock 2 in digital mode, which can't be told apart
+    /// by their polls
+    DualShockDigital,
+    /// DualShock
+    DualShock,
+    /// DualShock 2
+    DualShock2,
+    /// Guitar Hero controller
+    GuitarHero,
+    /// Namco JogCon
+    JogCon,
+    /// Namco NegCon
+    NegCon,
+    /// Namco GunCon
+    GunCon,
+    /// The Mad Maestro Baton
+    Baton,
+    /// Something that polled with an id we don't know
+    Unknown(u8),
+}
+
+impl ControllerKind {
+    /// The best guess from an id alone
+    pub fn from_id(id: DeviceId) -> Self {
+        match id {
+            DeviceId::NotPresent => ControllerKind::None,
+            DeviceId::Mouse => ControllerKind::Mouse,
+            DeviceId::Classic => ControllerKind::Classic,
+            DeviceId::AnalogJoystick => ControllerKind::AnalogJoystick,
+            DeviceId::DualShockDigital => ControllerKind::DualShockDigital,
+            DeviceId::DualShockAnalog => ControllerKind::DualShock,
+            DeviceId::DualShockPressure => ControllerKind::DualShock2,
+            DeviceId::JogCon => ControllerKind::JogCon,
+            DeviceId::NegCon => ControllerKind::NegCon,
+            DeviceId::GunCon => ControllerKind::GunCon,
+            DeviceId::Configuration | DeviceId::Other(_) => ControllerKind::Unknown(id.to_byte()),
+        }
+    }
+
+    /// Whether it has analog sticks, whether or not they're turned on. The
+    /// NegCon's twist and the Guitar Hero's whammy bar don't count.
+    pub fn has_analog_sticks(&self) -> bool {
+        matches!(self, ControllerKind::AnalogJoystick | ControllerKind::DualShockDigital |
+            ControllerKind::DualShock | ControllerKind::DualShock2)
+    }
+
+    /// Whether it can report how hard its buttons are pressed
+    pub fn has_pressure(&self) -> bool {
+        *self == ControllerKind::DualShock2
+    }
+
+    /// Whether anything is plugged in at all
+    pub fn is_present(&self) -> bool {
+        *self != ControllerKind::None
+    }
+}
+
+impl Device {
+    /// What kind of controller this came from. A DualShock in digital mode
+    /// polls just like the original controller, so it's `Classic` here, and
+    /// `Device::Unknown` doesn't keep its id, so it's `Unknown(0)`. `probe`
+    /// can tell more.
+    pub fn kind(&self) -> ControllerKind {
+        match self {
+            Device::None => ControllerKind::None,
+            Device::Unknown => ControllerKind::Unknown(0),
+            Device::ConfigurationMode => ControllerKind::Unknown(CONTROLLER_CONFIGURATION),
+            Device::Mouse(_) => ControllerKind::Mouse,
+            Device::Classic(_) => ControllerKind::Classic,
+            Device::AnalogJoystick(_) => ControllerKind::AnalogJoystick,
+            Device::DualShock(_) => ControllerKind::DualShock,
+            Device::DualShock2(_) => ControllerKind::DualShock2,
+            Device::GuitarHero(_) => ControllerKind::GuitarHero,
+            Device::JogCon(_) => ControllerKind::JogCon,
+            Device::NegCon(_) => ControllerKind::NegCon,
+            Device::GunCon(_) => ControllerKind::GunCon,
+            Device::Baton(_) => ControllerKind::Baton,
+        }
+    }
+}
+
+/// How sure `probe` is of what it found
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Confidence {
+    /// The controller's answers in escape mode matched one we know, or
+    /// nothing answered at all
+    Exact,
+    /// Going by the id it polls with
+    Guessed,
+    /// Neither its id nor its answers are anything we know
+    Unknown,
+}
+
+/// Which DualShock a controller really is, whatever mode it's in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DualShockHardware {
+    /// The original DualShock, which can't send pressures
+    Ds1,
+    /// A DualShock 2, which sends pressures once `enable_pressure` asks
+    Ds2,
+    /// Not a Sony DualShock, or one that answers with something we haven't
+    /// seen
+    Indeterminate,
+}
+
+/// What `probe` found
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// The kind of controller
+    pub kind: ControllerKind,
+    /// How `kind` was worked out
+    pub confidence: Confidence,
+    /// What the controller answered in escape mode, or `None` if it doesn't
+    /// have one
+    pub config: Option<ControllerConfiguration>,
+}
+
+impl Probe {
+    /// Which DualShock answered, see `ControllerConfiguration::hardware`
+    pub fn hardware(&self) -> DualShockHardware {
+        self.config.map_or(DualShockHardware::Indeterminate, |x| x.hardware())
+    }
+}
+
+/// Constants 2, 3a and 3b, which both DualShocks answer with in any mode
+const DUALSHOCK_CONST2: [u8; 5] = [0x00, 0x02, 0x00, 0x01, 0x00];
+const DUALSHOCK_CONST3A: [u8; 5] = [0x00, 0x00, 0x04, 0x00, 0x00];
+const DUALSHOCK_CONST3B: [u8; 5] = [0x00, 0x00, 0x07, 0x00, 0x00];
+
+impl ControllerConfiguration {
+    /// Which DualShock answered, even when its id can't tell (in analog
+    /// mode both poll as 0x73). The constants have to be the ones Sony's
+    /// pads answer with, and then the first status byte says which
+    /// generation it is. Clones and anything else come out `Indeterminate`
+    /// rather than guessed.
+    ///
+    /// Constants that came back short are only compared as far as they go,
+    /// but each has to have arrived.
+    pub fn hardware(&self) -> DualShockHardware {
+        let [_, _, _, const2, const3a, const3b] = self.raw();
+        if !answered_as(const2, &DUALSHOCK_CONST2) || !answered_as(const3a, &DUALSHOCK_CONST3A) || !answered_as(const3b, &DUALSHOCK_CONST3B) {
+            return DualShockHardware::Indeterminate;
+        }
+
+        match self.is_dualshock2() {
+            Some(true) => DualShockHardware::Ds2,
+            Some(false) => DualShockHardware::Ds1,
+            None => DualShockHardware::Indeterminate,
+        }
+    }
+}
+
+/// Whether an answer that arrived matches `expected` as far as it goes
+fn answered_as(answer: &[u8], expected: &[u8]) -> bool {
+    !answer.is_empty() && expected.starts_with(answer)
+}
+
+/// The parts of a controller's escape mode answers that set it apart
+struct Fingerprint {
+    /// The first status byte, see `ControllerConfiguration::is_dualshock2`
+    generation: u8,
+    /// How many vibration motors it says it has
+    actuators: u8,
+    /// How it describes its first motor
+    const1a: [u8; 5],
+    kind: ControllerKind,
+}
+
+/// How a Sony pad describes its small motor
+const SMALL_MOTOR: [u8; 5] = [0x00, 0x01, 0x02, 0x00, 0x0a];
+
+/// Every controller whose escape mode answers we know, from the protocol
+/// notes. A Guitar Hero controller isn't here, since nobody has written down
+/// what it answers, so it comes out as a DualShock.
+const FINGERPRINTS: [Fingerprint; 2] = [
+    Fingerprint { generation: 0x03, actuators: 2, const1a: SMALL_MOTOR, kind: ControllerKind::DualShock2 },
+    Fingerprint { generation: 0x01, actuators: 2, const1a: SMALL_MOTOR, kind: ControllerKind::DualShock },
+];
+
+fn fingerprint(config: &ControllerConfiguration) -> Option<ControllerKind> {
+    if config.status[1] != 0x02 {
+        return None;
+    }
+
+    FINGERPRINTS.iter()
+        .find(|x| x.generation == config.status[0] && x.actuators == config.actuators() && answered_as(config.raw()[1], &x.const1a))
+        .map(|x| x.kind)
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, const N: usize, R> PlayStationPort<SPI, CS, N, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Work out what's plugged in. This wakes the controller, reads its
+    /// status and constants in escape mode (see `read_config`) and looks them
+    /// up in a table of known controllers. Anything that doesn't answer
+    /// escape mode, or answers with something new, is guessed from its id.
+    /// Like `read_config`, this won't build for a port too short for escape
+    /// mode.
+    pub fn probe(&mut self) -> Result<Probe, Error<E>> {
+        Self::fits_escape_mode();
+        let mut buffer = [0u8; N];
+
+        // Wake up the controller if needed, and see how it polls
+        self.send_command(CMD_POLL, &mut buffer)?;
+        let id = DeviceId::from_byte(byte(&buffer, 1)?);
+
+        let probe = if id == DeviceId::NotPresent {
+            Probe { kind: ControllerKind::None, confidence: Confidence::Exact, config: None }
+        } else {
+            let (config, answered) = self.read_config_answered()?;
+            let config = if answered { Some(config) } else { None };
+
+            match (config.as_ref().and_then(fingerprint), ControllerKind::from_id(id)) {
+                (Some(kind), _) => Probe { kind, confidence: Confidence::Exact, config },
+                (None, kind @ ControllerKind::Unknown(_)) => Probe { kind, confidence: Confidence::Unknown, config },
+                (None, kind) => Probe { kind, confidence: Confidence::Guessed, config },
+            }
+        };
+
+        self.probed = Some(probe.kind);
+
+        Ok(probe)
+    }
+
+    /// What's plugged in, as far as the port knows: what `probe` found if
+    /// it's been run, or else whatever answered the last poll (see
+    /// `last_input`). `None` until either has happened, and again once the
+    /// controller is pulled out.
+    pub fn kind(&self) -> ControllerKind {
+        match (self.probed, self.last_input()) {
+            (Some(kind), _) => kind,
+            (None, Some(device)) => device.kind(),
+            (None, None) => ControllerKind::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dualshock::DualShock;
+    use guitarhero::GuitarHero;
+    use mock::{self, MockPin};
+    use testutil::SimulatedDualShock2;
+    use Device;
+
+    /// A DualShock in analog mode answering a probe, from the poll waking it
+    /// up to leaving escape mode. Synthetic: written out from the protocol
+    /// notes, not captured off a controller.
+    // TODO: Replace with bytes captured off a real DualShock once someone
+    // has a dump
+    const DUALSHOCK_PROBE: [&[u8]; 9] = [
+        &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80],
+        &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80],
+        &[0xff, 0xf3, 0x5a, 0x01, 0x02, 0x01, 0x02, 0x01, 0x00],
+        &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x01, 0x02, 0x00, 0x0a],
+        &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x01, 0x01, 0x01, 0x14],
+        &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00],
+        &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00],
+        &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00],
+        &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    ];
+
+    #[test]
+    fn dualshock2() {
+        let mut port = PlayStationPort::<_, MockPin>::new(SimulatedDualShock2::new(), None);
+
+        let probe = port.probe().unwrap();
+        assert_eq!(probe.kind, ControllerKind::DualShock2);
+        assert_eq!(probe.confidence, Confidence::Exact);
+        assert_eq!(probe.config.unwrap().is_dualshock2(), Some(true));
+        assert!(!port.dev.in_escape_mode());
+
+        // Polling it is just the same as ever
+        assert!(matches!(port.read_input(None), Ok(Device::Classic(_))));
+    }
+
+    #[test]
+    fn dualshock() {
+        let probe = mock::port(&DUALSHOCK_PROBE).probe().unwrap();
+        assert_eq!((probe.kind, probe.confidence), (ControllerKind::DualShock, Confidence::Exact));
+        assert_eq!(probe.hardware(), DualShockHardware::Ds1);
+
+        // Any port with room for escape mode can probe
+        let mut port = PlayStationPort::builder(mock::MockSpi::new(&DUALSHOCK_PROBE), None::<MockPin>)
+            .frame_size::<9>()
+            .build();
+        assert_eq!(port.probe().unwrap(), probe);
+    }
+
+    /// The escape mode answers in the protocol notes, from a DualShock 2 in
+    /// analog mode. Synthetic, like `DUALSHOCK_PROBE`.
+    // TODO: Replace with a capture too
+    const DUALSHOCK2_ANSWERS: ControllerConfiguration = ControllerConfiguration::new(
+        [0x03, 0x02, 0x01, 0x02, 0x01, 0x00],
+        [0x00, 0x01, 0x02, 0x00, 0x0a],
+        [0x00, 0x01, 0x01, 0x01, 0x14],
+        [0x00, 0x02, 0x00, 0x01, 0x00],
+        [0x00, 0x00, 0x04, 0x00, 0x00],
+        [0x00, 0x00, 0x07, 0x00, 0x00],
+    );
+
+    #[test]
+    fn guitar_hero() {
+        // Nobody has recorded what a Guitar Hero controller answers in escape
+        // mode. It polls like a DualShock, and whatever it answers won't be
+        // in `FINGERPRINTS`, which this stands in for with a DualShock's
+        // answers less the motor a guitar doesn't have.
+        let mut answers = DUALSHOCK_PROBE;
+        answers[3] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        let probe = mock::port(&answers).probe().unwrap();
+        assert_eq!((probe.kind, probe.confidence), (ControllerKind::DualShock, Confidence::Guessed));
+    }
+
+    #[test]
+    fn dualshock_hardware() {
+        assert_eq!(DUALSHOCK2_ANSWERS.hardware(), DualShockHardware::Ds2);
+
+        // A generation byte that isn't either DualShock's
+        let mut unknown = DUALSHOCK2_ANSWERS;
+        unknown.status[0] = 0x02;
+        assert_eq!(unknown.hardware(), DualShockHardware::Indeterminate);
+
+        // A clone that gets the status right but not the constants
+        let mut clone = DUALSHOCK2_ANSWERS;
+        clone.const3b = [0x00, 0x00, 0x06, 0x00, 0x00];
+        assert_eq!(clone.hardware(), DualShockHardware::Indeterminate);
+        assert_eq!(ControllerConfiguration::default().hardware(), DualShockHardware::Indeterminate);
+
+        // A DualShock 2 in analog mode polls just like the first DualShock
+        let mut controller = SimulatedDualShock2::new();
+        controller.press_analog_button();
+        let mut port = PlayStationPort::<_, MockPin>::new(controller, None);
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+        assert_eq!(port.probe().unwrap().hardware(), DualShockHardware::Ds2);
+
+        let classic: &[u8] = &[0xff, 0xc1, 0x5a, 0xff, 0xff];
+        assert_eq!(mock::port(&[classic; 9]).probe().unwrap().hardware(), DualShockHardware::Indeterminate);
+    }
+
+    #[test]
+    fn kinds() {
+        use baton::Baton;
+        use classic::Classic;
+        use dualshock::DualShock2;
+        use guncon::GunCon;
+        use jogcon::JogCon;
+        use mouse::Mouse;
+        use negcon::NegCon;
+
+        let frame = [0u8; 18];
+        let dualshock = DualShock::builder().build();
+        let cases = [
+            (Device::None, ControllerKind::None),
+            (Device::Unknown, ControllerKind::Unknown(0)),
+            (Device::ConfigurationMode, ControllerKind::Unknown(0xf3)),
+            (Device::Mouse(Mouse::from_frame(&frame).unwrap()), ControllerKind::Mouse),
+            (Device::Classic(Classic::builder().build()), ControllerKind::Classic),
+            (Device::AnalogJoystick(dualshock), ControllerKind::AnalogJoystick),
+            (Device::DualShock(dualshock), ControllerKind::DualShock),
+            (Device::DualShock2(DualShock2::builder().build()), ControllerKind::DualShock2),
+            (Device::GuitarHero(GuitarHero::default()), ControllerKind::GuitarHero),
+            (Device::JogCon(JogCon::from_frame(&frame).unwrap()), ControllerKind::JogCon),
+            (Device::NegCon(NegCon::from_frame(&frame).unwrap()), ControllerKind::NegCon),
+            (Device::GunCon(GunCon::from_frame(&frame).unwrap()), ControllerKind::GunCon),
+            (Device::Baton(Baton::from_frame(&frame).unwrap()), ControllerKind::Baton),
+        ];
+
+        for (device, kind) in cases.iter() {
+            assert_eq!(device.kind(), *kind);
+        }
+
+        let sticks = [ControllerKind::AnalogJoystick, ControllerKind::DualShockDigital, ControllerKind::DualShock, ControllerKind::DualShock2];
+        for (_, kind) in cases.iter() {
+            assert_eq!(kind.has_analog_sticks(), sticks.contains(kind), "{:?}", kind);
+            assert_eq!(kind.has_pressure(), *kind == ControllerKind::DualShock2, "{:?}", kind);
+            assert_eq!(kind.is_present(), *kind != ControllerKind::None, "{:?}", kind);
+        }
+        assert!(ControllerKind::DualShockDigital.has_analog_sticks());
+        assert!(!ControllerKind::DualShockDigital.has_pressure());
+    }
+
+    #[test]
+    fn port_kind() {
+        let mut port = PlayStationPort::<_, MockPin>::new(SimulatedDualShock2::new(), None);
+        assert_eq!(port.kind(), ControllerKind::None);
+
+        // A poll only sees the original controller's frame
+        port.read_input(None).unwrap();
+        assert_eq!(port.kind(), ControllerKind::Classic);
+
+        port.probe().unwrap();
+        assert_eq!(port.kind(), ControllerKind::DualShock2);
+
+        let mut port = mock::port(&[]);
+        port.read_input(None).unwrap();
+        assert_eq!(port.kind(), ControllerKind::None);
+    }
+
+    #[test]
+    fn refusing_escape_mode() {
+        // The original controller answers everything like a poll
+        let classic: &[u8] = &[0xff, 0xc1, 0x5a, 0xff, 0xff];
+        let mut port = mock::port(&[classic; 9]);
+
+        let probe = port.probe().unwrap();
+        assert_eq!(probe, Probe { kind: ControllerKind::Classic, confidence: Confidence::Guessed, config: None });
+
+        let odd: &[u8] = &[0xff, 0x95, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let probe = mock::port(&[odd; 9]).probe().unwrap();
+        assert_eq!((probe.kind, probe.confidence), (ControllerKind::Unknown(0x95), Confidence::Unknown));
+
+        let probe = mock::port(&[]).probe().unwrap();
+        assert_eq!((probe.kind, probe.confidence), (ControllerKind::None, Confidence::Exact));
+    }
+}
@@ -0,0 +1,645 @@
+//! The Protocol
+//! ============================
+//! Everything about the bytes on the wire, with no SPI or GPIO in sight: the
+//! frame constants and commands, building polls, checking frames and working
+//! out which controller sent one. `PlayStationPort` is built on top of this,
+//! and it can just as well decode a logic analyzer capture on a desktop.
+//!
+//! Frames are written here the way the protocol notes write them, with each
+//! byte's bits in their usual order. The controller sends the least
+//! significant bit first, so bytes fresh off (or headed for) SPI hardware
+//! that only does most significant bit first have to be flipped, which
+//! `build_poll_frame` and `parse_response` take care of.
+//!
+//! Nothing here indexes or slices a buffer without checking it's long
+//! enough first: `field` and `byte` hand back `Error::BufferTooSmall` instead
+//! of panicking, and the lint keeps it that way.
+
+use core::convert::{Infallible, TryFrom};
+use core::ops::Range;
+
+use super::{Device, DeviceRef, Error, PollCommand};
+
+/// The maximum length of a message from a controller, and the size of the
+/// buffers a port polls into unless it's told otherwise
+pub const MESSAGE_MAX_LENGTH: usize = 32;
+/// Acknoweldgement byte for header commnad
+pub const ACK_BYTE: u8 = 0x5a;
+/// Value read from the data line when nothing is driving it
+pub const LINE_IDLE: u8 = 0xff;
+/// Length of the command header
+pub const HEADER_LEN: usize = 3;
+
+/// Controller missing
+pub(crate) const CONTROLLER_NOT_PRESENT: u8 = 0xff;
+/// PlayStation mouse, SCPH-1030
+pub(crate) const CONTROLLER_MOUSE: u8 = 0x12;
+/// Original controller, SCPH-1080
+pub(crate) const CONTROLLER_CLASSIC: u8 = 0xc1;
+/// Analog Controller, SCPH-1110 (flightstick looking thing)
+pub(crate) const CONTROLLER_ANALOG_JOYSTICK: u8 = 0x53;
+/// DualShock in Digital mode
+pub(crate) const CONTROLLER_DUALSHOCK_DIGITAL: u8 = 0x41;
+/// DualShock
+pub(crate) const CONTROLLER_DUALSHOCK_ANALOG: u8 = 0x73;
+/// DuakShock 2
+pub(crate) const CONTROLLER_DUALSHOCK_PRESSURE: u8 = 0x79;
+/// JogCon
+pub(crate) const CONTROLLER_JOGCON: u8 = 0xe3;
+/// NegCon
+pub(crate) const CONTROLLER_NEGCON: u8 = 0x23;
+/// NegCon
+pub(crate) const CONTROLLER_GUNCON: u8 = 0x63;
+/// Configuration Mode
+pub(crate) const CONTROLLER_CONFIGURATION: u8 = 0xf3;
+
+/// Bits of a frame that never change, as (offset, mask, value) where the masked
+/// byte has to equal the value
+type Invariant = (usize, u8, u8);
+
+/// The invariants of every frame from a given device. A frame that breaks these
+/// has been corrupted somewhere along the way.
+///
+/// Digital pads have no L3 or R3, so those two button bits always read released,
+/// and the mouse leaves its first button byte and the top of the second high.
+const FRAME_INVARIANTS: &[(u8, &[Invariant])] = &[
+    (CONTROLLER_CLASSIC, &[(0, 0xff, LINE_IDLE), (2, 0xff, ACK_BYTE), (3, 0x06, 0x06)]),
+    (CONTROLLER_DUALSHOCK_DIGITAL, &[(0, 0xff, LINE_IDLE), (2, 0xff, ACK_BYTE), (3, 0x06, 0x06)]),
+    (CONTROLLER_DUALSHOCK_ANALOG, &[(0, 0xff, LINE_IDLE), (2, 0xff, ACK_BYTE)]),
+    (CONTROLLER_DUALSHOCK_PRESSURE, &[(0, 0xff, LINE_IDLE), (2, 0xff, ACK_BYTE)]),
+    (CONTROLLER_MOUSE, &[(0, 0xff, LINE_IDLE), (2, 0xff, ACK_BYTE), (3, 0xff, 0xff), (4, 0xf0, 0xf0)]),
+];
+
+/// Command to poll buttons
+pub const CMD_POLL: &[u8] = &[0x00, 0x42, 0x00];
+/// Command to poll only the digital buttons
+pub const CMD_POLL_BUTTONS: &[u8] = &[0x00, 0x42, 0x00, 0x00, 0x00];
+/// Command to enter escape mode
+pub const CMD_ENTER_ESCAPE_MODE: &[u8] = &[0x00, 0x43, 0x00, 0x01, 0x00];
+/// Command to exit escape mode
+pub const CMD_EXIT_ESCAPE_MODE: &[u8] = &[0x00, 0x43, 0x00, 0x00, 0x00];
+/// Command to set response format. Right now asks for all data
+pub const CMD_RESPONSE_FORMAT: &[u8] = &[0x00, 0x4F, 0x00, 0xFF, 0xFF, 0x03, 0x00, 0x00, 0x00];
+/// Command to initialize / customize pressure
+pub const CMD_INIT_PRESSURE: &[u8] = &[0x00, 0x40, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00];
+/// Command to set major mode (DualShock = 1 / Digital = 0)
+pub const CMD_SET_MODE: &[u8] = &[0x00, 0x44, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00];
+/// Command to read extended status
+pub const CMD_READ_STATUS: &[u8] = &[0x00, 0x45, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
+/// Command to read constant 1 at address 00
+pub const CMD_READ_CONST1A: &[u8] = &[0x00, 0x46, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
+/// Command to read constant 1 at address 01
+pub const CMD_READ_CONST1B: &[u8] = &[0x00, 0x46, 0x00, 0x01, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
+/// Command to read constant 2 at address 00
+pub const CMD_READ_CONST2: &[u8] = &[0x00, 0x47, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
+/// Command to read constant 3 at address 00
+pub const CMD_READ_CONST3A: &[u8] = &[0x00, 0x4C, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
+/// Command to read constant 3 at address 01
+pub const CMD_READ_CONST3B: &[u8] = &[0x00, 0x4C, 0x00, 0x01, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a];
+/// Command to enable DualShock motors
+pub const CMD_MOTOR_DUALSHOCK: &[u8] = &[0x00, 0x4D, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xff];
+/// Command to enable JogCon motor
+pub const CMD_MOTOR_JOGCON: &[u8] = &[0x00, 0x4D, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff];
+
+/// The length of the frame a device with this id returns. The low nibble
+/// is the number of 16bit words after the header. Devices that claim
+/// nothing (and empty ports) get the conservative maximum.
+pub const fn frame_len(id: u8) -> usize {
+    let len = match id & 0x0f {
+        0 => MESSAGE_MAX_LENGTH,
+        x => HEADER_LEN + x as usize * 2,
+    };
+
+    if len < MESSAGE_MAX_LENGTH { len } else { MESSAGE_MAX_LENGTH }
+}
+
+/// The active port to set on the Multitap
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MultitapPort {
+    /// The first port on the multi-tap and also the port when no tap
+    /// is present
+    A = 0x01,
+    /// The second port on the multi-top
+    B = 0x02,
+    /// The third port on the multi-top
+    C = 0x03,
+    /// The fourth port on the multi-top
+    D = 0x04,
+    /// Some unknown id when booting the PS2. Memory card?
+    M = 0x61,
+    /// This may be for the multitap itself
+    X = 0xff,
+}
+
+/// The id byte a device answers a poll with. The high nybble says what kind
+/// of device it is and the low nybble how many 16 bit words of data follow
+/// the header, so the same controller has a different id in each mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceId {
+    /// Nothing drove the data line, so nothing is plugged in (0xff)
+    NotPresent,
+    /// PlayStation mouse (0x12)
+    Mouse,
+    /// Original controller (0xc1)
+    Classic,
+    /// Sony's analog flight stick (0x53)
+    AnalogJoystick,
+    /// DualShock or DualShock 2 in digital mode (0x41)
+    DualShockDigital,
+    /// DualShock or DualShock 2 in analog mode (0x73)
+    DualShockAnalog,
+    /// DualShock 2 sending button pressures (0x79)
+    DualShockPressure,
+    /// JogCon (0xe3)
+    JogCon,
+    /// NegCon (0x23)
+    NegCon,
+    /// GunCon (0x63)
+    GunCon,
+    /// Any controller waiting for configuration commands (0xf3)
+    Configuration,
+    /// An id we don't know
+    Other(u8),
+}
+
+impl DeviceId {
+    /// Look up an id byte
+    pub const fn from_byte(byte: u8) -> Self {
+        match byte {
+            CONTROLLER_NOT_PRESENT => DeviceId::NotPresent,
+            CONTROLLER_MOUSE => DeviceId::Mouse,
+            CONTROLLER_CLASSIC => DeviceId::Classic,
+            CONTROLLER_ANALOG_JOYSTICK => DeviceId::AnalogJoystick,
+            CONTROLLER_DUALSHOCK_DIGITAL => DeviceId::DualShockDigital,
+            CONTROLLER_DUALSHOCK_ANALOG => DeviceId::DualShockAnalog,
+            CONTROLLER_DUALSHOCK_PRESSURE => DeviceId::DualShockPressure,
+            CONTROLLER_JOGCON => DeviceId::JogCon,
+            CONTROLLER_NEGCON => DeviceId::NegCon,
+            CONTROLLER_GUNCON => DeviceId::GunCon,
+            CONTROLLER_CONFIGURATION => DeviceId::Configuration,
+            x => DeviceId::Other(x),
+        }
+    }
+
+    /// The id byte as it appears on the wire
+    pub const fn to_byte(self) -> u8 {
+        match self {
+            DeviceId::NotPresent => CONTROLLER_NOT_PRESENT,
+            DeviceId::Mouse => CONTROLLER_MOUSE,
+            DeviceId::Classic => CONTROLLER_CLASSIC,
+            DeviceId::AnalogJoystick => CONTROLLER_ANALOG_JOYSTICK,
+            DeviceId::DualShockDigital => CONTROLLER_DUALSHOCK_DIGITAL,
+            DeviceId::DualShockAnalog => CONTROLLER_DUALSHOCK_ANALOG,
+            DeviceId::DualShockPressure => CONTROLLER_DUALSHOCK_PRESSURE,
+            DeviceId::JogCon => CONTROLLER_JOGCON,
+            DeviceId::NegCon => CONTROLLER_NEGCON,
+            DeviceId::GunCon => CONTROLLER_GUNCON,
+            DeviceId::Configuration => CONTROLLER_CONFIGURATION,
+            DeviceId::Other(x) => x,
+        }
+    }
+
+    /// The high nybble, saying what kind of device this is
+    pub const fn kind_nibble(self) -> u8 {
+        self.to_byte() >> 4
+    }
+
+    /// The low nybble, saying how many 16 bit words follow the header. 0
+    /// stands for 16.
+    pub const fn half_words(self) -> u8 {
+        self.to_byte() & 0x0f
+    }
+}
+
+impl From<u8> for DeviceId {
+    fn from(byte: u8) -> Self {
+        DeviceId::from_byte(byte)
+    }
+}
+
+impl From<DeviceId> for u8 {
+    fn from(id: DeviceId) -> Self {
+        id.to_byte()
+    }
+}
+
+/// What a frame's header said about the frame
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FrameMeta {
+    /// The device's id byte. The high nybble is the device type
+    pub id: u8,
+    /// How many 16bit words the device claims follow the header
+    pub half_words: u8,
+    /// Whether the device acknowledged the poll
+    pub ack_ok: bool,
+    /// How many extra polls it took to get past the controller changing
+    /// modes, see `PlayStationPort::smooth_transitions`
+    pub retries: u8,
+}
+
+impl FrameMeta {
+    /// The id byte, looked up
+    pub fn device_id(&self) -> DeviceId {
+        DeviceId::from_byte(self.id)
+    }
+
+    /// How long the frame is, header included, going by the id
+    pub fn frame_len(&self) -> usize {
+        frame_len(self.id)
+    }
+}
+
+/// Every byte with its bits reversed, so flipping costs one lookup even on
+/// cores without a bit reverse instruction
+const REVERSED: [u8; 256] = reversed_table();
+
+// Only ever runs at compile time, where going out of bounds can't build
+#[allow(clippy::indexing_slicing)]
+const fn reversed_table() -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < table.len() {
+        table[i] = (i as u8).reverse_bits();
+        i += 1;
+    }
+
+    table
+}
+
+/// Reverse the bits in every byte. The controller sends and expects the least
+/// significant bit first, which not all SPI hardware can do.
+// A `u8` can't index past the end of a 256 entry table
+#[allow(clippy::indexing_slicing)]
+pub(crate) fn flip(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        *byte = REVERSED[*byte as usize];
+    }
+}
+
+fn too_small<E>(needed: usize) -> Error<E> {
+    Error::BufferTooSmall { needed: u16::try_from(needed).unwrap_or(u16::MAX) }
+}
+
+/// `bytes[range]`, or `Error::BufferTooSmall` if it runs past the end
+pub(crate) fn field<E>(bytes: &[u8], range: Range<usize>) -> Result<&[u8], Error<E>> {
+    let needed = range.end;
+    bytes.get(range).ok_or_else(|| too_small(needed))
+}
+
+/// The same as `field`, but mutable
+pub(crate) fn field_mut<E>(bytes: &mut [u8], range: Range<usize>) -> Result<&mut [u8], Error<E>> {
+    let needed = range.end;
+    bytes.get_mut(range).ok_or_else(|| too_small(needed))
+}
+
+/// `bytes[offset]`, or `Error::BufferTooSmall` if it's past the end
+pub(crate) fn byte<E>(bytes: &[u8], offset: usize) -> Result<u8, Error<E>> {
+    bytes.get(offset).cloned().ok_or_else(|| too_small(offset.saturating_add(1)))
+}
+
+/// The first `L` bytes, or `None` if there aren't that many. The device
+/// parsers read their fields out of this, so every index is checked when
+/// it's built.
+pub(crate) fn leading<const L: usize>(bytes: &[u8]) -> Option<&[u8; L]> {
+    <&[u8; L]>::try_from(bytes.get(..L)?).ok()
+}
+
+/// Copy as much of `bytes` into `out` as fits
+pub(crate) fn fill(out: &mut [u8], bytes: &[u8]) {
+    for (out, &x) in out.iter_mut().zip(bytes) {
+        *out = x;
+    }
+}
+
+/// Fill `out` from `bytes`, starting at `offset`
+pub(crate) fn copy_field<E>(out: &mut [u8], bytes: &[u8], offset: usize) -> Result<(), Error<E>> {
+    out.copy_from_slice(field(bytes, offset..offset.saturating_add(out.len()))?);
+
+    Ok(())
+}
+
+/// Fill `out` from an escape mode answer in `bytes`, starting at `offset`,
+/// only as far as the answer's id says it goes. Returns how many bytes that
+/// was, leaving the rest of `out` zeroed. Anything that isn't an escape mode
+/// answer fills nothing.
+pub(crate) fn copy_answer<E>(out: &mut [u8], bytes: &[u8], offset: usize) -> Result<u8, Error<E>> {
+    let id = byte(bytes, 1)?;
+    let end = if id & 0xf0 == CONTROLLER_CONFIGURATION & 0xf0 && byte(bytes, 2)? == ACK_BYTE {
+        frame_len(id).min(bytes.len())
+    } else {
+        0
+    };
+
+    let len = end.saturating_sub(offset).min(out.len());
+    for x in out.iter_mut() {
+        *x = 0;
+    }
+    copy_field(field_mut(out, 0..len)?, bytes, offset)?;
+
+    Ok(len as u8)
+}
+
+/// The bytes of a poll before the multi-tap port is filled in and the bits
+/// are flipped, with `command` laid over the top
+pub(crate) fn poll_command(command: Option<&dyn PollCommand>) -> [u8; MESSAGE_MAX_LENGTH] {
+    let mut data = [0u8; MESSAGE_MAX_LENGTH];
+
+    for (x, &command) in data.iter_mut().zip(CMD_POLL) {
+        *x = command;
+    }
+
+    // Overlay the command to send with the poll...
+    if let Some(x) = command {
+        x.set_command(&mut data[HEADER_LEN..]);
+    }
+
+    data
+}
+
+/// The whole length of the frame a device with this id returns, where
+/// `frame_len` stops at `MESSAGE_MAX_LENGTH`. A length nybble of 0 stands
+/// for 16 words.
+pub(crate) const fn full_frame_len(id: u8) -> usize {
+    let half_words = match id & 0x0f {
+        0 => 16,
+        x => x as usize,
+    };
+
+    HEADER_LEN + half_words * 2
+}
+
+/// Make sure the id claims a length that fits in `max` bytes. No single
+/// controller claims more than `MESSAGE_MAX_LENGTH`, so with that as the
+/// limit, ids that do turn up when the id itself got mangled, like in a
+/// frame of all zeros. Only a multi-tap sends anything longer.
+fn check_id<E>(id: u8, max: usize) -> Result<(), Error<E>> {
+    if full_frame_len(id) > max {
+        return Err(Error::Garbage { offset: 1, byte: id });
+    }
+
+    Ok(())
+}
+
+/// Check the bytes the device should never change. Devices without an entry
+/// in `FRAME_INVARIANTS` always pass.
+fn check_invariants<E>(frame: &[u8], id: u8) -> Result<(), Error<E>> {
+    let invariants = FRAME_INVARIANTS.iter().find(|x| x.0 == id);
+
+    if let Some(&(_, bytes)) = invariants {
+        for &(offset, mask, value) in bytes {
+            let byte = byte(frame, offset)?;
+            if byte & mask != value {
+                return Err(Error::Garbage { offset: offset as u8, byte });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a whole frame (header included) for a missing ACK and corruption, where
+/// no device should claim more than `max` bytes. Nothing past the end of
+/// `frame` is looked at.
+pub(crate) fn check_frame<E>(frame: &[u8], max: usize) -> Result<(), Error<E>> {
+    // An empty port leaves the data line pulled high for the whole frame,
+    // which is nothing to complain about
+    let id = match frame.get(1) {
+        Some(&x) if x != CONTROLLER_NOT_PRESENT => x,
+        _ => return Ok(()),
+    };
+
+    check_id(id, max)?;
+
+    // Device polling will return `ACK_BYTE` in the third byte if the command
+    // was properly understood
+    if frame.get(2) != Some(&ACK_BYTE) {
+        return Err(Error::Timeout { bytes_completed: 2 });
+    }
+
+    // Never look past what actually arrived
+    if frame.len() < full_frame_len(id) {
+        return Err(Error::BadResponse);
+    }
+
+    check_invariants(frame, id)
+}
+
+/// Write a poll into `tx` ready to clock out, for driving the bus yourself
+/// (with DMA, say) instead of through `PlayStationPort`. The bits are already
+/// flipped for SPI hardware sending the most significant bit first, same as
+/// the port does. Returns how many bytes were written, which is as much of
+/// `tx` as a frame can fill.
+///
+/// Make `tx` as long as the frame you expect back, or `MESSAGE_MAX_LENGTH`
+/// if you don't know, and hand whatever comes back to `parse_response`.
+pub fn build_poll_frame(port: MultitapPort, command: Option<&dyn PollCommand>, tx: &mut [u8]) -> usize {
+    let mut data = poll_command(command);
+    data[0] = port as u8;
+    flip(&mut data);
+
+    for (x, &byte) in tx.iter_mut().zip(data.iter()) {
+        *x = byte;
+    }
+
+    tx.len().min(MESSAGE_MAX_LENGTH)
+}
+
+/// Work out which controller sent a frame, header included, with each byte's
+/// bits in their usual order (the way a logic analyzer set to least
+/// significant bit first shows them). Frames are checked the same way
+/// `PlayStationPort::read_input` checks them. Anything past
+/// `MESSAGE_MAX_LENGTH` is ignored.
+pub fn decode_frame(frame: &[u8]) -> Result<Device, Error<Infallible>> {
+    let frame = field(frame, 0..frame.len().min(MESSAGE_MAX_LENGTH))?;
+    check_frame(frame, MESSAGE_MAX_LENGTH)?;
+
+    DeviceRef::parse(frame)
+        .map(|x| x.to_owned())
+        .ok_or(Error::BadResponse)
+}
+
+/// The same as `decode_frame`, but for a frame as SPI hardware received it,
+/// with the bits of each byte still backwards
+pub fn parse_response(rx: &[u8]) -> Result<Device, Error<Infallible>> {
+    let mut frame = [0u8; MESSAGE_MAX_LENGTH];
+    let len = rx.len().min(MESSAGE_MAX_LENGTH);
+    for (x, &byte) in frame.iter_mut().zip(rx) {
+        *x = byte;
+    }
+    flip(&mut frame);
+
+    decode_frame(field(&frame, 0..len)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::{Button, Classic};
+    use dualshock::DualShock;
+    use mouse::Mouse;
+
+    /// Polls from a logic analyzer, back to back: a DualShock in digital
+    /// mode with Cross pressed then released, switching to analog (with a
+    /// configuration frame on the way), then unplugged and swapped for a
+    /// mouse
+    const CAPTURE: &[u8] = &[
+        0xff, 0x41, 0x5a, 0xff, 0xbf,
+        0xff, 0x41, 0x5a, 0xff, 0xff,
+        0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x7f, 0x00, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0x12, 0x5a, 0xff, 0xfb, 0x03, 0xfe,
+    ];
+
+    #[test]
+    fn decodes_a_capture() {
+        let mut devices = [Device::Unknown; 6];
+        let mut rest = CAPTURE;
+        for device in devices.iter_mut() {
+            // An empty port doesn't say how long it is, so those frames
+            // were captured at five bytes
+            let len = match rest[1] {
+                CONTROLLER_NOT_PRESENT => 5,
+                id => frame_len(id),
+            };
+            *device = decode_frame(&rest[..len]).unwrap();
+            rest = &rest[len..];
+        }
+        assert!(rest.is_empty());
+
+        let cross = Classic::builder().press(Button::Cross).build();
+        let dualshock = DualShock::builder().stick_right(0x80, 0x7f).stick_left(0x00, 0xff).build();
+        let mouse = Mouse::from_frame(&[0xff, 0xfb, 0x03, 0xfe]).unwrap();
+        assert_eq!(devices, [
+            Device::Classic(cross),
+            Device::Classic(Classic::default()),
+            Device::ConfigurationMode,
+            Device::DualShock(dualshock),
+            Device::None,
+            Device::Mouse(mouse),
+        ]);
+    }
+
+    #[test]
+    fn flipping_reverses_every_bit() {
+        for byte in 0..=255u8 {
+            // Bit n ends up as bit 7 - n
+            let expected = (0..8).fold(0, |x, bit| x | ((byte >> bit) & 1) << (7 - bit));
+
+            let mut flipped = [byte];
+            flip(&mut flipped);
+            assert_eq!(flipped[0], expected);
+
+            flip(&mut flipped);
+            assert_eq!(flipped[0], byte);
+        }
+    }
+
+    #[test]
+    fn lengths_come_from_the_id() {
+        assert_eq!(frame_len(0x41), 5);
+        assert_eq!(frame_len(0x79), 21);
+        assert_eq!(frame_len(0xff), MESSAGE_MAX_LENGTH);
+        assert_eq!(frame_len(0x80), MESSAGE_MAX_LENGTH);
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        type Decoded = Result<Device, Error<Infallible>>;
+
+        let corpus: &[(&str, &[u8], Decoded)] = &[
+            ("empty", &[], Err(Error::BadResponse)),
+            ("header cut off", &[0xff, 0x41], Err(Error::Timeout { bytes_completed: 2 })),
+            ("wrong ack", &[0xff, 0x41, 0x00, 0xff, 0xff], Err(Error::Timeout { bytes_completed: 2 })),
+            ("truncated digital", &[0xff, 0x41, 0x5a, 0xff], Err(Error::BadResponse)),
+            ("truncated dualshock", &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80], Err(Error::BadResponse)),
+            ("dualshock 2 cut to a dualshock", &[0xff, 0x79, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80], Err(Error::BadResponse)),
+            ("first byte driven", &[0x00, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80], Err(Error::Garbage { offset: 0, byte: 0x00 })),
+            ("digital pad holding L3", &[0xff, 0x41, 0x5a, 0xfd, 0xff], Err(Error::Garbage { offset: 3, byte: 0xfd })),
+            ("classic holding R3", &[0xff, 0xc1, 0x5a, 0xfb, 0xff], Err(Error::Garbage { offset: 3, byte: 0xfb })),
+            ("mouse with a low first byte", &[0xff, 0x12, 0x5a, 0x7f, 0xfc, 0x00, 0x00], Err(Error::Garbage { offset: 3, byte: 0x7f })),
+            ("mouse with a low top nybble", &[0xff, 0x12, 0x5a, 0xff, 0xbc, 0x00, 0x00], Err(Error::Garbage { offset: 4, byte: 0xbc })),
+            ("zero length nybble", &[0xff, 0x70, 0x5a, 0x00, 0x00, 0x00, 0x00], Err(Error::Garbage { offset: 1, byte: 0x70 })),
+            ("oversized length nybble", &[0xff, 0x7f, 0x5a, 0x00, 0x00, 0x00, 0x00], Err(Error::Garbage { offset: 1, byte: 0x7f })),
+            ("all zero", &[0x00; MESSAGE_MAX_LENGTH], Err(Error::Garbage { offset: 1, byte: 0x00 })),
+            ("all idle", &[LINE_IDLE; MESSAGE_MAX_LENGTH], Ok(Device::None)),
+        ];
+
+        for &(name, frame, ref expected) in corpus {
+            assert_eq!(&decode_frame(frame), expected, "{}", name);
+
+            let mut rx = [0u8; MESSAGE_MAX_LENGTH];
+            rx[..frame.len()].copy_from_slice(frame);
+            flip(&mut rx[..frame.len()]);
+            assert_eq!(&parse_response(&rx[..frame.len()]), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn full_deflection_is_not_a_stall() {
+        // Every axis and pressure reads 0xff at its limit, the same as an
+        // idle line, so none of these can be told from a device that stopped
+        // talking by looking at the bytes
+        let dualshock_holding_cross = [0xff, 0x73, 0x5a, 0xff, 0xbf, 0xff, 0xff, 0xff, 0xff];
+        let dualshock = [0xff, 0x73, 0x5a, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        let negcon = [0xff, 0x23, 0x5a, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        let mut dualshock2 = [LINE_IDLE; 21];
+        dualshock2[1..3].copy_from_slice(&[0x79, ACK_BYTE]);
+
+        assert!(matches!(decode_frame(&dualshock_holding_cross), Ok(Device::DualShock(_))));
+        assert!(matches!(decode_frame(&dualshock), Ok(Device::DualShock(_))));
+        assert!(matches!(decode_frame(&negcon), Ok(Device::NegCon(_))));
+        assert!(matches!(decode_frame(&dualshock2), Ok(Device::DualShock2(_))));
+    }
+
+    #[test]
+    fn nothing_panics() {
+        for id in 0..=255 {
+            for &fill in [0x00, ACK_BYTE, LINE_IDLE].iter() {
+                let mut frame = [fill; MESSAGE_MAX_LENGTH + 1];
+                frame[1] = id;
+
+                for len in 0..=frame.len() {
+                    let _ = decode_frame(&frame[..len]);
+                    let _ = parse_response(&frame[..len]);
+                    let _ = DeviceRef::parse(&frame[..len]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn short_buffers_are_errors() {
+        let mut bytes = [0x11, 0x22, 0x33];
+        assert_eq!(field::<()>(&bytes, 1..3), Ok(&[0x22, 0x33][..]));
+        assert_eq!(field::<()>(&bytes, 1..4), Err(Error::BufferTooSmall { needed: 4 }));
+        assert_eq!(field::<()>(&bytes, 4..4), Err(Error::BufferTooSmall { needed: 4 }));
+        assert!(field_mut::<()>(&mut bytes, 0..9).is_err());
+        assert_eq!(byte::<()>(&bytes, 2), Ok(0x33));
+        assert_eq!(byte::<()>(&[], 0), Err(Error::BufferTooSmall { needed: 1 }));
+
+        let mut out = [0u8; 2];
+        assert_eq!(copy_field::<()>(&mut out, &bytes, 2), Err(Error::BufferTooSmall { needed: 4 }));
+        assert_eq!(copy_field::<()>(&mut out, &bytes, usize::MAX), Err(Error::BufferTooSmall { needed: u16::MAX }));
+        assert_eq!(out, [0, 0]);
+        assert_eq!(copy_field::<()>(&mut out, &bytes, 1), Ok(()));
+        assert_eq!(out, [0x22, 0x33]);
+
+        // A frame cut off anywhere past the id is an error
+        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+        buffer[1] = CONTROLLER_CLASSIC;
+        buffer[2] = ACK_BYTE;
+        for len in 2..frame_len(CONTROLLER_CLASSIC) {
+            assert!(check_frame::<()>(&buffer[..len], MESSAGE_MAX_LENGTH).is_err(), "{}", len);
+        }
+
+        // A poll only fills as much as there's room for
+        let mut tx = [0u8; 1];
+        assert_eq!(build_poll_frame(MultitapPort::A, None, &mut tx), 1);
+        assert_eq!(tx, [0x80]);
+        assert_eq!(build_poll_frame(MultitapPort::A, None, &mut []), 0);
+    }
+}
@@ -0,0 +1,311 @@
+//! Input Recording
+//! ============================
+//! Keeps the last few polls around in a fixed amount of memory, so there's
+//! something to look at after things go wrong. Each poll is boiled down to the
+//! parts most programs care about: the buttons, the sticks and how hard the
+//! hardest pressed button is being pushed.
+
+use classic::{Classic, GamepadButtons};
+use dualshock::{DualShock, DualShock2};
+use super::Device;
+
+/// One poll, boiled down
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedFrame {
+    /// Standard buttons, all released for devices that don't have them
+    pub buttons: GamepadButtons,
+    /// The raw stick bytes in the order left X, left Y, right X, right Y, if
+    /// the device has sticks
+    pub sticks: Option<[u8; 4]>,
+    /// The highest pressure reading of any button, if the device reports them
+    pub pressure: Option<u8>,
+}
+
+impl RecordedFrame {
+    /// A frame with nothing pressed, no sticks and no pressures
+    pub const EMPTY: Self = Self {
+        buttons: GamepadButtons::all_released(),
+        sticks: None,
+        pressure: None,
+    };
+
+    /// Boil down a poll
+    pub fn from_device(device: &Device) -> Self {
+        let buttons = device.buttons().unwrap_or_default();
+
+        let (sticks, pressure) = match device {
+            Device::AnalogJoystick(x) | Device::DualShock(x) => (Some([x.lx, x.ly, x.rx, x.ry]), None),
+            Device::DualShock2(x) => {
+                (Some([x.lx, x.ly, x.rx, x.ry]), x.pressures.iter().cloned().max())
+            },
+            _ => (None, None),
+        };
+
+        Self {
+            buttons,
+            sticks,
+            pressure,
+        }
+    }
+}
+
+impl<'a> From<&'a Device> for RecordedFrame {
+    fn from(device: &'a Device) -> Self {
+        Self::from_device(device)
+    }
+}
+
+/// Rebuilds a controller from a recorded poll, so recordings can be played
+/// back (see `replay::ReplaySource`). Frames with a pressure come back as a
+/// DualShock 2 with every held button at that pressure, frames with only
+/// sticks as a DualShock, and everything else as an original controller.
+impl From<RecordedFrame> for Device {
+    fn from(frame: RecordedFrame) -> Self {
+        let buttons = frame.buttons;
+
+        match (frame.sticks, frame.pressure) {
+            (Some([lx, ly, rx, ry]), Some(pressure)) => {
+                let mut builder = DualShock2::builder().stick_left(lx, ly).stick_right(rx, ry);
+                for button in buttons.pressed() {
+                    builder = builder.press(button).pressure(button, pressure);
+                }
+
+                Device::DualShock2(builder.build())
+            },
+            (Some([lx, ly, rx, ry]), None) => Device::DualShock(DualShock { buttons, lx, ly, rx, ry }),
+            (None, _) => Device::Classic(Classic { buttons }),
+        }
+    }
+}
+
+/// Holds the last `N` polls. Once it's full, each new poll pushes out the
+/// oldest one.
+#[derive(Debug)]
+pub struct InputRecorder<const N: usize> {
+    frames: [RecordedFrame; N],
+    oldest: usize,
+    len: usize,
+    dropped: u32,
+}
+
+impl<const N: usize> InputRecorder<N> {
+    /// Create an empty recorder
+    pub const fn new() -> Self {
+        Self {
+            frames: [RecordedFrame::EMPTY; N],
+            oldest: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Record a poll, pushing out the oldest one if the recorder is full
+    // Both indices are taken `% N`, and `N` isn't zero past the check
+    #[allow(clippy::indexing_slicing)]
+    pub fn push(&mut self, device: &Device) {
+        if N == 0 {
+            self.dropped = self.dropped.saturating_add(1);
+            return;
+        }
+
+        let frame = RecordedFrame::from_device(device);
+        if self.len < N {
+            self.frames[(self.oldest + self.len) % N] = frame;
+            self.len += 1;
+        } else {
+            self.frames[self.oldest] = frame;
+            self.oldest = (self.oldest + 1) % N;
+            self.dropped = self.dropped.saturating_add(1);
+        }
+    }
+
+    /// Walk through the recorded polls, from oldest to newest
+    pub fn iter(&self) -> RecorderIter<'_> {
+        RecorderIter {
+            frames: &self.frames,
+            next: self.oldest,
+            remaining: self.len,
+        }
+    }
+
+    /// Forget everything recorded so far, including how many polls were
+    /// pushed out
+    pub fn clear(&mut self) {
+        self.oldest = 0;
+        self.len = 0;
+        self.dropped = 0;
+    }
+
+    /// How many polls are being held
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many polls were pushed out to make room for newer ones
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl<const N: usize> Default for InputRecorder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a InputRecorder<N> {
+    type Item = &'a RecordedFrame;
+    type IntoIter = RecorderIter<'a>;
+
+    fn into_iter(self) -> RecorderIter<'a> {
+        self.iter()
+    }
+}
+
+/// Walks through the polls in an `InputRecorder`, from oldest to newest.
+/// Created by `InputRecorder::iter()`.
+#[derive(Debug)]
+pub struct RecorderIter<'a> {
+    frames: &'a [RecordedFrame],
+    next: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for RecorderIter<'a> {
+    type Item = &'a RecordedFrame;
+
+    fn next(&mut self) -> Option<&'a RecordedFrame> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let frame = self.frames.get(self.next)?;
+        self.next = (self.next + 1) % self.frames.len();
+        self.remaining -= 1;
+
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for RecorderIter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use classic::Button;
+
+    // Tells polls apart by which buttons are held
+    fn classic(bits: u16) -> Device {
+        Device::Classic(Classic { buttons: GamepadButtons::from_pressed_bits(bits) })
+    }
+
+    fn held(recorder: &InputRecorder<4>) -> Vec<u16> {
+        recorder.iter().map(|x| x.buttons.pressed_bits()).collect()
+    }
+
+    #[test]
+    fn fills_in_order() {
+        let mut recorder = InputRecorder::<4>::new();
+        assert!(recorder.is_empty());
+        assert_eq!(recorder.iter().next(), None);
+
+        for bits in 1..=3 {
+            recorder.push(&classic(bits));
+        }
+
+        assert_eq!(held(&recorder), [1, 2, 3]);
+        assert_eq!(recorder.len(), 3);
+        assert_eq!(recorder.dropped_count(), 0);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut recorder = InputRecorder::<4>::new();
+
+        for bits in 1..=10 {
+            recorder.push(&classic(bits));
+        }
+
+        assert_eq!(held(&recorder), [7, 8, 9, 10]);
+        assert_eq!(recorder.iter().len(), 4);
+        assert_eq!(recorder.dropped_count(), 6);
+
+        recorder.clear();
+        assert!(recorder.is_empty());
+        assert_eq!(recorder.dropped_count(), 0);
+
+        recorder.push(&classic(11));
+        assert_eq!(held(&recorder), [11]);
+    }
+
+    #[test]
+    fn nothing_fits() {
+        let mut recorder = InputRecorder::<0>::new();
+
+        recorder.push(&classic(1));
+
+        assert!(recorder.is_empty());
+        assert_eq!(recorder.dropped_count(), 1);
+    }
+
+    #[test]
+    fn frames_are_boiled_down() {
+        let dualshock = DualShock::builder().press(Button::Cross).stick_left(0x10, 0x20).build();
+        let dualshock2 = DualShock2::builder()
+            .press(Button::Square)
+            .pressure(Button::Square, 0x40)
+            .pressure(Button::R2, 0xc0)
+            .build();
+
+        assert_eq!(RecordedFrame::from_device(&Device::DualShock(dualshock)), RecordedFrame {
+            buttons: GamepadButtons::from_pressed(&[Button::Cross]),
+            sticks: Some([0x10, 0x20, 0x80, 0x80]),
+            pressure: None,
+        });
+        assert_eq!(RecordedFrame::from(&Device::DualShock2(dualshock2)), RecordedFrame {
+            buttons: GamepadButtons::from_pressed(&[Button::Square]),
+            sticks: Some([0x80; 4]),
+            pressure: Some(0xc0),
+        });
+        assert_eq!(RecordedFrame::from_device(&Device::None), RecordedFrame::EMPTY);
+    }
+
+    #[test]
+    fn frames_are_rebuilt() {
+        let dualshock = DualShock::builder().press(Button::L3).stick_right(0x00, 0xff).build();
+        let dualshock2 = DualShock2::builder()
+            .press(Button::Cross)
+            .press(Button::R2)
+            .pressure(Button::Cross, 0x20)
+            .pressure(Button::R2, 0xc0)
+            .build();
+        let classic = Classic { buttons: GamepadButtons::from_pressed(&[Button::Start]) };
+
+        for &device in [Device::DualShock(dualshock), Device::Classic(classic)].iter() {
+            assert_eq!(Device::from(RecordedFrame::from_device(&device)), device);
+        }
+
+        // Only the hardest press survives recording
+        let rebuilt = Device::from(RecordedFrame::from_device(&Device::DualShock2(dualshock2)));
+        assert_eq!(rebuilt, Device::DualShock2(DualShock2::builder()
+            .press(Button::Cross)
+            .press(Button::R2)
+            .pressure(Button::Cross, 0xc0)
+            .pressure(Button::R2, 0xc0)
+            .build()));
+    }
+}
@@ -0,0 +1,284 @@
+//! Error Recovery
+//! ============================
+//! What the port does when a poll comes back wrong, or configuration fails,
+//! is up to a `RecoveryPolicy`. Each time something goes wrong the policy is
+//! asked what to do about it, and told how many times in a row it's gone
+//! wrong so far: poll again, set the controller up again first, or give up
+//! and hand the error back.
+//!
+//! Out of the box the port gives up straight away (`FailFast`), which is
+//! how it's always behaved. `RetryThenReconfigure` is for a controller that
+//! drops out of the mode it was put in, say from a loose connection.
+//!
+//! The policy is part of the port's type and kept inside it, so it's set up
+//! with `PortBuilder::recovery_policy` or `PlayStationPort::with_recovery_policy`
+//! rather than changed on the fly. `PlayStationPort::recovery_policy` gets at
+//! it again afterwards.
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use super::{AppliedConfig, Error, PlayStationPort};
+
+/// What to do about something that went wrong
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Try again straight away
+    Retry,
+    /// Set the controller up the way the last `enable_pressure` or
+    /// `enable_jogcon` did, then try again. With nothing set up yet, this is
+    /// the same as `Retry`.
+    Reconfigure,
+    /// Give up and hand back the error
+    Fail,
+}
+
+/// Decides what the port does about errors. Every hook is given which
+/// attempt failed, starting from 1 for the first failure of a call, and
+/// fails by default.
+///
+/// Polls through the `read_input` family ask `on_bad_response` and
+/// `on_timeout`. SPI errors and anything else that can't be helped by trying
+/// again always fail. The configuration calls (`enable_pressure`,
+/// `enable_jogcon`, `read_config` and `is_analog_active`) ask
+/// `on_config_failure`, where `Reconfigure` is the same as `Retry`.
+pub trait RecoveryPolicy {
+    /// A frame came back mangled (`Error::BadResponse` or `Error::Garbage`)
+    fn on_bad_response(&mut self, attempt: u8) -> Action {
+        let _ = attempt;
+        Action::Fail
+    }
+
+    /// The controller stopped answering partway (`Error::Timeout`)
+    fn on_timeout(&mut self, attempt: u8) -> Action {
+        let _ = attempt;
+        Action::Fail
+    }
+
+    /// One of the configuration calls failed
+    fn on_config_failure(&mut self, attempt: u8) -> Action {
+        let _ = attempt;
+        Action::Fail
+    }
+}
+
+/// Hand back every error straight away. This is the port's default.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FailFast;
+
+impl RecoveryPolicy for FailFast {}
+
+/// Poll again up to `retries` times, then set the controller up again and
+/// give it one last poll. Configuration is retried up to `retries` times.
+#[derive(Copy, Clone, Debug)]
+pub struct RetryThenReconfigure {
+    /// How many times to try again before reconfiguring
+    pub retries: u8,
+}
+
+impl RetryThenReconfigure {
+    /// Try again `retries` times before reconfiguring
+    pub const fn new(retries: u8) -> Self {
+        Self { retries }
+    }
+
+    fn on_poll_error(&self, attempt: u8) -> Action {
+        if attempt <= self.retries {
+            Action::Retry
+        } else if attempt == self.retries.saturating_add(1) {
+            Action::Reconfigure
+        } else {
+            Action::Fail
+        }
+    }
+}
+
+impl RecoveryPolicy for RetryThenReconfigure {
+    fn on_bad_response(&mut self, attempt: u8) -> Action {
+        self.on_poll_error(attempt)
+    }
+
+    fn on_timeout(&mut self, attempt: u8) -> Action {
+        self.on_poll_error(attempt)
+    }
+
+    fn on_config_failure(&mut self, attempt: u8) -> Action {
+        if attempt <= self.retries {
+            Action::Retry
+        } else {
+            Action::Fail
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, const N: usize, R> PlayStationPort<SPI, CS, N, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Ask the policy what to do about a failed poll
+    pub(crate) fn poll_action(&mut self, error: &Error<E>, attempt: u8) -> Action {
+        match error {
+            Error::BadResponse | Error::Garbage { .. } => self.recovery.on_bad_response(attempt),
+            Error::Timeout { .. } => self.recovery.on_timeout(attempt),
+            _ => Action::Fail,
+        }
+    }
+
+    /// Ask the policy what to do about failed configuration
+    pub(crate) fn config_action(&mut self, attempt: u8) -> Action {
+        self.recovery.on_config_failure(attempt)
+    }
+
+    /// Run whichever of `enable_pressure` or `enable_jogcon` was run last
+    pub(crate) fn reconfigure(&mut self) -> Result<(), Error<E>> {
+        match self.setup {
+            Some(AppliedConfig::Pressure) => self.setup_pressure(),
+            Some(AppliedConfig::JogCon) => self.setup_jogcon(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::sync::{Arc, Mutex};
+    use self::std::vec::Vec;
+    use super::*;
+    use mock;
+    use Device;
+
+    type Log = Arc<Mutex<Vec<(&'static str, u8)>>>;
+
+    /// Answers with `actions` in order, then fails, noting every hook
+    struct Counting {
+        log: Log,
+        actions: Vec<Action>,
+    }
+
+    impl Counting {
+        fn new(actions: &[Action]) -> (Self, Log) {
+            let log = Log::default();
+
+            (Counting { log: log.clone(), actions: actions.to_vec() }, log)
+        }
+
+        fn next(&mut self, hook: &'static str, attempt: u8) -> Action {
+            self.log.lock().unwrap().push((hook, attempt));
+            if self.actions.is_empty() {
+                Action::Fail
+            } else {
+                self.actions.remove(0)
+            }
+        }
+    }
+
+    impl RecoveryPolicy for Counting {
+        fn on_bad_response(&mut self, attempt: u8) -> Action {
+            self.next("bad response", attempt)
+        }
+
+        fn on_timeout(&mut self, attempt: u8) -> Action {
+            self.next("timeout", attempt)
+        }
+
+        fn on_config_failure(&mut self, attempt: u8) -> Action {
+            self.next("config", attempt)
+        }
+    }
+
+    #[test]
+    fn polls_do_what_the_policy_says() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let stalled: &[u8] = &[0xff, 0x73, 0x00];
+        let garbled: &[u8] = &[0x00, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        let mut responses = std::vec![config; 7];
+        responses.extend_from_slice(&[garbled, stalled]);
+        responses.extend_from_slice(&[config; 7]);
+        responses.extend_from_slice(&[dualshock, garbled]);
+
+        let mut port = mock::port(&responses);
+        port.enable_pressure().unwrap();
+
+        let (policy, log) = Counting::new(&[Action::Retry, Action::Reconfigure]);
+        let mut port = port.with_recovery_policy(policy);
+
+        // Polled again, then set up again and polled once more
+        assert!(matches!(port.read_input(None), Ok(Device::DualShock(_))));
+        assert_eq!(*log.lock().unwrap(), [("bad response", 1), ("timeout", 2)]);
+        assert_eq!(port.dev.sent.len(), 7 + 3 + 7);
+        assert_eq!(port.stats().retries, 2);
+        assert_eq!(port.last_error(), None);
+
+        // Attempts start over with every call, and this time it gives up
+        assert!(matches!(port.read_input(None), Err(Error::Garbage { .. })));
+        assert_eq!(log.lock().unwrap()[2..], [("bad response", 1)]);
+        assert_eq!(port.stats().retries, 2);
+
+        // Without a policy nothing is retried
+        let mut port = mock::port(&[garbled, dualshock]);
+        assert!(matches!(port.read_input(None), Err(Error::Garbage { .. })));
+    }
+
+    #[test]
+    fn configuration_does_what_the_policy_says() {
+        let (policy, log) = Counting::new(&[Action::Retry, Action::Reconfigure]);
+        let mut port = mock::port(&[]).with_recovery_policy(policy);
+        port.dev.fail = true;
+
+        assert_eq!(port.enable_pressure(), Err(Error::Spi(())));
+        assert_eq!(*log.lock().unwrap(), [("config", 1), ("config", 2), ("config", 3)]);
+        assert_eq!(port.stats().config_failures, 3);
+
+        // An empty port doesn't answer, and retrying doesn't change that
+        let (policy, log) = Counting::new(&[Action::Retry]);
+        let mut port = mock::port(&[]).with_recovery_policy(policy);
+
+        assert_eq!(port.is_analog_active(), Err(Error::UnsupportedDevice));
+        assert_eq!(*log.lock().unwrap(), [("config", 1), ("config", 2)]);
+        assert_eq!(port.dev.sent.len(), 6);
+    }
+
+    #[test]
+    fn built_in_policies() {
+        let mut policy = FailFast;
+        assert_eq!(policy.on_bad_response(1), Action::Fail);
+        assert_eq!(policy.on_timeout(1), Action::Fail);
+        assert_eq!(policy.on_config_failure(1), Action::Fail);
+
+        let mut policy = RetryThenReconfigure::new(2);
+        let polls: Vec<_> = (1..=4).map(|x| policy.on_bad_response(x)).collect();
+        assert_eq!(polls, [Action::Retry, Action::Retry, Action::Reconfigure, Action::Fail]);
+        assert_eq!(policy.on_timeout(3), Action::Reconfigure);
+        let config: Vec<_> = (1..=3).map(|x| policy.on_config_failure(x)).collect();
+        assert_eq!(config, [Action::Retry, Action::Retry, Action::Fail]);
+
+        // Never retrying still gets the one reconfigure
+        let mut policy = RetryThenReconfigure::new(0);
+        assert_eq!(policy.on_timeout(1), Action::Reconfigure);
+        assert_eq!(policy.on_config_failure(1), Action::Fail);
+    }
+
+    #[test]
+    fn reconfiguring_runs_the_last_setup() {
+        let config: &[u8] = &[0xff, 0xf3, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut port = mock::port(&[config; 5]).with_recovery_policy(RetryThenReconfigure::new(0));
+        port.enable_jogcon().unwrap();
+
+        // A stall, the JogCon set up again, then nothing there at all
+        let stalled: &[u8] = &[0xff, 0x73, 0x00];
+        port.dev.responses.push_back(stalled.to_vec());
+        assert_eq!(port.read_input(None), Ok(Device::None));
+
+        let sent = &port.dev.sent[6..11];
+        assert_eq!(sent, &port.dev.sent[..5]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Input Replay
+//! ============================
+//! Plays stored input back through `InputSource`, so code that normally reads
+//! a controller can be tested without one. The frames can be whole `Device`s
+//! or the `RecordedFrame`s kept by `recorder::InputRecorder`.
+
+use core::convert::Infallible;
+
+use super::{
+    Device,
+    Error,
+    InputSource,
+};
+
+/// What a replay does once it runs out of frames
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LoopMode {
+    /// Keep returning `Device::None`, as if the controller was unplugged
+    Stop,
+    /// Start again from the first frame
+    Repeat,
+}
+
+/// Hands out stored frames one at a time, in order
+#[derive(Debug)]
+pub struct ReplaySource<'a, T> {
+    frames: &'a [T],
+    next: usize,
+    mode: LoopMode,
+}
+
+impl<'a, T> ReplaySource<'a, T>
+where
+    T: Copy + Into<Device> {
+
+    /// Create a replay of `frames`
+    pub fn new(frames: &'a [T], mode: LoopMode) -> Self {
+        Self {
+            frames,
+            next: 0,
+            mode,
+        }
+    }
+
+    /// Go back to the first frame
+    pub fn rewind(&mut self) {
+        self.next = 0;
+    }
+
+    /// Which frame will be handed out next
+    pub fn position(&self) -> usize {
+        self.next
+    }
+
+    /// Whether a replay that stops at the end has got there. Replays that
+    /// repeat never finish, unless they're empty.
+    pub fn is_finished(&self) -> bool {
+        match self.mode {
+            LoopMode::Stop => self.next >= self.frames.len(),
+            LoopMode::Repeat => self.frames.is_empty(),
+        }
+    }
+}
+
+impl<'a, T> InputSource for ReplaySource<'a, T>
+where
+    T: Copy + Into<Device> {
+
+    type SpiError = Infallible;
+
+    fn read_input(&mut self) -> Result<Device, Error<Infallible>> {
+        if self.next >= self.frames.len() {
+            if let LoopMode::Repeat = self.mode {
+                self.next = 0;
+            }
+        }
+
+        match self.frames.get(self.next) {
+            Some(&frame) => {
+                self.next += 1;
+                Ok(frame.into())
+            },
+            None => Ok(Device::None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use classic::{Button, Classic, GamepadButtons};
+    use dualshock::DualShock;
+    use recorder::{InputRecorder, RecordedFrame};
+
+    /// A menu cursor: up and down move it, cross picks the current entry.
+    /// Holding a button only counts once.
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct Menu {
+        cursor: u8,
+        picked: Option<u8>,
+        previous: GamepadButtons,
+    }
+
+    impl Menu {
+        fn feed(&mut self, device: &Device) {
+            let buttons = device.buttons().unwrap_or_default();
+            let changes = buttons.changes_since(&self.previous);
+            self.previous = buttons;
+
+            for button in changes.pressed() {
+                match button {
+                    Button::Up => self.cursor = self.cursor.saturating_sub(1),
+                    Button::Down => self.cursor += 1,
+                    Button::Cross => self.picked = Some(self.cursor),
+                    _ => (),
+                }
+            }
+        }
+
+        fn step<S: InputSource>(&mut self, source: &mut S) -> Result<(), Error<S::SpiError>> {
+            let device = source.read_input()?;
+            self.feed(&device);
+
+            Ok(())
+        }
+    }
+
+    fn session() -> Vec<Device> {
+        let press = |buttons: &[Button]| Device::Classic(Classic { buttons: GamepadButtons::from_pressed(buttons) });
+        let stick = DualShock::builder().press(Button::Down).stick_left(0x00, 0x80).build();
+
+        [
+            press(&[]),
+            press(&[Button::Down]),
+            press(&[Button::Down]),
+            press(&[]),
+            Device::DualShock(stick),
+            press(&[Button::Up]),
+            press(&[Button::Down]),
+            press(&[Button::Cross]),
+        ].to_vec()
+    }
+
+    // What the menu should end up as after each step of the session
+    fn expected() -> Vec<(u8, Option<u8>)> {
+        [(0, None), (1, None), (1, None), (1, None), (2, None), (1, None), (2, None), (2, Some(2))].to_vec()
+    }
+
+    #[test]
+    fn replay_matches_live_devices() {
+        let devices = session();
+
+        // Driven straight from the devices
+        let mut live = Menu::default();
+        let mut states = Vec::new();
+        for device in devices.iter() {
+            live.feed(device);
+            states.push((live.cursor, live.picked));
+        }
+        assert_eq!(states, expected());
+
+        // Driven from a replay of the same devices
+        let mut replay = ReplaySource::new(&devices, LoopMode::Stop);
+        let mut menu = Menu::default();
+        let mut replayed = Vec::new();
+        while !replay.is_finished() {
+            menu.step(&mut replay).unwrap();
+            replayed.push((menu.cursor, menu.picked));
+        }
+        assert_eq!(replayed, states);
+        assert_eq!(menu, live);
+
+        // And from a recording of them
+        let mut recorder = InputRecorder::<16>::new();
+        for device in devices.iter() {
+            recorder.push(device);
+        }
+        let recording: Vec<RecordedFrame> = recorder.iter().cloned().collect();
+        let mut replay = ReplaySource::new(&recording, LoopMode::Stop);
+        let mut menu = Menu::default();
+        for _ in 0..recording.len() {
+            menu.step(&mut replay).unwrap();
+        }
+        assert_eq!(menu, live);
+    }
+
+    #[test]
+    fn loop_modes() {
+        let devices = session();
+
+        let mut stop = ReplaySource::new(&devices[..2], LoopMode::Stop);
+        assert_eq!(stop.read_input(), Ok(devices[0]));
+        assert_eq!(stop.read_input(), Ok(devices[1]));
+        assert!(stop.is_finished());
+        assert_eq!(stop.read_input(), Ok(Device::None));
+        assert_eq!(stop.read_input(), Ok(Device::None));
+
+        stop.rewind();
+        assert_eq!(stop.position(), 0);
+        assert_eq!(stop.read_input(), Ok(devices[0]));
+
+        let mut repeat = ReplaySource::new(&devices[..2], LoopMode::Repeat);
+        let read: Vec<Device> = (0..5).map(|_| repeat.read_input().unwrap()).collect();
+        assert_eq!(read, [devices[0], devices[1], devices[0], devices[1], devices[0]]);
+        assert!(!repeat.is_finished());
+
+        let mut empty = ReplaySource::<Device>::new(&[], LoopMode::Repeat);
+        assert!(empty.is_finished());
+        assert_eq!(empty.read_input(), Ok(Device::None));
+    }
+}
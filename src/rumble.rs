@@ -0,0 +1,246 @@
+//! Rumble Patterns
+//! ============================
+//! Running the motors at one strength and then cutting them feels harsh.
+//! A `RumblePattern` strings together steps of different strengths, and a
+//! `RumblePlayer` steps through one a poll at a time. The player has no clock
+//! of its own, so it moves exactly one poll forward each time it's ticked.
+//!
+//! The DualShock's small motor can only be on or off, while the large one has
+//! a strength from 0 to 255, so each tick gives back `(small, large)` ready
+//! for `ControlDS::new`.
+
+use dualshock::ControlDS;
+
+/// One step of a pattern, held for a number of polls
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RumbleSegment {
+    /// Whether the small motor is on
+    pub small: bool,
+    /// How strong the large motor runs
+    pub large: u8,
+    /// How many polls the step lasts
+    pub polls: u16,
+}
+
+impl RumbleSegment {
+    /// Create a step
+    pub const fn new(small: bool, large: u8, polls: u16) -> Self {
+        Self {
+            small,
+            large,
+            polls,
+        }
+    }
+
+    /// A step with both motors off
+    pub const fn rest(polls: u16) -> Self {
+        Self::new(false, 0, polls)
+    }
+}
+
+const SHORT_PULSE: [RumbleSegment; 1] = [RumbleSegment::new(true, 0xc0, 6)];
+
+const DOUBLE_TAP: [RumbleSegment; 3] = [
+    RumbleSegment::new(true, 0xff, 4),
+    RumbleSegment::rest(4),
+    RumbleSegment::new(true, 0xff, 4),
+];
+
+const RAMP_UP: [RumbleSegment; 4] = [
+    RumbleSegment::new(false, 0x40, 5),
+    RumbleSegment::new(false, 0x80, 5),
+    RumbleSegment::new(false, 0xc0, 5),
+    RumbleSegment::new(true, 0xff, 5),
+];
+
+/// A list of steps, played once or over and over
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RumblePattern<'a> {
+    /// The steps, in order
+    pub segments: &'a [RumbleSegment],
+    /// Whether to start over after the last step
+    pub looping: bool,
+}
+
+impl<'a> RumblePattern<'a> {
+    /// A quick bump, for confirming a button press
+    pub const SHORT_PULSE: RumblePattern<'static> = RumblePattern::new(&SHORT_PULSE, false);
+    /// Two quick bumps
+    pub const DOUBLE_TAP: RumblePattern<'static> = RumblePattern::new(&DOUBLE_TAP, false);
+    /// The large motor building up over 20 polls, with the small one joining
+    /// in at the end
+    pub const RAMP_UP: RumblePattern<'static> = RumblePattern::new(&RAMP_UP, false);
+
+    /// Create a pattern
+    pub const fn new(segments: &'a [RumbleSegment], looping: bool) -> Self {
+        Self {
+            segments,
+            looping,
+        }
+    }
+
+    /// The same pattern, but looping
+    pub const fn looped(self) -> Self {
+        Self::new(self.segments, true)
+    }
+
+    /// How many polls one pass through the pattern takes
+    pub fn polls(&self) -> u32 {
+        self.segments.iter().map(|x| u32::from(x.polls)).sum()
+    }
+}
+
+/// Plays a `RumblePattern`, one poll per tick
+#[derive(Copy, Clone, Debug)]
+pub struct RumblePlayer<'a> {
+    pattern: RumblePattern<'a>,
+    segment: usize,
+    elapsed: u16,
+}
+
+impl<'a> RumblePlayer<'a> {
+    /// Get ready to play a pattern from the start
+    pub fn new(pattern: RumblePattern<'a>) -> Self {
+        let mut player = Self {
+            pattern,
+            segment: 0,
+            elapsed: 0,
+        };
+        player.skip_empty();
+
+        player
+    }
+
+    /// Start the pattern over
+    pub fn restart(&mut self) {
+        *self = Self::new(self.pattern);
+    }
+
+    /// Whether the pattern has played through and isn't looping. A finished
+    /// player keeps the motors off.
+    pub fn is_finished(&self) -> bool {
+        self.segment >= self.pattern.segments.len()
+    }
+
+    /// The motor strengths for this poll as `(small, large)`, moving the
+    /// pattern on by one poll
+    pub fn tick(&mut self) -> (bool, u8) {
+        let segment = match self.pattern.segments.get(self.segment) {
+            Some(x) => *x,
+            None => return (false, 0),
+        };
+
+        self.elapsed += 1;
+        if self.elapsed >= segment.polls {
+            self.segment += 1;
+            self.elapsed = 0;
+            self.skip_empty();
+        }
+
+        (segment.small, segment.large)
+    }
+
+    /// The same as `tick`, but ready to send with a poll
+    pub fn tick_command(&mut self) -> ControlDS {
+        let (small, large) = self.tick();
+
+        ControlDS::new(small, large)
+    }
+
+    /// Move past steps that don't last any polls, looping back to the start
+    /// if the pattern allows it
+    fn skip_empty(&mut self) {
+        let segments = self.pattern.segments;
+
+        loop {
+            while segments.get(self.segment).is_some_and(|x| x.polls == 0) {
+                self.segment += 1;
+            }
+
+            // A pattern that's nothing but empty steps would loop forever
+            if self.segment < segments.len() || !self.pattern.looping || self.pattern.polls() == 0 {
+                return;
+            }
+
+            self.segment = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+
+    fn play(player: &mut RumblePlayer, polls: usize) -> Vec<(bool, u8)> {
+        (0..polls).map(|_| player.tick()).collect()
+    }
+
+    #[test]
+    fn plays_once() {
+        let mut player = RumblePlayer::new(RumblePattern::DOUBLE_TAP);
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&[(true, 0xff); 4]);
+        expected.extend_from_slice(&[(false, 0); 4]);
+        expected.extend_from_slice(&[(true, 0xff); 4]);
+        expected.extend_from_slice(&[(false, 0); 3]);
+
+        assert!(!player.is_finished());
+        assert_eq!(play(&mut player, 15), expected);
+        assert!(player.is_finished());
+
+        player.restart();
+        assert_eq!(player.tick(), (true, 0xff));
+    }
+
+    #[test]
+    fn ramps() {
+        let mut player = RumblePlayer::new(RumblePattern::RAMP_UP);
+        let larges: Vec<u8> = play(&mut player, 21).iter().map(|x| x.1).collect();
+
+        assert_eq!(RumblePattern::RAMP_UP.polls(), 20);
+        assert_eq!(larges[..5], [0x40; 5]);
+        assert_eq!(larges[5..10], [0x80; 5]);
+        assert_eq!(larges[10..15], [0xc0; 5]);
+        assert_eq!(larges[15..20], [0xff; 5]);
+        assert_eq!(larges[20], 0);
+    }
+
+    #[test]
+    fn loops() {
+        let mut player = RumblePlayer::new(RumblePattern::SHORT_PULSE.looped());
+
+        for _ in 0..3 {
+            assert_eq!(play(&mut player, 6), [(true, 0xc0); 6]);
+        }
+        assert!(!player.is_finished());
+
+        let command = player.tick_command();
+        assert_eq!((command.little, command.big), (true, 0xc0));
+    }
+
+    #[test]
+    fn empty_steps_are_skipped() {
+        let segments = [
+            RumbleSegment::rest(0),
+            RumbleSegment::new(true, 0x10, 1),
+            RumbleSegment::new(true, 0x20, 0),
+            RumbleSegment::new(false, 0x30, 2),
+        ];
+        let mut player = RumblePlayer::new(RumblePattern::new(&segments, true));
+        assert_eq!(play(&mut player, 6), [
+            (true, 0x10), (false, 0x30), (false, 0x30),
+            (true, 0x10), (false, 0x30), (false, 0x30),
+        ]);
+
+        let nothing = [RumbleSegment::rest(0); 2];
+        let mut player = RumblePlayer::new(RumblePattern::new(&nothing, true));
+        assert!(player.is_finished());
+        assert_eq!(player.tick(), (false, 0));
+    }
+}
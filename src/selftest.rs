@@ -0,0 +1,228 @@
+//! Self Test
+//! ============================
+//! When nothing answers, it's hard to tell from the outside whether the
+//! controller or the wiring is to blame. `PlayStationPort::self_test` runs a
+//! few checks that only need the port itself to be working:
+//!
+//! * With select released, nothing should drive the data line, so a transfer
+//!   reads back nothing but 0xff. Anything else means a missing pull-up or a
+//!   data line stuck low.
+//! * A poll comes back with an id we know, or cleanly finds nothing plugged
+//!   in.
+//! * With MOSI looped back to MISO, a pattern sent with select released comes
+//!   back as the observer was shown it going out. A bit order that doesn't
+//!   match the SPI hardware comes back reversed. Without the loop nothing
+//!   comes back and there's nothing to check, and with it the poll can't
+//!   pass, since the port only hears itself.
+//!
+//! Each check's result is kept in the `SelfTestReport`, so someone looking at
+//! a unit remotely can tell which one failed.
+
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::OutputPin;
+
+use protocol::*;
+use recovery::RecoveryPolicy;
+use super::{Error, PlayStationPort};
+
+/// Sent to check the flip, no byte of which reads the same reversed. It
+/// doesn't start with an address anything on the bus answers to, in case
+/// select isn't wired where the port thinks.
+const FLIP_PATTERN: [u8; 4] = [0x10, 0x12, 0x34, 0x56];
+
+/// How each of `self_test`'s checks went
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SelfTestReport {
+    /// Whether the data line read high with select released, or `None`
+    /// without a select pin to release
+    pub idle_line: Option<bool>,
+    /// Whether the poll answered with an id we know, or nothing plugged in
+    pub poll: bool,
+    /// The id the poll answered with, if it got that far
+    pub device_id: Option<DeviceId>,
+    /// Whether a looped back pattern came back as it was sent, or `None` if
+    /// it didn't come back at all or there's no select pin to release
+    pub tx_flip: Option<bool>,
+}
+
+impl SelfTestReport {
+    /// Whether every check that could run passed
+    pub fn passed(&self) -> bool {
+        self.idle_line != Some(false) && self.poll && self.tx_flip != Some(false)
+    }
+}
+
+#[allow(deprecated)]
+impl<E, SPI, CS, const N: usize, R> PlayStationPort<SPI, CS, N, R>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+    R: RecoveryPolicy {
+
+    /// Check the wiring, see `SelfTestReport`. Only an SPI error stops the
+    /// checks, and comes back as `Error::Spi`. The poll counts towards
+    /// `stats` like any other.
+    pub fn self_test(&mut self) -> Result<SelfTestReport, Error<E>> {
+        let idle_line = if self.select.is_some() {
+            Some(self.bus_idle::<5>()?)
+        } else {
+            None
+        };
+
+        let mut buffer = [0u8; N];
+        let (poll, device_id) = match self.read_port(None, &mut buffer) {
+            Err(Error::Spi(e)) => return Err(Error::Spi(e)),
+            result => {
+                let id = buffer.get(1).map(|&x| DeviceId::from_byte(x));
+                let known = match id {
+                    Some(DeviceId::Other(_)) | None => false,
+                    Some(_) => true,
+                };
+
+                (result.is_ok() && known, id)
+            },
+        };
+
+        let tx_flip = if self.select.is_some() {
+            self.check_flip()?
+        } else {
+            None
+        };
+
+        Ok(SelfTestReport { idle_line, poll, device_id, tx_flip })
+    }
+
+    /// Send `FLIP_PATTERN` with select released, flipped and shown to the
+    /// observer like any other transfer. Looped back, what the SPI device
+    /// received comes straight back, and flipping it back again has to give
+    /// what the observer was shown as sent.
+    fn check_flip(&mut self) -> Result<Option<bool>, E> {
+        let mut echo = FLIP_PATTERN;
+        let completed = self.released_transfer(&mut echo)?;
+
+        if completed < echo.len() || echo.iter().all(|&x| x == LINE_IDLE) {
+            return Ok(None);
+        }
+
+        Ok(Some(echo == FLIP_PATTERN))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::vec::Vec;
+    use super::*;
+    use core::cell::RefCell;
+    use mock::{self, MockPin, MockSpi};
+    use TransferDirection;
+
+    fn port(responses: &[&[u8]]) -> PlayStationPort<MockSpi, MockPin> {
+        PlayStationPort::new(MockSpi::new(responses), Some(MockPin::default()))
+    }
+
+    #[test]
+    fn healthy_wiring_passes() {
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let mut port = port(&[&[], dualshock]);
+
+        let report = port.self_test().unwrap();
+        assert_eq!(report, SelfTestReport {
+            idle_line: Some(true),
+            poll: true,
+            device_id: Some(DeviceId::DualShockAnalog),
+            tx_flip: None,
+        });
+        assert!(report.passed());
+
+        // The idle and looped back transfers never selected anything
+        assert_eq!(port.select.as_ref().unwrap().levels, [true, false, true]);
+
+        // Nothing plugged in is fine too, and without a select pin there's no
+        // idle line to check
+        let report = mock::port(&[]).self_test().unwrap();
+        assert_eq!((report.idle_line, report.device_id), (None, Some(DeviceId::NotPresent)));
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn stuck_low_data_line_is_flagged() {
+        let stuck: &[u8] = &[0x00; MESSAGE_MAX_LENGTH];
+        let mut port = port(&[stuck, stuck]);
+
+        let report = port.self_test().unwrap();
+        assert_eq!(report.idle_line, Some(false));
+        assert!(!report.poll);
+        assert_eq!(report.device_id, Some(DeviceId::Other(0x00)));
+        assert!(!report.passed());
+        assert_eq!(port.stats().garbage, 1);
+
+        // Something answering only when it's not selected spoils just the
+        // idle check
+        let dualshock: &[u8] = &[0xff, 0x73, 0x5a, 0xff, 0xff, 0x80, 0x80, 0x80, 0x80];
+        let report = self::port(&[stuck, dualshock]).self_test().unwrap();
+        assert_eq!((report.idle_line, report.poll), (Some(false), true));
+    }
+
+    /// MOSI wired straight to MISO, reversing every byte on the way if the
+    /// flip is broken
+    struct Looped {
+        reverse: bool,
+    }
+
+    impl spi::Transfer<u8> for Looped {
+        type Error = ();
+
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+            if self.reverse {
+                flip(words);
+            }
+
+            Ok(words)
+        }
+    }
+
+    #[test]
+    fn broken_flip_is_flagged() {
+        std::thread_local! {
+            static SEEN: RefCell<Vec<(TransferDirection, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        fn record(direction: TransferDirection, bytes: &[u8]) {
+            SEEN.with(|x| x.borrow_mut().push((direction, bytes.to_vec())));
+        }
+
+        for &reverse in [false, true].iter() {
+            let mut port = PlayStationPort::builder(Looped { reverse }, Some(MockPin::default()))
+                .observer(record)
+                .build();
+
+            let report = port.self_test().unwrap();
+            assert_eq!(report.tx_flip, Some(!reverse));
+            assert_eq!(report.idle_line, Some(true));
+
+            // The pattern goes past the observer like any other transfer
+            let seen = SEEN.with(|x| x.replace(Vec::new()));
+            assert_eq!(seen[seen.len() - 2], (TransferDirection::Sent, FLIP_PATTERN.to_vec()));
+
+            // The port only hears its own poll
+            assert!(!report.poll);
+            assert!(!report.passed());
+        }
+
+        let mut report = port(&[]).self_test().unwrap();
+        report.tx_flip = Some(false);
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn spi_errors_stop_the_test() {
+        let mut port = port(&[]);
+        port.dev.fail = true;
+
+        assert_eq!(port.self_test(), Err(Error::Spi(())));
+    }
+}
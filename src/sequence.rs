@@ -0,0 +1,191 @@
+//! Button Sequences
+//! ============================
+//! Watches for buttons pressed one after another in a set order, like cheat
+//! codes. Only presses count, so holding or releasing buttons never breaks a
+//! sequence, but pressing the wrong button or waiting too long does.
+
+use classic::{Button, ButtonChanges, GamepadButtons};
+
+/// Spots a sequence of `N` button presses. Each press has to come within
+/// `max_gap` polls of the one before it.
+#[derive(Debug)]
+pub struct SequenceDetector<const N: usize> {
+    sequence: [Button; N],
+    max_gap: u16,
+    /// How many presses of the sequence have been matched so far
+    progress: usize,
+    /// Polls since the last matched press
+    since_last: u16,
+    /// Buttons from the last `update_buttons`, for finding presses
+    previous: GamepadButtons,
+}
+
+impl<const N: usize> SequenceDetector<N> {
+    /// Watch for `sequence`, allowing up to `max_gap` polls between presses
+    pub const fn new(sequence: [Button; N], max_gap: u16) -> Self {
+        Self {
+            sequence,
+            max_gap,
+            progress: 0,
+            since_last: 0,
+            previous: GamepadButtons::all_released(),
+        }
+    }
+
+    /// Feed in what changed since the last poll. Returns `true` on the poll
+    /// the last press of the sequence lands, after which it starts over.
+    ///
+    /// A wrong press doesn't always start from nothing: if the presses so far
+    /// end with the start of the sequence, those still count. Pressing Up
+    /// three times while watching for Up, Up, Down leaves two presses matched.
+    /// When several buttons go down in the same poll they're taken in the
+    /// order they're sent, which is the order of `Button::ALL`.
+    pub fn update(&mut self, changes: &ButtonChanges) -> bool {
+        if self.progress > 0 {
+            self.since_last = self.since_last.saturating_add(1);
+            if self.since_last > self.max_gap {
+                self.progress = 0;
+            }
+        }
+
+        let mut complete = false;
+        for button in changes.pressed() {
+            self.progress = self.advance(button);
+            self.since_last = 0;
+
+            if N > 0 && self.progress == N {
+                self.progress = 0;
+                complete = true;
+            }
+        }
+
+        complete
+    }
+
+    /// The same as `update`, but works out what changed from the buttons
+    /// passed in last time
+    pub fn update_buttons(&mut self, buttons: &GamepadButtons) -> bool {
+        let changes = buttons.changes_since(&self.previous);
+        self.previous = *buttons;
+
+        self.update(&changes)
+    }
+
+    /// How many presses of the sequence have been matched so far
+    pub fn progress(&self) -> usize {
+        self.progress
+    }
+
+    /// Forget any partly matched sequence
+    pub fn reset(&mut self) {
+        self.progress = 0;
+        self.since_last = 0;
+    }
+
+    /// How many presses are matched after `button`, being the longest start
+    /// of the sequence that the matched presses plus `button` end with
+    // `progress` stays below `N`, so `matched` never runs past either end
+    #[allow(clippy::indexing_slicing)]
+    fn advance(&self, button: Button) -> usize {
+        let mut matched = (self.progress + 1).min(N);
+
+        while matched > 0 {
+            // The last `matched` presses are the last `matched - 1` matched
+            // ones followed by `button`
+            let earlier = &self.sequence[self.progress + 1 - matched..self.progress];
+            if self.sequence[matched - 1] == button && earlier == &self.sequence[..matched - 1] {
+                return matched;
+            }
+
+            matched -= 1;
+        }
+
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KONAMI: [Button; 10] = [
+        Button::Up, Button::Up, Button::Down, Button::Down,
+        Button::Left, Button::Right, Button::Left, Button::Right,
+        Button::Circle, Button::Cross,
+    ];
+
+    // Press and release each button in turn, with `gap` polls of nothing in
+    // between. Returns the polls the detector fired on.
+    fn play<const N: usize>(detector: &mut SequenceDetector<N>, presses: &[Button], gap: usize) -> usize {
+        let mut fired = 0;
+
+        for &button in presses {
+            if detector.update_buttons(&GamepadButtons::from_pressed(&[button])) {
+                fired += 1;
+            }
+            for _ in 0..gap {
+                assert!(!detector.update_buttons(&GamepadButtons::all_released()));
+            }
+        }
+
+        fired
+    }
+
+    #[test]
+    fn completes() {
+        let mut detector = SequenceDetector::new(KONAMI, 10);
+
+        assert_eq!(play(&mut detector, &KONAMI[..9], 3), 0);
+        assert_eq!(detector.progress(), 9);
+        assert_eq!(play(&mut detector, &KONAMI[9..], 3), 1);
+        assert_eq!(detector.progress(), 0);
+
+        // And again straight away
+        assert_eq!(play(&mut detector, &KONAMI, 1), 1);
+    }
+
+    #[test]
+    fn wrong_press_interrupts() {
+        let mut detector = SequenceDetector::new(KONAMI, 10);
+
+        assert_eq!(play(&mut detector, &[Button::Up, Button::Up, Button::Down, Button::Start], 1), 0);
+        assert_eq!(detector.progress(), 0);
+        assert_eq!(play(&mut detector, &KONAMI[2..], 1), 0);
+
+        // An extra Up still leaves the last two counting as the start
+        assert_eq!(play(&mut detector, &[Button::Up, Button::Up, Button::Up], 1), 0);
+        assert_eq!(detector.progress(), 2);
+        assert_eq!(play(&mut detector, &KONAMI[2..], 1), 1);
+
+        // Releases and held buttons don't get in the way
+        let mut held = SequenceDetector::new([Button::Left, Button::Right], 10);
+        assert!(!held.update_buttons(&GamepadButtons::from_pressed(&[Button::L1])));
+        assert!(!held.update_buttons(&GamepadButtons::from_pressed(&[Button::L1, Button::Left])));
+        assert!(!held.update_buttons(&GamepadButtons::from_pressed(&[Button::L1])));
+        assert!(held.update_buttons(&GamepadButtons::from_pressed(&[Button::L1, Button::Right])));
+    }
+
+    #[test]
+    fn waiting_too_long() {
+        let mut detector = SequenceDetector::new(KONAMI, 10);
+
+        // Nine idle polls plus the press itself is the most allowed
+        assert_eq!(play(&mut detector, &KONAMI, 9), 1);
+
+        assert_eq!(play(&mut detector, &KONAMI[..5], 9), 0);
+        assert_eq!(play(&mut detector, &KONAMI[5..], 10), 0);
+        assert_eq!(detector.progress(), 0);
+
+        detector.reset();
+        assert_eq!(play(&mut detector, &KONAMI[..4], 1), 0);
+        detector.reset();
+        assert_eq!(detector.progress(), 0);
+    }
+
+    #[test]
+    fn empty_sequence_never_fires() {
+        let mut detector = SequenceDetector::new([], 10);
+
+        assert_eq!(play(&mut detector, &KONAMI, 1), 0);
+    }
+}
@@ -0,0 +1,248 @@
+//! Bit-banged SPI
+//! ============================
+//! For boards without a spare SPI peripheral. Controllers are happy at a few
+//! hundred kHz, which is slow enough to drive from plain GPIO pins.
+//!
+//! The bus runs in SPI mode 3 (CPOL=1, CPHA=1): the clock idles high, each bit
+//! goes out on the falling edge and is read on the rising one. Bits go least
+//! significant first, the way the controller wants them, so the port shouldn't
+//! reverse them as it does for hardware SPI. Set it up with
+//! `BitOrder::LsbFirst`:
+//!
+//! ```ignore
+//! let spi = SoftSpi::new(clk, mosi, miso, delay);
+//! let port = PlayStationPort::builder(spi, Some(select))
+//!     .bit_order(BitOrder::LsbFirst)
+//!     .build();
+//! ```
+//!
+//! The select and acknowledge lines aren't handled here. Select is the pin passed
+//! to the port.
+
+use core::convert::Infallible;
+
+use hal::blocking::delay::DelayUs;
+use hal::blocking::spi;
+#[allow(deprecated)]
+use hal::digital::{InputPin, OutputPin};
+
+/// Half a clock period at 250 kHz, which every controller keeps up with
+pub const DEFAULT_HALF_PERIOD_US: u32 = 2;
+
+/// An SPI bus driven from GPIO pins
+#[derive(Debug)]
+pub struct SoftSpi<CLK, MOSI, MISO, D> {
+    clk: CLK,
+    mosi: MOSI,
+    miso: MISO,
+    delay: D,
+    half_period_us: u32,
+}
+
+#[allow(deprecated)]
+impl<CLK, MOSI, MISO, D> SoftSpi<CLK, MOSI, MISO, D>
+where
+    CLK: OutputPin,
+    MOSI: OutputPin,
+    MISO: InputPin,
+    D: DelayUs<u32> {
+
+    /// Create a bus clocked at 250 kHz. The clock pin is set high straight
+    /// away since that's where it idles.
+    pub fn new(clk: CLK, mosi: MOSI, miso: MISO, delay: D) -> Self {
+        Self::with_half_period(clk, mosi, miso, delay, DEFAULT_HALF_PERIOD_US)
+    }
+
+    /// Create a bus where each half of a clock cycle takes `half_period_us`
+    /// microseconds
+    pub fn with_half_period(mut clk: CLK, mosi: MOSI, miso: MISO, delay: D, half_period_us: u32) -> Self {
+        clk.set_high();
+
+        Self {
+            clk,
+            mosi,
+            miso,
+            delay,
+            half_period_us,
+        }
+    }
+
+    /// Give back the pins and the delay
+    pub fn release(self) -> (CLK, MOSI, MISO, D) {
+        (self.clk, self.mosi, self.miso, self.delay)
+    }
+
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut read = 0;
+
+        for bit in 0..8 {
+            self.clk.set_low();
+            if byte & (1 << bit) != 0 {
+                self.mosi.set_high();
+            } else {
+                self.mosi.set_low();
+            }
+            self.delay.delay_us(self.half_period_us);
+
+            self.clk.set_high();
+            if self.miso.is_high() {
+                read |= 1 << bit;
+            }
+            self.delay.delay_us(self.half_period_us);
+        }
+
+        read
+    }
+}
+
+#[allow(deprecated)]
+impl<CLK, MOSI, MISO, D> spi::Transfer<u8> for SoftSpi<CLK, MOSI, MISO, D>
+where
+    CLK: OutputPin,
+    MOSI: OutputPin,
+    MISO: InputPin,
+    D: DelayUs<u32> {
+
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        for word in words.iter_mut() {
+            *word = self.exchange(*word);
+        }
+
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use self::std::cell::RefCell;
+    use self::std::vec::Vec;
+    use super::*;
+    use hal::blocking::spi::Transfer;
+    use mock::MockPin;
+    use {BitOrder, PlayStationPort};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    enum Event {
+        Clock(bool),
+        Mosi(bool),
+        Sample,
+        Delay(u32),
+    }
+
+    /// Everything the bus did, plus the bits the controller sends back, first
+    /// one first
+    #[derive(Default)]
+    struct Bus {
+        events: RefCell<Vec<Event>>,
+        miso: RefCell<Vec<bool>>,
+    }
+
+    struct Out<'a>(&'a Bus, fn(bool) -> Event);
+
+    #[allow(deprecated)]
+    impl<'a> OutputPin for Out<'a> {
+        fn set_low(&mut self) {
+            self.0.events.borrow_mut().push((self.1)(false));
+        }
+
+        fn set_high(&mut self) {
+            self.0.events.borrow_mut().push((self.1)(true));
+        }
+    }
+
+    struct In<'a>(&'a Bus);
+
+    #[allow(deprecated)]
+    impl<'a> InputPin for In<'a> {
+        fn is_high(&self) -> bool {
+            self.0.events.borrow_mut().push(Event::Sample);
+            let mut miso = self.0.miso.borrow_mut();
+            if miso.is_empty() { true } else { miso.remove(0) }
+        }
+
+        fn is_low(&self) -> bool {
+            !self.is_high()
+        }
+    }
+
+    struct Delay<'a>(&'a Bus);
+
+    impl<'a> DelayUs<u32> for Delay<'a> {
+        fn delay_us(&mut self, us: u32) {
+            self.0.events.borrow_mut().push(Event::Delay(us));
+        }
+    }
+
+    fn spi(bus: &Bus) -> SoftSpi<Out<'_>, Out<'_>, In<'_>, Delay<'_>> {
+        SoftSpi::new(Out(bus, Event::Clock), Out(bus, Event::Mosi), In(bus), Delay(bus))
+    }
+
+    /// The MOSI level at each rising edge of the clock
+    fn sent_bits(events: &[Event]) -> Vec<bool> {
+        let mut mosi = false;
+        let mut bits = Vec::new();
+        for event in events {
+            match *event {
+                Event::Mosi(x) => mosi = x,
+                Event::Clock(true) => bits.push(mosi),
+                _ => (),
+            }
+        }
+
+        bits
+    }
+
+    /// The bits of `byte` in the order they go over the wire
+    fn bits(byte: u8) -> Vec<bool> {
+        (0..8).map(|x| byte & (1 << x) != 0).collect()
+    }
+
+    #[test]
+    fn mode_three_lsb_first() {
+        let bus = Bus::default();
+        bus.miso.borrow_mut().extend(bits(0xa5));
+        let mut spi = spi(&bus);
+
+        // The clock starts out idling high
+        assert_eq!(bus.events.borrow_mut().drain(..).collect::<Vec<_>>(), [Event::Clock(true)]);
+
+        let mut words = [0x42];
+        assert_eq!(spi.transfer(&mut words), Ok(&[0xa5][..]));
+
+        let events = bus.events.borrow();
+        assert_eq!(sent_bits(&events), bits(0x42));
+
+        // Each bit is one falling edge with the data, then a rising edge with
+        // the sample, leaving the clock high
+        for (i, cycle) in events.chunks(6).enumerate() {
+            assert_eq!(cycle, [
+                Event::Clock(false),
+                Event::Mosi(bits(0x42)[i]),
+                Event::Delay(2),
+                Event::Clock(true),
+                Event::Sample,
+                Event::Delay(2),
+            ]);
+        }
+        assert_eq!(events.len(), 8 * 6);
+    }
+
+    #[test]
+    fn lsb_first_through_the_port() {
+        let bus = Bus::default();
+        let mut port = PlayStationPort::builder(spi(&bus), None::<MockPin>).bit_order(BitOrder::LsbFirst).build();
+        bus.events.borrow_mut().clear();
+
+        let mut result = [0u8; 1];
+        port.send_command(&[0x00], &mut result).unwrap();
+
+        // The first byte picks multi-tap port A, 0x01, and the controller
+        // sees its lowest bit first
+        let wire = [true, false, false, false, false, false, false, false];
+        assert_eq!(sent_bits(&bus.events.borrow()), wire);
+    }
+}
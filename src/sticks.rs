@@ -0,0 +1,929 @@
+//! Analog Stick Helpers
+//! ============================
+//! Sticks rarely sit perfectly still at center, and most programs end up
+//! filtering them the same way. These helpers work on the signed stick values
+//! (see `dualshock::signed`), where 0 is center, apart from calibration which
+//! works on the raw bytes.
+
+use dualshock::{DualShock, STICK_CENTER};
+
+/// One axis of one of the analog sticks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Axis {
+    /// Left stick, left and right
+    LeftX,
+    /// Left stick, up and down
+    LeftY,
+    /// Right stick, left and right
+    RightX,
+    /// Right stick, up and down
+    RightY,
+}
+
+/// Where one axis rests and how far it actually travels, as raw bytes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisCalibration {
+    /// Where the axis sits when the stick is let go
+    pub center: u8,
+    /// The lowest value the axis reaches
+    pub min: u8,
+    /// The highest value the axis reaches
+    pub max: u8,
+}
+
+/// An axis that already rests at `STICK_CENTER` and covers the full range
+impl Default for AxisCalibration {
+    fn default() -> Self {
+        Self {
+            center: STICK_CENTER,
+            min: 0x00,
+            max: 0xff,
+        }
+    }
+}
+
+impl AxisCalibration {
+    /// Correct a raw reading so the resting position comes out as
+    /// `STICK_CENTER` and the ends of the range come out as 0x00 and 0xff
+    pub fn apply(&self, raw: u8) -> u8 {
+        let (raw, center) = (i32::from(raw), i32::from(self.center));
+
+        let corrected = if raw >= center {
+            let span = i32::from(self.max) - center;
+            if span <= 0 {
+                // Nothing was learned on this side, so anything past center
+                // is already further than the stick was seen to go
+                if raw == center { 0x80 } else { 0xff }
+            } else {
+                0x80 + (raw - center) * 0x7f / span
+            }
+        } else {
+            let span = center - i32::from(self.min);
+            if span <= 0 {
+                0x00
+            } else {
+                0x80 - (center - raw) * 0x80 / span
+            }
+        };
+
+        if corrected > 0xff {
+            0xff
+        } else if corrected < 0 {
+            0
+        } else {
+            corrected as u8
+        }
+    }
+}
+
+/// Corrects for sticks that don't rest at center or don't reach the ends of
+/// their range, which gets common as pads wear. This is plain data, so it can
+/// be stored (say in flash) and loaded again on the next boot.
+///
+/// To calibrate, call `learn_center` with the sticks let go, then
+/// `reset_range` followed by `learn_range` on every poll while the sticks are
+/// swept around their edges.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StickCalibration {
+    /// Calibration for each axis, in the order of `Axis`
+    pub axes: [AxisCalibration; 4],
+}
+
+impl StickCalibration {
+    /// The calibration for one axis
+    // `Axis` has four variants, one for each entry
+    #[allow(clippy::indexing_slicing)]
+    pub fn axis(&self, axis: Axis) -> &AxisCalibration {
+        &self.axes[axis as usize]
+    }
+
+    /// The calibration for one axis, for changing it
+    #[allow(clippy::indexing_slicing)]
+    pub fn axis_mut(&mut self, axis: Axis) -> &mut AxisCalibration {
+        &mut self.axes[axis as usize]
+    }
+
+    /// Take the current stick positions as the resting centers
+    pub fn learn_center(&mut self, ds: &DualShock) {
+        for &(axis, value) in readings(ds).iter() {
+            self.axis_mut(axis).center = value;
+        }
+    }
+
+    /// Forget the learned ranges, so `learn_range` starts from the centers
+    pub fn reset_range(&mut self) {
+        for axis in self.axes.iter_mut() {
+            axis.min = axis.center;
+            axis.max = axis.center;
+        }
+    }
+
+    /// Widen the ranges to include the current stick positions
+    pub fn learn_range(&mut self, ds: &DualShock) {
+        for &(axis, value) in readings(ds).iter() {
+            let axis = self.axis_mut(axis);
+            if value < axis.min {
+                axis.min = value;
+            }
+            if value > axis.max {
+                axis.max = value;
+            }
+        }
+    }
+
+    /// Correct a raw reading from the given axis
+    pub fn apply(&self, raw: u8, axis: Axis) -> u8 {
+        self.axis(axis).apply(raw)
+    }
+}
+
+fn readings(ds: &DualShock) -> [(Axis, u8); 4] {
+    [
+        (Axis::LeftX, ds.lx),
+        (Axis::LeftY, ds.ly),
+        (Axis::RightX, ds.rx),
+        (Axis::RightY, ds.ry),
+    ]
+}
+
+/// Follows sticks whose resting position wanders while the pad is in use, say
+/// as it warms up. Where `StickCalibration` is learned once, this keeps
+/// nudging its idea of each axis' center whenever the sticks are idle and
+/// shifts every reading by how far that center has moved.
+///
+/// Call `update` on every poll to have it decide for itself when the sticks
+/// are idle, or `adapt` during a calibration window when you know they are,
+/// along with `apply` for the rest of the time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DriftCompensator {
+    /// Center estimate for each axis in the order of `Axis`, in 256ths of a
+    /// step
+    centers: [u16; 4],
+    /// Each adaptation closes 1/2^shift of the gap to the current reading
+    shift: u8,
+    /// How close to the corrected center the sticks must stay to count as idle
+    idle_tolerance: u8,
+    /// How many polls in a row the sticks must be idle before `update` adapts
+    idle_polls: u16,
+    /// How many polls in a row the sticks have been idle so far
+    idle_count: u16,
+}
+
+/// Adapts slowly, and only once the sticks have sat within 24 of center for
+/// about a second of polling
+impl Default for DriftCompensator {
+    fn default() -> Self {
+        Self::new(6, 24, 60)
+    }
+}
+
+impl DriftCompensator {
+    /// Create a compensator that starts out assuming the sticks rest at
+    /// `STICK_CENTER`. Each adaptation moves the center 1/2^`shift` of the way
+    /// to the current reading, and never more than one step, so larger shifts
+    /// follow drift more slowly. `update` only adapts once the corrected
+    /// sticks have been within `idle_tolerance` of center for `idle_polls`
+    /// polls in a row.
+    pub const fn new(shift: u8, idle_tolerance: u8, idle_polls: u16) -> Self {
+        let center = (STICK_CENTER as u16) << 8;
+
+        Self {
+            centers: [center; 4],
+            shift,
+            idle_tolerance,
+            idle_polls,
+            idle_count: 0,
+        }
+    }
+
+    /// Where the given axis is currently thought to rest, as a raw byte
+    // `Axis` has four variants, one for each center
+    #[allow(clippy::indexing_slicing)]
+    pub fn center(&self, axis: Axis) -> u8 {
+        ((self.centers[axis as usize] + 0x80) >> 8) as u8
+    }
+
+    /// Move the center estimates a little towards the current stick
+    /// positions. Only call this while the sticks are known to be let go.
+    #[allow(clippy::indexing_slicing)]
+    pub fn adapt(&mut self, ds: &DualShock) {
+        for &(axis, value) in readings(ds).iter() {
+            let center = &mut self.centers[axis as usize];
+            let gap = (i32::from(value) << 8) - i32::from(*center);
+
+            // Shifting a negative gap rounds away from zero, so divide to keep
+            // both directions the same
+            let step = (gap / (1 << u32::from(self.shift.min(16)))).clamp(-0x100, 0x100);
+
+            *center = (i32::from(*center) + step) as u16;
+        }
+    }
+
+    /// The stick positions with the drift taken out
+    pub fn apply(&self, ds: &DualShock) -> DualShock {
+        let fix = |value: u8, axis: Axis| {
+            let offset = i32::from(self.center(axis)) - i32::from(STICK_CENTER);
+
+            (i32::from(value) - offset).clamp(0x00, 0xff) as u8
+        };
+
+        DualShock {
+            lx: fix(ds.lx, Axis::LeftX),
+            ly: fix(ds.ly, Axis::LeftY),
+            rx: fix(ds.rx, Axis::RightX),
+            ry: fix(ds.ry, Axis::RightY),
+            ..*ds
+        }
+    }
+
+    /// Correct a poll, adapting the centers first if the sticks have been
+    /// idle long enough. Pushing a stick past the idle tolerance starts the
+    /// count over, so holding a stick slightly off center for a long time is
+    /// never mistaken for drift.
+    pub fn update(&mut self, ds: &DualShock) -> DualShock {
+        if self.apply(ds).sticks_neutral(self.idle_tolerance) {
+            self.idle_count = self.idle_count.saturating_add(1);
+        } else {
+            self.idle_count = 0;
+        }
+
+        if self.idle_count >= self.idle_polls {
+            self.adapt(ds);
+        }
+
+        self.apply(ds)
+    }
+}
+
+/// Smooths out jitter on one axis with an exponential moving average. Each
+/// reading moves the output 1/2^shift of the way towards it, so larger shifts
+/// are smoother but slower to follow the stick.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisFilter {
+    shift: u8,
+    /// The average in 256ths of a step, or `None` before the first reading
+    state: Option<u16>,
+}
+
+impl AxisFilter {
+    /// Create a filter. A shift of 0 passes readings straight through, and
+    /// shifts past 8 are treated as 8.
+    pub const fn new(shift: u8) -> Self {
+        Self {
+            shift: if shift > 8 { 8 } else { shift },
+            state: None,
+        }
+    }
+
+    /// Feed in a raw reading and get back the smoothed one. The first reading
+    /// after creating or resetting the filter comes straight through.
+    pub fn apply(&mut self, raw: u8) -> u8 {
+        let target = i32::from(raw) << 8;
+        let state = match self.state {
+            Some(state) => {
+                // Round the step away from zero so the average always ends
+                // up exactly on a steady reading instead of stopping short
+                let gap = target - i32::from(state);
+                let round = (1 << self.shift) - 1;
+                let step = if gap >= 0 { (gap + round) >> self.shift } else { -((round - gap) >> self.shift) };
+
+                (i32::from(state) + step) as u16
+            },
+            None => target as u16,
+        };
+
+        self.state = Some(state);
+        self.value().unwrap_or(raw)
+    }
+
+    /// The current smoothed reading, rounded to the nearest step
+    pub fn value(&self) -> Option<u8> {
+        self.state.map(|x| ((u32::from(x) + 0x80) >> 8) as u8)
+    }
+
+    /// Forget the average, so the next reading comes straight through
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// An `AxisFilter` for each axis of both sticks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StickFilter {
+    /// The filter for each axis, in the order of `Axis`
+    pub axes: [AxisFilter; 4],
+}
+
+impl StickFilter {
+    /// Create filters for all four axes with the same shift
+    pub const fn new(shift: u8) -> Self {
+        Self { axes: [AxisFilter::new(shift); 4] }
+    }
+
+    /// Smooth the sticks. The buttons are passed through untouched.
+    pub fn apply(&mut self, ds: &DualShock) -> DualShock {
+        let [lx, ly, rx, ry] = &mut self.axes;
+
+        DualShock {
+            lx: lx.apply(ds.lx),
+            ly: ly.apply(ds.ly),
+            rx: rx.apply(ds.rx),
+            ry: ry.apply(ds.ry),
+            ..*ds
+        }
+    }
+
+    /// Forget the averages on all four axes
+    pub fn reset(&mut self) {
+        for axis in self.axes.iter_mut() {
+            axis.reset();
+        }
+    }
+}
+
+/// Ignores small stick movements around center. Anything outside the dead
+/// zone is rescaled so a stick pushed all the way still reads as full
+/// deflection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeadZone {
+    /// How far from center the stick has to move before it counts, out of 127
+    pub radius: u8,
+}
+
+impl DeadZone {
+    /// Create a dead zone of the given radius
+    pub const fn new(radius: u8) -> Self {
+        Self { radius }
+    }
+
+    /// Apply the dead zone to a stick's distance from center, so a stick
+    /// pushed diagonally is treated the same as one pushed straight. This is
+    /// what you want most of the time.
+    pub fn apply(&self, x: i8, y: i8) -> (i8, i8) {
+        let radius = i32::from(self.radius);
+        if radius >= 127 {
+            return (0, 0);
+        }
+
+        let (x, y) = (i32::from(x), i32::from(y));
+        let magnitude = isqrt((x * x + y * y) as u32) as i32;
+        if magnitude <= radius {
+            return (0, 0);
+        }
+
+        // Stretch the distance past the dead zone back out to the full range,
+        // then point it the way the stick was pushed. Corners come out past
+        // 127 and get clamped, so diagonals still reach the corners.
+        let scaled = (magnitude - radius) * 127 / (127 - radius);
+
+        (clamp(x * scaled / magnitude), clamp(y * scaled / magnitude))
+    }
+
+    /// Apply the dead zone to each axis on its own. This is cheaper, but makes
+    /// the stick snap to the axes when pushed close to straight.
+    pub fn apply_axial(&self, x: i8, y: i8) -> (i8, i8) {
+        (self.apply_axis(x), self.apply_axis(y))
+    }
+
+    /// Apply the dead zone to a single axis
+    pub fn apply_axis(&self, value: i8) -> i8 {
+        let radius = i32::from(self.radius);
+        if radius >= 127 {
+            return 0;
+        }
+
+        let value = i32::from(value);
+        if value.abs() <= radius {
+            return 0;
+        }
+
+        let scaled = (value.abs() - radius) * 127 / (127 - radius);
+
+        clamp(scaled * value.signum())
+    }
+}
+
+/// Maps a raw axis onto an output range, like a servo's pulse width or a
+/// PWM duty cycle. Each side of center is scaled on its own, so the input's
+/// center always lands on `out_center` even when it isn't halfway, and the
+/// output doesn't have to be centered either. Works on raw bytes, so it can
+/// take the output of `AxisCalibration::apply`.
+///
+/// All the math is integer, rounded to the nearest step. Readings past
+/// `in_min` or `in_max` saturate at the ends of the output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisMap {
+    /// The raw reading that maps to `out_min`
+    pub in_min: u8,
+    /// The raw reading that maps to `out_center`
+    pub in_center: u8,
+    /// The raw reading that maps to `out_max`
+    pub in_max: u8,
+    /// Output at `in_min`
+    pub out_min: u16,
+    /// Output at `in_center`, and anywhere inside the deadband
+    pub out_center: u16,
+    /// Output at `in_max`
+    pub out_max: u16,
+    /// How far from `in_center` the reading has to move before the output
+    /// does. The rest of each side is stretched to still reach the end.
+    pub deadband: u8,
+    /// Swap the ends, so `in_min` maps to `out_max` and the other way round
+    pub inverted: bool,
+}
+
+impl AxisMap {
+    /// Map the full raw range, centered at `STICK_CENTER`, onto `out_min` to
+    /// `out_max` through `out_center`, with no deadband
+    pub const fn new(out_min: u16, out_center: u16, out_max: u16) -> Self {
+        Self {
+            in_min: 0x00,
+            in_center: STICK_CENTER,
+            in_max: 0xff,
+            out_min,
+            out_center,
+            out_max,
+            deadband: 0,
+            inverted: false,
+        }
+    }
+
+    /// Where `raw` lands in the output range
+    pub fn map(&self, raw: u8) -> u16 {
+        let (raw, center, deadband) = (i32::from(raw), i32::from(self.in_center), i32::from(self.deadband));
+
+        let (travel, span, high) = if raw > center + deadband {
+            (raw - center - deadband, i32::from(self.in_max) - center - deadband, true)
+        } else if raw < center - deadband {
+            (center - deadband - raw, center - deadband - i32::from(self.in_min), false)
+        } else {
+            return self.out_center;
+        };
+
+        let end = if high != self.inverted { self.out_max } else { self.out_min };
+        if travel >= span {
+            // Covers a side with no room past the deadband, too
+            return end;
+        }
+
+        let reach = (i32::from(end) - i32::from(self.out_center)) * travel;
+        let half = span / 2 * reach.signum();
+
+        (i32::from(self.out_center) + (reach + half) / span) as u16
+    }
+}
+
+/// The direction and distance a stick is pushed, from its signed position.
+///
+/// The angle is in degrees from -180 to 180, measured the same way as
+/// `atan2(y, x)`. Since the Y axis isn't flipped, 0 is right, 90 is down,
+/// -90 is up and 180 is left. The angle uses an integer approximation of
+/// atan2 that is always within 1 degree of the exact answer. A centered stick
+/// has an angle of 0.
+///
+/// The magnitude is the distance from center, rounded down. Straight pushes
+/// reach 127 or 128, while a stick pushed into a corner can reach 181.
+pub fn stick_polar(x: i8, y: i8) -> (i16, u8) {
+    let (x, y) = (i32::from(x), i32::from(y));
+    let magnitude = isqrt((x * x + y * y) as u32) as u8;
+
+    let (ax, ay) = (x.abs(), y.abs());
+    if ax == 0 && ay == 0 {
+        return (0, 0);
+    }
+
+    // Work out the angle in the first octant, in tenths of a degree, then
+    // mirror it into place
+    let mut tenths = if ay <= ax {
+        atan_tenths(ay, ax)
+    } else {
+        900 - atan_tenths(ax, ay)
+    };
+    if x < 0 {
+        tenths = 1800 - tenths;
+    }
+
+    let degrees = ((tenths + 5) / 10) as i16;
+    if y < 0 { (-degrees, magnitude) } else { (degrees, magnitude) }
+}
+
+/// The same as `stick_polar`, but with exact floating point math. The angle is
+/// still in degrees.
+#[cfg(feature = "float")]
+pub fn stick_polar_f32(x: i8, y: i8) -> (f32, f32) {
+    let (x, y) = (f32::from(x), f32::from(y));
+
+    (libm::atan2f(y, x).to_degrees(), libm::sqrtf(x * x + y * y))
+}
+
+/// atan(n / d) in tenths of a degree for 0 <= n <= d, using the approximation
+/// atan(r) = 45r + 15.64r(1 - r) degrees, which is off by at most 0.36 degrees
+/// here once rounding is included
+fn atan_tenths(n: i32, d: i32) -> i32 {
+    let ratio = n * 1024 / d;
+
+    (450 * ratio * 1024 + 156 * ratio * (1024 - ratio)) / (1024 * 1024)
+}
+
+/// Integer square root, rounded down
+pub(crate) fn isqrt(value: u32) -> u32 {
+    if value < 2 {
+        return value;
+    }
+
+    let mut root = value;
+    let mut next = (root + value / root) / 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+
+    root
+}
+
+fn clamp(value: i32) -> i8 {
+    if value > 127 {
+        127
+    } else if value < -128 {
+        -128
+    } else {
+        value as i8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::Button;
+
+    const ZONE: DeadZone = DeadZone::new(20);
+
+    #[test]
+    fn square_roots() {
+        for value in 0..40_000 {
+            let root = isqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value);
+        }
+    }
+
+    #[test]
+    fn inside_zone() {
+        assert_eq!(ZONE.apply(0, 0), (0, 0));
+        assert_eq!(ZONE.apply(20, 0), (0, 0));
+        assert_eq!(ZONE.apply(-14, 14), (0, 0));
+        assert_eq!(ZONE.apply_axial(-20, 20), (0, 0));
+    }
+
+    #[test]
+    fn just_outside_zone() {
+        assert_eq!(ZONE.apply(22, 0), (2, 0));
+        assert_eq!(ZONE.apply(0, -22), (0, -2));
+        assert_eq!(ZONE.apply_axial(22, -22), (2, -2));
+
+        // On the diagonal this is already outside the radius even though
+        // neither axis is on its own
+        let (x, y) = ZONE.apply(16, 16);
+        assert!(x > 0 && x == y);
+        assert_eq!(ZONE.apply_axial(16, 16), (0, 0));
+    }
+
+    #[test]
+    fn full_deflection() {
+        assert_eq!(ZONE.apply(127, 0), (127, 0));
+        assert_eq!(ZONE.apply(0, -128), (0, -128));
+        assert_eq!(ZONE.apply(127, 127), (127, 127));
+        assert_eq!(ZONE.apply(-128, -128), (-128, -128));
+        assert_eq!(ZONE.apply(127, -128), (127, -128));
+        assert_eq!(ZONE.apply_axial(127, -128), (127, -128));
+    }
+
+    #[test]
+    fn no_zone_is_untouched() {
+        let zone = DeadZone::new(0);
+        for &(x, y) in [(0, 0), (1, -1), (64, 100), (-128, 127)].iter() {
+            assert_eq!(zone.apply(x, y), (x, y));
+        }
+    }
+
+    // Numbers from a well used pad, with sticks that rest off center and stop
+    // short of the ends
+    fn worn_pad() -> StickCalibration {
+        let mut calibration = StickCalibration::default();
+
+        calibration.learn_center(&DualShock { lx: 0x7a, ly: 0x86, rx: 0x80, ry: 0x7e, ..Default::default() });
+        calibration.reset_range();
+        calibration.learn_range(&DualShock { lx: 0x12, ly: 0x20, rx: 0x05, ry: 0x10, ..Default::default() });
+        calibration.learn_range(&DualShock { lx: 0xe9, ly: 0xf0, rx: 0xfa, ry: 0xe0, ..Default::default() });
+        calibration.learn_range(&DualShock { lx: 0x80, ly: 0x80, rx: 0x80, ry: 0x80, ..Default::default() });
+
+        calibration
+    }
+
+    #[test]
+    fn calibration_learns_center_and_range() {
+        let calibration = worn_pad();
+
+        assert_eq!(*calibration.axis(Axis::LeftX), AxisCalibration { center: 0x7a, min: 0x12, max: 0xe9 });
+        assert_eq!(*calibration.axis(Axis::LeftY), AxisCalibration { center: 0x86, min: 0x20, max: 0xf0 });
+        assert_eq!(*calibration.axis(Axis::RightX), AxisCalibration { center: 0x80, min: 0x05, max: 0xfa });
+        assert_eq!(*calibration.axis(Axis::RightY), AxisCalibration { center: 0x7e, min: 0x10, max: 0xe0 });
+    }
+
+    #[test]
+    fn calibration_corrects_worn_pad() {
+        let calibration = worn_pad();
+
+        assert_eq!(calibration.apply(0x7a, Axis::LeftX), 0x80);
+        assert_eq!(calibration.apply(0x86, Axis::LeftY), 0x80);
+        assert_eq!(calibration.apply(0x7e, Axis::RightY), 0x80);
+
+        assert_eq!(calibration.apply(0x12, Axis::LeftX), 0x00);
+        assert_eq!(calibration.apply(0xe9, Axis::LeftX), 0xff);
+        assert_eq!(calibration.apply(0x20, Axis::LeftY), 0x00);
+        assert_eq!(calibration.apply(0xf0, Axis::LeftY), 0xff);
+
+        // Past the learned range just pins to the ends
+        assert_eq!(calibration.apply(0x00, Axis::RightX), 0x00);
+        assert_eq!(calibration.apply(0xff, Axis::RightX), 0xff);
+    }
+
+    #[test]
+    fn default_calibration_is_untouched() {
+        let calibration = StickCalibration::default();
+
+        for raw in 0..=0xff {
+            assert_eq!(calibration.apply(raw, Axis::LeftX), raw);
+        }
+    }
+
+    fn exact_angle(x: i8, y: i8) -> f32 {
+        ::libm::atan2f(f32::from(y), f32::from(x)).to_degrees()
+    }
+
+    #[test]
+    fn polar_compass_points() {
+        let points = [
+            (127, 0, 0), (127, 127, 45), (0, 127, 90), (-127, 127, 135),
+            (-127, 0, 180), (-127, -127, -135), (0, -127, -90), (127, -127, -45),
+        ];
+
+        for &(x, y, angle) in points.iter() {
+            assert_eq!(stick_polar(x, y).0, angle);
+            assert!((exact_angle(x, y) - f32::from(angle)).abs() < 1e-3);
+        }
+
+        assert_eq!(stick_polar(0, 0), (0, 0));
+        assert_eq!(stick_polar(127, 0).1, 127);
+        assert_eq!(stick_polar(0, -128).1, 128);
+        assert_eq!(stick_polar(-128, -128).1, 181);
+    }
+
+    #[test]
+    fn polar_odd_angles() {
+        let points = [(100, 30), (17, -90), (-128, 5), (3, 1), (-50, -128), (1, -127), (-128, -1)];
+
+        for &(x, y) in points.iter() {
+            let (angle, _) = stick_polar(x, y);
+            assert!((f32::from(angle) - exact_angle(x, y)).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn polar_error_bound() {
+        for x in -128..=127 {
+            for y in -128..=127 {
+                if x == 0 && y == 0 {
+                    continue;
+                }
+
+                let (angle, magnitude) = stick_polar(x, y);
+                assert!((f32::from(angle) - exact_angle(x, y)).abs() <= 1.0);
+
+                let exact = libm::sqrtf(f32::from(x) * f32::from(x) + f32::from(y) * f32::from(y));
+                assert_eq!(u32::from(magnitude), exact as u32);
+            }
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn polar_float() {
+        let (angle, magnitude) = stick_polar_f32(0, -100);
+
+        assert!((angle + 90.0).abs() < 1e-3);
+        assert!((magnitude - 100.0).abs() < 1e-3);
+    }
+
+    fn sticks(lx: u8, ly: u8, rx: u8, ry: u8) -> DualShock {
+        DualShock { lx, ly, rx, ry, ..Default::default() }
+    }
+
+    #[test]
+    fn drift_is_followed() {
+        let mut drift = DriftCompensator::new(4, 24, 30);
+
+        // The left stick creeps from center up to 0x98 and the right one down
+        // to 0x6c, a step at a time
+        for poll in 0..3000u32 {
+            let creep = (poll / 120) as u8;
+            let corrected = drift.update(&sticks(0x80 + creep, 0x80, 0x80 - creep, 0x80));
+
+            if poll > 100 {
+                assert!(corrected.sticks_neutral(2), "poll {}: {:?}", poll, corrected);
+            }
+        }
+
+        assert_eq!(drift.center(Axis::LeftX), 0x98);
+        assert_eq!(drift.center(Axis::RightX), 0x68);
+        assert_eq!(drift.center(Axis::LeftY), 0x80);
+    }
+
+    #[test]
+    fn deflections_pass_through() {
+        let mut drift = DriftCompensator::new(4, 24, 30);
+        for _ in 0..1000 {
+            drift.update(&sticks(0x90, 0x80, 0x80, 0x80));
+        }
+        assert_eq!(drift.center(Axis::LeftX), 0x90);
+
+        // Full pushes keep their distance from the drifted center, and
+        // holding them doesn't drag the center along
+        for _ in 0..1000 {
+            let corrected = drift.update(&sticks(0xd0, 0x00, 0x80, 0xff));
+            assert_eq!((corrected.lx, corrected.ly, corrected.ry), (0xc0, 0x00, 0xff));
+        }
+        assert_eq!(drift.center(Axis::LeftX), 0x90);
+        assert_eq!(drift.center(Axis::LeftY), 0x80);
+
+        // Neither does a brief touch inside the idle tolerance
+        let mut touched = drift;
+        for _ in 0..29 {
+            touched.update(&sticks(0xa0, 0x80, 0x80, 0x80));
+        }
+        assert_eq!(touched.center(Axis::LeftX), 0x90);
+    }
+
+    #[test]
+    fn adaptation_is_bounded() {
+        let mut drift = DriftCompensator::new(0, 0, 0);
+
+        // Even with no smoothing the center moves at most a step per poll
+        drift.adapt(&sticks(0xff, 0x00, 0x80, 0x80));
+        assert_eq!(drift.center(Axis::LeftX), 0x81);
+        assert_eq!(drift.center(Axis::LeftY), 0x7f);
+        assert_eq!(drift.center(Axis::RightX), 0x80);
+
+        let untouched = DriftCompensator::default();
+        assert_eq!(untouched.apply(&sticks(0x12, 0x34, 0x56, 0x78)), sticks(0x12, 0x34, 0x56, 0x78));
+    }
+
+    #[test]
+    fn filter_step_response() {
+        let mut filter = AxisFilter::new(2);
+        assert_eq!(filter.value(), None);
+        assert_eq!(filter.apply(0x80), 0x80);
+
+        // Each poll covers a quarter of what's left, never overshooting, and
+        // lands exactly on the new reading
+        let mut outputs = [0u8; 30];
+        for output in outputs.iter_mut() {
+            *output = filter.apply(0xc0);
+        }
+        assert_eq!(outputs[..4], [0x90, 0x9c, 0xa5, 0xac]);
+        assert!(outputs.windows(2).all(|x| x[0] <= x[1]));
+        assert_eq!(outputs[29], 0xc0);
+
+        for output in outputs.iter_mut() {
+            *output = filter.apply(0x00);
+        }
+        assert_eq!(outputs[0], 0x90);
+        assert!(outputs.windows(2).all(|x| x[0] >= x[1]));
+        assert_eq!(outputs[29], 0x00);
+    }
+
+    #[test]
+    fn filter_settles_exactly() {
+        for shift in 0..=8 {
+            for &(from, to) in [(0x00, 0xff), (0xff, 0x00), (0x80, 0x81), (0x81, 0x80), (0x13, 0x7e)].iter() {
+                let mut filter = AxisFilter::new(shift);
+                filter.apply(from);
+
+                let mut last = from;
+                for _ in 0..4000 {
+                    last = filter.apply(to);
+                }
+                assert_eq!(last, to, "shift {} from {} to {}", shift, from, to);
+            }
+        }
+
+        // No shift is no filtering at all
+        let mut filter = AxisFilter::new(0);
+        filter.apply(0x00);
+        assert_eq!(filter.apply(0xff), 0xff);
+    }
+
+    #[test]
+    fn stick_filter_passes_buttons() {
+        let mut filter = StickFilter::new(1);
+        filter.apply(&sticks(0x80, 0x80, 0x80, 0x80));
+
+        let pressed = DualShock::builder().press(Button::Triangle).stick_left(0x00, 0xff).stick_right(0x40, 0xc0).build();
+        let smoothed = filter.apply(&pressed);
+        assert_eq!(smoothed.buttons, pressed.buttons);
+        assert_eq!((smoothed.lx, smoothed.ly, smoothed.rx, smoothed.ry), (0x40, 0xc0, 0x60, 0xa0));
+
+        filter.reset();
+        assert_eq!(filter.apply(&pressed), pressed);
+    }
+
+    #[test]
+    fn everything_dead() {
+        assert_eq!(DeadZone::new(200).apply(127, 127), (0, 0));
+        assert_eq!(DeadZone::new(127).apply_axis(-128), 0);
+    }
+
+    #[test]
+    fn axis_maps_onto_a_servo() {
+        // Pulse widths in microseconds
+        let servo = AxisMap::new(1000, 1500, 2000);
+        assert_eq!(servo.map(0x00), 1000);
+        assert_eq!(servo.map(0x80), 1500);
+        assert_eq!(servo.map(0xff), 2000);
+
+        // 128 steps below center and 127 above, each rounded
+        assert_eq!(servo.map(0x40), 1250);
+        assert_eq!(servo.map(0xc0), 1752);
+        assert_eq!(servo.map(0x81), 1504);
+
+        // An output that isn't centered, and one that runs downwards
+        assert_eq!(AxisMap::new(0, 100, 1000).map(0x40), 50);
+        assert_eq!(AxisMap::new(0, 100, 1000).map(0xff), 1000);
+        assert_eq!(AxisMap::new(2000, 1500, 1000).map(0x00), 2000);
+        assert_eq!(AxisMap::new(2000, 1500, 1000).map(0xc0), 1248);
+    }
+
+    #[test]
+    fn axis_map_deadband_and_saturation() {
+        let map = AxisMap {
+            in_min: 0x10,
+            in_center: 0x78,
+            in_max: 0xe0,
+            deadband: 8,
+            ..AxisMap::new(0, 500, 1000)
+        };
+
+        // Up to the edge of the deadband and just past it
+        assert_eq!(map.map(0x78 + 8), 500);
+        assert_eq!(map.map(0x78 - 8), 500);
+        assert_eq!(map.map(0x78 + 9), 505);
+        assert_eq!(map.map(0x78 - 9), 495);
+
+        // The ends, and past them
+        assert_eq!(map.map(0xe0), 1000);
+        assert_eq!(map.map(0xff), 1000);
+        assert_eq!(map.map(0x10), 0);
+        assert_eq!(map.map(0x00), 0);
+
+        // A deadband wider than a side leaves nothing but the ends
+        let wide = AxisMap { deadband: 0x80, ..AxisMap::new(0, 500, 1000) };
+        assert_eq!(wide.map(0x80), 500);
+        assert_eq!(wide.map(0x00), 500);
+        assert_eq!(wide.map(0xff), 500);
+        let wide = AxisMap { deadband: 0x7f, ..AxisMap::new(0, 500, 1000) };
+        assert_eq!(wide.map(0xff), 500);
+        assert_eq!(wide.map(0x00), 0);
+    }
+
+    #[test]
+    fn inverted_axis_map() {
+        let map = AxisMap { inverted: true, deadband: 4, ..AxisMap::new(1000, 1500, 2000) };
+
+        assert_eq!(map.map(0x00), 2000);
+        assert_eq!(map.map(0xff), 1000);
+        assert_eq!(map.map(0x84), 1500);
+        assert_eq!(map.map(0x40), 1742);
+
+        for raw in 0..=0xffu8 {
+            let plain = AxisMap { inverted: false, ..map }.map(raw);
+            assert_eq!(i32::from(map.map(raw)) - 1500, 1500 - i32::from(plain));
+        }
+    }
+}
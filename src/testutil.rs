@@ -0,0 +1,263 @@
+//! Simulated Controllers
+//! ============================
+//! For testing code built on `PlayStationPort` without a controller on the
+//! desk. `SimulatedDualShock2` implements `Transfer<u8>` and plays the part of
+//! a DualShock 2 on the other end of the bus, so it can be passed to
+//! `PlayStationPort::new` in place of a real SPI bus.
+//!
+//! The simulation follows the protocol notes rather than any one controller's
+//! quirks. It starts out in digital mode, only takes configuration commands
+//! in escape mode, and the id it answers with (and so the frame length)
+//! follows the mode and response format set up there. Bits are flipped on the
+//! way in and out just like the real wire, and the multi-tap byte is ignored.
+
+use core::convert::Infallible;
+
+use hal::blocking::spi;
+
+use dualshock::DualShock2;
+use protocol::*;
+
+/// Status bytes a DualShock 2 answers `CMD_READ_STATUS` with, less the analog
+/// mode byte
+const STATUS: [u8; 6] = [0x03, 0x02, 0x00, 0x02, 0x01, 0x00];
+
+/// A DualShock 2 living on the SPI bus
+#[derive(Copy, Clone, Debug)]
+pub struct SimulatedDualShock2 {
+    /// The buttons, sticks and pressures the controller reports. Change this
+    /// between polls to play back input.
+    pub state: DualShock2,
+    analog: bool,
+    pressures: bool,
+    locked: bool,
+    escape: bool,
+    ignores_lock: bool,
+}
+
+impl Default for SimulatedDualShock2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedDualShock2 {
+    /// A controller just plugged in: digital mode, nothing held, the sticks
+    /// centered and the mode unlocked
+    pub fn new() -> Self {
+        Self {
+            state: DualShock2::builder().build(),
+            analog: false,
+            pressures: false,
+            locked: false,
+            escape: false,
+            ignores_lock: false,
+        }
+    }
+
+    /// A third party pad that accepts the mode lock but lets the Analog
+    /// button change modes anyway, like plenty of cheap clones
+    pub fn ignoring_lock() -> Self {
+        Self {
+            ignores_lock: true,
+            ..Self::new()
+        }
+    }
+
+    /// The id the next poll will be answered with
+    pub fn id(&self) -> DeviceId {
+        if self.escape {
+            DeviceId::Configuration
+        } else if !self.analog {
+            DeviceId::DualShockDigital
+        } else if self.pressures {
+            DeviceId::DualShockPressure
+        } else {
+            DeviceId::DualShockAnalog
+        }
+    }
+
+    /// Whether the controller is waiting for configuration commands
+    pub fn in_escape_mode(&self) -> bool {
+        self.escape
+    }
+
+    /// Whether the mode was locked with `CMD_SET_MODE`, which stops the
+    /// Analog button from doing anything (unless the pad ignores it)
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Someone pressed the Analog button, switching between digital and
+    /// analog mode
+    pub fn press_analog_button(&mut self) {
+        if !self.locked || self.ignores_lock {
+            self.analog = !self.analog;
+        }
+    }
+
+    /// Work out the answer to `command`, which has already been flipped back
+    /// to the usual bit order. Anything past the end of the answer reads as
+    /// an idle line.
+    fn respond(&mut self, command: &[u8], response: &mut [u8]) {
+        let arg = |x: usize| command.get(x).cloned().unwrap_or(0);
+
+        let id = self.id();
+        let mut frame = [0u8; MESSAGE_MAX_LENGTH];
+        frame[0] = LINE_IDLE;
+        frame[1] = id.to_byte();
+        frame[2] = ACK_BYTE;
+
+        let mut state = [0u8; 18];
+        self.state.write_frame(&mut state);
+
+        let data = &mut frame[HEADER_LEN..];
+
+        if self.escape {
+            match arg(1) {
+                0x42 => fill(data, &state[..6]),
+                0x43 => self.escape = arg(3) != 0x00,
+                0x44 => {
+                    self.analog = arg(3) == 0x01;
+                    self.locked = arg(4) == 0x03;
+                },
+                0x45 => {
+                    let mut status = STATUS;
+                    status[2] = self.analog as u8;
+                    fill(data, &status);
+                },
+                0x46 if arg(3) == 0x00 => fill(data, &[0x00, 0x00, 0x01, 0x02, 0x00, 0x0a]),
+                0x46 => fill(data, &[0x00, 0x00, 0x01, 0x01, 0x01, 0x14]),
+                0x47 => fill(data, &[0x00, 0x00, 0x02, 0x00, 0x01, 0x00]),
+                0x4c if arg(3) == 0x00 => fill(data, &[0x00, 0x00, 0x00, 0x04, 0x00, 0x00]),
+                0x4c => fill(data, &[0x00, 0x00, 0x00, 0x07, 0x00, 0x00]),
+                0x4f => {
+                    // One bit per byte of the poll response. Anything past
+                    // the buttons and sticks means pressures.
+                    let enabled = arg(3).count_ones() + arg(4).count_ones() + arg(5).count_ones();
+                    self.pressures = enabled > 6;
+                },
+                _ => (),
+            }
+        } else {
+            // Outside escape mode everything is answered like a poll, and
+            // only the escape command itself does anything. Whatever's past
+            // the controller's frame length is cut off below.
+            fill(data, &state);
+
+            if arg(1) == 0x43 && arg(3) == 0x01 {
+                self.escape = true;
+            }
+        }
+
+        let mut answer = frame.iter().take(frame_len(id.to_byte()));
+        for byte in response.iter_mut() {
+            *byte = answer.next().cloned().unwrap_or(LINE_IDLE);
+        }
+    }
+}
+
+impl spi::Transfer<u8> for SimulatedDualShock2 {
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        let mut command = [0u8; MESSAGE_MAX_LENGTH];
+        fill(&mut command, words);
+        let len = words.len().min(MESSAGE_MAX_LENGTH);
+        let command = command.get_mut(..len).unwrap_or_default();
+        flip(command);
+
+        self.respond(command, words);
+        flip(words);
+
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::Button;
+    use mock::MockPin;
+    use {Device, PlayStationPort};
+
+    fn port(controller: SimulatedDualShock2) -> PlayStationPort<SimulatedDualShock2, MockPin> {
+        PlayStationPort::new(controller, None)
+    }
+
+    #[test]
+    fn pressure_mode_end_to_end() {
+        let state = DualShock2::builder()
+            .press(Button::Cross)
+            .pressure(Button::Cross, 0xc0)
+            .stick_left(0x20, 0xe0)
+            .build();
+        let mut controller = SimulatedDualShock2::new();
+        controller.state = state;
+        let mut port = port(controller);
+
+        match port.read_input(None) {
+            Ok(Device::Classic(x)) => assert!(x.buttons.cross()),
+            x => panic!("expected a digital pad, got {:?}", x),
+        }
+
+        port.enable_pressure().unwrap();
+        assert_eq!(port.dev.id(), DeviceId::DualShockPressure);
+        assert!(!port.dev.in_escape_mode());
+        assert_eq!(port.read_input(None), Ok(Device::DualShock2(state)));
+
+        // A whole poll gets the full 21 byte frame and then an idle line
+        let mut frame = [0u8; MESSAGE_MAX_LENGTH];
+        port.send_command(&poll_command(None), &mut frame).unwrap();
+        assert_eq!(frame[..HEADER_LEN], [LINE_IDLE, 0x79, ACK_BYTE]);
+        assert_eq!(DualShock2::from_frame(&frame[HEADER_LEN..21]), Some(state));
+        assert!(frame[21..].iter().all(|&x| x == LINE_IDLE));
+    }
+
+    #[test]
+    fn configuration_only_in_escape_mode() {
+        let mut port = port(SimulatedDualShock2::new());
+        let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+
+        port.send_command(CMD_SET_MODE, &mut buffer).unwrap();
+        assert_eq!(port.dev.id(), DeviceId::DualShockDigital);
+
+        port.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer).unwrap();
+        assert_eq!(buffer[1], 0x41);
+        port.send_command(CMD_SET_MODE, &mut buffer).unwrap();
+        assert_eq!(buffer[1], 0xf3);
+        port.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer).unwrap();
+        assert_eq!(port.dev.id(), DeviceId::DualShockAnalog);
+
+        let config = port.read_config().unwrap();
+        assert_eq!(config.is_dualshock2(), Some(true));
+        assert!(config.analog_enabled());
+        assert_eq!(config.actuators(), 2);
+        assert!(!port.dev.in_escape_mode());
+    }
+
+    #[test]
+    fn clones_ignore_the_lock() {
+        let lock = [0x00, 0x44, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00];
+
+        let locked = |controller| {
+            let mut port = port(controller);
+            let mut buffer = [0u8; MESSAGE_MAX_LENGTH];
+            port.send_command(CMD_ENTER_ESCAPE_MODE, &mut buffer).unwrap();
+            port.send_command(&lock, &mut buffer).unwrap();
+            port.send_command(CMD_EXIT_ESCAPE_MODE, &mut buffer).unwrap();
+
+            let (mut controller, _) = port.release();
+            assert!(controller.is_locked());
+            controller.press_analog_button();
+            controller.id()
+        };
+
+        assert_eq!(locked(SimulatedDualShock2::new()), DeviceId::DualShockAnalog);
+        assert_eq!(locked(SimulatedDualShock2::ignoring_lock()), DeviceId::DualShockDigital);
+
+        let mut unlocked = SimulatedDualShock2::new();
+        unlocked.press_analog_button();
+        assert_eq!(unlocked.id(), DeviceId::DualShockAnalog);
+    }
+}
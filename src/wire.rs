@@ -0,0 +1,260 @@
+//! Compact Encoding
+//! ============================
+//! A small, stable encoding of `Device` for sending controller state over
+//! links with tiny payloads, like radios. Every encoding fits in
+//! `COMPACT_MAX_LENGTH` bytes.
+//!
+//! The first byte is a tag saying which variant follows, and the rest is the
+//! controller's data exactly as it arrives after the frame's header, button
+//! words little endian and still active low:
+//!
+//! | Tag  | Variant             | Bytes after the tag |
+//! |------|---------------------|---------------------|
+//! | 0x00 | `None`              | 0                   |
+//! | 0x01 | `Unknown`           | 0                   |
+//! | 0x02 | `ConfigurationMode` | 0                   |
+//! | 0x03 | `Mouse`             | 4                   |
+//! | 0x04 | `Classic`           | 2                   |
+//! | 0x05 | `AnalogJoystick`    | 6                   |
+//! | 0x06 | `DualShock`         | 6                   |
+//! | 0x07 | `DualShock2`        | 18                  |
+//! | 0x08 | `GuitarHero`        | 6                   |
+//! | 0x09 | `JogCon`            | 5                   |
+//! | 0x0a | `NegCon`            | 6                   |
+//! | 0x0b | `GunCon`            | 6                   |
+//! | 0x0c | `Baton`             | 6                   |
+//!
+//! Tags won't be reused, so anything encoded now will decode the same way in
+//! later releases.
+
+use mouse::Mouse;
+use classic::Classic;
+use dualshock::{DualShock, DualShock2};
+use negcon::NegCon;
+use jogcon::JogCon;
+use guncon::GunCon;
+use guitarhero::GuitarHero;
+use baton::Baton;
+use super::Device;
+
+/// The longest a compact encoding can be, which is a DualShock 2
+pub const COMPACT_MAX_LENGTH: usize = 19;
+
+/// Reasons a device couldn't be encoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// The output buffer is too short
+    BufferTooSmall {
+        /// How many bytes the encoding needs
+        needed: u8,
+    },
+}
+
+/// Reasons bytes couldn't be decoded into a device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// There were no bytes at all
+    Empty,
+    /// The tag byte isn't one we know
+    UnknownTag(u8),
+    /// There weren't enough bytes for the variant the tag named
+    Truncated {
+        /// How many bytes the encoding needs, tag included
+        needed: u8,
+    },
+}
+
+const TAG_NONE: u8 = 0x00;
+const TAG_UNKNOWN: u8 = 0x01;
+const TAG_CONFIGURATION_MODE: u8 = 0x02;
+const TAG_MOUSE: u8 = 0x03;
+const TAG_CLASSIC: u8 = 0x04;
+const TAG_ANALOG_JOYSTICK: u8 = 0x05;
+const TAG_DUALSHOCK: u8 = 0x06;
+const TAG_DUALSHOCK2: u8 = 0x07;
+const TAG_GUITAR_HERO: u8 = 0x08;
+const TAG_JOGCON: u8 = 0x09;
+const TAG_NEGCON: u8 = 0x0a;
+const TAG_GUNCON: u8 = 0x0b;
+const TAG_BATON: u8 = 0x0c;
+
+/// How many bytes follow each tag
+fn payload_len(tag: u8) -> Option<usize> {
+    match tag {
+        TAG_NONE | TAG_UNKNOWN | TAG_CONFIGURATION_MODE => Some(0),
+        TAG_MOUSE => Some(4),
+        TAG_CLASSIC => Some(2),
+        TAG_JOGCON => Some(5),
+        TAG_DUALSHOCK2 => Some(18),
+        TAG_ANALOG_JOYSTICK | TAG_DUALSHOCK | TAG_GUITAR_HERO | TAG_NEGCON | TAG_GUNCON | TAG_BATON => Some(6),
+        _ => None,
+    }
+}
+
+impl Device {
+    fn compact_tag(&self) -> u8 {
+        match self {
+            Device::None => TAG_NONE,
+            Device::Unknown => TAG_UNKNOWN,
+            Device::ConfigurationMode => TAG_CONFIGURATION_MODE,
+            Device::Mouse(_) => TAG_MOUSE,
+            Device::Classic(_) => TAG_CLASSIC,
+            Device::AnalogJoystick(_) => TAG_ANALOG_JOYSTICK,
+            Device::DualShock(_) => TAG_DUALSHOCK,
+            Device::DualShock2(_) => TAG_DUALSHOCK2,
+            Device::GuitarHero(_) => TAG_GUITAR_HERO,
+            Device::JogCon(_) => TAG_JOGCON,
+            Device::NegCon(_) => TAG_NEGCON,
+            Device::GunCon(_) => TAG_GUNCON,
+            Device::Baton(_) => TAG_BATON,
+        }
+    }
+
+    /// Encode the device into `out` using the format described in the `wire`
+    /// module, returning how many bytes were written
+    pub fn serialize_compact(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        let tag = self.compact_tag();
+        let len = 1 + payload_len(tag).unwrap_or(0);
+        let (first, payload) = match out.get_mut(..len).and_then(|x| x.split_first_mut()) {
+            Some(x) => x,
+            None => return Err(EncodeError::BufferTooSmall { needed: len as u8 }),
+        };
+
+        *first = tag;
+        match self {
+            Device::None | Device::Unknown | Device::ConfigurationMode => (),
+            Device::Mouse(x) => x.write_frame(payload),
+            Device::Classic(x) => x.write_frame(payload),
+            Device::AnalogJoystick(x) | Device::DualShock(x) => x.write_frame(payload),
+            Device::DualShock2(x) => x.write_frame(payload),
+            Device::GuitarHero(x) => x.write_frame(payload),
+            Device::JogCon(x) => x.write_frame(payload),
+            Device::NegCon(x) => x.write_frame(payload),
+            Device::GunCon(x) => x.write_frame(payload),
+            Device::Baton(x) => x.write_frame(payload),
+        }
+
+        Ok(len)
+    }
+
+    /// Decode a device written by `serialize_compact`. Anything past the end
+    /// of the encoding is ignored.
+    pub fn deserialize_compact(data: &[u8]) -> Result<Device, DecodeError> {
+        let tag = *data.first().ok_or(DecodeError::Empty)?;
+        let len = 1 + payload_len(tag).ok_or(DecodeError::UnknownTag(tag))?;
+        let payload = data.get(1..len).ok_or(DecodeError::Truncated { needed: len as u8 })?;
+        let device = match tag {
+            TAG_NONE => Some(Device::None),
+            TAG_UNKNOWN => Some(Device::Unknown),
+            TAG_CONFIGURATION_MODE => Some(Device::ConfigurationMode),
+            TAG_MOUSE => Mouse::from_frame(payload).map(Device::Mouse),
+            TAG_CLASSIC => Classic::from_frame(payload).map(Device::Classic),
+            TAG_ANALOG_JOYSTICK => DualShock::from_frame(payload).map(Device::AnalogJoystick),
+            TAG_DUALSHOCK => DualShock::from_frame(payload).map(Device::DualShock),
+            TAG_DUALSHOCK2 => DualShock2::from_frame(payload).map(Device::DualShock2),
+            TAG_GUITAR_HERO => GuitarHero::from_frame(payload).map(Device::GuitarHero),
+            TAG_JOGCON => JogCon::from_frame(payload).map(Device::JogCon),
+            TAG_NEGCON => NegCon::from_frame(payload).map(Device::NegCon),
+            TAG_GUNCON => GunCon::from_frame(payload).map(Device::GunCon),
+            _ => Baton::from_frame(payload).map(Device::Baton),
+        };
+
+        // The lengths above match what each `from_frame` wants, so this
+        // can't fail, but there's no reason to panic if it ever does
+        device.ok_or(DecodeError::Truncated { needed: len as u8 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use classic::Button;
+
+    // One of everything, with every byte different so a misplaced one shows
+    fn devices() -> [Device; 13] {
+        let frame = [
+            0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+        ];
+
+        [
+            Device::None,
+            Device::Unknown,
+            Device::ConfigurationMode,
+            Device::Mouse(Mouse::from_frame(&frame).unwrap()),
+            Device::Classic(Classic::builder().press(Button::Start).press(Button::Square).build()),
+            Device::AnalogJoystick(DualShock::from_frame(&frame).unwrap()),
+            Device::DualShock(DualShock::builder().press(Button::R3).stick_left(0x00, 0xff).build()),
+            Device::DualShock2(DualShock2::from_frame(&frame).unwrap()),
+            Device::GuitarHero(GuitarHero::from_frame(&frame).unwrap()),
+            Device::JogCon(JogCon::from_frame(&frame).unwrap()),
+            Device::NegCon(NegCon::from_frame(&frame).unwrap()),
+            Device::GunCon(GunCon::from_frame(&frame).unwrap()),
+            Device::Baton(Baton::from_frame(&frame).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn round_trips() {
+        let lengths = [1, 1, 1, 5, 3, 7, 7, 19, 7, 6, 7, 7, 7];
+
+        for (device, &length) in devices().iter().zip(lengths.iter()) {
+            let mut out = [0u8; COMPACT_MAX_LENGTH];
+            assert_eq!(device.serialize_compact(&mut out), Ok(length), "{:?}", device);
+            assert_eq!(Device::deserialize_compact(&out[..length]), Ok(*device));
+
+            // Trailing bytes, like the rest of a radio payload, are ignored
+            let mut padded = [0xee; 32];
+            padded[..length].copy_from_slice(&out[..length]);
+            assert_eq!(Device::deserialize_compact(&padded), Ok(*device));
+        }
+    }
+
+    #[test]
+    fn format_is_fixed() {
+        let mut out = [0u8; COMPACT_MAX_LENGTH];
+
+        let classic = Device::Classic(Classic::builder().press(Button::Select).press(Button::Cross).build());
+        assert_eq!(classic.serialize_compact(&mut out), Ok(3));
+        assert_eq!(out[..3], [0x04, 0xfe, 0xbf]);
+
+        let dualshock = Device::DualShock(DualShock::builder().stick_left(0x10, 0x20).stick_right(0x30, 0x40).build());
+        assert_eq!(dualshock.serialize_compact(&mut out), Ok(7));
+        assert_eq!(out[..7], [0x06, 0xff, 0xff, 0x30, 0x40, 0x10, 0x20]);
+
+        assert_eq!(Device::None.serialize_compact(&mut out), Ok(1));
+        assert_eq!(out[0], 0x00);
+    }
+
+    #[test]
+    fn rejects_short_buffers() {
+        for device in devices().iter() {
+            let mut out = [0u8; COMPACT_MAX_LENGTH];
+            let length = device.serialize_compact(&mut out).unwrap();
+
+            for short in 0..length {
+                let mut small = [0u8; COMPACT_MAX_LENGTH];
+                assert_eq!(
+                    device.serialize_compact(&mut small[..short]),
+                    Err(EncodeError::BufferTooSmall { needed: length as u8 })
+                );
+
+                let expected = if short == 0 {
+                    DecodeError::Empty
+                } else {
+                    DecodeError::Truncated { needed: length as u8 }
+                };
+                assert_eq!(Device::deserialize_compact(&out[..short]), Err(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_tags() {
+        for tag in 0x0d..=0xff {
+            assert_eq!(Device::deserialize_compact(&[tag; 20]), Err(DecodeError::UnknownTag(tag)));
+        }
+    }
+}